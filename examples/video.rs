@@ -60,25 +60,6 @@ float4 PSMain(PS_INPUT Input) : SV_TARGET {
 }
 "#;
 
-macro_rules! format_guid {
-    ($guid:expr) => {
-        format!(
-            "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
-            $guid.Data1,
-            $guid.Data2,
-            $guid.Data3,
-            $guid.Data4[0],
-            $guid.Data4[1],
-            $guid.Data4[2],
-            $guid.Data4[3],
-            $guid.Data4[4],
-            $guid.Data4[5],
-            $guid.Data4[6],
-            $guid.Data4[7]
-        )
-    };
-}
-
 fn com_initialize() {
     use winapi::um::objbase::{COINIT_APARTMENTTHREADED, COINIT_DISABLE_OLE1DDE};
     unsafe {
@@ -95,14 +76,20 @@ fn com_uninitialize() {
     }
 }
 
-fn main() {
+fn main() -> noodle::Result<()> {
+    // With `--features leak-detector`, lists the references still held
+    // when `main` returns.
+    #[cfg(feature = "leak-detector")]
+    let _leaks = noodle::leaks::dump_on_drop();
+
     com_initialize();
-    mf::startup();
+    mf::startup()?;
 
-    {
+    // Shut Media Foundation down even if decoding fails.
+    let result = (|| -> noodle::Result<()> {
         let device: d3d11::Device = d3d11::create_hardware_device(
             d3d11::CreateDevice::VIDEO_SUPPORT | d3d11::CreateDevice::BGRA_SUPPORT,
-        );
+        )?;
 
         let multithread: Option<d3d11::Multithread> = device.as_();
         if let Some(multithread) = multithread {
@@ -115,22 +102,17 @@ fn main() {
             .text(VS_HLSL_SRC)
             .entry_point("VSMain")
             .target("vs_4_0_level_9_3")
-            .compile();
+            .compile()?;
 
-        let vs_byte_code = match vs_byte_code {
-            Ok(vs) => vs,
-            Err(s) => panic!("{}", s),
-        };
-
-        let vertex_shader = device.create_vertex_shader(&vs_byte_code);
+        let vertex_shader = device.create_vertex_shader(&vs_byte_code)?;
 
         let ps_byte_code = d3dcompiler::Compiler::default()
              .text(PS_HLSL_SRC)
              .entry_point("PSMain")
              .target("ps_4_0_level_9_1")
-             .compile().unwrap();
+             .compile()?;
 
-        let pixel_shader = device.create_pixel_shader(&ps_byte_code);
+        let pixel_shader = device.create_pixel_shader(&ps_byte_code)?;
 
         // Create our vertex input layout
         let layout = [
@@ -142,33 +124,31 @@ fn main() {
         let mut sam_desc = d3d11::SamplerDesc::default();
         sam_desc.Filter = D3D11_FILTER_MIN_MAG_MIP_LINEAR;
 
-        let manager = mf::create_dxgi_device_manager();
-        manager.reset_device(&device);
+        let manager = mf::create_dxgi_device_manager()?;
+        manager.reset_device(&device)?;
 
-        let mut attributes = mf::create_attributes(3);
-        attributes.set(mf::SourceReaderAttr::D3dManager, &manager);
-        attributes.set(mf::ReadWriteAttr::EnableHardwareTransforms, true);
-        attributes.set(mf::SourceReaderAttr::EnableAdvancedVideoProcessing, true);
+        let mut attributes = mf::create_attributes(3)?;
+        attributes.set(mf::SourceReaderAttr::D3dManager, &manager)?;
+        attributes.set(mf::ReadWriteAttr::EnableHardwareTransforms, true)?;
+        attributes.set(mf::SourceReaderAttr::EnableAdvancedVideoProcessing, true)?;
 
         let reader = mf::create_source_reader_from_url(
             "Rogue One - A Star Wars Story - Trailer.mp4",
             &attributes,
-        );
+        )
+        .map_err(|error| error.with_context("opening the trailer"))?;
 
-        let curr_media_type = reader.current_media_type(mf::SourceReaderStream::FirstVideo);
-        println!(
-            "curr media type: {}",
-            format_guid!(curr_media_type.major_type())
-        );
-        let attr = curr_media_type.get(mf::Attr::SubType);
+        let curr_media_type = reader.current_media_type(mf::SourceReaderStream::FirstVideo)?;
+        println!("curr media type: {}", curr_media_type.major_type()?);
+        let attr = curr_media_type.get(mf::Attr::SubType)?;
         if let Some(guid) = attr.as_guid() {
-            println!("SubType = {}", format_guid!(guid));
+            println!("SubType = {}", guid);
         }
 
-        let mut output_type = mf::create_media_type();
-        output_type.set(mf::Attr::MajorType, mf::MajorTypeAttr::Video);
-        output_type.set(mf::Attr::SubType, mf::VideoFormat::Nv12);
-        reader.set_current_media_type(mf::SourceReaderStream::FirstVideo, &output_type);
+        let mut output_type = mf::create_media_type()?;
+        output_type.set(mf::Attr::MajorType, mf::MajorTypeAttr::Video)?;
+        output_type.set(mf::Attr::SubType, mf::VideoFormat::Nv12)?;
+        reader.set_current_media_type(mf::SourceReaderStream::FirstVideo, &output_type)?;
 
         let mut frame_count = 0;
 
@@ -178,7 +158,7 @@ fn main() {
             let (_, flags, _, sample) = reader.read_sample(
                 mf::SourceReaderStream::FirstVideo,
                 mf::SourceReaderControlFlag::empty(),
-            );
+            )?;
 
             if flags.contains(mf::SourceReaderFlag::END_OF_STREAM) || sample.is_none() {
                 break;
@@ -188,7 +168,7 @@ fn main() {
                 println!("Frame {}", frame_count);
                 frame_count += 1;
 
-                let buffer = sample.convert_to_contiguous_buffer();
+                let buffer = sample.convert_to_contiguous_buffer()?;
                 let dxgi_buffer: Option<mf::BufferDxgi> = buffer.as_();
                 if let Some(dxgi_buffer) = dxgi_buffer {
                     println!("It's a DXGI buffer");
@@ -197,8 +177,10 @@ fn main() {
         }
 
         println!("Finished processing frames");
-    }
+        Ok(())
+    })();
 
-    mf::shutdown();
+    mf::shutdown()?;
     com_uninitialize();
+    result
 }
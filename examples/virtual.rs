@@ -3,9 +3,12 @@
 
 use itertools::iproduct;
 use noodle::{
-    d2d, d3d11, dcomp, dwrite, dxgi, offset, point, size, winuser, Point, Rect, Size, ToUtf16,
+    d2d, d3d11, dcomp, debug, dwrite, dxgi,
+    hud::Hud,
+    offset, point,
+    scheduler::{FrameScheduler, Mode},
+    size, winuser, Canvas, Point, Rect, Result, Size, WideString,
 };
-use std::ffi::OsStr;
 use winapi::um::{d3d11::D3D11_REQ_TEXTURE2D_U_OR_V_DIMENSION, winuser::*};
 
 fn hls_to_rgb(hue: f32, saturation: f32, lightness: f32) -> [f32; 3] {
@@ -64,19 +67,27 @@ struct TileRenderer {
 }
 
 impl TileRenderer {
-    fn new(dcomp_device: &dcomp::DesktopDevice, surface_size: i32) -> Self {
+    fn new(dcomp_device: &dcomp::DesktopDevice, surface_size: i32) -> Result<Self> {
         // Create the Dwrite factory and text format object
-        let dwrite_factory: dwrite::Factory = dwrite::create_shared_factory();
+        let dwrite_factory: dwrite::Factory = dwrite::create_shared_factory()?;
         let text_format = dwrite_factory.create_text_format(
             "Segoe UI",
+            None,
             dwrite::FontWeight::Bold,
             dwrite::FontStyle::Normal,
             dwrite::FontStretch::Normal,
             60.0,
             "en-US",
-        );
-        text_format.set_paragraph_alignment(dwrite::ParagraphAlignment::Center);
-        text_format.set_text_alignment(dwrite::TextAlignment::Center);
+        )?;
+        text_format.set_paragraph_alignment(dwrite::ParagraphAlignment::Center)?;
+        text_format.set_text_alignment(dwrite::TextAlignment::Center)?;
+
+        // Labels too big for their tile end in an ellipsis instead of spilling out of it.
+        let ellipsis = dwrite_factory.create_ellipsis_trimming_sign(&text_format)?;
+        text_format.set_trimming(
+            dwrite::Trimming::ellipsis(dwrite::TrimmingGranularity::Character),
+            Some(&ellipsis),
+        )?;
 
         // m_surfaceBrush = CreateVirtualDrawingSurfaceBrush();
         let virtual_surface = dcomp_device.create_virtual_surface(
@@ -84,26 +95,20 @@ impl TileRenderer {
             surface_size as u32,
             dxgi::Format::Bgra8,
             dxgi::AlphaMode::Ignore,
-        );
-
-        let visual = dcomp_device.create_visual();
-        visual.set_content(&virtual_surface);
+        )?;
 
-        #[cfg(feature = "paint-flashing")]
-        {
-            let visual_debug: dcomp::VisualDebug = visual.as_().unwrap();
-            visual_debug.enable_redraw_regions();
-        }
+        let visual = dcomp_device.create_visual()?;
+        visual.set_content(&virtual_surface)?;
 
-        Self {
+        Ok(Self {
             text_format,
             virtual_surface,
             visual,
             surface_size,
-        }
+        })
     }
 
-    fn draw_tile_range(&self, dc: &d2d::DeviceContext1, rect: &Rect, tiles: &[Tile]) {
+    fn draw_tile_range(&self, dc: &d2d::DeviceContext1, rect: &Rect, tiles: &[Tile]) -> Result<()> {
         let update_size = rect.size() - 5;
         // Making sure the update rect doesn't go past the maximum size of the surface.
         let update_rect = *rect & Rect::new(0, 0, self.surface_size, self.surface_size);
@@ -142,7 +147,7 @@ impl TileRenderer {
                     .draw(&constrained_update_rect, |dxgi_surface, offset| {
                         // Create a D2D compatible bitmap from the surface for rendering
                         let bitmap: d2d::Bitmap1 =
-                            dc.create_bitmap_from_dxgi_surface(&dxgi_surface, &properties);
+                            dc.create_bitmap_from_dxgi_surface(&dxgi_surface, &properties)?;
 
                         // Point the device context to the bitmap for rendering
                         dc.set_target(&bitmap);
@@ -155,11 +160,11 @@ impl TileRenderer {
                             let text_brush = dc.create_solid_color_brush(
                                 &(d2d::NamedColor::DimGray, 0.5).into(),
                                 None,
-                            );
+                            )?;
 
                             // Create a solid color brush for the tiles and which will be set to a different color before rendering.
                             let tile_brush =
-                                dc.create_solid_color_brush(&d2d::NamedColor::Green.into(), None);
+                                dc.create_solid_color_brush(&d2d::NamedColor::Green.into(), None)?;
 
                             // Get the offset difference that can be applied to every tile before drawing.
                             let difference_offset = offset - (x, y);
@@ -175,24 +180,28 @@ impl TileRenderer {
                                 dc.fill_rectangle(&tile_rectangle, &tile_brush);
 
                                 // DrawTextInTile(tile.row, tile.column, tileRectangle, d2dDeviceContext, textBrush);
-                                let text = format!("{},{}", tile.row, tile.column).to_utf16();
+                                let text =
+                                    WideString::new(&format!("{},{}", tile.row, tile.column))
+                                        .unwrap();
 
                                 // Drawing the text in the second third of the rectangle, so it is centered. The centerRect is the new rectangle that is 1/3rd of the height and placed at the center of the Tile.
                                 dc.draw_text(
-                                    &text,
+                                    text.as_slice(),
                                     &self.text_format,
                                     &tile_rectangle,
                                     &text_brush,
                                 );
                             }
-                        });
-                    });
+                            Ok(())
+                        })
+                    })?;
             }
         }
+        Ok(())
     }
 
-    pub fn trim(&self, trim_rect: &Rect) {
-        self.virtual_surface.trim(&[*trim_rect]);
+    pub fn trim(&self, trim_rect: &Rect) -> Result<()> {
+        self.virtual_surface.trim(&[*trim_rect])
     }
 }
 
@@ -218,9 +227,9 @@ impl TileDrawingManager {
     pub fn update_visible_region(
         &mut self,
         current_position: Point,
-        draw_fn: impl Fn(&Rect, &[Tile]),
-        trim_fn: impl Fn(&Rect),
-    ) {
+        draw_fn: impl Fn(&Rect, &[Tile]) -> Result<()>,
+        trim_fn: impl Fn(&Rect) -> Result<()>,
+    ) -> Result<()> {
         self.current_position = current_position;
 
         let required_tile_rect =
@@ -232,24 +241,24 @@ impl TileDrawingManager {
         // Draws the tiles that are required but not drawn.
         let rects_to_draw = required_tile_rect - self.drawn_tile_rect;
         if rects_to_draw.is_empty() {
-            return;
+            return Ok(());
         }
 
         for rect in &rects_to_draw {
-            Self::draw_tile_range(rect, &draw_fn);
+            Self::draw_tile_range(rect, &draw_fn)?;
         }
 
         // Trimming the tiles that are not visible on screen
-        self.trim(&required_tile_rect, trim_fn);
+        self.trim(&required_tile_rect, trim_fn)
     }
 
     /// Updates the Viewport Size of the application.
     pub fn update_viewport_size(
         &mut self,
         new_size: Size,
-        draw_fn: impl Fn(&Rect, &[Tile]),
-        trim_fn: impl Fn(&Rect),
-    ) {
+        draw_fn: impl Fn(&Rect, &[Tile]) -> Result<()>,
+        trim_fn: impl Fn(&Rect) -> Result<()>,
+    ) -> Result<()> {
         self.view_port_size = new_size;
 
         self.update_visible_region(self.current_position, draw_fn, trim_fn)
@@ -266,9 +275,9 @@ impl TileDrawingManager {
         .collect::<_>()
     }
 
-    fn draw_tile_range(tile_rect: &Rect, f: &dyn Fn(&Rect, &[Tile])) {
+    fn draw_tile_range(tile_rect: &Rect, f: &dyn Fn(&Rect, &[Tile]) -> Result<()>) -> Result<()> {
         let draw_rect = *tile_rect * Self::TILE_SIZE;
-        f(&draw_rect, &Self::tiles_for_range(&tile_rect));
+        f(&draw_rect, &Self::tiles_for_range(&tile_rect))
     }
 
     /// This function combines all the tiles into a single call, so the rendering is faster as opposed to calling BeginDraw on each tile.
@@ -291,67 +300,123 @@ impl TileDrawingManager {
     */
 
     /// Trims the tiles that are outside these co-ordinates. So only the contents that are visible are rendered, to save on memory.
-    fn trim(&mut self, required_rect: &Rect, trim_fn: impl Fn(&Rect)) {
+    fn trim(&mut self, required_rect: &Rect, trim_fn: impl Fn(&Rect) -> Result<()>) -> Result<()> {
         let trim_rect = *required_rect * Self::TILE_SIZE;
-        trim_fn(&trim_rect);
+        trim_fn(&trim_rect)?;
         self.drawn_tile_rect = *required_rect;
+        Ok(())
     }
 }
 
-fn create_d2d_device_context(dxgi_device: &dxgi::Device) -> d2d::DeviceContext1 {
+fn draw_hud(hud: &mut Hud, surface: &dcomp::Surface, dc: &d2d::DeviceContext1) -> Result<()> {
+    let properties = d2d::BitmapProperties1 {
+        pixelFormat: d2d::PixelFormat {
+            format: dxgi::Format::Bgra8.into(),
+            alphaMode: dxgi::AlphaMode::Premultiplied.into(),
+        },
+        bitmapOptions: d2d::BITMAP_OPTIONS_TARGET | d2d::BITMAP_OPTIONS_CANNOT_DRAW,
+        ..Default::default()
+    };
+    // The HUD isn't content, so keep it out of the paint stats it shows.
+    surface.draw_untracked(None, |dxgi_surface, offset| {
+        let bitmap = dc.create_bitmap_from_dxgi_surface(dxgi_surface, &properties)?;
+        dc.set_target(&bitmap);
+        dc.draw(|dc| {
+            let mut canvas = dc.clone();
+            Canvas::clear(&mut canvas);
+            hud.set_origin(point!(offset.x, offset.y));
+            hud.draw(&mut canvas);
+            Ok(())
+        })
+    })
+}
+
+fn create_d2d_device_context(dxgi_device: &dxgi::Device) -> Result<d2d::DeviceContext1> {
     // Create a single-threaded Direct2D factory with debugging information
     let options: d2d::FactoryOptions = d2d::DebugLevel::Information.into();
-    let d2d_factory: d2d::Factory2 = d2d::create_single_threaded_factory(&options);
+    let d2d_factory: d2d::Factory2 = d2d::create_single_threaded_factory(&options)?;
 
     // Create the Direct2D device that links back to the Direct3D device
-    let d2d_device: d2d::Device1 = d2d_factory.create_device(&dxgi_device);
+    let d2d_device: d2d::Device1 = d2d_factory.create_device(&dxgi_device)?;
     // Create the Direct2D device context that is the actual render target
     // and exposes drawing commands
     d2d_device.create_device_context()
 }
 
-fn create_dcomp_device(dxgi_device: &dxgi::Device) -> dcomp::DesktopDevice {
-    let dcomp_device: dcomp::DesktopDevice = dcomp::create_device_2(&dxgi_device);
+fn create_dcomp_device(dxgi_device: &dxgi::Device) -> Result<dcomp::DesktopDevice> {
+    let dcomp_device: dcomp::DesktopDevice = dcomp::create_device_2(&dxgi_device)?;
     //let dcomp_device: dcomp::DesktopDevice = dcomp::create_device_2(&d2d_device);
     let dcomp_device_debug: dcomp::DeviceDebug = dcomp_device.as_().unwrap();
-    dcomp_device_debug.enable_debug_counters();
-    dcomp_device
+    dcomp_device_debug.enable_debug_counters()?;
+    Ok(dcomp_device)
 }
 
-fn main() {
+fn main() -> Result<()> {
     let window = winuser::Window::create(
-        OsStr::new("Window"),
-        OsStr::new("Sample"),
+        "Window",
+        "Sample",
         WS_OVERLAPPEDWINDOW | WS_VISIBLE,
         800,
         600,
-    )
-    .unwrap();
+    )?;
 
-    let d3d11_device = d3d11::create_hardware_device(d3d11::CreateDevice::BGRA_SUPPORT);
+    let d3d11_device = d3d11::create_hardware_device(d3d11::CreateDevice::BGRA_SUPPORT)?;
     let dxgi_device: dxgi::Device = d3d11_device.as_().unwrap();
 
-    let d2d_dc = create_d2d_device_context(&dxgi_device);
-    let dcomp_device = create_dcomp_device(&dxgi_device);
+    let d2d_dc = create_d2d_device_context(&dxgi_device)?;
+    let dcomp_device = create_dcomp_device(&dxgi_device)?;
 
     let mut tile_drawing_manager = TileDrawingManager::default();
-    let tile_renderer = TileRenderer::new(&dcomp_device, TileDrawingManager::MAX_SURFACE_SIZE);
-
-    let target = dcomp_device.create_target_for_hwnd(window, true);
-    target.set_root(&tile_renderer.visual);
+    let tile_renderer = TileRenderer::new(&dcomp_device, TileDrawingManager::MAX_SURFACE_SIZE)?;
 
     let window_size = window.window_rect().size();
+
+    // Paint flashing, toggled with F2, is drawn on top of the tiles.
+    let overlay = dcomp::PaintOverlay::new(
+        &dcomp_device,
+        window_size.width() as u32,
+        window_size.height() as u32,
+    )?;
+    let root = dcomp_device.create_visual()?;
+    root.add_visual(
+        &tile_renderer.visual,
+        dcomp::Insert::Above,
+        None::<&dcomp::Visual2>,
+    )?;
+    root.add_visual(
+        overlay.visual(),
+        dcomp::Insert::Above,
+        None::<&dcomp::Visual2>,
+    )?;
+
+    let mut hud = Hud::new(point!(0, 0));
+    let hud_surface = dcomp_device.create_surface(
+        400,
+        200,
+        dxgi::Format::Bgra8,
+        dxgi::AlphaMode::Premultiplied,
+    )?;
+    let hud_visual = dcomp_device.create_visual()?;
+    hud_visual.set_content(&hud_surface)?;
+    hud_visual.set_offset([8.0, 8.0])?;
+    root.add_visual(&hud_visual, dcomp::Insert::Above, None::<&dcomp::Visual2>)?;
+
+    let target = dcomp_device.create_target_for_hwnd(window, true)?;
+    target.set_root(&root)?;
+
     tile_drawing_manager.update_viewport_size(
         window_size,
         |draw_rect, tiles| tile_renderer.draw_tile_range(&d2d_dc, draw_rect, tiles),
         |required_rect| tile_renderer.trim(required_rect),
-    );
-    dcomp_device.commit();
+    )?;
+    dcomp_device.commit()?;
 
     let mut current_position = point!(0, 0);
     let mut offset = offset!(5, 10);
+    let mut scheduler = FrameScheduler::new(Mode::VSync);
 
     loop {
+        let mut resized = None;
         let quit = winuser::process_pending_events(|message| {
             use winuser::MessageKind;
             match message.kind {
@@ -365,13 +430,16 @@ fn main() {
                 }
                 MessageKind::Size { .. } => {
                     // Update the view port to the new size of the parent window
-                    let window_size = message.window.window_rect().size();
-                    //tile_renderer.visual.set_size(&window_size);
-                    tile_drawing_manager.update_viewport_size(
-                        window_size,
-                        |draw_rect, tiles| tile_renderer.draw_tile_range(&d2d_dc, draw_rect, tiles),
-                        |required_rect| tile_renderer.trim(required_rect),
-                    );
+                    // once the messages are handled.
+                    resized = Some(message.window.window_rect().size());
+                    true
+                }
+                MessageKind::Other {
+                    msg: WM_KEYDOWN,
+                    wparam,
+                    ..
+                } if wparam == VK_F2 as usize => {
+                    debug::set_paint_flashing(!debug::paint_flashing());
                     true
                 }
                 _ => false,
@@ -382,49 +450,77 @@ fn main() {
             break;
         }
 
-        tile_renderer
-            .visual
-            .set_offset([-current_position.x as f32, -current_position.y as f32]);
+        if let Some(window_size) = resized {
+            //tile_renderer.visual.set_size(&window_size);
+            tile_drawing_manager.update_viewport_size(
+                window_size,
+                |draw_rect, tiles| tile_renderer.draw_tile_range(&d2d_dc, draw_rect, tiles),
+                |required_rect| tile_renderer.trim(required_rect),
+            )?;
+        }
 
-        tile_drawing_manager.update_visible_region(
-            current_position,
-            |draw_rect, tiles| tile_renderer.draw_tile_range(&d2d_dc, draw_rect, tiles),
-            |required_rect| tile_renderer.trim(required_rect),
-        );
+        hud.record_frame(scheduler.stats());
+        hud.record_paints(debug::last_frame_paints());
 
-        current_position += offset;
+        scheduler.frame(&dcomp_device, |_| {
+            tile_renderer
+                .visual
+                .set_offset([-current_position.x as f32, -current_position.y as f32])?;
 
-        current_position.x = match current_position.x {
-            x if x < 0 => {
-                offset.x = -offset.x;
-                0
-            }
-            x if x
-                >= TileDrawingManager::MAX_SURFACE_SIZE
-                    - tile_drawing_manager.view_port_size.width() =>
-            {
-                offset.x = -offset.x;
-                TileDrawingManager::MAX_SURFACE_SIZE - tile_drawing_manager.view_port_size.width()
-            }
-            x => x,
-        };
+            tile_drawing_manager.update_visible_region(
+                current_position,
+                |draw_rect, tiles| tile_renderer.draw_tile_range(&d2d_dc, draw_rect, tiles),
+                |required_rect| tile_renderer.trim(required_rect),
+            )?;
 
-        current_position.y = match current_position.y {
-            y if y < 0 => {
-                offset.y = -offset.y;
-                0
-            }
-            y if y
-                >= TileDrawingManager::MAX_SURFACE_SIZE
-                    - tile_drawing_manager.view_port_size.height() =>
-            {
-                offset.y = -offset.y;
-                TileDrawingManager::MAX_SURFACE_SIZE - tile_drawing_manager.view_port_size.height()
-            }
-            y => y,
-        };
+            overlay.set_origin(
+                &tile_renderer.virtual_surface,
+                offset!(-current_position.x, -current_position.y),
+            );
+            overlay.update(&d2d_dc)?;
+
+            let tiles = tile_drawing_manager.drawn_tile_rect.size();
+            let tile_count = (tiles.width() * tiles.height()) as usize;
+            let tile_bytes =
+                (TileDrawingManager::TILE_SIZE * TileDrawingManager::TILE_SIZE * 4) as usize;
+            hud.record_surface(tile_count, tile_count * tile_bytes);
+            draw_hud(&mut hud, &hud_surface, &d2d_dc)?;
+
+            current_position += offset;
+
+            current_position.x = match current_position.x {
+                x if x < 0 => {
+                    offset.x = -offset.x;
+                    0
+                }
+                x if x
+                    >= TileDrawingManager::MAX_SURFACE_SIZE
+                        - tile_drawing_manager.view_port_size.width() =>
+                {
+                    offset.x = -offset.x;
+                    TileDrawingManager::MAX_SURFACE_SIZE
+                        - tile_drawing_manager.view_port_size.width()
+                }
+                x => x,
+            };
 
-        dcomp_device.commit();
-        dcomp_device.wait_for_commit_completion();
+            current_position.y = match current_position.y {
+                y if y < 0 => {
+                    offset.y = -offset.y;
+                    0
+                }
+                y if y
+                    >= TileDrawingManager::MAX_SURFACE_SIZE
+                        - tile_drawing_manager.view_port_size.height() =>
+                {
+                    offset.y = -offset.y;
+                    TileDrawingManager::MAX_SURFACE_SIZE
+                        - tile_drawing_manager.view_port_size.height()
+                }
+                y => y,
+            };
+            Ok(())
+        })?;
     }
+    Ok(())
 }
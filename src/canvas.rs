@@ -0,0 +1,26 @@
+use crate::Rect;
+
+/// Minimal drawing interface shared by the Direct2D device context and the
+/// software `soft::Bitmap`, used by the debug overlays.
+///
+/// Colors are straight (not premultiplied) `[r, g, b, a]` in `0.0..=1.0`.
+pub trait Canvas {
+    /// Clear the whole target to transparent.
+    fn clear(&mut self);
+
+    fn fill_rect(&mut self, rect: &Rect, color: [f32; 4]);
+
+    /// Outline `rect` with a stroke of `width` pixels drawn inside it.
+    fn stroke_rect(&mut self, rect: &Rect, color: [f32; 4], width: f32) {
+        let w = (width.round() as i32).max(1);
+        if rect.width() <= 2 * w || rect.height() <= 2 * w {
+            self.fill_rect(rect, color);
+            return;
+        }
+        let (l, t, r, b) = (rect.left, rect.top, rect.right, rect.bottom);
+        self.fill_rect(&Rect::new(l, t, r, t + w), color);
+        self.fill_rect(&Rect::new(l, b - w, r, b), color);
+        self.fill_rect(&Rect::new(l, t + w, l + w, b - w), color);
+        self.fill_rect(&Rect::new(r - w, t + w, r, b - w), color);
+    }
+}
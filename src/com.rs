@@ -0,0 +1,446 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! COM objects implemented in Rust.
+//!
+//! Some APIs call back into the application through COM interfaces, such as
+//! `IMFSourceReaderCallback` for `SourceReader::read_sample_async`.
+//! `implement_com!` declares a Rust trait for such an interface, with a
+//! method for each entry of its vtable:
+//!
+//! ```ignore
+//! implement_com! {
+//!     pub trait SourceReaderCallback for IMFSourceReaderCallback(IMFSourceReaderCallbackVtbl): IUnknown {
+//!         fn on_read_sample => OnReadSample(status: HRESULT, ...) -> HRESULT;
+//!         ...
+//!     }
+//! }
+//! ```
+//!
+//! and then makes a Rust type implementing such traits into a COM object:
+//!
+//! ```ignore
+//! implement_com!(Decoder: [IMFSourceReaderCallback]);
+//! let callback: mf::AsyncCallback = decoder.into_com();
+//! ```
+//!
+//! The object has a vtable for each listed interface and answers
+//! `QueryInterface` for them, their bases and `IUnknown`. It is reference
+//! counted atomically, since callbacks usually arrive on other threads, and
+//! the Rust value is dropped with the last reference.
+//!
+//! A panic in a method doesn't unwind into the caller: the method returns
+//! `E_UNEXPECTED` instead, or zero or nothing for methods that don't return
+//! an `HRESULT`; see `Unwound`.
+//!
+//! The crate implements `IMFSourceReaderCallback`, in `mf`, and
+//! DirectWrite's font file loader and stream, behind
+//! `dwrite::Factory::create_font_collection`. Other callbacks are out of
+//! scope for now. In particular `ID3DInclude`, for `#include` in shaders,
+//! isn't a COM interface, having no `IUnknown` entries, so `implement_com!`
+//! can't implement it, and `IDWriteFontCollectionLoader` isn't needed, since
+//! collections are built from font sets.
+
+pub use crate::sys::{IUnknown, IUnknownVtbl, Interface, GUID};
+use crate::{
+    sys::{E_NOINTERFACE, E_POINTER, E_UNEXPECTED, HRESULT, S_OK, ULONG},
+    ComPtr, Guid,
+};
+use std::{
+    any::Any,
+    ffi::c_void,
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr,
+    sync::atomic::{fence, AtomicU32, Ordering},
+};
+
+/// A Rust type that implements a COM object. Implemented by
+/// `implement_com!(Type: [interfaces])`.
+///
+/// # Safety
+///
+/// Each vtable must be built by `Vtable::of::<Self, _>`.
+pub unsafe trait Implement: Sized + Send + Sync + 'static {
+    /// A vtable for each implemented interface. The first one also answers
+    /// for `IUnknown`, so its pointer is the object's identity.
+    fn vtables() -> Vec<Vtable>;
+
+    /// Moves `self` into a new COM object and returns its reference as `I`.
+    ///
+    /// Panics if `I` is not one of the implemented interfaces or their bases.
+    fn into_com<I, T>(self) -> T
+    where
+        I: Interface,
+        T: From<ComPtr<I>>,
+    {
+        let unknown = ComObject::create(self);
+        unknown
+            .query_interface::<I>()
+            .map(T::from)
+            .expect("the object doesn't implement the requested interface")
+    }
+}
+
+/// Builds the vtable of `I` for `Self`. Implemented by
+/// `implement_com!(trait ...)` for every type implementing the trait.
+///
+/// # Safety
+///
+/// `Vtbl` must have the layout of `I`'s vtable, starting with its bases',
+/// and every entry must expect `this` to point into an object created from
+/// `Self`.
+pub unsafe trait Implements<I>: Implement {
+    type Vtbl: Send + Sync + 'static;
+
+    /// The IIDs the vtable answers besides `IUnknown`: `I`'s, then those of
+    /// its bases.
+    fn iids() -> Vec<GUID>;
+
+    /// The vtable, with `unknown` for `IUnknown`'s entries.
+    fn vtbl(unknown: IUnknownVtbl) -> Self::Vtbl;
+}
+
+unsafe impl<T: Implement> Implements<IUnknown> for T {
+    type Vtbl = IUnknownVtbl;
+
+    fn iids() -> Vec<GUID> {
+        Vec::new()
+    }
+
+    fn vtbl(unknown: IUnknownVtbl) -> IUnknownVtbl {
+        unknown
+    }
+}
+
+/// One vtable of an object, and the IIDs it answers.
+pub struct Vtable {
+    iids: Vec<GUID>,
+    vtbl: *const c_void,
+    _storage: Box<dyn Any + Send + Sync>,
+}
+
+impl Vtable {
+    pub fn of<T, I>() -> Self
+    where
+        T: Implements<I>,
+    {
+        let unknown = IUnknownVtbl {
+            QueryInterface: query_interface::<T>,
+            AddRef: add_ref::<T>,
+            Release: release::<T>,
+        };
+        let storage = Box::new(T::vtbl(unknown));
+        Vtable {
+            iids: T::iids(),
+            vtbl: &*storage as *const T::Vtbl as *const c_void,
+            _storage: storage,
+        }
+    }
+}
+
+/// What a vtable entry returns if its implementation panics, since unwinding
+/// into the caller is undefined behavior. Implemented for the return types
+/// of the crate's interfaces.
+pub trait Unwound {
+    fn unwound() -> Self;
+}
+
+/// `HRESULT`s, which are `i32`s.
+impl Unwound for i32 {
+    fn unwound() -> Self {
+        E_UNEXPECTED
+    }
+}
+
+impl Unwound for u32 {
+    fn unwound() -> Self {
+        0
+    }
+}
+
+impl Unwound for () {
+    fn unwound() {}
+}
+
+/// Calls `f`, or returns `Unwound::unwound()` if it panics, for the vtable
+/// entries.
+pub fn guard<R: Unwound>(f: impl FnOnce() -> R) -> R {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| R::unwound())
+}
+
+/// The implementation behind `this`, for the vtable entries.
+///
+/// # Safety
+///
+/// `this` must be an interface pointer of a live object created from a `T`.
+pub unsafe fn this<'a, T: Implement, I>(this: *mut I) -> &'a T {
+    &(*object::<T, I>(this)).value
+}
+
+/// An interface pointer points here: the vtable pointer that callers see,
+/// followed by the object it belongs to.
+#[repr(C)]
+struct Slot {
+    vtbl: *const c_void,
+    object: *const c_void,
+}
+
+struct ComObject<T> {
+    refs: AtomicU32,
+    vtables: Vec<Vtable>,
+    /// One per vtable. Filled once, so the addresses don't move.
+    slots: Vec<Slot>,
+    value: T,
+}
+
+impl<T: Implement> ComObject<T> {
+    fn create(value: T) -> ComPtr<IUnknown> {
+        let vtables = T::vtables();
+        assert!(
+            !vtables.is_empty(),
+            "a COM object must implement an interface"
+        );
+        let object = Box::into_raw(Box::new(ComObject {
+            refs: AtomicU32::new(1),
+            vtables,
+            slots: Vec::new(),
+            value,
+        }));
+        let slots = unsafe { &(*object).vtables }
+            .iter()
+            .map(|vtable| Slot {
+                vtbl: vtable.vtbl,
+                object: object as *const c_void,
+            })
+            .collect();
+        unsafe {
+            let object = &mut *object;
+            object.slots = slots;
+            ComPtr::already_addrefed(&object.slots[0] as *const Slot as *mut IUnknown)
+        }
+    }
+}
+
+unsafe fn object<T, I>(this: *mut I) -> *mut ComObject<T> {
+    (*(this as *const Slot)).object as *mut ComObject<T>
+}
+
+unsafe extern "system" fn query_interface<T: Implement>(
+    this: *mut IUnknown,
+    riid: *const GUID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    if ppv.is_null() {
+        return E_POINTER;
+    }
+    let object = &*object::<T, _>(this);
+    let iid = Guid::from(&*riid);
+    let index = if iid == IUnknown::uuidof().into() {
+        Some(0)
+    } else {
+        object
+            .vtables
+            .iter()
+            .position(|vtable| vtable.iids.iter().any(|known| iid == known.into()))
+    };
+    match index {
+        Some(index) => {
+            let slot = &object.slots[index] as *const Slot as *mut IUnknown;
+            add_ref::<T>(slot);
+            *ppv = slot as *mut c_void;
+            S_OK
+        }
+        None => {
+            *ppv = ptr::null_mut();
+            E_NOINTERFACE
+        }
+    }
+}
+
+unsafe extern "system" fn add_ref<T: Implement>(this: *mut IUnknown) -> ULONG {
+    (*object::<T, _>(this)).refs.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+unsafe extern "system" fn release<T: Implement>(this: *mut IUnknown) -> ULONG {
+    let object = object::<T, _>(this);
+    let refs = (*object).refs.fetch_sub(1, Ordering::Release) - 1;
+    if refs == 0 {
+        fence(Ordering::Acquire);
+        guard(|| drop(Box::from_raw(object)));
+    }
+    refs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        impl_comptr, implement_com,
+        mock::{IBar, IBarVtbl, IBaz, IBazVtbl, IFoo, IFooVtbl},
+    };
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicU32},
+            Arc,
+        },
+        thread,
+    };
+
+    implement_com! {
+        trait Foo for IFoo(IFooVtbl): IUnknown {
+            fn value => Value() -> u32;
+        }
+    }
+
+    implement_com! {
+        trait Bar: Foo for IBar(IBarVtbl): IFoo {
+            fn set_value => SetValue(value: u32) -> HRESULT;
+        }
+    }
+
+    implement_com! {
+        trait Baz for IBaz(IBazVtbl): IUnknown {
+            fn double => Double() -> u32;
+        }
+    }
+
+    struct Counter {
+        value: AtomicU32,
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl Foo for Counter {
+        fn value(&self) -> u32 {
+            self.value.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Bar for Counter {
+        fn set_value(&self, value: u32) -> HRESULT {
+            self.value.store(value, Ordering::SeqCst);
+            S_OK
+        }
+    }
+
+    impl Baz for Counter {
+        fn double(&self) -> u32 {
+            self.value() * 2
+        }
+    }
+
+    impl Drop for Counter {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::SeqCst);
+        }
+    }
+
+    implement_com!(Counter: [IBar, IBaz]);
+
+    impl_comptr! { Wrapper: [IBaz, IUnknown] in Agile }
+
+    fn counter(value: u32) -> (Counter, Arc<AtomicBool>) {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let counter = Counter {
+            value: AtomicU32::new(value),
+            dropped: dropped.clone(),
+        };
+        (counter, dropped)
+    }
+
+    #[test]
+    fn vtables() {
+        let (counter, _) = counter(3);
+        let bar: ComPtr<IBar> = counter.into_com();
+        unsafe {
+            // `IBar`'s vtable starts with `IFoo`'s.
+            assert_eq!(bar.Value(), 3);
+            assert_eq!(bar.SetValue(4), S_OK);
+            let foo = bar.query_interface::<IFoo>().unwrap();
+            assert_eq!(foo.as_ptr() as usize, bar.as_ptr() as usize);
+            assert_eq!(foo.Value(), 4);
+
+            let baz = foo.query_interface::<IBaz>().unwrap();
+            assert_ne!(baz.as_ptr() as usize, bar.as_ptr() as usize);
+            assert_eq!(baz.Double(), 8);
+        }
+    }
+
+    #[test]
+    fn identity() {
+        let (counter, _) = counter(0);
+        let baz: Wrapper = counter.into_com();
+        let bar = baz.0.query_interface::<IBar>().unwrap();
+        let from_baz = baz.0.query_interface::<IUnknown>().unwrap();
+        let from_bar = bar.query_interface::<IUnknown>().unwrap();
+        assert!(from_baz == from_bar);
+        assert_eq!(from_bar.as_ptr() as usize, bar.as_ptr() as usize);
+
+        struct Unrelated;
+        impl Baz for Unrelated {
+            fn double(&self) -> u32 {
+                0
+            }
+        }
+        implement_com!(Unrelated: [IBaz]);
+        let unrelated: ComPtr<IBaz> = Unrelated.into_com();
+        assert!(unrelated.query_interface::<IFoo>().is_none());
+        assert!(unrelated.query_interface::<IUnknown>().is_some());
+    }
+
+    #[test]
+    fn refcounting() {
+        let (counter, dropped) = counter(0);
+        let foo: ComPtr<IFoo> = counter.into_com();
+        unsafe {
+            assert_eq!(foo.AddRef(), 2);
+            assert_eq!(foo.Release(), 1);
+        }
+
+        let baz = Wrapper::from(foo.query_interface::<IBaz>().unwrap());
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let baz = baz.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        drop(baz.clone());
+                    }
+                    unsafe { baz.0.Double() }
+                })
+            })
+            .collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), 0);
+        }
+        drop(foo);
+        assert!(!dropped.load(Ordering::SeqCst));
+        drop(baz);
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+    #[test]
+    fn panics() {
+        struct Panicky;
+        impl Foo for Panicky {
+            fn value(&self) -> u32 {
+                panic!("value");
+            }
+        }
+        impl Bar for Panicky {
+            fn set_value(&self, _value: u32) -> HRESULT {
+                panic!("set_value");
+            }
+        }
+        implement_com!(Panicky: [IBar]);
+
+        let bar: ComPtr<IBar> = Panicky.into_com();
+        unsafe {
+            assert_eq!(bar.SetValue(1), E_UNEXPECTED);
+            assert_eq!(bar.Value(), 0);
+            let unknown = bar.query_interface::<IUnknown>().unwrap();
+            assert_eq!(
+                unknown.QueryInterface(&IBar::uuidof(), ptr::null_mut()),
+                E_POINTER
+            );
+        }
+    }
+}
@@ -2,20 +2,28 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+#[cfg(feature = "leak-detector")]
+use crate::leaks;
+use crate::sys::{IUnknown, Interface, E_NOINTERFACE, S_OK};
 use std::ops::{Deref, DerefMut};
 use std::ptr;
-use winapi::{
-    shared::winerror::{E_NOINTERFACE, S_OK},
-    um::unknwnbase::IUnknown,
-    Interface,
-};
 
+/// Neither `Send` nor `Sync`, since that depends on the object; wrappers
+/// declare it with a `threading` model. To use an object on another thread,
+/// send its wrapper, which is `Send` if the object is `Agile`.
+///
+/// `repr(C)` so that `ComPtr`s of different interfaces share a layout, for
+/// `upcast_ref`.
 #[derive(Debug)]
+#[repr(C)]
 pub struct ComPtr<T>
 where
     T: Interface,
 {
     ptr: *mut T,
+    /// The reference's entry in the leak registry, or 0.
+    #[cfg(feature = "leak-detector")]
+    id: u64,
 }
 
 impl<T> ComPtr<T>
@@ -27,16 +35,30 @@ where
             if !ptr.is_null() {
                 (*(ptr as *mut IUnknown)).AddRef();
             }
-            ComPtr { ptr }
+            Self::already_addrefed(ptr)
         }
     }
 
     pub unsafe fn already_addrefed(ptr: *mut T) -> Self {
-        ComPtr { ptr }
+        ComPtr {
+            ptr,
+            #[cfg(feature = "leak-detector")]
+            id: if ptr.is_null() {
+                0
+            } else {
+                leaks::track::<T>()
+            },
+        }
     }
 
     pub unsafe fn getter_addrefs<Q>(&mut self) -> *mut *mut Q {
         self.release();
+        #[cfg(feature = "leak-detector")]
+        {
+            // The callee is about to store a reference here.
+            leaks::untrack(self.id);
+            self.id = leaks::track::<T>();
+        }
         &mut self.ptr as *mut *mut _ as *mut *mut Q
     }
 
@@ -59,7 +81,7 @@ where
             if hr == S_OK {
                 return Some(p);
             }
-            assert!(hr == E_NOINTERFACE);
+            debug_assert!(hr == E_NOINTERFACE);
             None
         }
     }
@@ -80,12 +102,42 @@ where
     pub fn forget(&mut self) -> *mut T {
         let ptr = self.ptr;
         self.ptr = ptr::null_mut();
+        #[cfg(feature = "leak-detector")]
+        {
+            leaks::untrack(self.id);
+            self.id = 0;
+        }
         ptr
     }
 
     pub fn is_null(&self) -> bool {
         self.ptr.is_null()
     }
+
+    /// Converts to a pointer to a base interface, keeping the reference.
+    ///
+    /// # Safety
+    ///
+    /// `T` must derive from `B`. A COM interface's vtable starts with its
+    /// base's, so the pointer is the same.
+    pub unsafe fn upcast<B>(mut self) -> ComPtr<B>
+    where
+        B: Interface,
+    {
+        ComPtr::already_addrefed(self.forget() as *mut B)
+    }
+
+    /// Views as a pointer to a base interface.
+    ///
+    /// # Safety
+    ///
+    /// As for `upcast`.
+    pub unsafe fn upcast_ref<B>(&self) -> &ComPtr<B>
+    where
+        B: Interface,
+    {
+        &*(self as *const ComPtr<T> as *const ComPtr<B>)
+    }
 }
 
 impl<T> Clone for ComPtr<T>
@@ -98,7 +150,7 @@ where
                 self.add_ref();
             }
         }
-        ComPtr { ptr: self.ptr }
+        unsafe { Self::already_addrefed(self.ptr) }
     }
 }
 
@@ -109,6 +161,8 @@ where
     fn default() -> Self {
         Self {
             ptr: ptr::null_mut(),
+            #[cfg(feature = "leak-detector")]
+            id: 0,
         }
     }
 }
@@ -150,7 +204,113 @@ where
         unsafe {
             self.release();
         }
+        #[cfg(feature = "leak-detector")]
+        leaks::untrack(self.id);
     }
 }
 
-unsafe impl<T> Send for ComPtr<T> where T: Interface {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Call, IBar, IBaz, IFoo, Mock};
+
+    #[test]
+    fn clone_and_drop() {
+        let (foo, mock) = Mock::builder().implements::<IFoo>().build::<IFoo>();
+        let clone = foo.clone();
+        assert!(clone == foo);
+        assert_eq!(mock.ref_count(), 2);
+        drop(foo);
+        assert_eq!(mock.ref_count(), 1);
+        drop(clone);
+        assert!(mock.is_destroyed());
+        assert_eq!(
+            mock.calls(),
+            [Call::AddRef(2), Call::Release(1), Call::Release(0)]
+        );
+        assert!(!mock.used_after_destroy());
+    }
+
+    #[test]
+    fn raw_pointers() {
+        let (foo, mock) = Mock::builder().implements::<IFoo>().build::<IFoo>();
+
+        // `from_ptr` takes a new reference, `already_addrefed` adopts one.
+        let shared = ComPtr::from_ptr(foo.as_ptr());
+        assert_eq!(mock.ref_count(), 2);
+        let mut owner = foo.clone();
+        let raw = owner.forget();
+        assert!(owner.is_null());
+        assert_eq!(mock.ref_count(), 3);
+        drop(owner);
+        assert_eq!(mock.ref_count(), 3);
+        let adopted = unsafe { ComPtr::already_addrefed(raw) };
+        drop((foo, shared, adopted));
+        assert!(mock.is_destroyed());
+        assert!(!mock.used_after_destroy());
+    }
+
+    #[test]
+    fn getter_addrefs() {
+        let (foo, old) = Mock::builder().implements::<IFoo>().build::<IFoo>();
+        let (baz, new) = Mock::builder()
+            .implements::<IFoo>()
+            .implements::<IBaz>()
+            .build::<IBaz>();
+
+        // The out parameter releases what it held and takes the result's
+        // reference, as a COM method returning an interface would.
+        let mut out = foo;
+        unsafe {
+            let hr = (*baz.as_ptr()).QueryInterface(&IFoo::uuidof(), out.getter_addrefs());
+            assert_eq!(hr, S_OK);
+        }
+        assert!(old.is_destroyed());
+        assert_eq!(new.ref_count(), 2);
+        assert_eq!(unsafe { out.Value() }, 0);
+        drop((out, baz));
+        assert!(new.is_destroyed());
+    }
+
+    #[test]
+    fn upcasts() {
+        let (bar, mock) = Mock::builder()
+            .implements::<IBar>()
+            .value(2)
+            .build::<IBar>();
+
+        // Neither view nor conversion calls into the object.
+        let foo: &ComPtr<IFoo> = unsafe { bar.upcast_ref() };
+        assert_eq!(unsafe { foo.Value() }, 2);
+        let foo: ComPtr<IFoo> = unsafe { bar.upcast() };
+        assert_eq!(mock.ref_count(), 1);
+        assert_eq!(mock.calls(), [Call::Method("Value")]);
+        drop(foo);
+        assert!(mock.is_destroyed());
+    }
+
+    #[test]
+    fn query_interface() {
+        let (foo, mock) = Mock::builder()
+            .implements::<IBar>()
+            .value(7)
+            .build::<IFoo>();
+        let bar = foo.query_interface::<IBar>().unwrap();
+        assert_eq!(unsafe { bar.Value() }, 7);
+        assert!(foo.query_interface::<IBaz>().is_none());
+        assert_eq!(mock.ref_count(), 2);
+        assert_eq!(
+            mock.calls(),
+            [
+                Call::QueryInterface("IBar", true),
+                Call::AddRef(2),
+                Call::Method("Value"),
+                Call::QueryInterface("IBaz", false),
+            ]
+        );
+
+        assert!(ComPtr::<IFoo>::default()
+            .query_interface::<IBar>()
+            .is_none());
+    }
+}
@@ -2,10 +2,13 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use std::ptr;
+use std::{cell::Cell, ptr, rc::Rc};
 
 use super::{Bitmap1, BitmapProperties1, BrushProperties, Color, Ellipse, RectF, SolidColorBrush};
-use crate::{impl_comptr, impl_interface, opt_ptr, AsPtr, ComPtr};
+use crate::{
+    call, hr, impl_comptr, impl_interface, opt_ptr, trace_span, AsPtr, Canvas, ComPtr, Rect,
+    Result,
+};
 use winapi::{
     shared::dxgi::IDXGISurface,
     um::{
@@ -17,7 +20,14 @@ use winapi::{
 };
 
 impl_comptr! {  DeviceContext: [ID2D1DeviceContext, ID2D1RenderTarget, ID2D1Resource, IUnknown] }
-impl_comptr! { DeviceContext1: [ID2D1DeviceContext1, ID2D1DeviceContext, ID2D1RenderTarget, ID2D1Resource, IUnknown] }
+// The field is the brush `Canvas` draws with, shared by clones.
+impl_comptr! { DeviceContext1(Rc<Cell<Option<SolidColorBrush>>>): [ID2D1DeviceContext1, ID2D1DeviceContext, ID2D1RenderTarget, ID2D1Resource, IUnknown] }
+
+impl From<ComPtr<ID2D1DeviceContext1>> for DeviceContext1 {
+    fn from(native: ComPtr<ID2D1DeviceContext1>) -> Self {
+        (native, Rc::default()).into()
+    }
+}
 
 // ID2D1RenderTarget
 impl_interface! {
@@ -26,46 +36,40 @@ impl_interface! {
             &self,
             color: &Color,
             brush_properties: impl Into<Option<&'a BrushProperties>>,
-        ) -> SolidColorBrush {
-            unsafe {
-                let mut native = ComPtr::<ID2D1SolidColorBrush>::default();
-                let hr = self.0.CreateSolidColorBrush(
-                    &**color,
-                    opt_ptr(brush_properties.into()),
-                    native.getter_addrefs(),
-                );
-                assert!(hr == 0);
-                SolidColorBrush(native)
-            }
+        ) -> Result<SolidColorBrush> {
+            let mut native = ComPtr::<ID2D1SolidColorBrush>::default();
+            hr!(self.0.CreateSolidColorBrush(
+                &**color,
+                opt_ptr(brush_properties.into()),
+                native.getter_addrefs(),
+            ))?;
+            Ok(native.into())
         }
 
         pub fn clear<'a>(&self, color: impl Into<Option<&'a Color>>) {
-            unsafe {
-                self.0.Clear(opt_ptr(color.into()));
-            }
+            call!(self.0.Clear(opt_ptr(color.into())));
         }
 
         pub fn fill_rectangle(&self, rect: &RectF, brush: &impl AsPtr<ID2D1Brush>) {
-            unsafe {
-                self.0.FillRectangle(&**rect, brush.as_ptr());
-            }
+            call!(self.0.FillRectangle(&**rect, brush.as_ptr()));
+        }
+
+        pub fn draw_rectangle(&self, rect: &RectF, brush: &impl AsPtr<ID2D1Brush>, stroke_width: f32) {
+            call!(self.0.DrawRectangle(&**rect, brush.as_ptr(), stroke_width, ptr::null_mut()));
         }
 
         pub fn fill_ellipse(&self, ellipse: &Ellipse, brush: &impl AsPtr<ID2D1Brush>) {
-            unsafe {
-                self.0.FillEllipse(ellipse, brush.as_ptr());
-            }
+            call!(self.0.FillEllipse(ellipse, brush.as_ptr()));
         }
 
         pub fn begin_draw(&self) {
-            unsafe { self.0.BeginDraw() }
+            call!(self.0.BeginDraw())
         }
 
-        pub fn end_draw(&self) {
-            unsafe {
-                let hr = self.0.EndDraw(ptr::null_mut(), ptr::null_mut());
-                assert!(hr == 0);
-            }
+        /// Finishes drawing, reporting errors from any drawing call since
+        /// `begin_draw`, such as `D2DERR_RECREATE_TARGET`.
+        pub fn end_draw(&self) -> Result<()> {
+            hr!(self.0.EndDraw(ptr::null_mut(), ptr::null_mut()))
         }
     }
 }
@@ -76,30 +80,72 @@ impl_interface! {
             &self,
             surface: &impl AsPtr<IDXGISurface>,
             bitmap_properties: &BitmapProperties1,
-        ) -> Bitmap1 {
-            unsafe {
-                //let mut native = Bitmap1::default();
-                let mut native = ComPtr::<ID2D1Bitmap1>::default();
-                let hr = self.0.CreateBitmapFromDxgiSurface(
-                    surface.as_ptr(),
-                    bitmap_properties,
-                    native.getter_addrefs(),
-                );
-                assert!(hr == 0);
-                Bitmap1(native)
-            }
+        ) -> Result<Bitmap1> {
+            //let mut native = Bitmap1::default();
+            let mut native = ComPtr::<ID2D1Bitmap1>::default();
+            hr!(self.0.CreateBitmapFromDxgiSurface(
+                surface.as_ptr(),
+                bitmap_properties,
+                native.getter_addrefs(),
+            ))?;
+            Ok(native.into())
         }
 
-        pub fn draw(&self, mut f: impl FnMut(&DeviceContext1)) {
+        /// Calls `f` between `begin_draw` and `end_draw`. An error from `f`
+        /// is returned once drawing has ended.
+        pub fn draw(&self, mut f: impl FnMut(&DeviceContext1) -> Result<()>) -> Result<()> {
+            let _span = trace_span!("draw");
             self.begin_draw();
-            f(self);
-            self.end_draw();
+            let drawn = f(self);
+            self.end_draw()?;
+            drawn
         }
 
         pub fn set_target(&self, image: &impl AsPtr<ID2D1Image>) {
-            unsafe {
-                self.0.SetTarget(image.as_ptr());
-            }
+            call!(self.0.SetTarget(image.as_ptr()));
         }
     }
 }
+
+impl Canvas for DeviceContext1 {
+    fn clear(&mut self) {
+        DeviceContext1::clear(self, &Color::with_alpha([0.0, 0.0, 0.0, 0.0]));
+    }
+
+    fn fill_rect(&mut self, rect: &Rect, color: [f32; 4]) {
+        self.with_brush(color, |dc, brush| dc.fill_rectangle(&(*rect).into(), brush));
+    }
+
+    fn stroke_rect(&mut self, rect: &Rect, color: [f32; 4], width: f32) {
+        // Direct2D centers the stroke on the outline, move it inside.
+        let inset = width / 2.0;
+        let rect = RectF::new(
+            rect.left as f32 + inset,
+            rect.top as f32 + inset,
+            rect.right as f32 - inset,
+            rect.bottom as f32 - inset,
+        );
+        self.with_brush(color, |dc, brush| dc.draw_rectangle(&rect, brush, width));
+    }
+}
+
+impl DeviceContext1 {
+    /// Calls `f` with the cached brush set to `color`, creating the brush on
+    /// first use. A brush only fails to be created when the device is lost,
+    /// which `end_draw` reports, so `f` can be skipped.
+    fn with_brush(&self, color: [f32; 4], f: impl FnOnce(&Self, &SolidColorBrush)) {
+        let color = Color::with_alpha(color);
+        let brush = match self.1.take() {
+            Some(brush) => {
+                brush.set_color(&color);
+                brush
+            }
+            None => match self.create_solid_color_brush(&color, None) {
+                Ok(brush) => brush,
+                Err(_) => return,
+            },
+        };
+        f(self, &brush);
+        self.1.set(Some(brush));
+    }
+}
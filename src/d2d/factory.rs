@@ -1,5 +1,5 @@
 use super::FactoryOptions;
-use crate::{comptr::ComPtr, impl_comptr, opt_ptr, AsPtr};
+use crate::{comptr::ComPtr, hr, impl_comptr, opt_ptr, AsPtr, Result};
 use winapi::{
     shared::dxgi::IDXGIDevice,
     um::{
@@ -12,37 +12,33 @@ use winapi::{
 
 impl_comptr! { Factory2: [ID2D1Factory2, ID2D1Factory1, ID2D1Factory]}
 
-pub fn create_single_threaded_factory<'a, I, T>(options: impl Into<Option<&'a FactoryOptions>>) -> T
+pub fn create_single_threaded_factory<'a, I, T>(
+    options: impl Into<Option<&'a FactoryOptions>>,
+) -> Result<T>
 where
     T: From<ComPtr<I>>,
     I: Interface,
 {
-    unsafe {
-        let mut native = ComPtr::<I>::default();
-        let hr = D2D1CreateFactory(
-            D2D1_FACTORY_TYPE_SINGLE_THREADED,
-            &I::uuidof(),
-            opt_ptr(options.into()),
-            native.getter_addrefs(),
-        );
-        assert!(hr == 0);
-        native.into()
-    }
+    let mut native = ComPtr::<I>::default();
+    hr!(D2D1CreateFactory(
+        D2D1_FACTORY_TYPE_SINGLE_THREADED,
+        &I::uuidof(),
+        opt_ptr(options.into()),
+        native.getter_addrefs(),
+    ))?;
+    Ok(native.into())
 }
 
 impl Factory2 {
-    pub fn create_device<I, T>(&self, device: &impl AsPtr<IDXGIDevice>) -> T
+    pub fn create_device<I, T>(&self, device: &impl AsPtr<IDXGIDevice>) -> Result<T>
     where
         T: From<ComPtr<I>>,
         I: Interface,
     {
-        unsafe {
-            let mut native = ComPtr::<I>::default();
-            let hr = self
-                .0
-                .CreateDevice(device.as_ptr(), native.getter_addrefs());
-            assert!(hr == 0);
-            native.into()
-        }
+        let mut native = ComPtr::<I>::default();
+        hr!(self
+            .0
+            .CreateDevice(device.as_ptr(), native.getter_addrefs()))?;
+        Ok(native.into())
     }
 }
@@ -1,4 +1,4 @@
-use crate::{hr, impl_comptr, impl_newtype, opt_ptr, AsPtr, ComPtr};
+use crate::{call, hr, impl_comptr, impl_newtype, opt_ptr, AsPtr, ComPtr, Result};
 use bitflags::bitflags;
 use derive_newtype::NewType;
 use std::ptr;
@@ -18,17 +18,17 @@ use winapi::um::{
     unknwnbase::IUnknown,
 };
 
-impl_comptr! { Buffer: [ID3D11Buffer, ID3D11Resource, ID3D11DeviceChild, IUnknown] }
-impl_comptr! { Device: [ID3D11Device, IUnknown] }
+impl_comptr! { Buffer: [ID3D11Buffer, ID3D11Resource, ID3D11DeviceChild, IUnknown] in Agile }
+impl_comptr! { Device: [ID3D11Device, IUnknown] in Agile }
 impl_comptr! { DeviceContext: [ID3D11DeviceContext, ID3D11DeviceChild, IUnknown] }
-impl_comptr! { InputLayout: [ID3D11InputLayout, ID3D11DeviceChild, IUnknown] }
-impl_comptr! { Multithread: [ID3D11Multithread, IUnknown] }
-impl_comptr! { PixelShader: [ID3D11PixelShader, ID3D11DeviceChild, IUnknown] }
-impl_comptr! { RenderTargetView: [ID3D11RenderTargetView, ID3D11View, ID3D11DeviceChild, IUnknown] }
-impl_comptr! { SamplerState: [ID3D11SamplerState, ID3D11DeviceChild, IUnknown] }
-impl_comptr! { ShaderResourceView: [ID3D11ShaderResourceView, ID3D11View, ID3D11DeviceChild, IUnknown] }
-impl_comptr! { Texture2d: [ID3D11Texture2D, ID3D11Resource, ID3D11DeviceChild, IUnknown] }
-impl_comptr! { VertexShader: [ID3D11VertexShader, ID3D11DeviceChild, IUnknown] }
+impl_comptr! { InputLayout: [ID3D11InputLayout, ID3D11DeviceChild, IUnknown] in Agile }
+impl_comptr! { Multithread: [ID3D11Multithread, IUnknown] in Agile }
+impl_comptr! { PixelShader: [ID3D11PixelShader, ID3D11DeviceChild, IUnknown] in Agile }
+impl_comptr! { RenderTargetView: [ID3D11RenderTargetView, ID3D11View, ID3D11DeviceChild, IUnknown] in Agile }
+impl_comptr! { SamplerState: [ID3D11SamplerState, ID3D11DeviceChild, IUnknown] in Agile }
+impl_comptr! { ShaderResourceView: [ID3D11ShaderResourceView, ID3D11View, ID3D11DeviceChild, IUnknown] in Agile }
+impl_comptr! { Texture2d: [ID3D11Texture2D, ID3D11Resource, ID3D11DeviceChild, IUnknown] in Agile }
+impl_comptr! { VertexShader: [ID3D11VertexShader, ID3D11DeviceChild, IUnknown] in Agile }
 
 bitflags! {
     pub struct CreateDevice: u32 {
@@ -49,7 +49,7 @@ impl_newtype! {
     pub struct Viewport(D3D11_VIEWPORT);
 }
 
-pub fn create_hardware_device(create_flags: CreateDevice) -> Device {
+pub fn create_hardware_device(create_flags: CreateDevice) -> Result<Device> {
     let mut device = ComPtr::<ID3D11Device>::default();
     hr!(D3D11CreateDevice(
         ptr::null_mut(),
@@ -62,8 +62,8 @@ pub fn create_hardware_device(create_flags: CreateDevice) -> Device {
         device.getter_addrefs(),
         ptr::null_mut(),
         ptr::null_mut(),
-    ));
-    device.into()
+    ))?;
+    Ok(device.into())
 }
 
 impl Device {
@@ -71,13 +71,13 @@ impl Device {
         &self,
         desc: &BufferDesc,
         initial_data: impl Into<Option<&'a SubresourceData>>,
-    ) -> Buffer {
+    ) -> Result<Buffer> {
         let initial_data = initial_data.into();
         let mut buffer = ComPtr::<ID3D11Buffer>::default();
         hr!(self
             .0
-            .CreateBuffer(&**desc, opt_ptr(initial_data), buffer.getter_addrefs()));
-        buffer.into()
+            .CreateBuffer(&**desc, opt_ptr(initial_data), buffer.getter_addrefs()))?;
+        Ok(buffer.into())
     }
     /*
         virtual HRESULT STDMETHODCALLTYPE CreateTexture1D( 
@@ -109,12 +109,12 @@ impl Device {
         &self,
         resource: impl AsPtr<ID3D11Resource>,
         desc: &RenderTargetViewDesc,
-    ) -> RenderTargetView {
+    ) -> Result<RenderTargetView> {
         let mut view = ComPtr::<ID3D11RenderTargetView>::default();
         hr!(self
             .0
-            .CreateRenderTargetView(resource.as_ptr(), &**desc, view.getter_addrefs()));
-        view.into()
+            .CreateRenderTargetView(resource.as_ptr(), &**desc, view.getter_addrefs()))?;
+        Ok(view.into())
     }
     /*
         virtual HRESULT STDMETHODCALLTYPE CreateDepthStencilView( 
@@ -122,7 +122,7 @@ impl Device {
             _In_opt_  const D3D11_DEPTH_STENCIL_VIEW_DESC *pDesc,
             _COM_Outptr_opt_  ID3D11DepthStencilView **ppDepthStencilView) = 0;
     */        
-    pub fn create_input_layout(&self, descs: &[InputElementDesc], byte_code: &[u8]) -> InputLayout {
+    pub fn create_input_layout(&self, descs: &[InputElementDesc], byte_code: &[u8]) -> Result<InputLayout> {
         let mut layout = ComPtr::<ID3D11InputLayout>::default();
         hr!(self.0.CreateInputLayout(
             descs.as_ptr() as *const _,
@@ -130,19 +130,19 @@ impl Device {
             byte_code.as_ptr() as *const _,
             byte_code.len() as _,
             layout.getter_addrefs()
-        ));
-        layout.into()
+        ))?;
+        Ok(layout.into())
     }
 
-    pub fn create_vertex_shader(&self, byte_code: &[u8]) -> VertexShader {
+    pub fn create_vertex_shader(&self, byte_code: &[u8]) -> Result<VertexShader> {
         let mut shader = ComPtr::<ID3D11VertexShader>::default();
         hr!(self.0.CreateVertexShader(
             byte_code.as_ptr() as *const _,
             byte_code.len() as _,
             ptr::null_mut(),
             shader.getter_addrefs()
-        ));
-        shader.into()
+        ))?;
+        Ok(shader.into())
     }
 
     /*
@@ -163,15 +163,15 @@ impl Device {
             _In_opt_  ID3D11ClassLinkage *pClassLinkage,
             _COM_Outptr_opt_  ID3D11GeometryShader **ppGeometryShader) = 0;
     */
-    pub fn create_pixel_shader(&self, byte_code: &[u8]) -> PixelShader {
+    pub fn create_pixel_shader(&self, byte_code: &[u8]) -> Result<PixelShader> {
         let mut shader = ComPtr::<ID3D11PixelShader>::default();
         hr!(self.0.CreatePixelShader(
             byte_code.as_ptr() as *const _,
             byte_code.len() as _,
             ptr::null_mut(),
             shader.getter_addrefs()
-        ));
-        shader.into()
+        ))?;
+        Ok(shader.into())
     }
     /*    
         virtual HRESULT STDMETHODCALLTYPE CreateHullShader( 
@@ -207,10 +207,10 @@ impl Device {
             _In_  const D3D11_RASTERIZER_DESC *pRasterizerDesc,
             _COM_Outptr_opt_  ID3D11RasterizerState **ppRasterizerState) = 0;
     */        
-    pub fn create_sampler_state(&self, desc: &SamplerDesc) -> SamplerState {
+    pub fn create_sampler_state(&self, desc: &SamplerDesc) -> Result<SamplerState> {
         let mut ss = ComPtr::<ID3D11SamplerState>::default();
-        hr!(self.0.CreateSamplerState(&**desc, ss.getter_addrefs()));
-        ss.into()
+        hr!(self.0.CreateSamplerState(&**desc, ss.getter_addrefs()))?;
+        Ok(ss.into())
     }
     /*
         virtual HRESULT STDMETHODCALLTYPE CreateQuery( 
@@ -287,7 +287,7 @@ impl Device {
     */
     pub fn immediate_context(&self) -> DeviceContext {
         let mut context = ComPtr::<ID3D11DeviceContext>::default();
-        unsafe { self.0.GetImmediateContext(context.getter_addrefs()) };
+        call!(self.0.GetImmediateContext(context.getter_addrefs()));
         context.into()
     }
     /*    
@@ -312,9 +312,7 @@ impl DeviceContext {
     */
     pub fn ps_set_shader<'a>(&self, shader: impl Into<Option<&'a PixelShader>>) {
         let shader = shader.into();
-        unsafe {
-            self.0.PSSetShader(shader.as_ptr(), ptr::null(), 0);
-        }
+        call!(self.0.PSSetShader(shader.as_ptr(), ptr::null(), 0));
     }
     /*
         virtual void STDMETHODCALLTYPE PSSetSamplers(
@@ -324,9 +322,7 @@ impl DeviceContext {
     */
     pub fn vs_set_shader<'a>(&self, shader: impl Into<Option<&'a VertexShader>>) {
         let shader = shader.into();
-        unsafe {
-            self.0.VSSetShader(shader.as_ptr(), ptr::null(), 0);
-        }
+        call!(self.0.VSSetShader(shader.as_ptr(), ptr::null(), 0));
     }
 
     pub fn draw_indexed(
@@ -335,10 +331,10 @@ impl DeviceContext {
         start_index_location: u32,
         base_vertex_location: i32,
     ) {
-        unsafe {
-            self.0
-                .DrawIndexed(index_count, start_index_location, base_vertex_location);
-        }
+        call!(
+            self.0.DrawIndexed(index_count, start_index_location, base_vertex_location),
+            index_count
+        );
     }
     /*
         virtual void STDMETHODCALLTYPE Draw(
@@ -439,13 +435,11 @@ impl DeviceContext {
             _In_reads_opt_(NumSamplers)  ID3D11SamplerState *const *ppSamplers) = 0;
     */
     pub fn om_set_render_targets(&self, views: &[RenderTargetView]) {
-        unsafe {
-            self.0.OMSetRenderTargets(
-                views.len() as _,
-                views.as_ptr() as *const _,
-                ptr::null_mut(),
-            )
-        }
+        call!(self.0.OMSetRenderTargets(
+            views.len() as _,
+            views.as_ptr() as *const _,
+            ptr::null_mut(),
+        ))
     }
     /*
         virtual void STDMETHODCALLTYPE OMSetRenderTargetsAndUnorderedAccessViews(
@@ -493,10 +487,7 @@ impl DeviceContext {
             _In_opt_  ID3D11RasterizerState *pRasterizerState) = 0;
     */
     pub fn rs_set_viewports(&self, viewports: &[Viewport]) {
-        unsafe {
-            self.0
-                .RSSetViewports(viewports.len() as _, viewports.as_ptr() as *const _)
-        };
+        call!(self.0.RSSetViewports(viewports.len() as _, viewports.as_ptr() as *const _));
     }
     /*
         virtual void STDMETHODCALLTYPE RSSetScissorRects(
@@ -831,7 +822,7 @@ impl DeviceContext {
 
 impl Multithread {
     pub fn set_multithread_protected(&self, protect: bool) -> bool {
-        let result = unsafe { self.0.SetMultithreadProtected(protect as _) };
+        let result = call!(self.0.SetMultithreadProtected(protect as _), protect);
         result != 0
     }
 }
@@ -843,7 +834,7 @@ pub struct Texture2dDesc(D3D11_TEXTURE2D_DESC);
 impl Texture2d {
     pub fn desc(&self) -> Texture2dDesc {
         let mut desc = D3D11_TEXTURE2D_DESC::default();
-        unsafe { self.0.GetDesc(&mut desc) };
+        call!(self.0.GetDesc(&mut desc));
         desc.into()
     }
 }
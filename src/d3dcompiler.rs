@@ -1,65 +1,74 @@
-use crate::{ComPtr};
+use crate::{call, hr, ComPtr, Result};
 use std::{ffi::CString, ptr, slice};
-use winapi::um::{
-    d3dcompiler::D3DCompile,
-    d3dcommon::ID3DBlob,
-};
+use winapi::um::{d3dcommon::ID3DBlob, d3dcompiler::D3DCompile};
 
 #[derive(Default)]
 pub struct Compiler {
     src: Vec<u8>,
-    source_name: Option<CString>,
-    entry_point: Option<CString>,
-    target: Option<CString>,
+    source_name: Option<Vec<u8>>,
+    entry_point: Option<Vec<u8>>,
+    target: Option<Vec<u8>>,
 }
 
 impl Compiler {
     pub fn text(mut self, src: impl Into<Vec<u8>>) -> Self {
-        self.src = CString::new(src.into()).unwrap().into_bytes_with_nul();
+        self.src = src.into();
         self
     }
 
     pub fn name(mut self, name: impl Into<Vec<u8>>) -> Self {
-        self.source_name = Some(CString::new(name.into()).unwrap());
+        self.source_name = Some(name.into());
         self
     }
 
     pub fn entry_point(mut self, name: impl Into<Vec<u8>>) -> Self {
-        self.entry_point = Some(CString::new(name.into()).unwrap());
+        self.entry_point = Some(name.into());
         self
     }
-    
+
     pub fn target(mut self, target: impl Into<Vec<u8>>) -> Self {
-        self.target = dbg!(Some(CString::new(target.into()).unwrap()));
+        self.target = Some(target.into());
         self
     }
-    
-    pub fn compile(self) -> Result<Vec<u8>, String> {
-        let source_name = self.source_name.as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null());
-        let entry_point = self.entry_point.as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null());
-        let target = self.target.as_ref().map(|s| dbg!(s).as_ptr()).unwrap_or(ptr::null());
+
+    /// Compiles the shader to byte code. A failure carries the compiler's
+    /// messages, if it gave any, as the error's context, and a string with
+    /// a NUL fails with `E_INVALIDARG`.
+    pub fn compile(self) -> Result<Vec<u8>> {
+        let src = CString::new(self.src)?;
+        let source_name = self.source_name.map(CString::new).transpose()?;
+        let entry_point = self.entry_point.map(CString::new).transpose()?;
+        let target = self.target.map(CString::new).transpose()?;
 
         let mut code = ComPtr::<ID3DBlob>::default();
         let mut error_msgs = ComPtr::<ID3DBlob>::default();
-
-        let hr = unsafe { D3DCompile(
-            self.src.as_ptr() as *const _,
-            self.src.len(),
-            source_name,
+        let result = hr!(D3DCompile(
+            src.as_ptr() as *const _,
+            src.as_bytes().len(),
+            source_name.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
             ptr::null(),
             ptr::null_mut(),
-            entry_point,
-            target,
+            entry_point.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+            target.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
             0,
             0,
             code.getter_addrefs(),
-            error_msgs.getter_addrefs()) };
-        if hr == 0 {
-            let code = unsafe { slice::from_raw_parts(code.GetBufferPointer() as *const _,code.GetBufferSize() as _) };
-            Ok(code.to_vec())
-        } else {
-            let errors = unsafe { slice::from_raw_parts(error_msgs.GetBufferPointer() as *const _, error_msgs.GetBufferSize() as _) };
-            Err(unsafe { CString::from_vec_unchecked(errors.to_vec()) }.to_string_lossy().into_owned())
+            error_msgs.getter_addrefs(),
+        ));
+        if let Err(error) = result {
+            if error_msgs.is_null() {
+                return Err(error);
+            }
+            let errors = blob_bytes(&error_msgs);
+            let errors = errors.split(|&b| b == 0).next().unwrap_or(errors);
+            return Err(error.with_context(String::from_utf8_lossy(errors).trim_end()));
         }
+        Ok(blob_bytes(&code).to_vec())
     }
 }
+
+fn blob_bytes(blob: &ComPtr<ID3DBlob>) -> &[u8] {
+    let data = call!(blob.GetBufferPointer());
+    let len = call!(blob.GetBufferSize());
+    unsafe { slice::from_raw_parts(data as *const u8, len) }
+}
@@ -1,5 +1,5 @@
 use super::Target;
-use crate::{impl_comptr, impl_interface, winuser, ComPtr};
+use crate::{hr, impl_comptr, impl_interface, scheduler, winuser, ComPtr, Result};
 use winapi::um::{
     dcomp::{
         IDCompositionDesktopDevice, IDCompositionDevice, IDCompositionDevice2,
@@ -8,6 +8,8 @@ use winapi::um::{
     unknwnbase::IUnknown,
 };
 
+// A device and the objects it creates share the batch of changes that the
+// next `Commit` sends, and aren't thread-safe.
 impl_comptr! { Device: [IDCompositionDevice, IUnknown] }
 impl_comptr! { Device2: [IDCompositionDevice2, IUnknown] }
 impl_comptr! { DesktopDevice: [IDCompositionDesktopDevice, IDCompositionDevice2, IUnknown] }
@@ -15,53 +17,58 @@ impl_comptr! { DeviceDebug: [IDCompositionDeviceDebug, IUnknown] }
 
 impl_interface! {
     impl [Device, DesktopDevice] {
-        pub fn create_target_for_hwnd(&self, window: winuser::Window, top_most: bool) -> Target {
-            unsafe {
-                let mut target = ComPtr::<IDCompositionTarget>::default();
-                let hr = self.0.CreateTargetForHwnd(
-                    window.as_hwnd(),
-                    top_most as i32,
-                    target.getter_addrefs(),
-                );
-                assert!(hr == 0);
-                target.into()
-            }
+        pub fn create_target_for_hwnd(
+            &self,
+            window: winuser::Window,
+            top_most: bool,
+        ) -> Result<Target> {
+            let mut target = ComPtr::<IDCompositionTarget>::default();
+            hr!(self.0.CreateTargetForHwnd(
+                window.as_hwnd(),
+                top_most as i32,
+                target.getter_addrefs(),
+            ))?;
+            Ok(target.into())
         }
     }
 }
 
 impl_interface! {
     impl [Device, Device2, DesktopDevice] {
-        pub fn commit(&self) {
-            unsafe {
-                let hr = self.0.Commit();
-                assert!(hr == 0);
-            }
+        pub fn commit(&self) -> Result<()> {
+            hr!(self.0.Commit())
         }
 
-        pub fn wait_for_commit_completion(&self) {
-            unsafe {
-                let hr = self.0.WaitForCommitCompletion();
-                assert!(hr == 0);
-            }
+        pub fn wait_for_commit_completion(&self) -> Result<()> {
+            hr!(self.0.WaitForCommitCompletion())
         }
     }
 }
 
+macro_rules! impl_commit {
+    ($($name:ident),*) => {
+        $(impl scheduler::Commit for $name {
+            fn commit(&self) -> Result<()> {
+                $name::commit(self)
+            }
+
+            fn wait_for_commit_completion(&self) -> Result<()> {
+                $name::wait_for_commit_completion(self)
+            }
+        })*
+    };
+}
+
+impl_commit! { Device, Device2, DesktopDevice }
+
 impl_interface! {
     impl DeviceDebug {
-        pub fn disable_debug_counters(&self) {
-            unsafe {
-                let hr = self.0.DisableDebugCounters();
-                assert!(hr == 0);
-            }
+        pub fn disable_debug_counters(&self) -> Result<()> {
+            hr!(self.0.DisableDebugCounters())
         }
 
-        pub fn enable_debug_counters(&self) {
-            unsafe {
-                let hr = self.0.EnableDebugCounters();
-                assert!(hr == 0);
-            }
+        pub fn enable_debug_counters(&self) -> Result<()> {
+            hr!(self.0.EnableDebugCounters())
         }
     }
 }
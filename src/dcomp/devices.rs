@@ -0,0 +1,80 @@
+use super::{create_device_2, DesktopDevice};
+use crate::{d2d, d3d11, device::DeviceFactory, dxgi, mf, Result};
+
+/// One generation of the device chain, created in dependency order.
+#[derive(Clone, Debug)]
+pub struct Devices {
+    pub d3d11: d3d11::Device,
+    pub dxgi: dxgi::Device,
+    pub d2d: d2d::Device1,
+    pub d2d_context: d2d::DeviceContext1,
+    pub dcomp: DesktopDevice,
+}
+
+/// Builds `Devices` for a `device::DeviceManager`.
+///
+/// The Direct2D factory does not depend on the device and is kept across
+/// generations. So is the Media Foundation device manager, if one is set:
+/// source readers hold on to it, and each new Direct3D device is handed to
+/// it with `reset_device`.
+pub struct DeviceChain {
+    flags: d3d11::CreateDevice,
+    d2d_factory: d2d::Factory2,
+    video: Option<mf::DXGIDeviceManager>,
+}
+
+impl DeviceChain {
+    /// `flags` are passed to `create_hardware_device`; `BGRA_SUPPORT` is
+    /// always added since Direct2D requires it.
+    pub fn new(flags: d3d11::CreateDevice, options: &d2d::FactoryOptions) -> Result<Self> {
+        Ok(Self {
+            flags: flags | d3d11::CreateDevice::BGRA_SUPPORT,
+            d2d_factory: d2d::create_single_threaded_factory(options)?,
+            video: None,
+        })
+    }
+
+    /// Resets `manager` to each new Direct3D device. Media Foundation uses
+    /// the device from several threads, so multithread protection is turned
+    /// on, and `flags` should include `VIDEO_SUPPORT`.
+    pub fn with_video(mut self, manager: mf::DXGIDeviceManager) -> Self {
+        self.video = Some(manager);
+        self
+    }
+
+    pub fn d2d_factory(&self) -> &d2d::Factory2 {
+        &self.d2d_factory
+    }
+
+    pub fn video(&self) -> Option<&mf::DXGIDeviceManager> {
+        self.video.as_ref()
+    }
+}
+
+impl DeviceFactory for DeviceChain {
+    type Devices = Devices;
+
+    fn create(&mut self) -> Result<Devices> {
+        let d3d11 = d3d11::create_hardware_device(self.flags)?;
+        let dxgi: dxgi::Device = d3d11.downcast()?;
+        let d2d: d2d::Device1 = self.d2d_factory.create_device(&dxgi)?;
+        let d2d_context = d2d.create_device_context()?;
+        let dcomp: DesktopDevice = create_device_2(&dxgi)?;
+
+        if let Some(video) = &self.video {
+            let multithread: Option<d3d11::Multithread> = d3d11.as_();
+            if let Some(multithread) = multithread {
+                multithread.set_multithread_protected(true);
+            }
+            video.reset_device(&d3d11)?;
+        }
+
+        Ok(Devices {
+            d3d11,
+            dxgi,
+            d2d,
+            d2d_context,
+            dcomp,
+        })
+    }
+}
@@ -1,9 +1,12 @@
 mod device;
+mod devices;
+mod overlay;
 mod surface;
 mod target;
+mod tree;
 mod visual;
 
-use crate::{comptr::ComPtr, AsPtr};
+use crate::{comptr::ComPtr, hr, AsPtr, Result};
 use winapi::{
     um::{
         dcomp::{DCompositionCreateDevice, DCompositionCreateDevice2, DCompositionCreateDevice3},
@@ -13,27 +16,27 @@ use winapi::{
 };
 
 pub use device::{DesktopDevice, Device, Device2, DeviceDebug};
+pub use devices::{DeviceChain, Devices};
+pub use overlay::PaintOverlay;
 pub use surface::{Surface, VirtualSurface};
 pub use target::Target;
+pub use tree::Content;
 pub use visual::{Insert, Visual, Visual2, Visual3, VisualDebug};
 
 macro_rules! impl_create_device {
     ($($name:ident, $create_device:ident),*) => {
-        $(pub fn $name<I, T>(dxgi_device: &impl AsPtr<IUnknown>) -> T
+        $(pub fn $name<I, T>(dxgi_device: &impl AsPtr<IUnknown>) -> Result<T>
         where
             T: From<ComPtr<I>>,
             I: Interface,
         {
-            unsafe {
-                let mut device = ComPtr::<I>::default();
-                let hr = $create_device(
-                    dxgi_device.as_ptr() as *const _,
-                    &I::uuidof(),
-                    device.getter_addrefs()
-                );
-                assert!(hr == 0);
-                device.into()
-            }
+            let mut device = ComPtr::<I>::default();
+            hr!($create_device(
+                dxgi_device.as_ptr() as *const _,
+                &I::uuidof(),
+                device.getter_addrefs()
+            ))?;
+            Ok(device.into())
         })*
     }
 }
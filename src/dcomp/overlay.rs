@@ -0,0 +1,87 @@
+use super::{DesktopDevice, Surface, Visual2};
+use crate::{d2d, debug, dxgi, AsPtr, Canvas, Offset, Result};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
+use winapi::um::dcomp::IDCompositionSurface;
+
+/// Layer that renders the paint flashing history of `debug` as fading
+/// outlines.
+///
+/// Add `visual()` as the topmost child of the root visual and call `update`
+/// every frame before committing. Nothing is drawn while paint flashing is
+/// disabled.
+#[derive(Debug)]
+pub struct PaintOverlay {
+    visual: Visual2,
+    surface: Surface,
+    origins: RefCell<HashMap<usize, Offset>>,
+    drawn: Cell<bool>,
+}
+
+impl PaintOverlay {
+    pub fn new(device: &DesktopDevice, width: u32, height: u32) -> Result<Self> {
+        let surface = device.create_surface(
+            width,
+            height,
+            dxgi::Format::Bgra8,
+            dxgi::AlphaMode::Premultiplied,
+        )?;
+        let visual = device.create_visual()?;
+        visual.set_content(&surface)?;
+        Ok(Self {
+            visual,
+            surface,
+            origins: RefCell::default(),
+            drawn: Cell::new(false),
+        })
+    }
+
+    pub fn visual(&self) -> &Visual2 {
+        &self.visual
+    }
+
+    /// Where the top left corner of `surface` is, relative to the overlay.
+    /// Surfaces default to the overlay origin.
+    pub fn set_origin(&self, surface: &impl AsPtr<IDCompositionSurface>, origin: Offset) {
+        self.origins
+            .borrow_mut()
+            .insert(surface.as_ptr() as usize, origin);
+    }
+
+    /// Redraw the overlay from the current paint history.
+    pub fn update(&self, dc: &d2d::DeviceContext1) -> Result<()> {
+        let enabled = debug::paint_flashing();
+        if !enabled && !self.drawn.get() {
+            return Ok(());
+        }
+        self.drawn.set(enabled);
+
+        let properties = d2d::BitmapProperties1 {
+            pixelFormat: d2d::PixelFormat {
+                format: dxgi::Format::Bgra8.into(),
+                alphaMode: dxgi::AlphaMode::Premultiplied.into(),
+            },
+            bitmapOptions: d2d::BITMAP_OPTIONS_TARGET | d2d::BITMAP_OPTIONS_CANNOT_DRAW,
+            ..Default::default()
+        };
+        let origins = self.origins.borrow();
+        // Drawing the overlay must not show up in the history it displays.
+        self.surface.draw_untracked(None, |dxgi_surface, offset| {
+            let bitmap = dc.create_bitmap_from_dxgi_surface(dxgi_surface, &properties)?;
+            dc.set_target(&bitmap);
+            dc.draw(|dc| {
+                let mut canvas = dc.clone();
+                Canvas::clear(&mut canvas);
+                debug::with_paint_history(|history| {
+                    history.render(&mut canvas, |surface| {
+                        let origin = origins.get(&surface).copied().unwrap_or_default();
+                        Some(origin + offset)
+                    })
+                });
+                Ok(())
+            })
+        })
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     dcomp::{DesktopDevice, Device, Device2},
-    dxgi, impl_comptr, impl_interface, opt_ptr, ComPtr, Offset, Rect,
+    debug, dxgi, hr, impl_comptr, impl_interface, opt_ptr, trace_span, ComPtr, Offset, Rect,
+    Result,
 };
 use std::convert::TryInto;
 use winapi::{
@@ -12,6 +13,7 @@ use winapi::{
     Interface,
 };
 
+// Only one update can be drawn at a time, between `BeginDraw` and `EndDraw`.
 impl_comptr! { Surface: [IDCompositionSurface, IUnknown] }
 impl_comptr! { VirtualSurface: [IDCompositionVirtualSurface, IDCompositionSurface, IUnknown] }
 
@@ -23,19 +25,16 @@ impl_interface! {
             height: u32,
             pixel_format: dxgi::Format,
             alpha_mode: dxgi::AlphaMode,
-        ) -> Surface {
-            unsafe {
-                let mut surface = ComPtr::<IDCompositionSurface>::default();
-                let hr = self.0.CreateSurface(
-                    width,
-                    height,
-                    pixel_format.into(),
-                    alpha_mode.into(),
-                    surface.getter_addrefs(),
-                );
-                assert!(hr == 0);
-                surface.into()
-            }
+        ) -> Result<Surface> {
+            let mut surface = ComPtr::<IDCompositionSurface>::default();
+            hr!(self.0.CreateSurface(
+                width,
+                height,
+                pixel_format.into(),
+                alpha_mode.into(),
+                surface.getter_addrefs(),
+            ), width, height, format = ?pixel_format)?;
+            Ok(surface.into())
         }
 
         pub fn create_virtual_surface(
@@ -44,46 +43,78 @@ impl_interface! {
             initial_height: u32,
             pixel_format: dxgi::Format,
             alpha_mode: dxgi::AlphaMode,
-        ) -> VirtualSurface {
-            unsafe {
-                let mut surface = ComPtr::<IDCompositionVirtualSurface>::default();
-                let hr = self.0.CreateVirtualSurface(
-                    initial_width,
-                    initial_height,
-                    pixel_format.into(),
-                    alpha_mode.into(),
-                    surface.getter_addrefs(),
-                );
-                assert!(hr == 0);
-                surface.into()
-            }
+        ) -> Result<VirtualSurface> {
+            let mut surface = ComPtr::<IDCompositionVirtualSurface>::default();
+            hr!(self.0.CreateVirtualSurface(
+                initial_width,
+                initial_height,
+                pixel_format.into(),
+                alpha_mode.into(),
+                surface.getter_addrefs(),
+            ), width = initial_width, height = initial_height, format = ?pixel_format)?;
+            Ok(surface.into())
         }
     }
 }
 
 impl_interface! {
     impl [Surface, VirtualSurface] {
+        /// Draw into `update_rect`, or the whole surface if `None`. The
+        /// update is recorded for paint flashing, see `debug`. Errors from
+        /// `f` are returned after the drawing is ended.
         pub fn draw<'a>(
             &self,
             update_rect: impl Into<Option<&'a Rect>>,
-            mut f: impl FnMut(&dxgi::Surface, Offset))
+            f: impl FnMut(&dxgi::Surface, Offset) -> Result<()>)
+            -> Result<()>
+        {
+            self.draw_impl(update_rect.into(), true, f)
+        }
+
+        /// `draw` without recording the update, for overlays such as a
+        /// `hud::Hud`, which would otherwise show up in the paint statistics
+        /// they display and be flashed on every update.
+        pub fn draw_untracked<'a>(
+            &self,
+            update_rect: impl Into<Option<&'a Rect>>,
+            f: impl FnMut(&dxgi::Surface, Offset) -> Result<()>)
+            -> Result<()>
         {
+            self.draw_impl(update_rect.into(), false, f)
+        }
+
+        fn draw_impl(
+            &self,
+            update_rect: Option<&Rect>,
+            record: bool,
+            mut f: impl FnMut(&dxgi::Surface, Offset) -> Result<()>)
+            -> Result<()>
+        {
+            let _span = trace_span!("draw", update_rect = ?update_rect);
             let mut update_object = ComPtr::<IDXGISurface>::default();
             let mut update_offset = Offset::default();
-            let hr = unsafe {
-                self.0.BeginDraw(
-                    opt_ptr(update_rect.into()),
-                    &IDXGISurface::uuidof(),
-                    update_object.getter_addrefs(),
-                    &mut *update_offset)
-            };
-            assert!(hr == 0);
-            let surface = update_object.into();
-            f(&surface, update_offset);
-            let hr = unsafe {
-                self.0.EndDraw()
-            };
-            assert!(hr == 0);
+            hr!(self.0.BeginDraw(
+                opt_ptr(update_rect),
+                &IDXGISurface::uuidof(),
+                update_object.getter_addrefs(),
+                &mut *update_offset), update_rect = ?update_rect)?;
+            let surface: dxgi::Surface = update_object.into();
+            if record {
+                let rect = match update_rect {
+                    Some(rect) => Some(*rect),
+                    // The texture may be shared with other surfaces, so this
+                    // only approximates the surface size.
+                    None => surface.size().ok().map(|size| {
+                        Rect::new(0, 0, size.width() - update_offset.x, size.height() - update_offset.y)
+                    }),
+                };
+                if let Some(rect) = rect {
+                    debug::record_paint(self.0.as_ptr() as usize, rect);
+                }
+            }
+            let drawn = f(&surface, update_offset);
+            hr!(self.0.EndDraw())?;
+            drawn
         }
 
         pub fn scroll<'a>(
@@ -91,35 +122,27 @@ impl_interface! {
             scroll_rect: impl Into<Option<&'a Rect>>,
             clip_rect: impl Into<Option<&'a Rect>>,
             offset: (i32, i32)
-        ) {
-            unsafe {
-                let hr = self.0.Scroll(
-                    opt_ptr(scroll_rect.into()),
-                    opt_ptr(clip_rect.into()),
-                    offset.0,
-                    offset.1
-                );
-                assert!(hr == 0);
-            }
+        ) -> Result<()> {
+            hr!(self.0.Scroll(
+                opt_ptr(scroll_rect.into()),
+                opt_ptr(clip_rect.into()),
+                offset.0,
+                offset.1
+            ))
         }
     }
 }
 
 impl VirtualSurface {
-    pub fn resize(&self, width: u32, height: u32) {
-        unsafe {
-            let hr = self.0.Resize(width, height);
-            assert!(hr == 0);
-        }
+    pub fn resize(&self, width: u32, height: u32) -> Result<()> {
+        hr!(self.0.Resize(width, height), width, height)
     }
 
-    pub fn trim(&self, rects: &[Rect]) {
-        unsafe {
-            // Rect is a thin wrapper around RECT, so &[Rect] is &[RECT]
-            let hr = self
-                .0
-                .Trim(rects.as_ptr() as *const _, rects.len().try_into().unwrap());
-            assert!(hr == 0);
-        }
+    pub fn trim(&self, rects: &[Rect]) -> Result<()> {
+        // Rect is a thin wrapper around RECT, so &[Rect] is &[RECT]
+        hr!(
+            self.0.Trim(rects.as_ptr() as *const _, rects.len().try_into().unwrap()),
+            rects = ?rects
+        )
     }
 }
@@ -1,4 +1,4 @@
-use crate::{impl_comptr, AsPtr};
+use crate::{hr, impl_comptr, AsPtr, Result};
 use winapi::um::{
     dcomp::{IDCompositionTarget, IDCompositionVisual},
     unknwnbase::IUnknown,
@@ -7,10 +7,7 @@ use winapi::um::{
 impl_comptr! { Target: [IDCompositionTarget, IUnknown] }
 
 impl Target {
-    pub fn set_root(&self, visual: &impl AsPtr<IDCompositionVisual>) {
-        unsafe {
-            let hr = self.0.SetRoot(visual.as_ptr());
-            assert!(hr == 0);
-        }
+    pub fn set_root(&self, visual: &impl AsPtr<IDCompositionVisual>) -> Result<()> {
+        hr!(self.0.SetRoot(visual.as_ptr()))
     }
 }
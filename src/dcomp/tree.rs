@@ -0,0 +1,90 @@
+use super::{DesktopDevice, Insert, Surface, VirtualSurface, Visual2, Visual3};
+use crate::{
+    tree::{Compositor, Matrix3x2},
+    AsPtr, Rect, Result,
+};
+use winapi::um::unknwnbase::IUnknown;
+
+/// Content that can be attached to a node of a `tree::VisualTree`.
+#[derive(Clone, Debug)]
+pub enum Content {
+    Surface(Surface),
+    VirtualSurface(VirtualSurface),
+}
+
+impl AsPtr<IUnknown> for Content {
+    fn as_ptr(&self) -> *mut IUnknown {
+        match self {
+            Content::Surface(surface) => surface.as_ptr(),
+            Content::VirtualSurface(surface) => surface.as_ptr(),
+        }
+    }
+}
+
+impl From<Surface> for Content {
+    fn from(surface: Surface) -> Self {
+        Content::Surface(surface)
+    }
+}
+
+impl From<VirtualSurface> for Content {
+    fn from(surface: VirtualSurface) -> Self {
+        Content::VirtualSurface(surface)
+    }
+}
+
+impl Compositor for DesktopDevice {
+    type Visual = Visual2;
+    type Content = Content;
+
+    fn create_visual(&self) -> Result<Visual2> {
+        DesktopDevice::create_visual(self)
+    }
+
+    fn add_visual(
+        &self,
+        parent: &Visual2,
+        visual: &Visual2,
+        below: Option<&Visual2>,
+    ) -> Result<()> {
+        // With no reference visual the new child goes above its siblings, but
+        // the tree only does that when `parent` has no children.
+        parent.add_visual(visual, Insert::Above, below)
+    }
+
+    fn remove_all_visuals(&self, parent: &Visual2) -> Result<()> {
+        parent.remove_all_visuals()
+    }
+
+    fn set_offset(&self, visual: &Visual2, offset: [f32; 2]) -> Result<()> {
+        visual.set_offset(offset)
+    }
+
+    fn set_content(&self, visual: &Visual2, content: Option<&Content>) -> Result<()> {
+        match content {
+            Some(content) => visual.set_content(content),
+            None => visual.clear_content(),
+        }
+    }
+
+    fn set_clip(&self, visual: &Visual2, clip: Option<&Rect>) -> Result<()> {
+        visual.set_clip(clip)
+    }
+
+    fn set_opacity(&self, visual: &Visual2, opacity: f32) -> Result<()> {
+        // Opacity needs IDCompositionVisual3 (Windows 8.1).
+        let visual: Option<Visual3> = visual.as_();
+        match visual {
+            Some(visual) => visual.set_opacity(opacity),
+            None => Ok(()),
+        }
+    }
+
+    fn set_transform(&self, visual: &Visual2, transform: &Matrix3x2) -> Result<()> {
+        visual.set_transform(transform)
+    }
+
+    fn commit(&self) -> Result<()> {
+        DesktopDevice::commit(self)
+    }
+}
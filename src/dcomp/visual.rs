@@ -1,10 +1,14 @@
 use crate::{
+    d2d::RectF,
     dcomp::{DesktopDevice, Device, Device2},
-    impl_comptr, impl_interface, AsPtr, ComPtr,
+    hr, impl_comptr, impl_interface,
+    tree::Matrix3x2,
+    AsPtr, ComPtr, Rect, Result,
 };
 use winapi::{
     shared::minwindef::{BOOL, FALSE, TRUE},
     um::{
+        dcommon::D2D_MATRIX_3X2_F,
         dcomp::{
             IDCompositionVisual, IDCompositionVisual2, IDCompositionVisual3,
             IDCompositionVisualDebug,
@@ -13,6 +17,8 @@ use winapi::{
     },
 };
 
+// Changes to visuals are batched on their device until it commits, see
+// `dcomp::Device`.
 impl_comptr! { Visual: [IDCompositionVisual, IUnknown] }
 impl_comptr! { Visual2: [IDCompositionVisual2, IDCompositionVisual, IUnknown] }
 impl_comptr! { VisualDebug: [IDCompositionVisualDebug, IDCompositionVisual2, IDCompositionVisual, IUnknown] }
@@ -35,45 +41,36 @@ impl From<Insert> for BOOL {
 
 impl_interface! {
     impl Device {
-        pub fn create_visual(&self) -> Visual {
-            unsafe {
-                let mut visual = ComPtr::<IDCompositionVisual>::default();
-                let hr = self.0.CreateVisual(visual.getter_addrefs());
-                assert!(hr == 0);
-                visual.into()
-            }
+        pub fn create_visual(&self) -> Result<Visual> {
+            let mut visual = ComPtr::<IDCompositionVisual>::default();
+            hr!(self.0.CreateVisual(visual.getter_addrefs()))?;
+            Ok(visual.into())
         }
     }
 }
 
 impl_interface! {
     impl [Device2, DesktopDevice] {
-        pub fn create_visual(&self) -> Visual2 {
-            unsafe {
-                let mut visual = ComPtr::<IDCompositionVisual2>::default();
-                let hr = self.0.CreateVisual(visual.getter_addrefs());
-                assert!(hr == 0);
-                visual.into()
-            }
+        pub fn create_visual(&self) -> Result<Visual2> {
+            let mut visual = ComPtr::<IDCompositionVisual2>::default();
+            hr!(self.0.CreateVisual(visual.getter_addrefs()))?;
+            Ok(visual.into())
         }
     }
 }
 
 impl_interface! {
     impl [Visual, Visual2, Visual3] {
-        fn _set_content(&self, content: *mut IUnknown) {
-            unsafe {
-                let hr = self.0.SetContent(content);
-                assert!(hr == 0);
-            }
+        fn _set_content(&self, content: *mut IUnknown) -> Result<()> {
+            hr!(self.0.SetContent(content))
         }
 
-        pub fn set_content(&self, content: &impl AsPtr<IUnknown>) {
-            self._set_content(content.as_ptr());
+        pub fn set_content(&self, content: &impl AsPtr<IUnknown>) -> Result<()> {
+            self._set_content(content.as_ptr())
         }
 
-        pub fn clear_content(&self) {
-            self._set_content(std::ptr::null_mut());
+        pub fn clear_content(&self) -> Result<()> {
+            self._set_content(std::ptr::null_mut())
         }
 
         pub fn add_visual<'a, V: 'a>(
@@ -81,60 +78,68 @@ impl_interface! {
             visual: impl AsPtr<IDCompositionVisual>,
             insert: Insert,
             reference_visual: impl Into<Option<&'a V>>)
+            -> Result<()>
         where
             V: AsPtr<IDCompositionVisual>
         {
-            unsafe {
-                let hr = self.0.AddVisual(
-                    visual.as_ptr(),
-                    insert.into(),
-                    reference_visual.into().as_ptr()
-                );
-                assert!(hr == 0);
-            }
+            hr!(self.0.AddVisual(
+                visual.as_ptr(),
+                insert.into(),
+                reference_visual.into().as_ptr()
+            ))
         }
 
-        pub fn remove_visual(&self, visual: impl AsPtr<IDCompositionVisual>) {
-            unsafe {
-                let hr = self.0.RemoveVisual(visual.as_ptr());
-                assert!(hr == 0);
-            }
+        pub fn remove_visual(&self, visual: impl AsPtr<IDCompositionVisual>) -> Result<()> {
+            hr!(self.0.RemoveVisual(visual.as_ptr()))
+        }
+
+        pub fn remove_all_visuals(&self) -> Result<()> {
+            hr!(self.0.RemoveAllVisuals())
         }
 
-        pub fn remove_all_visuals(&self) {
-            unsafe {
-                let hr = self.0.RemoveAllVisuals();
-                assert!(hr == 0);
+        pub fn set_offset(&self, offset: [f32;2]) -> Result<()> {
+            hr!(self.0.SetOffsetX_1(offset[0]))?;
+            hr!(self.0.SetOffsetY_1(offset[1]))
+        }
+
+        pub fn set_clip(&self, clip: Option<&Rect>) -> Result<()> {
+            let visual: &IDCompositionVisual = &self.0;
+            match clip {
+                Some(clip) => hr!(visual.SetClip_1(&*RectF::from(*clip))),
+                None => hr!(visual.SetClip_2(std::ptr::null())),
             }
         }
 
-        pub fn set_offset(&self, offset: [f32;2]) {
-            let hr = unsafe {
-                self.0.SetOffsetX_1(offset[0])
-            };
-            assert!(hr == 0);
-            let hr = unsafe {
-                self.0.SetOffsetY_1(offset[1])
+        pub fn set_transform(&self, transform: &Matrix3x2) -> Result<()> {
+            let matrix = D2D_MATRIX_3X2_F {
+                matrix: [
+                    [transform.m11, transform.m12],
+                    [transform.m21, transform.m22],
+                    [transform.dx, transform.dy],
+                ],
             };
-            assert!(hr == 0);
+            // Visual3 shadows SetTransform with a 3D version, so go through
+            // the IDCompositionVisual vtable explicitly.
+            let visual: &IDCompositionVisual = &self.0;
+            hr!(visual.SetTransform_1(&matrix))
         }
     }
 }
 
+impl Visual3 {
+    pub fn set_opacity(&self, opacity: f32) -> Result<()> {
+        hr!(self.0.SetOpacity_1(opacity))
+    }
+}
+
 impl_interface! {
     impl [Visual3, VisualDebug] {
-        pub fn disable_redraw_regions(&self) {
-            unsafe {
-                let hr = self.0.DisableRedrawRegions();
-                assert!(hr == 0);
-            }
+        pub fn disable_redraw_regions(&self) -> Result<()> {
+            hr!(self.0.DisableRedrawRegions())
         }
 
-        pub fn enable_redraw_regions(&self) {
-            unsafe {
-                let hr = self.0.EnableRedrawRegions();
-                assert!(hr == 0);
-            }
+        pub fn enable_redraw_regions(&self) -> Result<()> {
+            hr!(self.0.EnableRedrawRegions())
         }
     }
 }
@@ -0,0 +1,321 @@
+//! Runtime debugging aids.
+//!
+//! Paint flashing: while enabled, every `draw` on a DirectComposition or
+//! `soft` surface records its update rectangle in a per-thread
+//! `PaintHistory`. `dcomp::PaintOverlay` renders recent rectangles as fading
+//! outlines above the rest of the scene, and `set_paint_log` writes the same
+//! records as text.
+//!
+//! The number and area of update rectangles per frame are counted even while
+//! paint flashing is off, see `last_frame_paints`.
+//!
+//! Frames are delimited by `end_frame`, which `scheduler::FrameScheduler`
+//! calls after every commit.
+
+use crate::{Canvas, Offset, Rect};
+use std::{cell::RefCell, collections::VecDeque, io::Write};
+
+/// Number of frames a paint stays visible on the overlay by default.
+pub const DEFAULT_FADE_FRAMES: u32 = 30;
+
+/// Outline colors, cycled per frame so consecutive paints are distinguishable.
+pub const PAINT_COLORS: [[f32; 3]; 6] = [
+    [1.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, 0.0, 1.0],
+    [1.0, 1.0, 0.0],
+    [0.0, 1.0, 1.0],
+    [1.0, 0.0, 1.0],
+];
+
+/// One `draw` call on a surface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PaintRecord {
+    pub frame: u64,
+    /// Identifies the surface drawn to, e.g. its COM pointer.
+    pub surface: usize,
+    /// Update rectangle in surface coordinates.
+    pub rect: Rect,
+}
+
+impl std::fmt::Display for PaintRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "paint frame={} surface={:#x} rect=({}, {}, {}, {})",
+            self.frame,
+            self.surface,
+            self.rect.left,
+            self.rect.top,
+            self.rect.right,
+            self.rect.bottom
+        )
+    }
+}
+
+/// Paints recorded over the last few frames.
+#[derive(Clone, Debug)]
+pub struct PaintHistory {
+    frame: u64,
+    fade_frames: u32,
+    records: VecDeque<PaintRecord>,
+}
+
+impl Default for PaintHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_FADE_FRAMES)
+    }
+}
+
+impl PaintHistory {
+    /// Records are kept for `fade_frames` frames, at least one.
+    pub fn new(fade_frames: u32) -> Self {
+        Self {
+            frame: 0,
+            fade_frames: fade_frames.max(1),
+            records: VecDeque::new(),
+        }
+    }
+
+    /// Index of the frame currently being recorded.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn fade_frames(&self) -> u32 {
+        self.fade_frames
+    }
+
+    pub fn record(&mut self, surface: usize, rect: Rect) -> PaintRecord {
+        let record = PaintRecord {
+            frame: self.frame,
+            surface,
+            rect,
+        };
+        self.records.push_back(record);
+        record
+    }
+
+    /// Start a new frame and forget records that have fully faded.
+    pub fn end_frame(&mut self) {
+        self.frame += 1;
+        let oldest = self.frame.saturating_sub(u64::from(self.fade_frames) - 1);
+        while self.records.front().map_or(false, |r| r.frame < oldest) {
+            self.records.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// All retained records, oldest first.
+    pub fn records(&self) -> impl Iterator<Item = &PaintRecord> {
+        self.records.iter()
+    }
+
+    /// Records of the frame currently being recorded.
+    pub fn current(&self) -> impl Iterator<Item = &PaintRecord> {
+        let frame = self.frame;
+        self.records.iter().filter(move |r| r.frame == frame)
+    }
+
+    /// Opacity of `record` on the overlay: 1.0 for the current frame, falling
+    /// linearly to zero after `fade_frames`.
+    pub fn opacity(&self, record: &PaintRecord) -> f32 {
+        let age = self.frame.saturating_sub(record.frame) as f32;
+        (1.0 - age / self.fade_frames as f32).max(0.0)
+    }
+
+    /// Draw every record as an outline, oldest first. `origin` maps a surface
+    /// to the position of its origin on the canvas; surfaces it returns `None`
+    /// for are skipped.
+    pub fn render(
+        &self,
+        canvas: &mut impl Canvas,
+        mut origin: impl FnMut(usize) -> Option<Offset>,
+    ) {
+        for record in &self.records {
+            let opacity = self.opacity(record);
+            if opacity <= 0.0 {
+                continue;
+            }
+            if let Some(offset) = origin(record.surface) {
+                let [r, g, b] = PAINT_COLORS[(record.frame % PAINT_COLORS.len() as u64) as usize];
+                canvas.stroke_rect(&(record.rect + offset), [r, g, b, opacity], 2.0);
+            }
+        }
+    }
+}
+
+/// Number and total area of the update rectangles of one frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PaintStats {
+    pub rects: usize,
+    pub area: i64,
+}
+
+#[derive(Default)]
+struct PaintFlashing {
+    enabled: bool,
+    history: PaintHistory,
+    log: Option<Box<dyn Write>>,
+    current: PaintStats,
+    last: PaintStats,
+}
+
+thread_local! {
+    static PAINT_FLASHING: RefCell<PaintFlashing> = RefCell::new(PaintFlashing::default());
+}
+
+/// Turn paint recording on or off for the current thread. Turning it off
+/// also drops the recorded history.
+pub fn set_paint_flashing(enabled: bool) {
+    PAINT_FLASHING.with(|p| {
+        let mut p = p.borrow_mut();
+        p.enabled = enabled;
+        if !enabled {
+            p.history.clear();
+        }
+    })
+}
+
+pub fn paint_flashing() -> bool {
+    PAINT_FLASHING.with(|p| p.borrow().enabled)
+}
+
+/// Write a line for every recorded paint to `log`, or stop logging with
+/// `None`. Write errors are ignored.
+pub fn set_paint_log(log: Option<Box<dyn Write>>) {
+    PAINT_FLASHING.with(|p| p.borrow_mut().log = log)
+}
+
+/// Replace the history, e.g. to change how long paints stay visible.
+pub fn set_paint_history(history: PaintHistory) {
+    PAINT_FLASHING.with(|p| p.borrow_mut().history = history)
+}
+
+pub fn with_paint_history<R>(f: impl FnOnce(&PaintHistory) -> R) -> R {
+    PAINT_FLASHING.with(|p| f(&p.borrow().history))
+}
+
+/// Paint statistics of the last completed frame.
+pub fn last_frame_paints() -> PaintStats {
+    PAINT_FLASHING.with(|p| p.borrow().last)
+}
+
+/// Record a paint of `rect` on `surface`. Only counted in `PaintStats` unless
+/// paint flashing is enabled. Called by the surface types in this crate;
+/// custom surfaces can call it too.
+pub fn record_paint(surface: usize, rect: Rect) {
+    PAINT_FLASHING.with(|p| {
+        let p = &mut *p.borrow_mut();
+        p.current.rects += 1;
+        p.current.area += i64::from(rect.width()) * i64::from(rect.height());
+        if !p.enabled {
+            return;
+        }
+        let record = p.history.record(surface, rect);
+        if let Some(log) = &mut p.log {
+            let _ = writeln!(log, "{}", record);
+        }
+    })
+}
+
+/// Mark the end of a frame in the paint history.
+pub fn end_frame() {
+    PAINT_FLASHING.with(|p| {
+        let mut p = p.borrow_mut();
+        p.last = std::mem::take(&mut p.current);
+        if p.enabled {
+            p.history.end_frame();
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rect, soft};
+    use std::{io, rc::Rc};
+
+    #[derive(Clone, Default)]
+    struct SharedLog(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedLog {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn history_fades() {
+        let mut history = PaintHistory::new(3);
+        history.record(1, rect!(0, 0, 10, 10));
+        history.end_frame();
+        history.record(1, rect!(10, 0, 20, 10));
+        assert_eq!(history.records().count(), 2);
+        assert_eq!(history.current().count(), 1);
+
+        let first = *history.records().next().unwrap();
+        assert!((history.opacity(&first) - 2.0 / 3.0).abs() < 1e-6);
+
+        history.end_frame();
+        history.end_frame();
+        let frames: Vec<_> = history.records().map(|r| r.frame).collect();
+        assert_eq!(frames, vec![1]);
+    }
+
+    #[test]
+    fn render_outlines() {
+        let mut history = PaintHistory::new(2);
+        history.record(7, rect!(2, 2, 12, 12));
+        history.record(8, rect!(0, 0, 4, 4));
+
+        let mut bitmap = soft::Bitmap::new(20, 20);
+        history.render(&mut bitmap, |surface| {
+            if surface == 7 {
+                Some(Offset::new(4, 4))
+            } else {
+                None
+            }
+        });
+        assert_eq!(bitmap.pixel(6, 10), 0xFFFF_0000);
+        assert_eq!(bitmap.pixel(10, 10), 0);
+        assert_eq!(bitmap.pixel(1, 1), 0);
+    }
+
+    #[test]
+    fn records_surface_draws() {
+        let surface = soft::VirtualSurface::new(64, 64);
+        surface.draw(&rect!(0, 0, 8, 8), |_, _| {});
+        assert_eq!(with_paint_history(|h| h.records().count()), 0);
+
+        let log = SharedLog::default();
+        set_paint_log(Some(Box::new(log.clone())));
+        set_paint_flashing(true);
+        surface.draw(&rect!(0, 0, 8, 8), |_, _| {});
+        end_frame();
+        surface.draw(&rect!(8, 8, 16, 16), |_, _| {});
+
+        let rects: Vec<_> =
+            with_paint_history(|h| h.records().map(|r| (r.frame, r.rect)).collect());
+        assert_eq!(
+            rects,
+            vec![(0, rect!(0, 0, 8, 8)), (1, rect!(8, 8, 16, 16))]
+        );
+
+        let text = String::from_utf8(log.0.borrow().clone()).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("paint frame=1 surface=0x"));
+        assert!(lines[1].ends_with("rect=(8, 8, 16, 16)"));
+
+        set_paint_flashing(false);
+        assert_eq!(with_paint_history(|h| h.records().count()), 0);
+    }
+}
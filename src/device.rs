@@ -0,0 +1,412 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Device-lost detection and recovery.
+//!
+//! When the GPU is reset or its driver is updated, the Direct3D device and
+//! everything created from it (DXGI, Direct2D and DirectComposition objects,
+//! and the device held by Media Foundation) stop working at once. Calls start
+//! failing with `DXGI_ERROR_DEVICE_REMOVED` or `D2DERR_RECREATE_TARGET`, and
+//! the only way forward is to release them all and build a new chain.
+//!
+//! `DeviceManager` owns the current chain, produced by a `DeviceFactory`.
+//! Results of device calls go through `check`, which recognizes device loss,
+//! tells each registered `DeviceOwner` to drop what it holds and releases the
+//! chain. `recover` then builds a new generation and hands it to the owners
+//! so they can recreate their resources. On Windows, `dcomp::DeviceChain`
+//! builds the real chain; tests use a fake factory.
+
+use crate::{Error, Hresult, Result};
+
+/// `HRESULT`s that mean the device is gone and must be recreated.
+const DEVICE_LOST: [u32; 5] = [
+    0x887a_0005, // DXGI_ERROR_DEVICE_REMOVED
+    0x887a_0006, // DXGI_ERROR_DEVICE_HUNG
+    0x887a_0007, // DXGI_ERROR_DEVICE_RESET
+    0x887a_0020, // DXGI_ERROR_DRIVER_INTERNAL_ERROR
+    0x8899_000c, // D2DERR_RECREATE_TARGET
+];
+
+/// Whether `hresult` reports a lost device.
+pub fn is_device_lost(hresult: Hresult) -> bool {
+    DEVICE_LOST.contains(&(hresult.0 as u32))
+}
+
+/// Builds a device chain, once at start and again after each loss.
+pub trait DeviceFactory {
+    type Devices;
+
+    fn create(&mut self) -> Result<Self::Devices>;
+}
+
+/// Something holding resources created from a device chain, such as
+/// surfaces, brushes or a video decoder.
+pub trait DeviceOwner<D> {
+    /// The devices are lost. Release everything created from them; they are
+    /// released right after this returns.
+    fn device_lost(&mut self);
+
+    /// A new chain is ready. `generation` counts the chains built so far.
+    ///
+    /// If this fails, or another owner's restore does, the new chain is lost
+    /// again and every owner is told so, including those that never saw it.
+    fn device_restored(&mut self, devices: &D, generation: u64) -> Result<()>;
+}
+
+/// Whether a `DeviceManager` has a working chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    Ready,
+    Lost,
+}
+
+/// Owns the current device chain and rebuilds it after it is lost.
+pub struct DeviceManager<F: DeviceFactory> {
+    factory: F,
+    devices: Option<F::Devices>,
+    generation: u64,
+    owners: Vec<Box<dyn DeviceOwner<F::Devices>>>,
+}
+
+impl<F: DeviceFactory> DeviceManager<F> {
+    /// Builds the first chain. Fails if it can't be built, lost or not, as
+    /// there is nothing to recover yet.
+    pub fn new(mut factory: F) -> Result<Self> {
+        let devices = factory.create()?;
+        Ok(Self {
+            factory,
+            devices: Some(devices),
+            generation: 1,
+            owners: Vec::new(),
+        })
+    }
+
+    pub fn state(&self) -> State {
+        if self.devices.is_some() {
+            State::Ready
+        } else {
+            State::Lost
+        }
+    }
+
+    /// The current chain, or `None` while lost.
+    pub fn devices(&self) -> Option<&F::Devices> {
+        self.devices.as_ref()
+    }
+
+    /// Number of chains built, starting at 1 for the one `new` builds.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn factory(&self) -> &F {
+        &self.factory
+    }
+
+    /// Adds an owner. It is expected to have created its resources from the
+    /// current chain, so it is only notified of later losses and rebuilds.
+    pub fn register(&mut self, owner: impl DeviceOwner<F::Devices> + 'static) {
+        self.owners.push(Box::new(owner));
+    }
+
+    /// Passes `Ok` values through as `Some`. A device-lost error puts the
+    /// manager in the lost state and returns `Ok(None)`; other errors are
+    /// returned unchanged.
+    pub fn check<T>(&mut self, result: Result<T>) -> Result<Option<T>> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(error) if is_device_lost(error.hresult()) => {
+                self.lose();
+                Ok(None)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Notifies the owners and releases the chain. Does nothing if it is
+    /// already lost.
+    pub fn lose(&mut self) {
+        if self.devices.is_none() {
+            return;
+        }
+        for owner in &mut self.owners {
+            owner.device_lost();
+        }
+        self.devices = None;
+    }
+
+    /// Builds a new chain if the current one is lost, and hands it to the
+    /// owners in registration order. Returns whether a new chain was built.
+    ///
+    /// If the device is lost again while building, or while an owner
+    /// restores, the manager stays lost and `recover` can be retried later,
+    /// typically on the next frame.
+    pub fn recover(&mut self) -> Result<bool> {
+        if self.devices.is_some() {
+            return Ok(false);
+        }
+        let devices = match self.factory.create() {
+            Ok(devices) => devices,
+            Err(error) => return lost_or(error),
+        };
+        self.generation += 1;
+        for owner in &mut self.owners {
+            if let Err(error) = owner.device_restored(&devices, self.generation) {
+                self.devices = Some(devices);
+                self.lose();
+                return lost_or(error);
+            }
+        }
+        self.devices = Some(devices);
+        Ok(true)
+    }
+
+    /// Recovers if needed and runs `f` with the chain, checking its result.
+    /// Returns `Ok(None)` if the device is lost, before or during `f`.
+    pub fn run<T>(&mut self, f: impl FnOnce(&F::Devices) -> Result<T>) -> Result<Option<T>> {
+        self.recover()?;
+        let result = match &self.devices {
+            Some(devices) => f(devices),
+            None => return Ok(None),
+        };
+        self.check(result)
+    }
+}
+
+/// `Ok(false)` for a device-lost error, the error otherwise.
+fn lost_or(error: Error) -> Result<bool> {
+    if is_device_lost(error.hresult()) {
+        Ok(false)
+    } else {
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys::HRESULT;
+    use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+    const E_FAIL: HRESULT = 0x8000_4005_u32 as HRESULT;
+    const DEVICE_REMOVED: HRESULT = 0x887a_0005_u32 as HRESULT;
+    const RECREATE_TARGET: HRESULT = 0x8899_000c_u32 as HRESULT;
+
+    /// A device that fails with the queued `HRESULT`s, then succeeds.
+    #[derive(Debug)]
+    struct FakeDevice {
+        generation: u64,
+        failures: RefCell<VecDeque<HRESULT>>,
+    }
+
+    impl FakeDevice {
+        fn draw(&self) -> Result<u64> {
+            match self.failures.borrow_mut().pop_front() {
+                Some(hr) => Err(Error::new(hr, "Draw")),
+                None => Ok(self.generation),
+            }
+        }
+    }
+
+    /// Creates `FakeDevice`s, failing with the queued `HRESULT`s first.
+    #[derive(Default)]
+    struct FakeFactory {
+        created: u64,
+        create_failures: VecDeque<HRESULT>,
+        draw_failures: Rc<RefCell<VecDeque<HRESULT>>>,
+    }
+
+    impl DeviceFactory for FakeFactory {
+        type Devices = FakeDevice;
+
+        fn create(&mut self) -> Result<FakeDevice> {
+            if let Some(hr) = self.create_failures.pop_front() {
+                return Err(Error::new(hr, "D3D11CreateDevice"));
+            }
+            self.created += 1;
+            Ok(FakeDevice {
+                generation: self.created,
+                failures: RefCell::new(self.draw_failures.borrow_mut().drain(..).collect()),
+            })
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Lost(&'static str),
+        Restored(&'static str, u64),
+    }
+
+    struct Owner {
+        name: &'static str,
+        log: Rc<RefCell<Vec<Event>>>,
+        restore_failures: VecDeque<HRESULT>,
+    }
+
+    impl DeviceOwner<FakeDevice> for Owner {
+        fn device_lost(&mut self) {
+            self.log.borrow_mut().push(Event::Lost(self.name));
+        }
+
+        fn device_restored(&mut self, devices: &FakeDevice, generation: u64) -> Result<()> {
+            assert_eq!(devices.generation, generation);
+            if let Some(hr) = self.restore_failures.pop_front() {
+                return Err(Error::new(hr, "CreateBitmap"));
+            }
+            self.log
+                .borrow_mut()
+                .push(Event::Restored(self.name, generation));
+            Ok(())
+        }
+    }
+
+    fn manager(factory: FakeFactory) -> (DeviceManager<FakeFactory>, Rc<RefCell<Vec<Event>>>) {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut manager = DeviceManager::new(factory).unwrap();
+        for &name in &["surface", "video"] {
+            manager.register(Owner {
+                name,
+                log: log.clone(),
+                restore_failures: VecDeque::new(),
+            });
+        }
+        (manager, log)
+    }
+
+    #[test]
+    fn detects_lost_devices() {
+        assert!(is_device_lost(Hresult(DEVICE_REMOVED)));
+        assert!(is_device_lost(Hresult(RECREATE_TARGET)));
+        assert!(!is_device_lost(Hresult(E_FAIL)));
+        assert!(!is_device_lost(Hresult(0)));
+    }
+
+    #[test]
+    fn loss_and_recovery() {
+        let (mut manager, log) = manager(FakeFactory::default());
+        assert_eq!(manager.state(), State::Ready);
+        assert_eq!(manager.generation(), 1);
+        assert_eq!(manager.run(FakeDevice::draw), Ok(Some(1)));
+        assert!(log.borrow().is_empty());
+
+        // A lost device notifies the owners and releases the chain.
+        let result = Err(Error::new(DEVICE_REMOVED, "Present"));
+        assert_eq!(manager.check::<()>(result), Ok(None));
+        assert_eq!(manager.state(), State::Lost);
+        assert!(manager.devices().is_none());
+        assert_eq!(
+            *log.borrow(),
+            [Event::Lost("surface"), Event::Lost("video")]
+        );
+
+        // Losing it again is a no-op.
+        manager.lose();
+        assert_eq!(log.borrow().len(), 2);
+
+        // The next run rebuilds the chain and restores the owners in order.
+        log.borrow_mut().clear();
+        assert_eq!(manager.run(FakeDevice::draw), Ok(Some(2)));
+        assert_eq!(manager.state(), State::Ready);
+        assert_eq!(manager.generation(), 2);
+        assert_eq!(
+            *log.borrow(),
+            [Event::Restored("surface", 2), Event::Restored("video", 2)]
+        );
+        assert_eq!(manager.recover(), Ok(false));
+    }
+
+    #[test]
+    fn loss_during_run() {
+        let factory = FakeFactory::default();
+        factory
+            .draw_failures
+            .borrow_mut()
+            .push_back(RECREATE_TARGET);
+        let draw_failures = factory.draw_failures.clone();
+        let (mut manager, log) = manager(factory);
+
+        draw_failures.borrow_mut().push_back(E_FAIL);
+        assert_eq!(manager.run(FakeDevice::draw), Ok(None));
+        assert_eq!(manager.state(), State::Lost);
+
+        // The second generation fails with an ordinary error, which is
+        // returned without losing the device.
+        let error = manager.run(FakeDevice::draw).unwrap_err();
+        assert_eq!(error.hresult(), Hresult(E_FAIL));
+        assert_eq!(manager.state(), State::Ready);
+        assert_eq!(manager.run(FakeDevice::draw), Ok(Some(2)));
+        assert_eq!(
+            *log.borrow(),
+            [
+                Event::Lost("surface"),
+                Event::Lost("video"),
+                Event::Restored("surface", 2),
+                Event::Restored("video", 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_rebuilds() {
+        let (mut manager, log) = manager(FakeFactory::default());
+        manager.factory.create_failures = vec![DEVICE_REMOVED, E_FAIL].into();
+        manager.lose();
+
+        // Lost again while building: stay lost and retry later.
+        assert_eq!(manager.recover(), Ok(false));
+        assert_eq!(manager.state(), State::Lost);
+        assert_eq!(
+            manager.run(|_| Ok(())),
+            Err(Error::new(E_FAIL, "D3D11CreateDevice"))
+        );
+        assert_eq!(manager.generation(), 1);
+
+        assert_eq!(manager.recover(), Ok(true));
+        assert_eq!(manager.generation(), 2);
+        assert_eq!(log.borrow().len(), 4);
+    }
+
+    #[test]
+    fn failed_restore() {
+        let (mut manager, log) = manager(FakeFactory::default());
+        manager.register(Owner {
+            name: "bitmap",
+            log: log.clone(),
+            restore_failures: vec![RECREATE_TARGET, E_FAIL].into(),
+        });
+        manager.lose();
+        log.borrow_mut().clear();
+
+        // An owner losing the device while restoring releases the new chain,
+        // telling the owners that already restored.
+        assert_eq!(manager.recover(), Ok(false));
+        assert_eq!(manager.state(), State::Lost);
+        assert_eq!(
+            *log.borrow(),
+            [
+                Event::Restored("surface", 2),
+                Event::Restored("video", 2),
+                Event::Lost("surface"),
+                Event::Lost("video"),
+                Event::Lost("bitmap"),
+            ]
+        );
+
+        // Other errors are returned, and the chain is released the same way.
+        assert_eq!(manager.recover(), Err(Error::new(E_FAIL, "CreateBitmap")));
+        assert_eq!(manager.state(), State::Lost);
+
+        log.borrow_mut().clear();
+        assert_eq!(manager.recover(), Ok(true));
+        assert_eq!(manager.generation(), 4);
+        assert_eq!(
+            *log.borrow(),
+            [
+                Event::Restored("surface", 4),
+                Event::Restored("video", 4),
+                Event::Restored("bitmap", 4),
+            ]
+        );
+    }
+}
@@ -0,0 +1,172 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::{font_loader::MemoryFonts, Factory};
+use crate::{hr, impl_comptr, text, ComPtr, Error, Result, ToWideStr};
+use std::{ptr, rc::Rc, sync::Arc};
+use winapi::{
+    shared::winerror::E_NOINTERFACE,
+    um::{
+        dwrite::{IDWriteFontCollection, DWRITE_FONT_SIMULATIONS_NONE},
+        dwrite_1::DWRITE_UNICODE_RANGE,
+        dwrite_2::{IDWriteFactory2, IDWriteFontFallback, IDWriteFontFallbackBuilder},
+        dwrite_3::{
+            IDWriteFactory3, IDWriteFontCollection1, IDWriteFontFaceReference, IDWriteFontSet,
+            IDWriteFontSetBuilder,
+        },
+        unknwnbase::IUnknown,
+    },
+};
+
+impl_comptr! { FontFallback: [IDWriteFontFallback, IUnknown] in Agile }
+
+impl_comptr! {
+    FontCollection(Extras): [IDWriteFontCollection1, IDWriteFontCollection, IUnknown] in Agile
+}
+
+/// What travels with a `FontCollection`: the fallback built from its
+/// fallback families, so `create_text_format` can apply it, and the loader
+/// of its in-memory fonts, which must stay registered while they're used.
+#[derive(Clone, Default)]
+pub struct Extras {
+    fallback: Option<FontFallback>,
+    fonts: Option<Arc<MemoryFonts>>,
+}
+
+impl FontCollection {
+    crate fn font_fallback(&self) -> Option<&FontFallback> {
+        self.1.fallback.as_ref()
+    }
+}
+
+impl Factory {
+    /// Builds a DirectWrite font collection holding the faces of
+    /// `collection`, to pass to `create_text_format`. Requires Windows 10,
+    /// and fails with `E_NOINTERFACE` before it.
+    ///
+    /// Faces added with `add_data` are read from memory, through a font
+    /// file loader registered with this factory until the collection is
+    /// dropped, so keep it alive while formats and layouts use it.
+    pub fn create_font_collection(
+        &self,
+        collection: &text::FontCollection,
+    ) -> Result<FontCollection> {
+        let factory = self
+            .0
+            .query_interface::<IDWriteFactory3>()
+            .ok_or_else(|| Error::new(E_NOINTERFACE, "QueryInterface"))?;
+        let mut builder = ComPtr::<IDWriteFontSetBuilder>::default();
+        hr!(factory.CreateFontSetBuilder(builder.getter_addrefs()))?;
+
+        // The faces of a collection file share its data, so it's copied once.
+        let mut data: Vec<&Rc<[u8]>> = Vec::new();
+        for (face, path) in collection.sources() {
+            if path.is_none() && !data.iter().any(|data| Rc::ptr_eq(data, face.data())) {
+                data.push(face.data());
+            }
+        }
+        let fonts = if data.is_empty() {
+            None
+        } else {
+            let data = data.iter().map(|data| Arc::from(&data[..])).collect();
+            Some(Arc::new(MemoryFonts::register(&self.0, data)?))
+        };
+
+        for (face, path) in collection.sources() {
+            let mut reference = ComPtr::<IDWriteFontFaceReference>::default();
+            match path {
+                Some(path) => {
+                    let wide_path = path.to_wide_str()?;
+                    hr!(factory.CreateFontFaceReference_1(
+                        wide_path.as_ptr(),
+                        ptr::null(),
+                        face.index(),
+                        DWRITE_FONT_SIMULATIONS_NONE,
+                        reference.getter_addrefs(),
+                    ))
+                    .map_err(|error| error.with_context(format!("loading {}", path.display())))?;
+                }
+                None => {
+                    // `fonts` holds the data of every face without a path.
+                    let fonts = fonts.as_ref().unwrap();
+                    let index = data
+                        .iter()
+                        .position(|data| Rc::ptr_eq(data, face.data()))
+                        .unwrap();
+                    let file = fonts.font_file(index as u32)?;
+                    hr!(factory.CreateFontFaceReference_2(
+                        file.as_ptr(),
+                        face.index(),
+                        DWRITE_FONT_SIMULATIONS_NONE,
+                        reference.getter_addrefs(),
+                    ))?;
+                }
+            }
+            hr!(builder.AddFontFaceReference_1(reference.as_ptr()))?;
+        }
+
+        let mut font_set = ComPtr::<IDWriteFontSet>::default();
+        hr!(builder.CreateFontSet(font_set.getter_addrefs()))?;
+        let mut font_collection = ComPtr::<IDWriteFontCollection1>::default();
+        hr!(
+            factory.CreateFontCollectionFromFontSet(
+                font_set.as_ptr(),
+                font_collection.getter_addrefs(),
+            )
+        )?;
+
+        let fallback = if collection.fallback().is_empty() {
+            None
+        } else {
+            Some(self.create_font_fallback(
+                collection.fallback(),
+                font_collection.as_ptr() as *mut IDWriteFontCollection,
+            )?)
+        };
+        Ok((font_collection, Extras { fallback, fonts }).into())
+    }
+
+    /// Maps every character to `families`, in order, and then to the system
+    /// fallback.
+    fn create_font_fallback(
+        &self,
+        families: &[String],
+        collection: *mut IDWriteFontCollection,
+    ) -> Result<FontFallback> {
+        let factory = self
+            .0
+            .query_interface::<IDWriteFactory2>()
+            .ok_or_else(|| Error::new(E_NOINTERFACE, "QueryInterface"))?;
+        let mut builder = ComPtr::<IDWriteFontFallbackBuilder>::default();
+        hr!(factory.CreateFontFallbackBuilder(builder.getter_addrefs()))?;
+
+        let names = families
+            .iter()
+            .map(|family| family.to_wide_str())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut name_ptrs: Vec<_> = names.iter().map(|name| name.as_ptr()).collect();
+        let range = DWRITE_UNICODE_RANGE {
+            first: 0,
+            last: 0x10ffff,
+        };
+        hr!(builder.AddMapping(
+            &range,
+            1,
+            name_ptrs.as_mut_ptr(),
+            name_ptrs.len() as u32,
+            collection,
+            ptr::null(),
+            ptr::null(),
+            1.0,
+        ))?;
+
+        let mut system = ComPtr::<IDWriteFontFallback>::default();
+        hr!(factory.GetSystemFontFallback(system.getter_addrefs()))?;
+        hr!(builder.AddMappings(system.as_ptr()))?;
+
+        let mut fallback = ComPtr::<IDWriteFontFallback>::default();
+        hr!(builder.CreateFontFallback(fallback.getter_addrefs()))?;
+        Ok(fallback.into())
+    }
+}
@@ -0,0 +1,172 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Fonts loaded by DirectWrite from memory.
+//!
+//! `IDWriteFactory5::CreateInMemoryFontFileLoader` isn't in the bindings, so
+//! this is the same loader implemented with `implement_com!`: a font file is
+//! an index into the loader's fonts, and its stream reads straight from the
+//! font's data.
+
+use crate::{call, com::Implement, hr, implement_com, ComPtr, Result};
+use std::{ffi::c_void, mem, ptr, sync::Arc};
+use winapi::{
+    shared::{
+        minwindef::UINT32,
+        winerror::{E_FAIL, E_INVALIDARG, E_NOTIMPL, E_POINTER, HRESULT, S_OK},
+    },
+    um::{
+        dwrite::{
+            IDWriteFactory, IDWriteFontFile, IDWriteFontFileLoader, IDWriteFontFileLoaderVtbl,
+            IDWriteFontFileStream, IDWriteFontFileStreamVtbl,
+        },
+        unknwnbase::IUnknown,
+    },
+};
+
+implement_com! {
+    pub trait FontFileLoader for IDWriteFontFileLoader(IDWriteFontFileLoaderVtbl): IUnknown {
+        fn create_stream_from_key => CreateStreamFromKey(
+            key: *const c_void,
+            key_size: UINT32,
+            stream: *mut *mut IDWriteFontFileStream
+        ) -> HRESULT;
+    }
+}
+
+implement_com! {
+    pub trait FontFileStream for IDWriteFontFileStream(IDWriteFontFileStreamVtbl): IUnknown {
+        fn read_file_fragment => ReadFileFragment(
+            fragment_start: *mut *const c_void,
+            file_offset: u64,
+            fragment_size: u64,
+            fragment_context: *mut *mut c_void
+        ) -> HRESULT;
+        fn release_file_fragment => ReleaseFileFragment(fragment_context: *mut c_void) -> ();
+        fn get_file_size => GetFileSize(file_size: *mut u64) -> HRESULT;
+        fn get_last_write_time => GetLastWriteTime(last_write_time: *mut u64) -> HRESULT;
+    }
+}
+
+struct MemoryLoader(Vec<Arc<[u8]>>);
+
+struct MemoryStream(Arc<[u8]>);
+
+implement_com!(MemoryLoader: [IDWriteFontFileLoader]);
+implement_com!(MemoryStream: [IDWriteFontFileStream]);
+
+impl FontFileLoader for MemoryLoader {
+    fn create_stream_from_key(
+        &self,
+        key: *const c_void,
+        key_size: UINT32,
+        stream: *mut *mut IDWriteFontFileStream,
+    ) -> HRESULT {
+        if stream.is_null() {
+            return E_POINTER;
+        }
+        unsafe { *stream = ptr::null_mut() };
+        if key.is_null() || key_size as usize != mem::size_of::<u32>() {
+            return E_INVALIDARG;
+        }
+        let index = unsafe { ptr::read_unaligned(key as *const u32) };
+        match self.0.get(index as usize) {
+            Some(data) => {
+                let mut native: ComPtr<IDWriteFontFileStream> =
+                    MemoryStream(data.clone()).into_com();
+                unsafe { *stream = native.forget() };
+                S_OK
+            }
+            None => E_INVALIDARG,
+        }
+    }
+}
+
+impl FontFileStream for MemoryStream {
+    fn read_file_fragment(
+        &self,
+        fragment_start: *mut *const c_void,
+        file_offset: u64,
+        fragment_size: u64,
+        fragment_context: *mut *mut c_void,
+    ) -> HRESULT {
+        if fragment_start.is_null() || fragment_context.is_null() {
+            return E_POINTER;
+        }
+        unsafe {
+            *fragment_start = ptr::null();
+            *fragment_context = ptr::null_mut();
+        }
+        match file_offset.checked_add(fragment_size) {
+            Some(end) if end <= self.0.len() as u64 => {
+                let start = self.0[file_offset as usize..].as_ptr();
+                unsafe { *fragment_start = start as *const c_void };
+                S_OK
+            }
+            _ => E_FAIL,
+        }
+    }
+
+    fn release_file_fragment(&self, _fragment_context: *mut c_void) {}
+
+    fn get_file_size(&self, file_size: *mut u64) -> HRESULT {
+        if file_size.is_null() {
+            return E_POINTER;
+        }
+        unsafe { *file_size = self.0.len() as u64 };
+        S_OK
+    }
+
+    /// Fonts in memory have no write time, which DirectWrite allows.
+    fn get_last_write_time(&self, last_write_time: *mut u64) -> HRESULT {
+        if !last_write_time.is_null() {
+            unsafe { *last_write_time = 0 };
+        }
+        E_NOTIMPL
+    }
+}
+
+/// A loader of fonts in memory, registered with a factory until dropped.
+crate struct MemoryFonts {
+    factory: ComPtr<IDWriteFactory>,
+    loader: ComPtr<IDWriteFontFileLoader>,
+    count: u32,
+}
+
+// The factory and loader are both free-threaded.
+unsafe impl Send for MemoryFonts {}
+unsafe impl Sync for MemoryFonts {}
+
+impl MemoryFonts {
+    /// Registers a loader of `fonts` with `factory`.
+    crate fn register(factory: &ComPtr<IDWriteFactory>, fonts: Vec<Arc<[u8]>>) -> Result<Self> {
+        let count = fonts.len() as u32;
+        let loader: ComPtr<IDWriteFontFileLoader> = MemoryLoader(fonts).into_com();
+        hr!(factory.RegisterFontFileLoader(loader.as_ptr()))?;
+        Ok(MemoryFonts {
+            factory: factory.clone(),
+            loader,
+            count,
+        })
+    }
+
+    /// The font file of the `index`th font passed to `register`.
+    crate fn font_file(&self, index: u32) -> Result<ComPtr<IDWriteFontFile>> {
+        assert!(index < self.count);
+        let mut file = ComPtr::<IDWriteFontFile>::default();
+        hr!(self.factory.CreateCustomFontFileReference(
+            &index as *const u32 as *const c_void,
+            mem::size_of::<u32>() as UINT32,
+            self.loader.as_ptr(),
+            file.getter_addrefs(),
+        ))?;
+        Ok(file)
+    }
+}
+
+impl Drop for MemoryFonts {
+    fn drop(&mut self) {
+        call!(self.factory.UnregisterFontFileLoader(self.loader.as_ptr()));
+    }
+}
@@ -1,12 +1,21 @@
+mod font_collection;
+mod font_loader;
 mod text_format;
+mod text_layout;
 
-use crate::{impl_comptr, text::ToUtf16, ComPtr};
+use crate::{
+    d2d::SolidColorBrush,
+    hr, impl_comptr,
+    text::{RichText, TextAttribute},
+    AsPtr, ComPtr, Result, ToWideStr,
+};
 use std::ptr;
 use winapi::{
     um::{
         dwrite::{
-            DWriteCreateFactory, IDWriteFactory, IDWriteTextFormat, DWRITE_FACTORY_TYPE_SHARED,
-            DWRITE_FONT_STRETCH, DWRITE_FONT_STRETCH_CONDENSED, DWRITE_FONT_STRETCH_EXPANDED,
+            DWriteCreateFactory, IDWriteFactory, IDWriteFontCollection, IDWriteInlineObject,
+            IDWriteTextFormat, IDWriteTextLayout, DWRITE_FACTORY_TYPE_SHARED, DWRITE_FONT_STRETCH,
+            DWRITE_FONT_STRETCH_CONDENSED, DWRITE_FONT_STRETCH_EXPANDED,
             DWRITE_FONT_STRETCH_EXTRA_CONDENSED, DWRITE_FONT_STRETCH_EXTRA_EXPANDED,
             DWRITE_FONT_STRETCH_NORMAL, DWRITE_FONT_STRETCH_SEMI_CONDENSED,
             DWRITE_FONT_STRETCH_SEMI_EXPANDED, DWRITE_FONT_STRETCH_ULTRA_CONDENSED,
@@ -17,33 +26,26 @@ use winapi::{
             DWRITE_FONT_WEIGHT_EXTRA_LIGHT, DWRITE_FONT_WEIGHT_LIGHT, DWRITE_FONT_WEIGHT_MEDIUM,
             DWRITE_FONT_WEIGHT_NORMAL, DWRITE_FONT_WEIGHT_SEMI_BOLD, DWRITE_FONT_WEIGHT_SEMI_LIGHT,
             DWRITE_FONT_WEIGHT_THIN, DWRITE_PARAGRAPH_ALIGNMENT, DWRITE_PARAGRAPH_ALIGNMENT_CENTER,
-            DWRITE_PARAGRAPH_ALIGNMENT_FAR, DWRITE_PARAGRAPH_ALIGNMENT_NEAR, DWRITE_TEXT_ALIGNMENT,
+            DWRITE_PARAGRAPH_ALIGNMENT_FAR, DWRITE_PARAGRAPH_ALIGNMENT_NEAR,
+            DWRITE_READING_DIRECTION, DWRITE_READING_DIRECTION_LEFT_TO_RIGHT,
+            DWRITE_READING_DIRECTION_RIGHT_TO_LEFT, DWRITE_TEXT_ALIGNMENT,
             DWRITE_TEXT_ALIGNMENT_CENTER, DWRITE_TEXT_ALIGNMENT_JUSTIFIED,
-            DWRITE_TEXT_ALIGNMENT_LEADING, DWRITE_TEXT_ALIGNMENT_TRAILING,
+            DWRITE_TEXT_ALIGNMENT_LEADING, DWRITE_TEXT_ALIGNMENT_TRAILING, DWRITE_TRIMMING,
+            DWRITE_TRIMMING_GRANULARITY, DWRITE_TRIMMING_GRANULARITY_CHARACTER,
+            DWRITE_TRIMMING_GRANULARITY_NONE, DWRITE_TRIMMING_GRANULARITY_WORD,
         },
         unknwnbase::IUnknown,
     },
     Interface,
 };
 
-pub use text_format::TextFormat;
-
-/// The font weight enumeration describes common values for degree of blackness or thickness of strokes of characters in a font.
-/// Font weight values less than 1 or greater than 999 are considered to be invalid, and they are rejected by font API functions.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub enum FontWeight {
-    Thin,
-    ExtraLight,
-    Light,
-    SemiLight,
-    Normal,
-    Medium,
-    SemiBold,
-    Bold,
-    ExtraBold,
-    Black,
-    ExtraBlack,
-}
+pub use crate::text::{
+    FontStretch, FontStyle, FontWeight, ParagraphAlignment, ReadingDirection, TextAlignment,
+    Trimming, TrimmingGranularity,
+};
+pub use font_collection::{FontCollection, FontFallback};
+pub use text_format::{InlineObject, TextFormat};
+pub use text_layout::TextLayout;
 
 impl Into<DWRITE_FONT_WEIGHT> for FontWeight {
     fn into(self) -> DWRITE_FONT_WEIGHT {
@@ -64,20 +66,12 @@ impl Into<DWRITE_FONT_WEIGHT> for FontWeight {
     }
 }
 
-/// The font stretch enumeration describes relative change from the normal aspect ratio
-/// as specified by a font designer for the glyphs in a font.
-/// Values less than 1 or greater than 9 are considered to be invalid, and they are rejected by font API functions.
-pub enum FontStretch {
-    Undefined,
-    UltraCondensed,
-    ExtraCondensed,
-    Condensed,
-    SemiCondensed,
-    Normal,
-    SemiExpanded,
-    Expanded,
-    ExtraExpanded,
-    UltraExpanded,
+/// Takes the nearest named weight, so every `DWRITE_FONT_WEIGHT_*` constant
+/// converts back to the weight it came from.
+impl From<DWRITE_FONT_WEIGHT> for FontWeight {
+    fn from(weight: DWRITE_FONT_WEIGHT) -> Self {
+        FontWeight::from_numeric(weight.min(u32::from(u16::MAX)) as u16)
+    }
 }
 
 impl Into<DWRITE_FONT_STRETCH> for FontStretch {
@@ -98,13 +92,11 @@ impl Into<DWRITE_FONT_STRETCH> for FontStretch {
     }
 }
 
-/// The font style enumeration describes the slope style of a font face, such as Normal, Italic or Oblique.
-/// Values other than the ones defined in the enumeration are considered to be invalid, and they are rejected by font API functions.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub enum FontStyle {
-    Normal,
-    Oblique,
-    Italic,
+/// Values outside the enumeration are `Undefined`.
+impl From<DWRITE_FONT_STRETCH> for FontStretch {
+    fn from(stretch: DWRITE_FONT_STRETCH) -> Self {
+        FontStretch::from_numeric(stretch.min(u32::from(u16::MAX)) as u16)
+    }
 }
 
 impl Into<DWRITE_FONT_STYLE> for FontStyle {
@@ -118,18 +110,15 @@ impl Into<DWRITE_FONT_STYLE> for FontStyle {
     }
 }
 
-/// Alignment of paragraph text along the reading direction axis relative to
-/// the leading and trailing edge of the layout box.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub enum TextAlignment {
-    /// The leading edge of the paragraph text is aligned to the layout box's leading edge.
-    Leading,
-    /// The trailing edge of the paragraph text is aligned to the layout box's trailing edge.
-    Trailing,
-    /// The center of the paragraph text is aligned to the center of the layout box.
-    Center,
-    /// Align text to the leading side, and also justify text to fill the lines.
-    Justified,
+/// Values outside the enumeration are `Normal`.
+impl From<DWRITE_FONT_STYLE> for FontStyle {
+    fn from(style: DWRITE_FONT_STYLE) -> Self {
+        match style {
+            DWRITE_FONT_STYLE_OBLIQUE => FontStyle::Oblique,
+            DWRITE_FONT_STYLE_ITALIC => FontStyle::Italic,
+            _ => FontStyle::Normal,
+        }
+    }
 }
 
 impl Into<DWRITE_TEXT_ALIGNMENT> for TextAlignment {
@@ -144,17 +133,6 @@ impl Into<DWRITE_TEXT_ALIGNMENT> for TextAlignment {
     }
 }
 
-/// Alignment of paragraph text along the flow direction axis relative to the
-/// flow's beginning and ending edge of the layout box.
-pub enum ParagraphAlignment {
-    /// The first line of paragraph is aligned to the flow's beginning edge of the layout box.
-    Near,
-    /// The last line of paragraph is aligned to the flow's ending edge of the layout box.
-    Far,
-    /// The center of the paragraph is aligned to the center of the flow of the layout box.
-    Center,
-}
-
 impl Into<DWRITE_PARAGRAPH_ALIGNMENT> for ParagraphAlignment {
     fn into(self) -> DWRITE_PARAGRAPH_ALIGNMENT {
         use ParagraphAlignment::*;
@@ -166,51 +144,146 @@ impl Into<DWRITE_PARAGRAPH_ALIGNMENT> for ParagraphAlignment {
     }
 }
 
-impl_comptr! { Factory: [IDWriteFactory, IUnknown] }
+impl Into<DWRITE_READING_DIRECTION> for ReadingDirection {
+    fn into(self) -> DWRITE_READING_DIRECTION {
+        use ReadingDirection::*;
+        match self {
+            LeftToRight => DWRITE_READING_DIRECTION_LEFT_TO_RIGHT,
+            RightToLeft => DWRITE_READING_DIRECTION_RIGHT_TO_LEFT,
+        }
+    }
+}
+
+impl Into<DWRITE_TRIMMING_GRANULARITY> for TrimmingGranularity {
+    fn into(self) -> DWRITE_TRIMMING_GRANULARITY {
+        use TrimmingGranularity::*;
+        match self {
+            None => DWRITE_TRIMMING_GRANULARITY_NONE,
+            Character => DWRITE_TRIMMING_GRANULARITY_CHARACTER,
+            Word => DWRITE_TRIMMING_GRANULARITY_WORD,
+        }
+    }
+}
+
+impl Into<DWRITE_TRIMMING> for Trimming {
+    fn into(self) -> DWRITE_TRIMMING {
+        DWRITE_TRIMMING {
+            granularity: self.granularity.into(),
+            delimiter: self.delimiter.map_or(0, u32::from),
+            delimiterCount: self.delimiter_count,
+        }
+    }
+}
 
-pub fn create_shared_factory<T, I>() -> T
+impl_comptr! { Factory: [IDWriteFactory, IUnknown] in Agile }
+
+pub fn create_shared_factory<T, I>() -> Result<T>
 where
     T: From<ComPtr<I>>,
     I: Interface,
 {
-    unsafe {
-        let mut factory = ComPtr::<I>::default();
-        let hr = DWriteCreateFactory(
-            DWRITE_FACTORY_TYPE_SHARED,
-            &I::uuidof(),
-            factory.getter_addrefs(),
-        );
-        assert!(hr == 0);
-        factory.into()
-    }
+    let mut factory = ComPtr::<I>::default();
+    hr!(DWriteCreateFactory(
+        DWRITE_FACTORY_TYPE_SHARED,
+        &I::uuidof(),
+        factory.getter_addrefs(),
+    ))?;
+    Ok(factory.into())
 }
 
 impl Factory {
+    /// Looks `family_name` up in `collection`, or in the system fonts when
+    /// `collection` is `None`. A collection's fallback families apply to the
+    /// format. Fails with `E_INVALIDARG` if either name contains a NUL.
     pub fn create_text_format(
         &self,
-        family_name: &str,
-        /*collection,*/ weight: FontWeight,
+        family_name: impl ToWideStr,
+        collection: Option<&FontCollection>,
+        weight: FontWeight,
         style: FontStyle,
         stretch: FontStretch,
         size: f32,
-        locale_name: &str,
-    ) -> TextFormat {
-        let family_name = family_name.to_utf16();
-        let locale_name = locale_name.to_utf16();
+        locale_name: impl ToWideStr,
+    ) -> Result<TextFormat> {
+        let family_name = family_name.to_wide_str()?;
+        let locale_name = locale_name.to_wide_str()?;
         let mut text_format = ComPtr::<IDWriteTextFormat>::default();
-        let hr = unsafe {
-            self.0.CreateTextFormat(
-                family_name.as_ptr(),
-                ptr::null_mut(),
-                weight.into(),
-                style.into(),
-                stretch.into(),
-                size,
-                locale_name.as_ptr(),
-                text_format.getter_addrefs(),
-            )
-        };
-        assert!(hr == 0);
-        text_format.into()
+        hr!(self.0.CreateTextFormat(
+            family_name.as_ptr(),
+            collection.map_or(ptr::null_mut(), AsPtr::<IDWriteFontCollection>::as_ptr),
+            weight.into(),
+            style.into(),
+            stretch.into(),
+            size,
+            locale_name.as_ptr(),
+            text_format.getter_addrefs(),
+        ))?;
+        let text_format: TextFormat = text_format.into();
+        if let Some(fallback) = collection.and_then(FontCollection::font_fallback) {
+            text_format.set_font_fallback(fallback)?;
+        }
+        Ok(text_format)
+    }
+
+    /// Creates the sign DirectWrite draws where `text_format` trims text,
+    /// for `TextFormat::set_trimming`.
+    pub fn create_ellipsis_trimming_sign(&self, text_format: &TextFormat) -> Result<InlineObject> {
+        let mut sign = ComPtr::<IDWriteInlineObject>::default();
+        hr!(self
+            .0
+            .CreateEllipsisTrimmingSign(text_format.as_ptr(), sign.getter_addrefs()))?;
+        Ok(sign.into())
+    }
+
+    /// Lays out `text` with `text_format` in a box of `max_width` by
+    /// `max_height` DIPs.
+    pub fn create_text_layout(
+        &self,
+        text: &str,
+        text_format: &TextFormat,
+        max_width: f32,
+        max_height: f32,
+    ) -> Result<TextLayout> {
+        let text: Vec<u16> = text.encode_utf16().collect();
+        let mut text_layout = ComPtr::<IDWriteTextLayout>::default();
+        hr!(self.0.CreateTextLayout(
+            text.as_ptr(),
+            text.len() as u32,
+            text_format.as_ptr(),
+            max_width,
+            max_height,
+            text_layout.getter_addrefs(),
+        ))?;
+        Ok(text_layout.into())
+    }
+
+    /// Lays out rich text, applying its attributes in the order they were
+    /// set. `brush` makes the brush for each color attribute.
+    pub fn create_rich_text_layout(
+        &self,
+        text: &RichText,
+        text_format: &TextFormat,
+        max_width: f32,
+        max_height: f32,
+        mut brush: impl FnMut([f32; 4]) -> Result<SolidColorBrush>,
+    ) -> Result<TextLayout> {
+        let layout = self.create_text_layout(text.text(), text_format, max_width, max_height)?;
+        for (range, attribute) in text.attributes() {
+            let range = range.clone();
+            match attribute {
+                TextAttribute::FamilyName(name) => layout.set_font_family_name(name, range),
+                TextAttribute::Weight(weight) => layout.set_font_weight(*weight, range),
+                TextAttribute::Style(style) => layout.set_font_style(*style, range),
+                TextAttribute::Stretch(stretch) => layout.set_font_stretch(*stretch, range),
+                TextAttribute::Size(size) => layout.set_font_size(*size, range),
+                TextAttribute::Color(color) => layout.set_brush(&brush(*color)?, range),
+                TextAttribute::Underline(underline) => layout.set_underline(*underline, range),
+                TextAttribute::Strikethrough(strikethrough) => {
+                    layout.set_strikethrough(*strikethrough, range)
+                }
+                TextAttribute::LetterSpacing(spacing) => layout.set_letter_spacing(*spacing, range),
+            }?;
+        }
+        Ok(layout)
     }
 }
@@ -2,36 +2,65 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use super::{ParagraphAlignment, TextAlignment};
+use super::{
+    FontFallback, ParagraphAlignment, ReadingDirection, TextAlignment, TextLayout, Trimming,
+};
 use crate::{
     d2d::{DeviceContext, DeviceContext1, RectF},
-    impl_comptr, impl_interface, AsPtr,
+    call, hr, impl_comptr, impl_interface, AsPtr, Error, Result,
 };
-use winapi::um::{
-    d2d1::{ID2D1Brush, D2D1_DRAW_TEXT_OPTIONS_NONE},
-    dcommon::DWRITE_MEASURING_MODE_NATURAL,
-    dwrite::IDWriteTextFormat,
-    unknwnbase::IUnknown,
+use std::ptr;
+use winapi::{
+    shared::winerror::E_NOINTERFACE,
+    um::{
+        d2d1::{ID2D1Brush, D2D1_DRAW_TEXT_OPTIONS_NONE},
+        dcommon::DWRITE_MEASURING_MODE_NATURAL,
+        dwrite::{IDWriteInlineObject, IDWriteTextFormat, DWRITE_TRIMMING},
+        dwrite_2::IDWriteTextFormat1,
+        unknwnbase::IUnknown,
+    },
 };
 
 impl_comptr! { TextFormat: [IDWriteTextFormat, IUnknown] }
+impl_comptr! { InlineObject: [IDWriteInlineObject, IUnknown] }
 
 impl_interface! {
-    impl TextFormat {
+    impl [TextFormat, TextLayout] {
         /// Set alignment option of text relative to layout box's leading and trailing edge.
-        pub fn set_text_alignment(&self, alignment: TextAlignment) {
-            let hr = unsafe {
-                self.0.SetTextAlignment(alignment.into())
-            };
-            assert!(hr == 0);
+        pub fn set_text_alignment(&self, alignment: TextAlignment) -> Result<()> {
+            hr!(self.0.SetTextAlignment(alignment.into()))
         }
 
         /// Set alignment option of paragraph relative to layout box's top and bottom edge.
-        pub fn set_paragraph_alignment(&self, alignment: ParagraphAlignment) {
-            let hr = unsafe {
-                self.0.SetParagraphAlignment(alignment.into())
-            };
-            assert!(hr == 0);
+        pub fn set_paragraph_alignment(&self, alignment: ParagraphAlignment) -> Result<()> {
+            hr!(self.0.SetParagraphAlignment(alignment.into()))
+        }
+
+        /// Set the base direction of paragraphs.
+        pub fn set_reading_direction(&self, direction: ReadingDirection) -> Result<()> {
+            hr!(self.0.SetReadingDirection(direction.into()))
+        }
+
+        /// Set how text that overflows the layout box is trimmed, and the sign
+        /// drawn where it is cut, such as one from
+        /// `Factory::create_ellipsis_trimming_sign`. DirectWrite ignores
+        /// `trimming.ellipsis` and draws no sign without one.
+        pub fn set_trimming(&self, trimming: Trimming, sign: Option<&InlineObject>) -> Result<()> {
+            let trimming: DWRITE_TRIMMING = trimming.into();
+            hr!(self.0.SetTrimming(
+                &trimming,
+                sign.map_or(ptr::null_mut(), AsPtr::<IDWriteInlineObject>::as_ptr),
+            ))
+        }
+
+        /// Set the fallback used for characters the format's font lacks.
+        /// Requires Windows 8.1, and fails with `E_NOINTERFACE` before it.
+        pub fn set_font_fallback(&self, fallback: &FontFallback) -> Result<()> {
+            let format = self
+                .0
+                .query_interface::<IDWriteTextFormat1>()
+                .ok_or_else(|| Error::new(E_NOINTERFACE, "QueryInterface"))?;
+            hr!(format.SetFontFallback(fallback.as_ptr()))
         }
     }
 }
@@ -48,17 +77,15 @@ impl_interface! {
             layout_rect: &RectF,
             default_fill_brush: &impl AsPtr<ID2D1Brush>,
         ) {
-            unsafe {
-                self.0.DrawText(
-                    text.as_ptr(),
-                    text.len() as u32,
-                    text_format.as_ptr(),
-                    &**layout_rect,
-                    default_fill_brush.as_ptr(),
-                    D2D1_DRAW_TEXT_OPTIONS_NONE,
-                    DWRITE_MEASURING_MODE_NATURAL,
-                )
-            }
+            call!(self.0.DrawText(
+                text.as_ptr(),
+                text.len() as u32,
+                text_format.as_ptr(),
+                &**layout_rect,
+                default_fill_brush.as_ptr(),
+                D2D1_DRAW_TEXT_OPTIONS_NONE,
+                DWRITE_MEASURING_MODE_NATURAL,
+            ))
         }
     }
 }
@@ -0,0 +1,277 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::{FontStretch, FontStyle, FontWeight};
+use crate::{
+    d2d::{DeviceContext, DeviceContext1, Point2F},
+    call, hr, impl_comptr, impl_interface,
+    sys::HRESULT,
+    text::{HitTestMetrics, HitTestPoint, LineMetrics, TextMetrics},
+    AsPtr, Error, Result, ToWideStr,
+};
+use std::{ops::Range, ptr};
+use winapi::{
+    shared::winerror::{ERROR_INSUFFICIENT_BUFFER, E_NOINTERFACE, HRESULT_FROM_WIN32},
+    um::{
+        d2d1::{ID2D1Brush, D2D1_DRAW_TEXT_OPTIONS_NONE},
+        dwrite::{
+            IDWriteTextFormat, IDWriteTextLayout, DWRITE_HIT_TEST_METRICS, DWRITE_LINE_METRICS,
+            DWRITE_TEXT_METRICS, DWRITE_TEXT_RANGE,
+        },
+        dwrite_1::IDWriteTextLayout1,
+        unknwnbase::IUnknown,
+    },
+};
+
+impl_comptr! { TextLayout: [IDWriteTextLayout, IDWriteTextFormat, IUnknown] }
+
+impl From<DWRITE_LINE_METRICS> for LineMetrics {
+    fn from(m: DWRITE_LINE_METRICS) -> Self {
+        Self {
+            length: m.length,
+            trailing_whitespace_length: m.trailingWhitespaceLength,
+            newline_length: m.newlineLength,
+            height: m.height,
+            baseline: m.baseline,
+            is_trimmed: m.isTrimmed != 0,
+        }
+    }
+}
+
+impl From<DWRITE_TEXT_METRICS> for TextMetrics {
+    fn from(m: DWRITE_TEXT_METRICS) -> Self {
+        Self {
+            left: m.left,
+            top: m.top,
+            width: m.width,
+            width_including_trailing_whitespace: m.widthIncludingTrailingWhitespace,
+            height: m.height,
+            layout_width: m.layoutWidth,
+            layout_height: m.layoutHeight,
+            line_count: m.lineCount,
+        }
+    }
+}
+
+impl From<DWRITE_HIT_TEST_METRICS> for HitTestMetrics {
+    fn from(m: DWRITE_HIT_TEST_METRICS) -> Self {
+        Self {
+            text_position: m.textPosition,
+            length: m.length,
+            left: m.left,
+            top: m.top,
+            width: m.width,
+            height: m.height,
+            bidi_level: m.bidiLevel,
+            is_text: m.isText != 0,
+            is_trimmed: m.isTrimmed != 0,
+        }
+    }
+}
+
+/// Calls the DirectWrite getter `call` that fills an array, first asking
+/// for the number of elements.
+fn get_array<T: Copy + Default>(
+    call: &'static str,
+    get: impl Fn(*mut T, u32, *mut u32) -> HRESULT,
+) -> Result<Vec<T>> {
+    let mut count = 0;
+    let hr = get(ptr::null_mut(), 0, &mut count);
+    if hr != HRESULT_FROM_WIN32(ERROR_INSUFFICIENT_BUFFER) {
+        Error::check(hr, call)?;
+    }
+    let mut items = vec![T::default(); count as usize];
+    Error::check(get(items.as_mut_ptr(), count, &mut count), call)?;
+    items.truncate(count as usize);
+    Ok(items)
+}
+
+fn text_range(range: Range<u32>) -> DWRITE_TEXT_RANGE {
+    DWRITE_TEXT_RANGE {
+        startPosition: range.start,
+        length: range.end.saturating_sub(range.start),
+    }
+}
+
+impl_interface! {
+    impl TextLayout {
+        pub fn max_width(&self) -> f32 {
+            call!(self.0.GetMaxWidth())
+        }
+
+        pub fn set_max_width(&self, max_width: f32) -> Result<()> {
+            hr!(self.0.SetMaxWidth(max_width))
+        }
+
+        pub fn max_height(&self) -> f32 {
+            call!(self.0.GetMaxHeight())
+        }
+
+        pub fn set_max_height(&self, max_height: f32) -> Result<()> {
+            hr!(self.0.SetMaxHeight(max_height))
+        }
+
+        /// Metrics for each line, in order.
+        pub fn line_metrics(&self) -> Result<Vec<LineMetrics>> {
+            let lines: Vec<DWRITE_LINE_METRICS> =
+                get_array("GetLineMetrics", |lines, max, count| {
+                    call!(self.0.GetLineMetrics(lines, max, count))
+                })?;
+            Ok(lines.into_iter().map(LineMetrics::from).collect())
+        }
+
+        /// Whether text was trimmed to fit, in which case a UI might offer
+        /// the full text in a tooltip.
+        pub fn is_trimmed(&self) -> Result<bool> {
+            Ok(self.line_metrics()?.iter().any(|line| line.is_trimmed))
+        }
+
+        /// Shrinks the layout box to the first `max_lines` lines if there
+        /// are more, so that a format that trims cuts the text after them.
+        pub fn set_max_lines(&self, max_lines: u32) -> Result<()> {
+            let lines = self.line_metrics()?;
+            let max_lines = max_lines.max(1) as usize;
+            if lines.len() > max_lines {
+                let height = lines[..max_lines].iter().map(|line| line.height).sum();
+                self.set_max_height(self.max_height().min(height))?;
+            }
+            Ok(())
+        }
+
+        pub fn metrics(&self) -> Result<TextMetrics> {
+            let mut metrics = Default::default();
+            hr!(self.0.GetMetrics(&mut metrics))?;
+            Ok(TextMetrics::from(metrics))
+        }
+
+        /// Finds the character nearest to a point relative to the layout box.
+        pub fn hit_test_point(&self, x: f32, y: f32) -> Result<HitTestPoint> {
+            let mut is_trailing_hit = 0;
+            let mut is_inside = 0;
+            let mut metrics = Default::default();
+            hr!(self.0.HitTestPoint(x, y, &mut is_trailing_hit, &mut is_inside, &mut metrics))?;
+            Ok(HitTestPoint {
+                is_trailing_hit: is_trailing_hit != 0,
+                is_inside: is_inside != 0,
+                metrics: metrics.into(),
+            })
+        }
+
+        /// Returns the caret position for a text position, along with the
+        /// metrics of the character there.
+        pub fn hit_test_text_position(
+            &self,
+            text_position: u32,
+            is_trailing_hit: bool,
+        ) -> Result<(f32, f32, HitTestMetrics)> {
+            let mut x = 0.0;
+            let mut y = 0.0;
+            let mut metrics = Default::default();
+            hr!(self.0.HitTestTextPosition(
+                text_position,
+                is_trailing_hit as i32,
+                &mut x,
+                &mut y,
+                &mut metrics,
+            ))?;
+            Ok((x, y, metrics.into()))
+        }
+
+        /// Returns the rectangles covered by a range of text, offset by the
+        /// origin.
+        pub fn hit_test_text_range(
+            &self,
+            text_position: u32,
+            text_length: u32,
+            origin_x: f32,
+            origin_y: f32,
+        ) -> Result<Vec<HitTestMetrics>> {
+            let ranges: Vec<DWRITE_HIT_TEST_METRICS> =
+                get_array("HitTestTextRange", |ranges, max, count| {
+                    call!(self.0.HitTestTextRange(
+                        text_position,
+                        text_length,
+                        origin_x,
+                        origin_y,
+                        ranges,
+                        max,
+                        count,
+                    ))
+                })?;
+            Ok(ranges.into_iter().map(HitTestMetrics::from).collect())
+        }
+
+        // Formatting of ranges, which count UTF-16 code units.
+
+        /// Fails with `E_INVALIDARG` if `family_name` contains a NUL.
+        pub fn set_font_family_name(
+            &self,
+            family_name: impl ToWideStr,
+            range: Range<u32>,
+        ) -> Result<()> {
+            let family_name = family_name.to_wide_str()?;
+            hr!(self.0.SetFontFamilyName(family_name.as_ptr(), text_range(range)))
+        }
+
+        pub fn set_font_weight(&self, weight: FontWeight, range: Range<u32>) -> Result<()> {
+            hr!(self.0.SetFontWeight(weight.into(), text_range(range)))
+        }
+
+        pub fn set_font_style(&self, style: FontStyle, range: Range<u32>) -> Result<()> {
+            hr!(self.0.SetFontStyle(style.into(), text_range(range)))
+        }
+
+        pub fn set_font_stretch(&self, stretch: FontStretch, range: Range<u32>) -> Result<()> {
+            hr!(self.0.SetFontStretch(stretch.into(), text_range(range)))
+        }
+
+        pub fn set_font_size(&self, size: f32, range: Range<u32>) -> Result<()> {
+            hr!(self.0.SetFontSize(size, text_range(range)))
+        }
+
+        pub fn set_underline(&self, underline: bool, range: Range<u32>) -> Result<()> {
+            hr!(self.0.SetUnderline(underline as i32, text_range(range)))
+        }
+
+        pub fn set_strikethrough(&self, strikethrough: bool, range: Range<u32>) -> Result<()> {
+            hr!(self.0.SetStrikethrough(strikethrough as i32, text_range(range)))
+        }
+
+        /// Makes Direct2D draw the range with `brush` rather than the brush
+        /// passed to `draw_text_layout`.
+        pub fn set_brush(&self, brush: &impl AsPtr<ID2D1Brush>, range: Range<u32>) -> Result<()> {
+            hr!(self.0.SetDrawingEffect(brush.as_ptr() as *mut IUnknown, text_range(range)))
+        }
+
+        /// Adds `spacing` DIPs after each character of the range. Fails with
+        /// `E_NOINTERFACE` before Windows 8.
+        pub fn set_letter_spacing(&self, spacing: f32, range: Range<u32>) -> Result<()> {
+            let layout = self
+                .0
+                .query_interface::<IDWriteTextLayout1>()
+                .ok_or_else(|| Error::new(E_NOINTERFACE, "QueryInterface"))?;
+            hr!(layout.SetCharacterSpacing(0.0, spacing, 0.0, text_range(range)))
+        }
+    }
+}
+
+// ID2D1RenderTarget
+impl_interface! {
+    impl [DeviceContext, DeviceContext1] {
+        /// Draws formatted text with its top left corner at `origin`.
+        pub fn draw_text_layout(
+            &self,
+            origin: Point2F,
+            text_layout: &TextLayout,
+            default_fill_brush: &impl AsPtr<ID2D1Brush>,
+        ) {
+            call!(self.0.DrawTextLayout(
+                origin,
+                text_layout.as_ptr(),
+                default_fill_brush.as_ptr(),
+                D2D1_DRAW_TEXT_OPTIONS_NONE,
+            ))
+        }
+    }
+}
@@ -9,8 +9,8 @@ use winapi::{
     um::unknwnbase::IUnknown,
 };
 
-impl_comptr!( Device: [IDXGIDevice, IUnknown]);
-impl_comptr!(Device1: [IDXGIDevice1, IDXGIDevice, IUnknown]);
-impl_comptr!(Device2: [IDXGIDevice2, IDXGIDevice1, IDXGIDevice, IUnknown]);
-impl_comptr!(Device3: [IDXGIDevice3, IDXGIDevice2, IDXGIDevice1, IDXGIDevice, IUnknown]);
-impl_comptr!(Device4: [IDXGIDevice4, IDXGIDevice3, IDXGIDevice2, IDXGIDevice1, IDXGIDevice, IUnknown]);
+impl_comptr!( Device: [IDXGIDevice, IUnknown] in Agile);
+impl_comptr!(Device1: [IDXGIDevice1, IDXGIDevice, IUnknown] in Agile);
+impl_comptr!(Device2: [IDXGIDevice2, IDXGIDevice1, IDXGIDevice, IUnknown] in Agile);
+impl_comptr!(Device3: [IDXGIDevice3, IDXGIDevice2, IDXGIDevice1, IDXGIDevice, IUnknown] in Agile);
+impl_comptr!(Device4: [IDXGIDevice4, IDXGIDevice3, IDXGIDevice2, IDXGIDevice1, IDXGIDevice, IUnknown] in Agile);
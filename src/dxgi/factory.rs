@@ -1,4 +1,4 @@
-use crate::{comptr::ComPtr, impl_comptr};
+use crate::{call, comptr::ComPtr, hr, impl_comptr, Error, Result};
 use derive_newtype::NewType;
 use std::{ffi::OsString, os::windows::ffi::OsStringExt};
 use winapi::{
@@ -15,38 +15,36 @@ use winapi::{
     Interface,
 };
 
-impl_comptr! {  Factory: [IDXGIFactory,]}
-impl_comptr! { Factory1: [IDXGIFactory1, IDXGIFactory]}
-impl_comptr! { Factory2: [IDXGIFactory2, IDXGIFactory1, IDXGIFactory]}
+impl_comptr! {  Factory: [IDXGIFactory,] in Agile}
+impl_comptr! { Factory1: [IDXGIFactory1, IDXGIFactory] in Agile}
+impl_comptr! { Factory2: [IDXGIFactory2, IDXGIFactory1, IDXGIFactory] in Agile}
 
-pub fn create_factory<I>() -> ComPtr<I>
+pub fn create_factory<I>() -> Result<ComPtr<I>>
 where
     I: Interface,
 {
-    unsafe {
-        let mut native = ComPtr::<I>::default();
-        let hr = CreateDXGIFactory1(&I::uuidof(), native.getter_addrefs());
-        assert!(hr == 0);
-        native
-    }
+    let mut native = ComPtr::<I>::default();
+    hr!(CreateDXGIFactory1(&I::uuidof(), native.getter_addrefs()))?;
+    Ok(native)
 }
 
-pub fn create_factory_2(debug: bool) -> Factory2 {
+pub fn create_factory_2(debug: bool) -> Result<Factory2> {
     let flags = if debug { DXGI_CREATE_FACTORY_DEBUG } else { 0 };
 
-    unsafe {
-        let mut factory = ComPtr::<IDXGIFactory2>::default();
-        let hr = CreateDXGIFactory2(flags, &IDXGIFactory2::uuidof(), factory.getter_addrefs());
-        assert!(hr == 0);
-        factory.into()
-    }
+    let mut factory = ComPtr::<IDXGIFactory2>::default();
+    hr!(CreateDXGIFactory2(
+        flags,
+        &IDXGIFactory2::uuidof(),
+        factory.getter_addrefs()
+    ))?;
+    Ok(factory.into())
 }
 
-impl_comptr!( Adapter: [IDXGIAdapter, IDXGIObject, IUnknown]);
-impl_comptr!(Adapter1: [IDXGIAdapter1, IDXGIAdapter, IDXGIObject, IUnknown]);
+impl_comptr!( Adapter: [IDXGIAdapter, IDXGIObject, IUnknown] in Agile);
+impl_comptr!(Adapter1: [IDXGIAdapter1, IDXGIAdapter, IDXGIObject, IUnknown] in Agile);
 
 impl Factory2 {
-    pub fn adapters1<'a>(&'a self) -> impl Iterator<Item = Adapter1> + 'a {
+    pub fn adapters1<'a>(&'a self) -> impl Iterator<Item = Result<Adapter1>> + 'a {
         Adapter1Iter {
             factory: self,
             curr: 0,
@@ -86,11 +84,10 @@ impl std::fmt::Debug for AdapterDesc1 {
 }
 
 impl Adapter1 {
-    pub fn desc1(&self) -> AdapterDesc1 {
+    pub fn desc1(&self) -> Result<AdapterDesc1> {
         let mut result = AdapterDesc1::default();
-        let hr = unsafe { self.0.GetDesc1(&mut *result) };
-        assert!(hr == 0);
-        result
+        hr!(self.0.GetDesc1(&mut *result))?;
+        Ok(result)
     }
 }
 
@@ -100,19 +97,14 @@ struct Adapter1Iter<'a> {
 }
 
 impl Iterator for Adapter1Iter<'_> {
-    type Item = Adapter1;
+    type Item = Result<Adapter1>;
     fn next(&mut self) -> Option<Self::Item> {
         let mut adapter = ComPtr::<IDXGIAdapter1>::default();
-        let hr = unsafe {
-            self.factory
-                .0
-                .EnumAdapters1(self.curr, adapter.getter_addrefs())
-        };
+        let hr = call!(self.factory.0.EnumAdapters1(self.curr, adapter.getter_addrefs()), index = self.curr);
         self.curr += 1;
         match hr {
-            0 => Some(adapter.into()),
             DXGI_ERROR_NOT_FOUND => None,
-            _ => unreachable!(),
+            hr => Some(Error::check(hr, "EnumAdapters1").map(|()| adapter.into())),
         }
     }
 }
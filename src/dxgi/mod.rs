@@ -14,7 +14,7 @@ pub use surface::{Surface, Surface1, Surface2};
 pub use swap_chain::{SwapChain1, SwapChainDesc1};
 
 // ===== DXGI_FORMAT =====
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub enum AlphaMode {
     Unspecified,
     Premultiplied,
@@ -47,7 +47,7 @@ impl From<AlphaMode> for DXGI_ALPHA_MODE {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub enum Format {
     Bgra8,
     Nv12,
@@ -1,6 +1,6 @@
 use crate::{
     dxgi::{Adapter1, Format, OverlaySupport},
-    impl_comptr, impl_interface, AsPtr, ComPtr,
+    call, hr, impl_comptr, impl_interface, AsPtr, ComPtr, Error, Result,
 };
 use winapi::{
     shared::{
@@ -17,7 +17,7 @@ use winapi::{
 };
 
 impl Adapter1 {
-    pub fn outputs<'a>(&'a self) -> impl Iterator<Item = Output> + 'a {
+    pub fn outputs<'a>(&'a self) -> impl Iterator<Item = Result<Output>> + 'a {
         OutputIter {
             adapter: self,
             curr: 0,
@@ -25,13 +25,13 @@ impl Adapter1 {
     }
 }
 
-impl_comptr!( Output: [IDXGIOutput, IDXGIObject, IUnknown]);
-impl_comptr!(Output1: [IDXGIOutput1, IDXGIOutput, IDXGIObject, IUnknown]);
-impl_comptr!(Output2: [IDXGIOutput2, IDXGIOutput1, IDXGIOutput, IDXGIObject, IUnknown]);
-impl_comptr!(Output3: [IDXGIOutput3, IDXGIOutput2, IDXGIOutput1, IDXGIOutput, IDXGIObject, IUnknown]);
-impl_comptr!(Output4: [IDXGIOutput4, IDXGIOutput3, IDXGIOutput2, IDXGIOutput1, IDXGIOutput, IDXGIObject, IUnknown]);
-impl_comptr!(Output5: [IDXGIOutput5, IDXGIOutput4, IDXGIOutput3, IDXGIOutput2, IDXGIOutput1, IDXGIOutput, IDXGIObject, IUnknown]);
-impl_comptr!(Output6: [IDXGIOutput6, IDXGIOutput5, IDXGIOutput4, IDXGIOutput3, IDXGIOutput2, IDXGIOutput1, IDXGIOutput, IDXGIObject, IUnknown]);
+impl_comptr!( Output: [IDXGIOutput, IDXGIObject, IUnknown] in Agile);
+impl_comptr!(Output1: [IDXGIOutput1, IDXGIOutput, IDXGIObject, IUnknown] in Agile);
+impl_comptr!(Output2: [IDXGIOutput2, IDXGIOutput1, IDXGIOutput, IDXGIObject, IUnknown] in Agile);
+impl_comptr!(Output3: [IDXGIOutput3, IDXGIOutput2, IDXGIOutput1, IDXGIOutput, IDXGIObject, IUnknown] in Agile);
+impl_comptr!(Output4: [IDXGIOutput4, IDXGIOutput3, IDXGIOutput2, IDXGIOutput1, IDXGIOutput, IDXGIObject, IUnknown] in Agile);
+impl_comptr!(Output5: [IDXGIOutput5, IDXGIOutput4, IDXGIOutput3, IDXGIOutput2, IDXGIOutput1, IDXGIOutput, IDXGIObject, IUnknown] in Agile);
+impl_comptr!(Output6: [IDXGIOutput6, IDXGIOutput5, IDXGIOutput4, IDXGIOutput3, IDXGIOutput2, IDXGIOutput1, IDXGIOutput, IDXGIObject, IUnknown] in Agile);
 
 struct OutputIter<'a> {
     adapter: &'a Adapter1,
@@ -39,19 +39,14 @@ struct OutputIter<'a> {
 }
 
 impl Iterator for OutputIter<'_> {
-    type Item = Output;
+    type Item = Result<Output>;
     fn next(&mut self) -> Option<Self::Item> {
         let mut output = ComPtr::<IDXGIOutput>::default();
-        let hr = unsafe {
-            self.adapter
-                .0
-                .EnumOutputs(self.curr, output.getter_addrefs())
-        };
+        let hr = call!(self.adapter.0.EnumOutputs(self.curr, output.getter_addrefs()), index = self.curr);
         self.curr += 1;
         match hr {
-            0 => Some(output.into()),
             DXGI_ERROR_NOT_FOUND => None,
-            _ => unreachable!(),
+            hr => Some(Error::check(hr, "EnumOutputs").map(|()| output.into())),
         }
     }
 }
@@ -59,7 +54,7 @@ impl Iterator for OutputIter<'_> {
 impl_interface! {
     impl [Output2, Output3, Output4, Output5, Output6] {
         pub fn supports_overlays(&self) -> bool {
-            unsafe { self.0.SupportsOverlays() != FALSE }
+            call!(self.0.SupportsOverlays()) != FALSE
         }
     }
 }
@@ -70,14 +65,12 @@ impl_interface! {
             &self,
             format: Format,
             concerned_device: &impl AsPtr<IUnknown>,
-        ) -> OverlaySupport {
+        ) -> Result<OverlaySupport> {
             let mut flags = 0;
-            let hr = unsafe {
-                self.0
-                    .CheckOverlaySupport(format.into(), concerned_device.as_ptr(), &mut flags)
-            };
-            assert!(hr == 0);
-            flags.into()
+            hr!(self
+                .0
+                .CheckOverlaySupport(format.into(), concerned_device.as_ptr(), &mut flags))?;
+            Ok(flags.into())
         }
     }
 }
\ No newline at end of file
@@ -1,9 +1,19 @@
-use crate::impl_comptr;
+use crate::{hr, impl_comptr, impl_interface, Result, Size};
 use winapi::shared::{
-    dxgi::{IDXGIDeviceSubObject, IDXGIObject, IDXGISurface, IDXGISurface1},
+    dxgi::{IDXGIDeviceSubObject, IDXGIObject, IDXGISurface, IDXGISurface1, DXGI_SURFACE_DESC},
     dxgi1_2::IDXGISurface2,
 };
 
-impl_comptr! { Surface: [IDXGISurface, IDXGIDeviceSubObject, IDXGIObject] }
-impl_comptr! { Surface1: [IDXGISurface1, IDXGISurface, IDXGIDeviceSubObject, IDXGIObject] }
-impl_comptr! { Surface2: [IDXGISurface2, IDXGISurface1, IDXGISurface, IDXGIDeviceSubObject, IDXGIObject] }
+impl_comptr! { Surface: [IDXGISurface, IDXGIDeviceSubObject, IDXGIObject] in Agile }
+impl_comptr! { Surface1: [IDXGISurface1, IDXGISurface, IDXGIDeviceSubObject, IDXGIObject] in Agile }
+impl_comptr! { Surface2: [IDXGISurface2, IDXGISurface1, IDXGISurface, IDXGIDeviceSubObject, IDXGIObject] in Agile }
+
+impl_interface! {
+    impl [Surface, Surface1, Surface2] {
+        pub fn size(&self) -> Result<Size> {
+            let mut desc = DXGI_SURFACE_DESC::default();
+            hr!(self.0.GetDesc(&mut desc))?;
+            Ok(Size::new(desc.Width as i32, desc.Height as i32))
+        }
+    }
+}
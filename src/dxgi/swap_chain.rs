@@ -1,6 +1,6 @@
 use crate::{
     dxgi::{Factory2, Output},
-    impl_comptr, AsPtr, ComPtr,
+    hr, impl_comptr, AsPtr, ComPtr, Result,
 };
 use winapi::{
     shared::{
@@ -13,6 +13,8 @@ use winapi::{
 
 pub use winapi::shared::dxgi1_2::DXGI_SWAP_CHAIN_DESC1 as SwapChainDesc1;
 
+// `Present` and `ResizeBuffers` change the swap chain's buffers, so it can't
+// be used from two threads at once.
 impl_comptr! { SwapChain : [IDXGISwapChain, IDXGIDeviceSubObject, IDXGIObject, IUnknown] }
 impl_comptr! { SwapChain1: [IDXGISwapChain1, IDXGISwapChain, IDXGIDeviceSubObject, IDXGIObject, IUnknown] }
 
@@ -22,42 +24,35 @@ impl Factory2 {
         device: &impl AsPtr<IUnknown>,
         desc: &SwapChainDesc1,
         restrict_to_output: impl Into<Option<Output>>,
-    ) -> SwapChain1 {
+    ) -> Result<SwapChain1> {
         let restrict_to_output = restrict_to_output.into();
-        unsafe {
-            let mut swap_chain = ComPtr::<IDXGISwapChain1>::default();
-            let hr = self.0.CreateSwapChainForComposition(
-                device.as_ptr(),
-                desc,
-                restrict_to_output.as_ptr(),
-                swap_chain.getter_addrefs(),
-            );
-            assert!(hr == 0);
-            swap_chain.into()
-        }
+        let mut swap_chain = ComPtr::<IDXGISwapChain1>::default();
+        hr!(self.0.CreateSwapChainForComposition(
+            device.as_ptr(),
+            desc,
+            restrict_to_output.as_ptr(),
+            swap_chain.getter_addrefs(),
+        ))?;
+        Ok(swap_chain.into())
     }
 }
 
 impl SwapChain1 {
-    pub fn get_buffer<I>(&self, buffer: u32) -> ComPtr<I>
+    pub fn get_buffer<I>(&self, buffer: u32) -> Result<ComPtr<I>>
     where
         ComPtr<I>: AsPtr<IDXGISurface>,
         I: Interface,
     {
-        unsafe {
-            let mut native = ComPtr::<I>::default();
-            let hr = self
-                .0
-                .GetBuffer(buffer, &I::uuidof(), native.getter_addrefs());
-            assert!(hr == 0);
-            native
-        }
+        let mut native = ComPtr::<I>::default();
+        hr!(self
+            .0
+            .GetBuffer(buffer, &I::uuidof(), native.getter_addrefs()))?;
+        Ok(native)
     }
 
-    pub fn present(&self, sync_interval: u32, flags: u32) {
-        unsafe {
-            let hr = self.0.Present(sync_interval, flags);
-            assert!(hr == 0);
-        }
+    /// Presents the back buffer. Success codes, such as
+    /// `DXGI_STATUS_OCCLUDED` while the window is hidden, are `Ok`.
+    pub fn present(&self, sync_interval: u32, flags: u32) -> Result<()> {
+        hr!(self.0.Present(sync_interval, flags))
     }
 }
@@ -0,0 +1,167 @@
+//! Errors from failed Windows API calls.
+//!
+//! Wrappers that call fallible methods return `Result`, with an `Error` that
+//! names the method and carries the `HRESULT` it returned, decoded by
+//! `Hresult`. Callers can add context about what they were doing, such as
+//! which file failed to load.
+
+use crate::{
+    sys::{E_INVALIDARG, HRESULT},
+    Hresult, InteriorNulError,
+};
+use std::{ffi::NulError, fmt};
+
+/// `Result` with `Error` as the default error type.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A call that returned a failure `HRESULT`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    hresult: Hresult,
+    call: &'static str,
+    context: Option<String>,
+}
+
+impl Error {
+    /// An error for `call` failing with `hresult`. If `call` is the text of
+    /// a call expression, as `hr!` passes, only the method name is kept.
+    pub fn new(hresult: HRESULT, call: &'static str) -> Self {
+        Self {
+            hresult: hresult.into(),
+            call: method_name(call),
+            context: None,
+        }
+    }
+
+    /// `Ok` for success codes, which includes `S_FALSE` and other positive
+    /// values, and an error for `call` otherwise.
+    pub fn check(hresult: HRESULT, call: &'static str) -> Result<()> {
+        if hresult >= 0 {
+            Ok(())
+        } else {
+            Err(Self::new(hresult, call))
+        }
+    }
+
+    /// Adds a description of what the call was for.
+    pub fn with_context(mut self, context: impl Into<String>) -> Self {
+        self.context = Some(context.into());
+        self
+    }
+
+    pub fn hresult(&self) -> Hresult {
+        self.hresult
+    }
+
+    /// Name of the method that failed, such as `CreateSurface`.
+    pub fn call(&self) -> &'static str {
+        self.call
+    }
+
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(context) = &self.context {
+            write!(f, "{}: ", context)?;
+        }
+        write!(f, "{} failed with {}", self.call, self.hresult)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A string with a NUL can't be passed to Windows, so wrappers taking
+/// strings fail with `E_INVALIDARG` for it.
+impl From<InteriorNulError> for Error {
+    fn from(error: InteriorNulError) -> Self {
+        Error::new(E_INVALIDARG, "to_wide_str").with_context(error.to_string())
+    }
+}
+
+/// The same goes for byte strings passed as C strings.
+impl From<NulError> for Error {
+    fn from(error: NulError) -> Self {
+        Error::new(E_INVALIDARG, "CString").with_context(error.to_string())
+    }
+}
+
+/// `self.0.CreateSurface(width, ...)` becomes `CreateSurface`.
+crate fn method_name(call: &'static str) -> &'static str {
+    let callee = call.split('(').next().unwrap_or(call);
+    callee
+        .rsplit(|c| c == '.' || c == ':')
+        .next()
+        .unwrap_or(callee)
+        .trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const E_FAIL: HRESULT = 0x8000_4005_u32 as HRESULT;
+    const DXGI_ERROR_DEVICE_REMOVED: HRESULT = 0x887a_0005_u32 as HRESULT;
+
+    #[test]
+    fn success_codes() {
+        assert_eq!(Error::check(0, "Commit"), Ok(()));
+        // S_FALSE and DXGI_STATUS_OCCLUDED succeed.
+        assert_eq!(Error::check(1, "Commit"), Ok(()));
+        assert_eq!(Error::check(0x087a_0001, "Present"), Ok(()));
+    }
+
+    #[test]
+    fn failures() {
+        let error = Error::check(E_FAIL, "self.0.Commit()").unwrap_err();
+        assert_eq!(error.hresult(), Hresult(E_FAIL));
+        assert_eq!(error.call(), "Commit");
+        assert_eq!(error.context(), None);
+        assert_eq!(
+            error.to_string(),
+            "Commit failed with E_FAIL (0x80004005): Unspecified failure."
+        );
+
+        let error = Error::new(
+            DXGI_ERROR_DEVICE_REMOVED,
+            "self\n    .0\n    .CreateSurface(width, height, format.into(), surface.getter_addrefs())",
+        )
+        .with_context("allocating the back buffer");
+        assert_eq!(error.call(), "CreateSurface");
+        assert_eq!(
+            error.to_string(),
+            "allocating the back buffer: CreateSurface failed with \
+             DXGI_ERROR_DEVICE_REMOVED (0x887a0005): \
+             The GPU was physically removed or the driver was upgraded."
+        );
+
+        assert_eq!(
+            Error::new(E_FAIL, "D3D11CreateDevice(ptr::null_mut(), 0)").call(),
+            "D3D11CreateDevice"
+        );
+        assert_eq!(
+            Error::new(E_FAIL, "winapi::um::mfapi::MFStartup(MF_VERSION, 0)").call(),
+            "MFStartup"
+        );
+    }
+    #[test]
+    fn interior_nul() {
+        use crate::ToWideStr;
+
+        let error: Error = "a\0b".to_wide_str().unwrap_err().into();
+        assert_eq!(error.hresult(), Hresult(E_INVALIDARG));
+        assert_eq!(error.call(), "to_wide_str");
+        assert_eq!(error.context(), Some("interior NUL at UTF-16 offset 1"));
+
+        let error: Error = std::ffi::CString::new("a\0b").unwrap_err().into();
+        assert_eq!(error.hresult(), Hresult(E_INVALIDARG));
+        assert_eq!(error.call(), "CString");
+        assert_eq!(
+            error.context(),
+            Some("nul byte found in provided data at position: 1")
+        );
+    }
+}
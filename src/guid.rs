@@ -0,0 +1,267 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! GUIDs as plain values.
+//!
+//! Interfaces, media types and attribute keys are all named by GUIDs, which
+//! winapi leaves as a bare struct. `Guid` has the same layout, compares,
+//! orders and hashes by value, and reads and writes the registry format,
+//! `{6B29FC40-CA47-1067-B31D-00DD010662DA}`.
+
+use crate::sys::GUID;
+use std::{error, fmt, str::FromStr};
+
+/// Fields are ordered as in the registry format, so GUIDs sort as their
+/// strings do.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(C)]
+pub struct Guid {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
+/// The error from parsing a string that isn't a GUID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseGuidError(());
+
+impl Guid {
+    pub const fn from_fields(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> Self {
+        Guid {
+            data1,
+            data2,
+            data3,
+            data4,
+        }
+    }
+
+    /// The GUID whose registry format reads as the hex digits of `value`.
+    pub const fn from_u128(value: u128) -> Self {
+        let low = value as u64;
+        Guid {
+            data1: (value >> 96) as u32,
+            data2: (value >> 80) as u16,
+            data3: (value >> 64) as u16,
+            data4: [
+                (low >> 56) as u8,
+                (low >> 48) as u8,
+                (low >> 40) as u8,
+                (low >> 32) as u8,
+                (low >> 24) as u8,
+                (low >> 16) as u8,
+                (low >> 8) as u8,
+                low as u8,
+            ],
+        }
+    }
+
+    pub fn to_u128(self) -> u128 {
+        let mut value = u128::from(self.data1) << 96
+            | u128::from(self.data2) << 80
+            | u128::from(self.data3) << 64;
+        for (i, byte) in self.data4.iter().enumerate() {
+            value |= u128::from(*byte) << (56 - 8 * i);
+        }
+        value
+    }
+
+    /// Parses `8-4-4-4-12` hex digits, in either case, optionally in braces.
+    pub fn parse(s: &str) -> Result<Self, ParseGuidError> {
+        match decode(s.as_bytes()) {
+            Some(value) => Ok(Guid::from_u128(value)),
+            None => Err(ParseGuidError(())),
+        }
+    }
+
+    /// `parse` for `guid!`, where the panic is a compile error.
+    #[doc(hidden)]
+    pub const fn parse_or_panic(s: &str) -> Self {
+        match decode(s.as_bytes()) {
+            Some(value) => Guid::from_u128(value),
+            None => panic!("invalid GUID literal"),
+        }
+    }
+
+    pub fn is_nil(self) -> bool {
+        self == Guid::default()
+    }
+
+    /// A pointer for API calls taking a `REFGUID`, valid as long as `self`.
+    pub fn as_ptr(&self) -> *const GUID {
+        self as *const Guid as *const GUID
+    }
+}
+
+/// The 128-bit value of a GUID string, or `None` if it isn't one.
+const fn decode(s: &[u8]) -> Option<u128> {
+    let (mut i, end) = match s.len() {
+        36 => (0, 36),
+        38 if s[0] == b'{' && s[37] == b'}' => (1, 37),
+        _ => return None,
+    };
+    let start = i;
+    let mut value = 0u128;
+    while i < end {
+        let c = s[i];
+        match i - start {
+            8 | 13 | 18 | 23 => {
+                if c != b'-' {
+                    return None;
+                }
+            }
+            _ => {
+                let digit = match c {
+                    b'0'..=b'9' => c - b'0',
+                    b'a'..=b'f' => c - b'a' + 10,
+                    b'A'..=b'F' => c - b'A' + 10,
+                    _ => return None,
+                };
+                value = value << 4 | digit as u128;
+            }
+        }
+        i += 1;
+    }
+    Some(value)
+}
+
+impl FromStr for Guid {
+    type Err = ParseGuidError;
+
+    fn from_str(s: &str) -> Result<Self, ParseGuidError> {
+        Guid::parse(s)
+    }
+}
+
+impl fmt::Display for Guid {
+    /// The registry format. The alternate form, `{:#}`, omits the braces.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let d = &self.data4;
+        let digits = format!(
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+            self.data1, self.data2, self.data3, d[0], d[1], d[2], d[3], d[4], d[5], d[6], d[7]
+        );
+        if f.alternate() {
+            f.write_str(&digits)
+        } else {
+            write!(f, "{{{}}}", digits)
+        }
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for ParseGuidError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("expected a GUID of the form {XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX}")
+    }
+}
+
+impl error::Error for ParseGuidError {}
+
+impl From<GUID> for Guid {
+    fn from(guid: GUID) -> Self {
+        Guid::from_fields(guid.Data1, guid.Data2, guid.Data3, guid.Data4)
+    }
+}
+
+impl From<&GUID> for Guid {
+    fn from(guid: &GUID) -> Self {
+        Guid::from(*guid)
+    }
+}
+
+impl From<Guid> for GUID {
+    fn from(guid: Guid) -> Self {
+        GUID {
+            Data1: guid.data1,
+            Data2: guid.data2,
+            Data3: guid.data3,
+            Data4: guid.data4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guid;
+    use std::collections::HashSet;
+
+    const NV12: Guid = guid!("3231564E-0000-0010-8000-00AA00389B71");
+
+    #[test]
+    fn parse_and_format() {
+        assert_eq!(
+            NV12,
+            Guid::from_fields(
+                0x3231_564e,
+                0,
+                0x10,
+                [0x80, 0, 0, 0xaa, 0, 0x38, 0x9b, 0x71]
+            )
+        );
+        assert_eq!(NV12.to_string(), "{3231564E-0000-0010-8000-00AA00389B71}");
+        assert_eq!(
+            format!("{:#}", NV12),
+            "3231564E-0000-0010-8000-00AA00389B71"
+        );
+        assert_eq!(
+            format!("{:?}", Some(NV12)),
+            "Some({3231564E-0000-0010-8000-00AA00389B71})"
+        );
+
+        for s in &[
+            "3231564E-0000-0010-8000-00AA00389B71",
+            "{3231564e-0000-0010-8000-00aa00389b71}",
+        ] {
+            assert_eq!(s.parse::<Guid>(), Ok(NV12));
+        }
+        assert_eq!(NV12.to_string().parse::<Guid>(), Ok(NV12));
+
+        for s in &[
+            "",
+            "3231564E00000010800000AA00389B71",
+            "{3231564E-0000-0010-8000-00AA00389B71",
+            "3231564E-0000-0010-8000-00AA00389B71}",
+            "(3231564E-0000-0010-8000-00AA00389B71)",
+            "3231564E-0000-0010-8000+00AA00389B71",
+            "3231564G-0000-0010-8000-00AA00389B71",
+            "3231564E-0000-0010-8000-00AA00389B7",
+        ] {
+            assert!(s.parse::<Guid>().is_err(), "{:?} parsed", s);
+        }
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(NV12.to_u128(), 0x3231_564e_0000_0010_8000_00aa_0038_9b71);
+        assert_eq!(Guid::from_u128(NV12.to_u128()), NV12);
+        assert!(Guid::default().is_nil());
+        assert!(!NV12.is_nil());
+
+        // Ordering follows the registry format, not the memory layout.
+        let low = guid!("00000001-FFFF-0000-0000-000000000000");
+        let high = guid!("00000002-0000-0000-0000-000000000000");
+        assert!(low < high);
+        assert!(guid!("00000000-0000-0000-0000-000000000001") > Guid::default());
+
+        let set: HashSet<_> = vec![NV12, low, NV12].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn raw() {
+        let raw = GUID::from(NV12);
+        assert_eq!(raw.Data1, 0x3231_564e);
+        assert_eq!(raw.Data4, NV12.data4);
+        assert_eq!(Guid::from(raw), NV12);
+        assert_eq!(Guid::from(&raw), NV12);
+        assert_eq!(unsafe { (*NV12.as_ptr()).Data3 }, 0x10);
+    }
+}
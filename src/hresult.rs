@@ -0,0 +1,393 @@
+//! Decoding of `HRESULT` values.
+//!
+//! An `HRESULT` packs a severity bit, the facility that produced it and a
+//! facility-specific code. `Hresult` splits those apart and looks up the
+//! symbolic name and a description for the codes the DXGI, Direct3D 11,
+//! Direct2D, DirectWrite, DirectComposition and Media Foundation wrappers can
+//! return, so `0xc00d36b9` reads as `MF_E_NO_MORE_TYPES`. The table is plain
+//! data, so decoding works on every platform.
+
+use crate::sys::HRESULT;
+use std::fmt;
+
+/// Whether an `HRESULT` reports success or failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Success,
+    Failure,
+}
+
+/// The component that defined an `HRESULT`'s code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Facility {
+    /// Generic COM codes such as `E_FAIL`.
+    Null,
+    Rpc,
+    Dispatch,
+    /// Codes defined by the interface that returned them.
+    Itf,
+    /// A Win32 error code wrapped in an `HRESULT`.
+    Win32,
+    Windows,
+    MediaFoundation,
+    Dxgi,
+    D3d11,
+    /// Shared by DirectWrite and DirectComposition.
+    Dwrite,
+    D2d,
+    Other(u16),
+}
+
+impl From<u16> for Facility {
+    fn from(facility: u16) -> Self {
+        match facility {
+            0 => Facility::Null,
+            1 => Facility::Rpc,
+            2 => Facility::Dispatch,
+            4 => Facility::Itf,
+            7 => Facility::Win32,
+            8 => Facility::Windows,
+            0xd => Facility::MediaFoundation,
+            0x87a => Facility::Dxgi,
+            0x87c => Facility::D3d11,
+            0x898 => Facility::Dwrite,
+            0x899 => Facility::D2d,
+            other => Facility::Other(other),
+        }
+    }
+}
+
+/// An `HRESULT` with accessors for its parts and, for known codes, its name
+/// and description.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hresult(pub HRESULT);
+
+impl Hresult {
+    pub fn severity(self) -> Severity {
+        if self.0 < 0 {
+            Severity::Failure
+        } else {
+            Severity::Success
+        }
+    }
+
+    pub fn is_success(self) -> bool {
+        self.severity() == Severity::Success
+    }
+
+    pub fn is_failure(self) -> bool {
+        self.severity() == Severity::Failure
+    }
+
+    pub fn facility(self) -> Facility {
+        (((self.0 as u32) >> 16) as u16 & 0x1fff).into()
+    }
+
+    /// The facility-specific part, the low 16 bits.
+    pub fn code(self) -> u16 {
+        self.0 as u16
+    }
+
+    /// The symbolic name, such as `DXGI_ERROR_DEVICE_REMOVED`.
+    pub fn name(self) -> Option<&'static str> {
+        self.entry().map(|&(_, name, _)| name)
+    }
+
+    pub fn description(self) -> Option<&'static str> {
+        self.entry().map(|&(_, _, description)| description)
+    }
+
+    fn entry(self) -> Option<&'static (u32, &'static str, &'static str)> {
+        CODES.iter().find(|&&(value, _, _)| value == self.0 as u32)
+    }
+}
+
+impl From<HRESULT> for Hresult {
+    fn from(hresult: HRESULT) -> Self {
+        Hresult(hresult)
+    }
+}
+
+impl From<Hresult> for HRESULT {
+    fn from(hresult: Hresult) -> Self {
+        hresult.0
+    }
+}
+
+impl fmt::Display for Hresult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.entry() {
+            Some((_, name, description)) => {
+                write!(f, "{} (0x{:08x}): {}", name, self.0, description)
+            }
+            None => write!(f, "0x{:08x}", self.0),
+        }
+    }
+}
+
+impl fmt::Debug for Hresult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("Hresult");
+        debug.field("value", &format_args!("0x{:08x}", self.0));
+        if let Some(name) = self.name() {
+            debug.field("name", &format_args!("{}", name));
+        }
+        debug
+            .field("severity", &self.severity())
+            .field("facility", &self.facility())
+            .field("code", &format_args!("0x{:04x}", self.code()))
+            .finish()
+    }
+}
+
+impl std::error::Error for Hresult {}
+
+/// Known codes as `(value, name, description)`.
+#[rustfmt::skip]
+static CODES: &[(u32, &str, &str)] = &[
+    // Generic COM
+    (0x0000_0000, "S_OK", "The operation succeeded."),
+    (0x0000_0001, "S_FALSE", "The operation succeeded with a negative answer."),
+    (0x8000_4001, "E_NOTIMPL", "Not implemented."),
+    (0x8000_4002, "E_NOINTERFACE", "The interface is not supported."),
+    (0x8000_4003, "E_POINTER", "Invalid pointer."),
+    (0x8000_4004, "E_ABORT", "The operation was aborted."),
+    (0x8000_4005, "E_FAIL", "Unspecified failure."),
+    (0x8000_ffff, "E_UNEXPECTED", "Catastrophic failure."),
+    (0x8007_0005, "E_ACCESSDENIED", "Access denied."),
+    (0x8007_0006, "E_HANDLE", "Invalid handle."),
+    (0x8007_000e, "E_OUTOFMEMORY", "Ran out of memory."),
+    (0x8007_0057, "E_INVALIDARG", "One or more arguments are invalid."),
+    (0x8007_007a, "ERROR_INSUFFICIENT_BUFFER", "The data area passed to a call is too small."),
+    // DXGI
+    (0x087a_0001, "DXGI_STATUS_OCCLUDED", "The window is occluded; presentation was skipped."),
+    (0x087a_0002, "DXGI_STATUS_CLIPPED", "The output is clipped."),
+    (0x087a_0004, "DXGI_STATUS_NO_REDIRECTION", "The window is not redirected."),
+    (0x087a_0005, "DXGI_STATUS_NO_DESKTOP_ACCESS", "The desktop is not accessible."),
+    (0x087a_0006, "DXGI_STATUS_GRAPHICS_VIDPN_SOURCE_IN_USE", "The video source is in use by another device."),
+    (0x087a_0007, "DXGI_STATUS_MODE_CHANGED", "The display mode was changed."),
+    (0x087a_0008, "DXGI_STATUS_MODE_CHANGE_IN_PROGRESS", "A display mode change is in progress."),
+    (0x087a_0009, "DXGI_STATUS_UNOCCLUDED", "The window is no longer occluded."),
+    (0x087a_000a, "DXGI_STATUS_DDA_WAS_STILL_DRAWING", "The desktop duplication was still drawing."),
+    (0x887a_0001, "DXGI_ERROR_INVALID_CALL", "The method call is invalid, for example a parameter is wrong."),
+    (0x887a_0002, "DXGI_ERROR_NOT_FOUND", "The object or index was not found."),
+    (0x887a_0003, "DXGI_ERROR_MORE_DATA", "The buffer is too small for the data."),
+    (0x887a_0004, "DXGI_ERROR_UNSUPPORTED", "The requested functionality is not supported."),
+    (0x887a_0005, "DXGI_ERROR_DEVICE_REMOVED", "The GPU was physically removed or the driver was upgraded."),
+    (0x887a_0006, "DXGI_ERROR_DEVICE_HUNG", "The device failed because of badly formed commands."),
+    (0x887a_0007, "DXGI_ERROR_DEVICE_RESET", "The device was reset after a badly formed command."),
+    (0x887a_000a, "DXGI_ERROR_WAS_STILL_DRAWING", "The GPU was busy when the operation was requested."),
+    (0x887a_000b, "DXGI_ERROR_FRAME_STATISTICS_DISJOINT", "Frame statistics are not contiguous."),
+    (0x887a_000c, "DXGI_ERROR_GRAPHICS_VIDPN_SOURCE_IN_USE", "The video source is in use by another device."),
+    (0x887a_0020, "DXGI_ERROR_DRIVER_INTERNAL_ERROR", "The driver encountered a problem."),
+    (0x887a_0021, "DXGI_ERROR_NONEXCLUSIVE", "A global counter resource is in use."),
+    (0x887a_0022, "DXGI_ERROR_NOT_CURRENTLY_AVAILABLE", "The resource or request is not currently available."),
+    (0x887a_0023, "DXGI_ERROR_REMOTE_CLIENT_DISCONNECTED", "The remote client disconnected."),
+    (0x887a_0024, "DXGI_ERROR_REMOTE_OUTOFMEMORY", "The remote device ran out of memory."),
+    (0x887a_0025, "DXGI_ERROR_MODE_CHANGE_IN_PROGRESS", "A display mode change is in progress."),
+    (0x887a_0026, "DXGI_ERROR_ACCESS_LOST", "The desktop duplication interface is invalid."),
+    (0x887a_0027, "DXGI_ERROR_WAIT_TIMEOUT", "The wait timed out."),
+    (0x887a_0028, "DXGI_ERROR_SESSION_DISCONNECTED", "The remote desktop session was disconnected."),
+    (0x887a_0029, "DXGI_ERROR_RESTRICT_TO_OUTPUT_STALE", "The output restriction no longer applies."),
+    (0x887a_002a, "DXGI_ERROR_CANNOT_PROTECT_CONTENT", "Content protection is not available."),
+    (0x887a_002b, "DXGI_ERROR_ACCESS_DENIED", "Access to the resource was denied."),
+    (0x887a_002c, "DXGI_ERROR_NAME_ALREADY_EXISTS", "A resource with the name already exists."),
+    (0x887a_002d, "DXGI_ERROR_SDK_COMPONENT_MISSING", "A required SDK component is missing."),
+    // Direct3D 11
+    (0x887c_0001, "D3D11_ERROR_TOO_MANY_UNIQUE_STATE_OBJECTS", "There are too many unique instances of a state object."),
+    (0x887c_0002, "D3D11_ERROR_FILE_NOT_FOUND", "The file was not found."),
+    (0x887c_0003, "D3D11_ERROR_TOO_MANY_UNIQUE_VIEW_OBJECTS", "There are too many unique instances of a view object."),
+    (0x887c_0004, "D3D11_ERROR_DEFERRED_CONTEXT_MAP_WITHOUT_INITIAL_DISCARD", "A deferred context mapped a resource without first discarding it."),
+    // Direct2D
+    (0x8899_0001, "D2DERR_WRONG_STATE", "The object was not in the correct state to process the method."),
+    (0x8899_0002, "D2DERR_NOT_INITIALIZED", "The object has not yet been initialized."),
+    (0x8899_0003, "D2DERR_UNSUPPORTED_OPERATION", "The requested operation is not supported."),
+    (0x8899_0004, "D2DERR_SCANNER_FAILED", "The geometry scanner failed to process the data."),
+    (0x8899_0005, "D2DERR_SCREEN_ACCESS_DENIED", "Direct2D could not access the screen."),
+    (0x8899_0006, "D2DERR_DISPLAY_STATE_INVALID", "A valid display state could not be determined."),
+    (0x8899_0007, "D2DERR_ZERO_VECTOR", "The supplied vector is zero."),
+    (0x8899_0008, "D2DERR_INTERNAL_ERROR", "An internal error occurred."),
+    (0x8899_0009, "D2DERR_DISPLAY_FORMAT_NOT_SUPPORTED", "The display format is not supported."),
+    (0x8899_000a, "D2DERR_INVALID_CALL", "A valid call was made in an invalid state."),
+    (0x8899_000b, "D2DERR_NO_HARDWARE_DEVICE", "A hardware device is not available."),
+    (0x8899_000c, "D2DERR_RECREATE_TARGET", "The device was lost; recreate the render target and its resources."),
+    (0x8899_000d, "D2DERR_TOO_MANY_SHADER_ELEMENTS", "The shader has too many elements."),
+    (0x8899_000e, "D2DERR_SHADER_COMPILE_FAILED", "The shader failed to compile."),
+    (0x8899_000f, "D2DERR_MAX_TEXTURE_SIZE_EXCEEDED", "The requested size exceeds the maximum texture size."),
+    (0x8899_0010, "D2DERR_UNSUPPORTED_VERSION", "The requested Direct2D version is not supported."),
+    (0x8899_0011, "D2DERR_BAD_NUMBER", "Invalid number."),
+    (0x8899_0012, "D2DERR_WRONG_FACTORY", "Objects from different factories were used together."),
+    (0x8899_0013, "D2DERR_LAYER_ALREADY_IN_USE", "The layer is already in use."),
+    (0x8899_0014, "D2DERR_POP_CALL_DID_NOT_MATCH_PUSH", "The pop call did not match the corresponding push."),
+    (0x8899_0015, "D2DERR_WRONG_RESOURCE_DOMAIN", "The resource belongs to a different render target."),
+    (0x8899_0016, "D2DERR_PUSH_POP_UNBALANCED", "The push and pop calls were unbalanced."),
+    (0x8899_0017, "D2DERR_RENDER_TARGET_HAS_LAYER_OR_CLIPRECT", "The render target has a layer or clip pushed."),
+    (0x8899_0018, "D2DERR_INCOMPATIBLE_BRUSH_TYPES", "The brush types are incompatible."),
+    (0x8899_0019, "D2DERR_WIN32_ERROR", "An unknown Win32 failure occurred."),
+    (0x8899_001a, "D2DERR_TARGET_NOT_GDI_COMPATIBLE", "The render target is not compatible with GDI."),
+    (0x8899_001b, "D2DERR_TEXT_EFFECT_IS_WRONG_TYPE", "A text client drawing effect is of the wrong type."),
+    (0x8899_001c, "D2DERR_TEXT_RENDERER_NOT_RELEASED", "A text renderer is still holding the render target."),
+    (0x8899_001d, "D2DERR_EXCEEDS_MAX_BITMAP_SIZE", "The requested size exceeds the maximum bitmap size."),
+    (0x8899_001e, "D2DERR_INVALID_GRAPH_CONFIGURATION", "The effect graph is misconfigured."),
+    (0x8899_001f, "D2DERR_INVALID_INTERNAL_GRAPH_CONFIGURATION", "An effect's internal transform graph is misconfigured."),
+    (0x8899_0020, "D2DERR_CYCLIC_GRAPH", "The effect graph contains a cycle."),
+    (0x8899_0021, "D2DERR_BITMAP_CANNOT_DRAW", "The bitmap's options do not allow drawing."),
+    (0x8899_0022, "D2DERR_OUTSTANDING_BITMAP_REFERENCES", "The operation failed because the bitmap is still referenced."),
+    (0x8899_0023, "D2DERR_ORIGINAL_TARGET_NOT_BOUND", "The original render target is not bound."),
+    (0x8899_0024, "D2DERR_INVALID_TARGET", "The bitmap cannot be set as a target."),
+    (0x8899_0025, "D2DERR_BITMAP_BOUND_AS_TARGET", "The bitmap is bound as the current target."),
+    (0x8899_0026, "D2DERR_INSUFFICIENT_DEVICE_CAPABILITIES", "The device lacks a required capability."),
+    (0x8899_0027, "D2DERR_INTERMEDIATE_TOO_LARGE", "An intermediate texture is too large."),
+    (0x8899_0028, "D2DERR_EFFECT_IS_NOT_REGISTERED", "The effect is not registered."),
+    (0x8899_0029, "D2DERR_INVALID_PROPERTY", "The property does not exist."),
+    (0x8899_002a, "D2DERR_NO_SUBPROPERTIES", "The property has no sub-properties."),
+    (0x8899_002b, "D2DERR_PRINT_JOB_CLOSED", "The print job is already closed."),
+    (0x8899_002c, "D2DERR_PRINT_FORMAT_NOT_SUPPORTED", "The print format is not supported."),
+    (0x8899_002d, "D2DERR_TOO_MANY_TRANSFORM_INPUTS", "A transform has too many inputs."),
+    // DirectWrite
+    (0x8898_5000, "DWRITE_E_FILEFORMAT", "The font file format is not recognized or is invalid."),
+    (0x8898_5001, "DWRITE_E_UNEXPECTED", "An unexpected font file error occurred."),
+    (0x8898_5002, "DWRITE_E_NOFONT", "The font does not exist in the collection."),
+    (0x8898_5003, "DWRITE_E_FILENOTFOUND", "The font file does not exist."),
+    (0x8898_5004, "DWRITE_E_FILEACCESS", "The font file could not be opened."),
+    (0x8898_5005, "DWRITE_E_FONTCOLLECTIONOBSOLETE", "The font collection is obsolete because fonts were installed or removed."),
+    (0x8898_5006, "DWRITE_E_ALREADYREGISTERED", "The loader is already registered."),
+    (0x8898_5007, "DWRITE_E_CACHEFORMAT", "The font cache has an invalid format."),
+    (0x8898_5008, "DWRITE_E_CACHEVERSION", "The font cache is for a different version."),
+    (0x8898_5009, "DWRITE_E_UNSUPPORTEDOPERATION", "The operation is not supported for this kind of font."),
+    (0x8898_500a, "DWRITE_E_TEXTRENDERERINCOMPATIBLE", "The text renderer does not support the requested interface."),
+    (0x8898_500b, "DWRITE_E_FLOWDIRECTIONCONFLICTS", "The flow direction conflicts with the reading direction."),
+    (0x8898_500c, "DWRITE_E_NOCOLOR", "The glyph run has no color glyphs."),
+    // DirectComposition
+    (0x8898_0800, "DCOMPOSITION_ERROR_WINDOW_ALREADY_COMPOSED", "The window already has a composition target."),
+    (0x8898_0801, "DCOMPOSITION_ERROR_SURFACE_BEING_RENDERED", "The surface is already being drawn."),
+    (0x8898_0802, "DCOMPOSITION_ERROR_SURFACE_NOT_BEING_RENDERED", "The surface is not being drawn."),
+    // Media Foundation
+    (0x8004_1000, "MF_E_DXGI_DEVICE_NOT_INITIALIZED", "The DXGI device manager has no device."),
+    (0x8004_1001, "MF_E_DXGI_NEW_VIDEO_DEVICE", "The DXGI device manager's device was replaced; close and reopen the handle."),
+    (0x8004_1002, "MF_E_DXGI_VIDEO_DEVICE_LOCKED", "The device is locked by another caller."),
+    (0xc00d_36b0, "MF_E_PLATFORM_NOT_INITIALIZED", "Media Foundation has not been started; call MFStartup."),
+    (0xc00d_36b1, "MF_E_BUFFERTOOSMALL", "The buffer is too small."),
+    (0xc00d_36b2, "MF_E_INVALIDREQUEST", "The request is invalid in the current state."),
+    (0xc00d_36b3, "MF_E_INVALIDSTREAMNUMBER", "The stream number is invalid."),
+    (0xc00d_36b4, "MF_E_INVALIDMEDIATYPE", "The media type is invalid or not supported."),
+    (0xc00d_36b5, "MF_E_NOTACCEPTING", "The object is not accepting input."),
+    (0xc00d_36b6, "MF_E_NOT_INITIALIZED", "The object is not initialized."),
+    (0xc00d_36b7, "MF_E_UNSUPPORTED_REPRESENTATION", "The representation is not supported."),
+    (0xc00d_36b9, "MF_E_NO_MORE_TYPES", "There are no more media types at this index."),
+    (0xc00d_36ba, "MF_E_UNSUPPORTED_SERVICE", "The service is not supported."),
+    (0xc00d_36bb, "MF_E_UNEXPECTED", "An unexpected error occurred."),
+    (0xc00d_36bc, "MF_E_INVALIDNAME", "Invalid name."),
+    (0xc00d_36bd, "MF_E_INVALIDTYPE", "Invalid type."),
+    (0xc00d_36be, "MF_E_INVALID_FILE_FORMAT", "The file does not conform to its specification."),
+    (0xc00d_36bf, "MF_E_INVALIDINDEX", "The index is out of range."),
+    (0xc00d_36c0, "MF_E_INVALID_TIMESTAMP", "An invalid timestamp was given."),
+    (0xc00d_36c3, "MF_E_UNSUPPORTED_SCHEME", "The URL scheme is not supported."),
+    (0xc00d_36c4, "MF_E_UNSUPPORTED_BYTESTREAM_TYPE", "The byte stream type is not supported."),
+    (0xc00d_36c5, "MF_E_UNSUPPORTED_TIME_FORMAT", "The time format is not supported."),
+    (0xc00d_36c8, "MF_E_NO_SAMPLE_TIMESTAMP", "The sample has no timestamp."),
+    (0xc00d_36c9, "MF_E_NO_SAMPLE_DURATION", "The sample has no duration."),
+    (0xc00d_36cb, "MF_E_INVALID_STREAM_DATA", "The stream contains invalid data."),
+    (0xc00d_36cf, "MF_E_RT_UNAVAILABLE", "Real-time services are not available."),
+    (0xc00d_36d0, "MF_E_UNSUPPORTED_RATE", "The playback rate is not supported."),
+    (0xc00d_36d5, "MF_E_NOT_FOUND", "The value was not found."),
+    (0xc00d_36d6, "MF_E_NOT_AVAILABLE", "The request is not available."),
+    (0xc00d_36d7, "MF_E_NO_CLOCK", "There is no presentation clock."),
+    (0xc00d_36e2, "MF_E_INVALID_KEY", "The key is invalid."),
+    (0xc00d_36e3, "MF_E_BAD_STARTUP_VERSION", "MFStartup was called with the wrong version."),
+    (0xc00d_36e5, "MF_E_INVALID_POSITION", "The seek position is invalid."),
+    (0xc00d_36e6, "MF_E_ATTRIBUTENOTFOUND", "The attribute is not set."),
+    (0xc00d_36e7, "MF_E_PROPERTY_TYPE_NOT_ALLOWED", "The property type is not allowed."),
+    (0xc00d_36ed, "MF_E_OPERATION_CANCELLED", "The operation was cancelled."),
+    (0xc00d_36ee, "MF_E_BYTESTREAM_NOT_SEEKABLE", "The byte stream does not support seeking."),
+    (0xc00d_36f0, "MF_E_CANNOT_PARSE_BYTESTREAM", "The byte stream could not be parsed."),
+    (0xc00d_36fa, "MF_E_CANNOT_CREATE_SINK", "The media sink could not be created."),
+    (0xc00d_36fb, "MF_E_BYTESTREAM_UNKNOWN_LENGTH", "The byte stream's length is unknown."),
+    (0xc00d_36fe, "MF_E_FORMAT_CHANGE_NOT_SUPPORTED", "The format change is not supported."),
+    (0xc00d_36ff, "MF_E_INVALID_WORKQUEUE", "The work queue is invalid."),
+    (0xc00d_3700, "MF_E_DRM_UNSUPPORTED", "DRM-protected content is not supported."),
+    (0xc00d_3701, "MF_E_UNAUTHORIZED", "Access is not authorized."),
+    (0xc00d_3702, "MF_E_OUT_OF_RANGE", "The value is out of range."),
+    (0xc00d_3704, "MF_E_HW_MFT_FAILED_START_STREAMING", "The hardware transform failed to start streaming."),
+    (0xc00d_3e80, "MF_E_NO_EVENTS_AVAILABLE", "No events are queued."),
+    (0xc00d_3e82, "MF_E_INVALID_STATE_TRANSITION", "The state transition is invalid."),
+    (0xc00d_3e84, "MF_E_END_OF_STREAM", "The end of the stream was reached."),
+    (0xc00d_3e85, "MF_E_SHUTDOWN", "The object was shut down."),
+    (0xc00d_3e8c, "MF_E_INVALID_FORMAT", "The data format is invalid."),
+    (0xc00d_6d60, "MF_E_TRANSFORM_TYPE_NOT_SET", "A media type must be set on the transform first."),
+    (0xc00d_6d61, "MF_E_TRANSFORM_STREAM_CHANGE", "The transform's output format changed."),
+    (0xc00d_6d62, "MF_E_TRANSFORM_INPUT_REMAINING", "The transform still has input to process."),
+    (0xc00d_6d72, "MF_E_TRANSFORM_NEED_MORE_INPUT", "The transform needs more input to produce output."),
+    (0xc00d_6d74, "MF_E_TRANSFORM_CANNOT_CHANGE_MEDIATYPE_WHILE_PROCESSING", "The media type cannot change while the transform is processing."),
+    (0xc00d_6d76, "MF_E_UNSUPPORTED_D3D_TYPE", "The Direct3D device type is not supported."),
+    (0xc00d_6d77, "MF_E_TRANSFORM_ASYNC_LOCKED", "The asynchronous transform must be unlocked first."),
+    (0xc00d_7170, "MF_E_INSUFFICIENT_BUFFER", "The buffer is too small."),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hresult(value: u32) -> Hresult {
+        Hresult(value as HRESULT)
+    }
+
+    #[test]
+    fn decode() {
+        let removed = hresult(0x887a_0005);
+        assert_eq!(removed.severity(), Severity::Failure);
+        assert_eq!(removed.facility(), Facility::Dxgi);
+        assert_eq!(removed.code(), 5);
+
+        let occluded = hresult(0x087a_0001);
+        assert!(occluded.is_success());
+        assert_eq!(occluded.facility(), Facility::Dxgi);
+
+        assert_eq!(hresult(0xc00d_36b9).facility(), Facility::MediaFoundation);
+        assert_eq!(hresult(0x8898_0800).facility(), Facility::Dwrite);
+        assert_eq!(hresult(0x8899_000c).facility(), Facility::D2d);
+        assert_eq!(hresult(0x887c_0002).facility(), Facility::D3d11);
+        assert_eq!(hresult(0x8007_0057).facility(), Facility::Win32);
+        assert_eq!(hresult(0x8000_4005).facility(), Facility::Null);
+        assert_eq!(hresult(0x8123_0001).facility(), Facility::Other(0x123));
+    }
+
+    #[test]
+    fn lookup() {
+        assert_eq!(hresult(0xc00d_36b9).name(), Some("MF_E_NO_MORE_TYPES"));
+        assert_eq!(hresult(0x8899_000c).name(), Some("D2DERR_RECREATE_TARGET"));
+        assert_eq!(
+            hresult(0x8898_0801).name(),
+            Some("DCOMPOSITION_ERROR_SURFACE_BEING_RENDERED")
+        );
+        assert_eq!(hresult(0x8123_0001).name(), None);
+        assert_eq!(hresult(0x8123_0001).description(), None);
+
+        for (i, &(value, name, _)) in CODES.iter().enumerate() {
+            assert!(
+                CODES[..i].iter().all(|&(other, _, _)| other != value),
+                "{} is listed twice",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(
+            hresult(0x887a_0005).to_string(),
+            "DXGI_ERROR_DEVICE_REMOVED (0x887a0005): \
+             The GPU was physically removed or the driver was upgraded."
+        );
+        assert_eq!(hresult(0x8123_0001).to_string(), "0x81230001");
+        assert_eq!(
+            format!("{:?}", hresult(0xc00d_36b9)),
+            "Hresult { value: 0xc00d36b9, name: MF_E_NO_MORE_TYPES, \
+             severity: Failure, facility: MediaFoundation, code: 0x36b9 }"
+        );
+        assert_eq!(
+            format!("{:?}", hresult(0x8123_0001)),
+            "Hresult { value: 0x81230001, severity: Failure, facility: Other(291), code: 0x0001 }"
+        );
+    }
+}
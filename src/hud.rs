@@ -0,0 +1,464 @@
+//! Immediate-mode heads-up display of frame timings and surface statistics.
+//!
+//! Feed a `Hud` every frame with `record_*` and draw it on any `Canvas`:
+//!
+//! ```ignore
+//! hud.record_frame(scheduler.stats());
+//! hud.record_paints(debug::last_frame_paints());
+//! hud.record_surface(surface.tile_count(), surface.memory_usage());
+//! hud.draw(&mut canvas);
+//! ```
+//!
+//! Text uses a built-in 3x5 pixel font, so the HUD needs nothing from the
+//! backend beyond filling rectangles. Draw it into a surface with
+//! `draw_untracked`, so that it doesn't count in the paint stats it shows.
+
+use crate::{debug::PaintStats, scheduler::FrameStats, soft, Canvas, Point, Rect};
+use std::time::Duration;
+
+/// Width of the frame time graph, one pixel column per frame.
+pub const GRAPH_WIDTH: i32 = crate::scheduler::STATS_WINDOW as i32;
+pub const GRAPH_HEIGHT: i32 = 40;
+/// Frame time at the top of the graph. Longer frames are clipped.
+pub const GRAPH_RANGE: Duration = Duration::from_millis(50);
+
+const PADDING: i32 = 4;
+const GLYPH_SCALE: i32 = 2;
+const GLYPH_ADVANCE: i32 = 4 * GLYPH_SCALE;
+const LINE_HEIGHT: i32 = 6 * GLYPH_SCALE + 2;
+
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const TEXT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const TARGET_LINE: [f32; 4] = [1.0, 1.0, 1.0, 0.4];
+const GOOD: [f32; 4] = [0.2, 0.9, 0.2, 1.0];
+const SLOW: [f32; 4] = [0.9, 0.9, 0.2, 1.0];
+const BAD: [f32; 4] = [0.9, 0.2, 0.2, 1.0];
+
+/// Frame time of a 60Hz display, drawn as a line across the graph.
+const TARGET_FRAME_TIME: Duration = Duration::from_micros(16_667);
+
+/// Everything the HUD displays, aggregated from the `record_*` calls.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HudStats {
+    /// Oldest first.
+    pub frame_times: Vec<Duration>,
+    pub average_frame_time: Option<Duration>,
+    pub max_frame_time: Option<Duration>,
+    pub commit_latency: Option<Duration>,
+    pub paints: PaintStats,
+    pub surfaces: usize,
+    pub tiles: usize,
+    /// Bytes.
+    pub surface_memory: usize,
+}
+
+impl HudStats {
+    /// The text lines shown below the graph.
+    pub fn lines(&self) -> Vec<String> {
+        let fps = self
+            .average_frame_time
+            .filter(|t| *t > Duration::from_secs(0))
+            .map(|t| 1.0 / t.as_secs_f64());
+        vec![
+            format!(
+                "FRAME {} MS {} FPS",
+                format_ms(self.average_frame_time),
+                fps.map_or_else(|| "-".to_string(), |fps| format!("{:.0}", fps))
+            ),
+            format!("MAX {} MS", format_ms(self.max_frame_time)),
+            format!("COMMIT {} MS", format_ms(self.commit_latency)),
+            format!("RECTS {} AREA {} PX", self.paints.rects, self.paints.area),
+            format!(
+                "SURFACES {} TILES {} {}",
+                self.surfaces,
+                self.tiles,
+                format_bytes(self.surface_memory)
+            ),
+        ]
+    }
+}
+
+fn format_ms(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => format!("{:.1}", duration.as_secs_f64() * 1000.0),
+        None => "-".to_string(),
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    const MB: usize = 1024 * 1024;
+    if bytes >= MB {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    } else {
+        format!("{} KB", (bytes + 1023) / 1024)
+    }
+}
+
+/// Where the parts of the HUD go, in canvas coordinates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HudLayout {
+    pub panel: Rect,
+    pub graph: Rect,
+    /// Bounds of each text line.
+    pub lines: Vec<Rect>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Hud {
+    origin: Point,
+    stats: HudStats,
+}
+
+impl Hud {
+    /// A HUD with its top left corner at `origin`.
+    pub fn new(origin: Point) -> Self {
+        Self {
+            origin,
+            stats: HudStats::default(),
+        }
+    }
+
+    pub fn origin(&self) -> Point {
+        self.origin
+    }
+
+    pub fn set_origin(&mut self, origin: Point) {
+        self.origin = origin;
+    }
+
+    pub fn stats(&self) -> &HudStats {
+        &self.stats
+    }
+
+    pub fn record_frame(&mut self, stats: &FrameStats) {
+        self.stats.frame_times = stats.frame_times().collect();
+        self.stats.average_frame_time = stats.average_frame_time();
+        self.stats.max_frame_time = stats.max_frame_time();
+        self.stats.commit_latency = stats.commit_latencies().last();
+    }
+
+    pub fn record_paints(&mut self, paints: PaintStats) {
+        self.stats.paints = paints;
+    }
+
+    /// Add one surface to this frame's totals. Totals are reset by `draw`.
+    pub fn record_surface(&mut self, tiles: usize, memory: usize) {
+        self.stats.surfaces += 1;
+        self.stats.tiles += tiles;
+        self.stats.surface_memory += memory;
+    }
+
+    pub fn record_virtual_surface(&mut self, surface: &soft::VirtualSurface) {
+        self.record_surface(surface.tile_count(), surface.memory_usage());
+    }
+
+    pub fn layout(&self) -> HudLayout {
+        let lines = self.stats.lines();
+        let widest = lines.iter().map(|line| text_width(line)).max();
+        let width = GRAPH_WIDTH.max(widest.unwrap_or(0));
+
+        let left = self.origin.x + PADDING;
+        let top = self.origin.y + PADDING;
+        let graph = Rect::new(left, top, left + GRAPH_WIDTH, top + GRAPH_HEIGHT);
+        let lines: Vec<_> = lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let y = graph.bottom + PADDING + i as i32 * LINE_HEIGHT;
+                Rect::new(left, y, left + text_width(line), y + 5 * GLYPH_SCALE)
+            })
+            .collect();
+        let bottom = lines.last().map_or(graph.bottom, |r| r.bottom);
+        let panel = Rect::new(
+            self.origin.x,
+            self.origin.y,
+            left + width + PADDING,
+            bottom + PADDING,
+        );
+        HudLayout {
+            panel,
+            graph,
+            lines,
+        }
+    }
+
+    pub fn draw(&mut self, canvas: &mut impl Canvas) {
+        let layout = self.layout();
+        canvas.fill_rect(&layout.panel, BACKGROUND);
+
+        let graph = layout.graph;
+        let skip = GRAPH_WIDTH as usize - self.stats.frame_times.len().min(GRAPH_WIDTH as usize);
+        for (i, time) in self.stats.frame_times.iter().enumerate() {
+            let height = bar_height(*time);
+            let x = graph.left + (skip + i) as i32;
+            let color = if *time <= TARGET_FRAME_TIME {
+                GOOD
+            } else if *time <= TARGET_FRAME_TIME * 2 {
+                SLOW
+            } else {
+                BAD
+            };
+            canvas.fill_rect(
+                &Rect::new(x, graph.bottom - height, x + 1, graph.bottom),
+                color,
+            );
+        }
+        let target = graph.bottom - bar_height(TARGET_FRAME_TIME);
+        canvas.fill_rect(
+            &Rect::new(graph.left, target, graph.right, target + 1),
+            TARGET_LINE,
+        );
+
+        for (line, bounds) in self.stats.lines().iter().zip(&layout.lines) {
+            draw_text(canvas, line, Point::new(bounds.left, bounds.top), TEXT);
+        }
+
+        self.stats.surfaces = 0;
+        self.stats.tiles = 0;
+        self.stats.surface_memory = 0;
+    }
+}
+
+fn bar_height(time: Duration) -> i32 {
+    let fraction = time.as_secs_f64() / GRAPH_RANGE.as_secs_f64();
+    (fraction.min(1.0) * f64::from(GRAPH_HEIGHT)).round() as i32
+}
+
+fn text_width(text: &str) -> i32 {
+    match text.chars().count() as i32 {
+        0 => 0,
+        n => n * GLYPH_ADVANCE - GLYPH_SCALE,
+    }
+}
+
+fn draw_text(canvas: &mut impl Canvas, text: &str, origin: Point, color: [f32; 4]) {
+    for (i, c) in text.chars().enumerate() {
+        let x = origin.x + i as i32 * GLYPH_ADVANCE;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            let y = origin.y + row as i32 * GLYPH_SCALE;
+            // One rectangle for each run of set pixels in the row.
+            let set = |col: i32| col < 3 && bits & (0b100 >> col) != 0;
+            let mut col = 0;
+            while col < 3 {
+                if !set(col) {
+                    col += 1;
+                    continue;
+                }
+                let start = col;
+                while set(col) {
+                    col += 1;
+                }
+                canvas.fill_rect(
+                    &Rect::new(
+                        x + start * GLYPH_SCALE,
+                        y,
+                        x + col * GLYPH_SCALE,
+                        y + GLYPH_SCALE,
+                    ),
+                    color,
+                );
+            }
+        }
+    }
+}
+
+/// Rows of a 3x5 glyph, most significant bit on the left.
+#[rustfmt::skip]
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b110, 0b101, 0b010],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b010, 0b101, 0b010, 0b101, 0b010],
+        '9' => [0b010, 0b101, 0b011, 0b001, 0b110],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        debug, point, rect,
+        scheduler::{Commit, FrameScheduler, ManualClock, Mode},
+        soft,
+    };
+
+    struct NullDevice;
+
+    impl Commit for NullDevice {
+        fn commit(&self) -> crate::Result<()> {
+            Ok(())
+        }
+        fn wait_for_commit_completion(&self) -> crate::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn aggregates_stats() {
+        let clock = ManualClock::new();
+        let mut scheduler = FrameScheduler::with_clock(Mode::OnDemand, clock.clone());
+        let surface = soft::VirtualSurface::new(1024, 1024);
+        for (i, time) in [10, 20, 30].iter().enumerate() {
+            scheduler.request_frame();
+            let drawn = scheduler.frame(&NullDevice, |_| {
+                surface.draw(&rect!(0, 0, 10, 10), |_, _| {});
+                if i == 2 {
+                    surface.draw(&rect!(300, 0, 310, 20), |_, _| {});
+                }
+                clock.advance(ms(*time));
+                Ok(())
+            });
+            assert_eq!(drawn, Ok(true));
+        }
+
+        let mut hud = Hud::new(point!(0, 0));
+        hud.record_frame(scheduler.stats());
+        hud.record_paints(debug::last_frame_paints());
+        hud.record_virtual_surface(&surface);
+
+        let stats = hud.stats();
+        assert_eq!(stats.frame_times, vec![ms(10), ms(20)]);
+        assert_eq!(stats.average_frame_time, Some(ms(15)));
+        assert_eq!(stats.max_frame_time, Some(ms(20)));
+        assert_eq!(stats.commit_latency, Some(ms(0)));
+        assert_eq!(
+            stats.paints,
+            PaintStats {
+                rects: 2,
+                area: 300
+            }
+        );
+        assert_eq!((stats.surfaces, stats.tiles), (1, 2));
+        assert_eq!(
+            stats.lines(),
+            vec![
+                "FRAME 15.0 MS 67 FPS",
+                "MAX 20.0 MS",
+                "COMMIT 0.0 MS",
+                "RECTS 2 AREA 300 PX",
+                "SURFACES 1 TILES 2 512 KB",
+            ]
+        );
+    }
+
+    #[test]
+    fn layout_and_draw() {
+        let mut hud = Hud::new(point!(10, 20));
+        hud.stats.frame_times = vec![ms(10), ms(50)];
+
+        let layout = hud.layout();
+        assert_eq!(layout.graph, rect!(14, 24, 14 + GRAPH_WIDTH, 64));
+        assert_eq!(layout.lines.len(), 5);
+        assert_eq!(layout.lines[0].top, 68);
+        assert_eq!(layout.lines[1].top - layout.lines[0].top, LINE_HEIGHT);
+        assert_eq!(layout.panel.left, 10);
+        assert_eq!(layout.panel.bottom, layout.lines[4].bottom + PADDING);
+        assert!(layout
+            .lines
+            .iter()
+            .all(|l| l.right <= layout.panel.right - PADDING));
+
+        let mut bitmap = soft::Bitmap::new(300, 200);
+        hud.draw(&mut bitmap);
+        let graph = layout.graph;
+        // The newest frame is on the right; 50ms fills the whole height.
+        let last = graph.right - 1;
+        assert_eq!(bitmap.pixel(last, graph.top), 0xFFE6_3333);
+        // 10ms is 8 pixels high.
+        assert_eq!(bitmap.pixel(last - 1, graph.bottom - 8), 0xFF33_E633);
+        assert_ne!(bitmap.pixel(last - 1, graph.bottom - 9), 0xFF33_E633);
+        assert_eq!(bitmap.pixel(0, 0), 0);
+        // Background is drawn under the text.
+        assert_ne!(
+            bitmap.pixel(layout.panel.right - 1, layout.panel.bottom - 1),
+            0
+        );
+    }
+
+    #[derive(Default)]
+    struct Rects(Vec<Rect>);
+
+    impl Canvas for Rects {
+        fn clear(&mut self) {}
+
+        fn fill_rect(&mut self, rect: &Rect, _: [f32; 4]) {
+            self.0.push(*rect);
+        }
+    }
+
+    #[test]
+    fn text_runs() {
+        // Each row of a glyph is filled with one rectangle per run.
+        let mut rects = Rects::default();
+        draw_text(&mut rects, "T8", point!(0, 0), TEXT);
+        let s = GLYPH_SCALE;
+        assert_eq!(rects.0.len(), 5 + 7);
+        assert_eq!(rects.0[0], rect!(0, 0, 3 * s, s));
+        assert_eq!(rects.0[1], rect!(s, s, 2 * s, 2 * s));
+        assert_eq!(
+            rects.0[6],
+            rect!(GLYPH_ADVANCE, s, GLYPH_ADVANCE + s, 2 * s)
+        );
+        assert_eq!(
+            rects.0[7],
+            rect!(GLYPH_ADVANCE + 2 * s, s, GLYPH_ADVANCE + 3 * s, 2 * s)
+        );
+
+        // The same pixels as filling each one.
+        let mut bitmap = soft::Bitmap::new(40, 20);
+        draw_text(&mut bitmap, "%4:", point!(1, 2), TEXT);
+        for (i, c) in "%4:".chars().enumerate() {
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..3 {
+                    let x = 1 + i as i32 * GLYPH_ADVANCE + col * s;
+                    let y = 2 + row as i32 * s;
+                    let lit = bits & (0b100 >> col) != 0;
+                    assert_eq!(bitmap.pixel(x + 1, y + 1) != 0, lit, "{:?}", c);
+                }
+            }
+        }
+    }
+}
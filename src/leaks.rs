@@ -0,0 +1,276 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Tracking of live COM references, with the `leak-detector` feature.
+//!
+//! Every `ComPtr` that holds, or is about to receive, a reference is
+//! registered with its interface, thread and the backtrace of where it was
+//! created, until it is dropped or forgotten. A reference that outlives the
+//! code that created it, such as a bitmap created on every frame and kept
+//! in a growing list, shows up in `counts` as a `live` number that keeps
+//! growing, and in `report` with the backtrace of its creation.
+//!
+//! Capturing a backtrace for every reference is slow, so this is meant for
+//! debugging sessions rather than regular builds.
+
+use std::{
+    backtrace::Backtrace,
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    ptr,
+    sync::{Mutex, MutexGuard, Once},
+    thread::{self, ThreadId},
+};
+
+/// Per-interface counts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    /// References currently held.
+    pub live: usize,
+    /// References taken since the start of the process.
+    pub created: usize,
+}
+
+/// A reference that is still held.
+#[derive(Debug)]
+pub struct Outstanding {
+    /// The interface's name, such as `ID2D1Bitmap1`.
+    pub interface: &'static str,
+    /// The thread that created the reference.
+    pub thread: ThreadId,
+    pub backtrace: String,
+}
+
+/// References held now, oldest first.
+pub fn outstanding() -> Vec<Outstanding> {
+    let registry = registry();
+    let mut live: Vec<_> = registry.live.iter().collect();
+    live.sort_by_key(|&(id, _)| *id);
+    live.into_iter()
+        .map(|(_, entry)| Outstanding {
+            interface: entry.interface,
+            thread: entry.thread,
+            backtrace: entry.backtrace.to_string(),
+        })
+        .collect()
+}
+
+/// Counts for each interface that has been referenced, by name.
+pub fn counts() -> BTreeMap<&'static str, Counts> {
+    registry().counts.clone()
+}
+
+/// A summary of the counts followed by each outstanding reference, or an
+/// empty string if there are none.
+pub fn report() -> String {
+    let outstanding = outstanding();
+    if outstanding.is_empty() {
+        return String::new();
+    }
+    let mut report = format!("{} outstanding COM references\n", outstanding.len());
+    for (interface, counts) in counts() {
+        if counts.live > 0 {
+            let _ = writeln!(
+                report,
+                "  {}: {} live, {} created",
+                interface, counts.live, counts.created
+            );
+        }
+    }
+    for reference in &outstanding {
+        let _ = writeln!(
+            report,
+            "\n{} created on {:?} at:\n{}",
+            reference.interface, reference.thread, reference.backtrace
+        );
+    }
+    report
+}
+
+/// Prints `report` to stderr.
+pub fn dump() {
+    eprint!("{}", report());
+}
+
+/// Dumps outstanding references when dropped. Keep one alive for the
+/// duration of `main` to see what is left over at shutdown.
+#[must_use]
+pub struct DumpOnDrop;
+
+pub fn dump_on_drop() -> DumpOnDrop {
+    DumpOnDrop
+}
+
+impl Drop for DumpOnDrop {
+    fn drop(&mut self) {
+        dump();
+    }
+}
+
+/// Registers a reference to `T`, returning its id for `untrack`.
+crate fn track<T>() -> u64 {
+    let interface = interface_name(std::any::type_name::<T>());
+    let mut registry = registry();
+    registry.next_id += 1;
+    let id = registry.next_id;
+    registry.live.insert(
+        id,
+        Entry {
+            interface,
+            thread: thread::current().id(),
+            backtrace: Backtrace::force_capture(),
+        },
+    );
+    let counts = registry.counts.entry(interface).or_default();
+    counts.live += 1;
+    counts.created += 1;
+    id
+}
+
+/// Unregisters the reference `id`. Does nothing for 0, the id of a `ComPtr`
+/// that isn't tracked.
+crate fn untrack(id: u64) {
+    if id == 0 {
+        return;
+    }
+    let mut registry = registry();
+    if let Some(entry) = registry.live.remove(&id) {
+        if let Some(counts) = registry.counts.get_mut(entry.interface) {
+            counts.live -= 1;
+        }
+    }
+}
+
+struct Entry {
+    interface: &'static str,
+    thread: ThreadId,
+    backtrace: Backtrace,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u64,
+    live: HashMap<u64, Entry>,
+    counts: BTreeMap<&'static str, Counts>,
+}
+
+fn registry() -> MutexGuard<'static, Registry> {
+    static INIT: Once = Once::new();
+    static mut REGISTRY: *const Mutex<Registry> = ptr::null();
+    // `REGISTRY` is only written inside `call_once`, which finishes before
+    // any thread reads it, and the registry is never freed.
+    INIT.call_once(|| unsafe { REGISTRY = Box::into_raw(Box::default()) });
+    let registry = unsafe { &*REGISTRY };
+    // A panic while the lock is held leaves the maps consistent.
+    registry
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// `winapi::um::d2d1_1::ID2D1Bitmap1` becomes `ID2D1Bitmap1`.
+fn interface_name(type_name: &'static str) -> &'static str {
+    type_name.rsplit("::").next().unwrap_or(type_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{IBar, IBaz, IFoo, Mock},
+        sys::Interface,
+        ComPtr,
+    };
+
+    /// Outstanding references created by this test's thread, as tests run
+    /// in parallel.
+    fn mine() -> Vec<Outstanding> {
+        let thread = thread::current().id();
+        outstanding()
+            .into_iter()
+            .filter(|reference| reference.thread == thread)
+            .collect()
+    }
+
+    fn interfaces(outstanding: &[Outstanding]) -> Vec<&'static str> {
+        outstanding
+            .iter()
+            .map(|reference| reference.interface)
+            .collect()
+    }
+
+    #[test]
+    fn tracks_references() {
+        let (foo, mock) = Mock::builder()
+            .implements::<IBar>()
+            .implements::<IBaz>()
+            .build::<IFoo>();
+        let bar = foo.query_interface::<IBar>().unwrap();
+        let baz = foo.query_interface::<IBaz>().unwrap();
+        let clone = baz.clone();
+        assert_eq!(interfaces(&mine()), ["IFoo", "IBar", "IBaz", "IBaz"]);
+        assert!(mine()[0].backtrace.contains("tracks_references"));
+
+        drop((bar, clone));
+        assert_eq!(interfaces(&mine()), ["IFoo", "IBaz"]);
+
+        // A forgotten reference is no longer tracked; adopting it again is.
+        let mut baz = baz;
+        let raw = baz.forget();
+        assert_eq!(interfaces(&mine()), ["IFoo"]);
+        let baz = unsafe { ComPtr::already_addrefed(raw) };
+        assert_eq!(interfaces(&mine()), ["IFoo", "IBaz"]);
+
+        // Null pointers are not references.
+        let null = ComPtr::<IFoo>::default();
+        drop(null.clone());
+        assert_eq!(mine().len(), 2);
+
+        drop((foo, baz));
+        assert!(mine().is_empty());
+        assert!(mock.is_destroyed());
+    }
+
+    #[test]
+    fn out_parameters() {
+        let (foo, _mock) = Mock::builder().implements::<IBar>().build::<IFoo>();
+        let mut bar = ComPtr::<IBar>::default();
+        unsafe {
+            (*foo.as_ptr()).QueryInterface(&IBar::uuidof(), bar.getter_addrefs());
+        }
+        assert_eq!(interfaces(&mine()), ["IFoo", "IBar"]);
+
+        // Reusing the out parameter replaces the entry.
+        unsafe {
+            (*foo.as_ptr()).QueryInterface(&IBar::uuidof(), bar.getter_addrefs());
+        }
+        assert_eq!(interfaces(&mine()), ["IFoo", "IBar"]);
+        drop((foo, bar));
+        assert!(mine().is_empty());
+    }
+
+    #[test]
+    fn counts_and_report() {
+        let (foo, _mock) = Mock::builder().implements::<IBaz>().build::<IBaz>();
+        let leaked: Vec<_> = (0..3).map(|_| foo.clone()).collect();
+        let counts = counts()["IBaz"];
+        assert!(counts.live >= 4);
+        assert!(counts.created >= 4);
+
+        let report = report();
+        assert!(report.contains("outstanding COM references"));
+        assert!(report.contains("IBaz: "));
+        assert!(report.contains("counts_and_report"));
+        drop((foo, leaked));
+        assert!(mine().is_empty());
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(
+            interface_name("winapi::um::d2d1_1::ID2D1Bitmap1"),
+            "ID2D1Bitmap1"
+        );
+        assert_eq!(interface_name("IFoo"), "IFoo");
+    }
+}
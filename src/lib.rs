@@ -1,29 +1,64 @@
 #![warn(clippy::all)]
 #![feature(crate_visibility_modifier)]
+#![feature(const_if_match, const_loop, const_panic)]
+#![cfg_attr(feature = "leak-detector", feature(backtrace))]
 mod macros;
 
+pub mod com;
 mod comptr;
+#[cfg(windows)]
 pub mod d2d;
+#[cfg(windows)]
 pub mod d3d11;
+#[cfg(windows)]
 pub mod d3dcompiler;
+#[cfg(windows)]
 pub mod dcomp;
+pub mod debug;
+pub mod device;
+#[cfg(windows)]
 pub mod dwrite;
+#[cfg(windows)]
 pub mod dxgi;
+pub mod hud;
+#[cfg(windows)]
 pub mod mf;
+pub mod scheduler;
+pub mod soft;
+pub mod text;
+pub mod threading;
+#[cfg(feature = "tracing")]
+pub mod trace;
+pub mod tree;
+#[cfg(windows)]
 pub mod winuser;
 
+mod canvas;
+mod error;
+mod guid;
+mod hresult;
+#[cfg(feature = "leak-detector")]
+pub mod leaks;
+#[cfg(test)]
+mod mock;
 mod rect;
-mod text;
+mod region;
+mod sys;
 
+pub use canvas::Canvas;
 pub use comptr::ComPtr;
 use derive_newtype::NewType;
+pub use error::{Error, Result};
+pub use guid::{Guid, ParseGuidError};
+pub use hresult::{Facility, Hresult, Severity};
 pub use rect::Rect;
+pub use region::Region;
 use std::{
     ops::{Add, AddAssign, Div, Mul, Neg, Sub},
     ptr,
 };
-pub use text::ToUtf16;
-use winapi::shared::windef::{POINT, SIZE};
+use sys::{POINT, SIZE};
+pub use text::{InteriorNulError, ToWideStr, WideStr, WideString};
 
 pub trait AsPtr<T> {
     fn as_ptr(&self) -> *mut T;
@@ -47,6 +82,7 @@ where
     }
 }
 
+#[cfg(windows)]
 crate fn opt_ptr<T, U>(opt: Option<&'_ U>) -> *const T
 where
     U: std::ops::Deref<Target = T>,
@@ -167,6 +203,13 @@ impl Neg for Offset {
     }
 }
 
+impl Add for Offset {
+    type Output = Offset;
+    fn add(self, rhs: Self) -> Self::Output {
+        offset!(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
 impl Sub for Offset {
     type Output = Offset;
     fn sub(self, rhs: Self) -> Self::Output {
@@ -1,41 +1,224 @@
 
+/// Makes a call that returns an `HRESULT`, giving `Err` with the name of
+/// the method if it fails.
+///
+/// With the `tracing` feature, the call is also recorded as an event, with
+/// any fields after the call, such as `hr!(self.0.Resize(w, h), width = w)`;
+/// see `trace`.
+#[cfg(not(feature = "tracing"))]
 #[macro_export]
 macro_rules! hr {
-    ($hr:expr) => {
-        {
-            let hr = unsafe { $hr };
-            assert!(hr == 0);
+    ($hr:expr $(, $($field:tt)+)?) => {
+        $crate::Error::check(unsafe { $hr }, stringify!($hr))
+    }
+}
+
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! hr {
+    (@traced $interface:expr, $method:expr, $hr:expr $(, $($field:tt)+)?) => {{
+        let start = std::time::Instant::now();
+        let hresult = unsafe { $hr };
+        let duration_us = start.elapsed().as_micros() as u64;
+        let method: &'static str = $method;
+        if hresult >= 0 {
+            $crate::trace::tracing::event!(
+                target: "noodle::com",
+                $crate::trace::tracing::Level::TRACE,
+                interface = $interface,
+                method,
+                hresult = %$crate::trace::HresultField(hresult),
+                ok = true,
+                duration_us
+                $(, $($field)+)?
+            );
+        } else {
+            $crate::trace::tracing::event!(
+                target: "noodle::com",
+                $crate::trace::tracing::Level::WARN,
+                interface = $interface,
+                method,
+                hresult = %$crate::trace::HresultField(hresult),
+                ok = false,
+                duration_us
+                $(, $($field)+)?
+            );
         }
+        $crate::Error::check(hresult, method)
+    }};
+    ($recv:ident . $field:tt . $method:ident ( $($arg:tt)* ) $(, $($fields:tt)+)?) => {
+        $crate::hr!(
+            @traced $crate::trace::interface_of(&$recv.$field),
+            stringify!($method),
+            $recv.$field.$method($($arg)*)
+            $(, $($fields)+)?
+        )
+    };
+    ($recv:ident . $method:ident ( $($arg:tt)* ) $(, $($fields:tt)+)?) => {
+        $crate::hr!(
+            @traced $crate::trace::interface_of(&$recv),
+            stringify!($method),
+            $recv.$method($($arg)*)
+            $(, $($fields)+)?
+        )
+    };
+    ($hr:expr $(, $($field:tt)+)?) => {
+        $crate::hr!(
+            @traced "",
+            $crate::trace::method_name(stringify!($hr)),
+            $hr
+            $(, $($field)+)?
+        )
+    };
+}
+
+/// Makes a call whose result isn't an `HRESULT` to check, such as a `void`
+/// method, a getter or an enumeration that ends with an error code, and
+/// returns its result.
+///
+/// With the `tracing` feature, the call is recorded as by `hr!`, without the
+/// `hresult` and `ok` fields.
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! call {
+    ($call:expr $(, $($field:tt)+)?) => {
+        unsafe { $call }
     }
 }
 
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! call {
+    (@traced $interface:expr, $method:expr, $call:expr $(, $($field:tt)+)?) => {{
+        let start = std::time::Instant::now();
+        let result = unsafe { $call };
+        let duration_us = start.elapsed().as_micros() as u64;
+        let method: &'static str = $method;
+        $crate::trace::tracing::event!(
+            target: "noodle::com",
+            $crate::trace::tracing::Level::TRACE,
+            interface = $interface,
+            method,
+            duration_us
+            $(, $($field)+)?
+        );
+        result
+    }};
+    ($recv:ident . $outer:tt . $field:tt . $method:ident ( $($arg:tt)* ) $(, $($fields:tt)+)?) => {
+        $crate::call!(
+            @traced $crate::trace::interface_of(&$recv.$outer.$field),
+            stringify!($method),
+            $recv.$outer.$field.$method($($arg)*)
+            $(, $($fields)+)?
+        )
+    };
+    ($recv:ident . $field:tt . $method:ident ( $($arg:tt)* ) $(, $($fields:tt)+)?) => {
+        $crate::call!(
+            @traced $crate::trace::interface_of(&$recv.$field),
+            stringify!($method),
+            $recv.$field.$method($($arg)*)
+            $(, $($fields)+)?
+        )
+    };
+    ($recv:ident . $method:ident ( $($arg:tt)* ) $(, $($fields:tt)+)?) => {
+        $crate::call!(
+            @traced $crate::trace::interface_of(&$recv),
+            stringify!($method),
+            $recv.$method($($arg)*)
+            $(, $($fields)+)?
+        )
+    };
+    ($call:expr $(, $($field:tt)+)?) => {
+        $crate::call!(
+            @traced "",
+            $crate::trace::method_name(stringify!($call)),
+            $call
+            $(, $($field)+)?
+        )
+    };
+}
+
+/// Enters a span around a unit of work, such as a frame or a draw, with the
+/// `tracing` feature. Keep the result alive for the duration of the work.
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! trace_span {
+    ($name:expr $(, $($field:tt)+)?) => {
+        ()
+    };
+}
+
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! trace_span {
+    ($name:expr $(, $($field:tt)+)?) => {
+        $crate::trace::tracing::debug_span!($name $(, $($field)+)?).entered()
+    };
+}
+
+/// Declares a wrapper of `ComPtr<$com>` that also answers for `$extra`, the
+/// interface's bases. A trailing `in Model` names its `threading` model,
+/// `SingleThreaded` if omitted.
+///
+/// The wrapper converts into a `ComPtr` of each listed interface without a
+/// `QueryInterface`. `Agile` wrappers can also lend one with `AsRef`; bound
+/// wrappers don't, since a `ComPtr` has no `Affinity` to check on each use.
 #[macro_export]
 macro_rules! impl_comptr {
-    ($name:ident: [$com:ty, $($extra:ty),*]) => {
+    ($name:ident: [$com:ty, $($extra:ty),*] in $model:ident) => {
         #[derive(Clone, Default)]
-        pub struct $name(crate $crate::comptr::ComPtr<$com>);
+        pub struct $name(
+            crate $crate::comptr::ComPtr<$com>,
+            $crate::threading::Affinity<$crate::threading::$model>,
+        );
 
         impl From<$crate::ComPtr<$com>> for $name {
             fn from(comptr: $crate::ComPtr<$com>) -> Self {
-                Self(comptr)
+                Self(comptr, $crate::threading::Affinity::new())
+            }
+        }
+
+        impl $crate::threading::Threaded for $name {
+            type Model = $crate::threading::$model;
+
+            fn affinity(&self) -> &$crate::threading::Affinity<Self::Model> {
+                &self.1
             }
         }
 
-        impl_comptr! { @impl $name: [$com, $($extra),*] }
+        impl_comptr! { @impl $name $model: [$com, $($extra),*] }
     };
-    ($name:ident($field:ty): [$com:ty, $($extra:ty),*]) => {
+    ($name:ident($field:ty): [$com:ty, $($extra:ty),*] in $model:ident) => {
         #[derive(Clone, Default)]
-        pub struct $name(crate $crate::comptr::ComPtr<$com>, $field);
+        pub struct $name(
+            crate $crate::comptr::ComPtr<$com>,
+            $field,
+            $crate::threading::Affinity<$crate::threading::$model>,
+        );
 
         impl From<($crate::ComPtr<$com>, $field)> for $name {
             fn from(tuple: ($crate::ComPtr<$com>, $field)) -> Self {
-                Self(tuple.0, tuple.1)
+                Self(tuple.0, tuple.1, $crate::threading::Affinity::new())
+            }
+        }
+
+        impl $crate::threading::Threaded for $name {
+            type Model = $crate::threading::$model;
+
+            fn affinity(&self) -> &$crate::threading::Affinity<Self::Model> {
+                &self.2
             }
         }
 
-        impl_comptr! { @impl $name: [$com, $($extra),*] }
+        impl_comptr! { @impl $name $model: [$com, $($extra),*] }
     };
-    (@impl $name:ident: [$com:ty, $($extra:ty),*]) => {
+    ($name:ident: [$com:ty, $($extra:ty),*]) => {
+        impl_comptr! { $name: [$com, $($extra),*] in SingleThreaded }
+    };
+    ($name:ident($field:ty): [$com:ty, $($extra:ty),*]) => {
+        impl_comptr! { $name($field): [$com, $($extra),*] in SingleThreaded }
+    };
+    (@impl $name:ident $model:ident: [$com:ty, $($extra:ty),*]) => {
         impl std::fmt::Debug for $name {
             fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
                 f.debug_struct(stringify!($name))
@@ -49,16 +232,49 @@ macro_rules! impl_comptr {
             pub fn as_<Q,I>(&self) -> Option<Q>
             where
                 Q: From<$crate::ComPtr<I>>,
-                I: winapi::Interface,
+                I: $crate::sys::Interface,
             {
+                $crate::threading::Threaded::affinity(self).check();
                 self.0.query_interface().map(Q::from)
             }
+
+            /// `as_`, failing with `E_NOINTERFACE` if the object doesn't
+            /// implement the interface.
+            #[allow(dead_code)]
+            pub fn downcast<T, I>(&self) -> $crate::Result<T>
+            where
+                T: From<$crate::ComPtr<I>>,
+                I: $crate::sys::Interface,
+            {
+                self.as_().ok_or_else(|| {
+                    $crate::Error::new($crate::sys::E_NOINTERFACE, "QueryInterface")
+                })
+            }
+
+            /// Converts to the wrapper of a base interface, without a
+            /// `QueryInterface`.
+            #[allow(dead_code)]
+            pub fn upcast<T, I>(self) -> T
+            where
+                T: From<$crate::ComPtr<I>>,
+                I: $crate::sys::Interface,
+                $crate::ComPtr<I>: From<Self>,
+            {
+                T::from(self.into())
+            }
         }
 
-        impl_comptr! { @interface $name $com }
-        $(impl_comptr! { @interface $name $extra })*
+        impl_comptr! { @send $name $model }
+        impl_comptr! { @interface $name $model $com: $com }
+        $(impl_comptr! { @interface $name $model $com: $extra })*
     };
-    (@interface $name:ident $int:ty) => {
+    (@send $name:ident Agile) => {
+        // `ComPtr` is neither, since it doesn't know the object's model.
+        unsafe impl Send for $name {}
+        unsafe impl Sync for $name {}
+    };
+    (@send $name:ident $model:ident) => {};
+    (@interface $name:ident $model:ident $com:ty: $int:ty) => {
         // unsafe impl $crate::AsInterface<$int> for $name {
         //     unsafe fn raw_ptr(&self) -> *mut $int {
         //         (&*self.native.get()).as_ptr() as
@@ -66,10 +282,38 @@ macro_rules! impl_comptr {
         // }
         impl $crate::AsPtr<$int> for $name {
             fn as_ptr(&self) -> *mut $int {
+               $crate::threading::Threaded::affinity(self).check();
                self.0.as_ptr()  as *mut $int
             }
         }
-    }
+
+        // The casts below are only sound if `$int` is `$com` or one of its
+        // bases. Interfaces deref to their base, so this fails to compile
+        // for anything else.
+        const _: () = {
+            #[allow(dead_code)]
+            fn inherits(native: &$com) -> &$int {
+                native
+            }
+        };
+
+        impl From<$name> for $crate::ComPtr<$int> {
+            fn from(wrapper: $name) -> Self {
+                $crate::threading::Threaded::affinity(&wrapper).check();
+                unsafe { wrapper.0.upcast() }
+            }
+        }
+
+        impl_comptr! { @as_ref $name $model $int }
+    };
+    (@as_ref $name:ident Agile $int:ty) => {
+        impl AsRef<$crate::ComPtr<$int>> for $name {
+            fn as_ref(&self) -> &$crate::ComPtr<$int> {
+                unsafe { self.0.upcast_ref() }
+            }
+        }
+    };
+    (@as_ref $name:ident $model:ident $int:ty) => {};
 }
 
 #[macro_export]
@@ -96,4 +340,85 @@ macro_rules! impl_newtype {
 
         )*
     }
-}
\ No newline at end of file
+}
+/// Implements COM interfaces in Rust; see the `com` module.
+///
+/// `implement_com! { trait Name for IFace(IFaceVtbl): IBase { ... } }`
+/// declares a trait with a method for each of `IFace`'s own vtable entries,
+/// each written as `fn rust_name => VtblField(args) -> Ret;`. A trait for
+/// an interface derived from another than `IUnknown` names the base's trait
+/// as its supertrait: `trait Name: BaseName for ...`.
+///
+/// `implement_com!(Type: [IFace, ...])` makes `Type`, which implements the
+/// traits of the listed interfaces, into a COM object with `into_com`.
+///
+/// A method that panics returns `com::Unwound::unwound()` to its caller, so
+/// every return type must implement `com::Unwound`.
+#[macro_export]
+macro_rules! implement_com {
+    (
+        $(#[$attr:meta])*
+        $vis:vis trait $name:ident $(: $super:ident)? for $iface:ident($vtbl:ident): $parent:ident {
+            $(
+                $(#[$method_attr:meta])*
+                fn $method:ident => $field:ident($($arg:ident: $ty:ty),*) -> $ret:ty;
+            )*
+        }
+    ) => {
+        $(#[$attr])*
+        $vis trait $name: $($super +)? $crate::com::Implement {
+            $(
+                $(#[$method_attr])*
+                fn $method(&self, $($arg: $ty),*) -> $ret;
+            )*
+        }
+
+        unsafe impl<T: $name> $crate::com::Implements<$iface> for T {
+            type Vtbl = $vtbl;
+
+            fn iids() -> Vec<$crate::com::GUID> {
+                let mut iids = vec![<$iface as $crate::com::Interface>::uuidof()];
+                iids.extend(<T as $crate::com::Implements<$parent>>::iids());
+                iids
+            }
+
+            fn vtbl(unknown: $crate::com::IUnknownVtbl) -> $vtbl {
+                $(
+                    #[allow(non_snake_case)]
+                    unsafe extern "system" fn $field<T: $name>(
+                        this: *mut $iface,
+                        $($arg: $ty),*
+                    ) -> $ret {
+                        $crate::com::guard(|| $crate::com::this::<T, _>(this).$method($($arg),*))
+                    }
+                )*
+                $vtbl {
+                    parent: <T as $crate::com::Implements<$parent>>::vtbl(unknown),
+                    $($field: $field::<T>,)*
+                }
+            }
+        }
+    };
+    ($type:ty: [$($iface:ident),+ $(,)?]) => {
+        unsafe impl $crate::com::Implement for $type {
+            fn vtables() -> Vec<$crate::com::Vtable> {
+                vec![$($crate::com::Vtable::of::<Self, $iface>()),+]
+            }
+        }
+    };
+}
+
+/// A GUID literal, parsed at compile time.
+///
+/// ```ignore
+/// const MFVideoFormat_NV12: Guid = guid!("3231564E-0000-0010-8000-00AA00389B71");
+/// ```
+///
+/// Braces are optional, as with `parse`. An invalid literal fails to compile.
+#[macro_export]
+macro_rules! guid {
+    ($guid:literal) => {{
+        const GUID: $crate::Guid = $crate::Guid::parse_or_panic($guid);
+        GUID
+    }};
+}
@@ -1,9 +1,14 @@
-use crate::{impl_comptr, impl_interface, AsPtr, ComPtr, ToUtf16};
+use crate::{
+    call, hr, impl_comptr, impl_interface, implement_com, AsPtr, ComPtr, Error, Guid, Result,
+    ToWideStr,
+    WideStr,
+};
 use bitflags::bitflags;
 use winapi::{
     shared::{
         guiddef::{GUID, REFGUID},
-        winerror::{S_FALSE, S_OK},
+        minwindef::DWORD,
+        winerror::S_OK,
     },
     um::{
         mfapi::{
@@ -13,22 +18,25 @@ use winapi::{
         },
         mfobjects::{
             IMF2DBuffer, IMFAttributes, IMFDXGIBuffer, IMFDXGIDeviceManager, IMFMediaBuffer,
-            IMFMediaType, IMFSample, MF_ATTRIBUTE_BLOB, MF_ATTRIBUTE_DOUBLE, MF_ATTRIBUTE_GUID,
-            MF_ATTRIBUTE_IUNKNOWN, MF_ATTRIBUTE_STRING, MF_ATTRIBUTE_UINT32, MF_ATTRIBUTE_UINT64,
+            IMFMediaEvent, IMFMediaType, IMFSample, MF_ATTRIBUTE_BLOB, MF_ATTRIBUTE_DOUBLE,
+            MF_ATTRIBUTE_GUID, MF_ATTRIBUTE_IUNKNOWN, MF_ATTRIBUTE_STRING, MF_ATTRIBUTE_UINT32,
+            MF_ATTRIBUTE_UINT64,
         },
         mfreadwrite::{
-            IMFSourceReader, MFCreateSourceReaderFromURL, MF_READWRITE_ENABLE_HARDWARE_TRANSFORMS,
+            IMFSourceReader, IMFSourceReaderCallback, IMFSourceReaderCallbackVtbl,
+            MFCreateSourceReaderFromURL, MF_READWRITE_ENABLE_HARDWARE_TRANSFORMS,
             MF_SOURCE_READERF_ALLEFFECTSREMOVED, MF_SOURCE_READERF_CURRENTMEDIATYPECHANGED,
             MF_SOURCE_READERF_ENDOFSTREAM, MF_SOURCE_READERF_ERROR,
             MF_SOURCE_READERF_NATIVEMEDIATYPECHANGED, MF_SOURCE_READERF_NEWSTREAM,
             MF_SOURCE_READERF_STREAMTICK, MF_SOURCE_READER_ALL_STREAMS,
-            MF_SOURCE_READER_ANY_STREAM, MF_SOURCE_READER_CONTROLF_DRAIN,
-            MF_SOURCE_READER_D3D_MANAGER, MF_SOURCE_READER_ENABLE_ADVANCED_VIDEO_PROCESSING,
-            MF_SOURCE_READER_FIRST_AUDIO_STREAM, MF_SOURCE_READER_FIRST_VIDEO_STREAM,
+            MF_SOURCE_READER_ANY_STREAM, MF_SOURCE_READER_ASYNC_CALLBACK,
+            MF_SOURCE_READER_CONTROLF_DRAIN, MF_SOURCE_READER_D3D_MANAGER,
+            MF_SOURCE_READER_ENABLE_ADVANCED_VIDEO_PROCESSING, MF_SOURCE_READER_FIRST_AUDIO_STREAM,
+            MF_SOURCE_READER_FIRST_VIDEO_STREAM,
         },
         propidl::PROPVARIANT,
         unknwnbase::IUnknown,
-        winnt::HRESULT,
+        winnt::{HRESULT, LONGLONG},
     },
     Interface,
 };
@@ -41,6 +49,7 @@ pub enum Attr {
 
 #[derive(Clone, Copy)]
 pub enum SourceReaderAttr {
+    AsyncCallback,
     D3dManager,
     EnableAdvancedVideoProcessing,
 }
@@ -56,7 +65,7 @@ pub enum MajorTypeAttr {
     Video,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub enum SourceReaderStream {
     All,
     Any,
@@ -90,64 +99,89 @@ pub enum VideoFormat {
     Nv12,
 }
 
-impl_comptr! { Attributes: [IMFAttributes, IUnknown] }
-impl_comptr! { DXGIDeviceManager(u32): [IMFDXGIDeviceManager, IUnknown] }
-impl_comptr! { SourceReader: [IMFSourceReader, IUnknown] }
-impl_comptr! { MediaType: [IMFMediaType, IMFAttributes, IUnknown] }
-impl_comptr! { Sample: [IMFSample, IMFAttributes, IUnknown] }
+// Attribute stores, and so media types and samples, lock around each call.
+// The device manager exists to share a device between threads, and the
+// source reader is called from the threads its callback runs on.
+impl_comptr! { Attributes: [IMFAttributes, IUnknown] in Agile }
+impl_comptr! { DXGIDeviceManager(u32): [IMFDXGIDeviceManager, IUnknown] in Agile }
+impl_comptr! { SourceReader: [IMFSourceReader, IUnknown] in Agile }
+impl_comptr! { MediaType: [IMFMediaType, IMFAttributes, IUnknown] in Agile }
+impl_comptr! { Sample: [IMFSample, IMFAttributes, IUnknown] in Agile }
+// A buffer is locked and unlocked in pairs, which two threads would mix up.
 impl_comptr! { MediaBuffer: [IMFMediaBuffer, IUnknown] }
 impl_comptr! { Buffer2D: [IMF2DBuffer, IUnknown] }
 impl_comptr! { BufferDxgi: [IMFDXGIBuffer, IUnknown] }
+// Implementations are `Send` and `Sync`, see `implement_com!`.
+impl_comptr! { AsyncCallback: [IMFSourceReaderCallback, IUnknown] in Agile }
 
-pub fn startup() {
-    unsafe {
-        MFStartup(MF_VERSION, MFSTARTUP_FULL);
+implement_com! {
+    /// Receives the results of `SourceReader::read_sample_async`, on a Media
+    /// Foundation worker thread. Make an implementation into an
+    /// `AsyncCallback` with `implement_com!` and `into_com`, and set it as
+    /// `SourceReaderAttr::AsyncCallback` when creating the reader.
+    pub trait SourceReaderCallback for IMFSourceReaderCallback(IMFSourceReaderCallbackVtbl): IUnknown {
+        /// `sample` may be null, such as at the end of the stream.
+        fn on_read_sample => OnReadSample(
+            status: HRESULT,
+            stream_index: DWORD,
+            stream_flags: DWORD,
+            timestamp: LONGLONG,
+            sample: *mut IMFSample
+        ) -> HRESULT;
+        fn on_flush => OnFlush(stream_index: DWORD) -> HRESULT;
+        fn on_event => OnEvent(stream_index: DWORD, event: *mut IMFMediaEvent) -> HRESULT;
     }
 }
 
-pub fn shutdown() {
-    unsafe {
-        MFShutdown();
-    }
+pub fn startup() -> Result<()> {
+    hr!(MFStartup(MF_VERSION, MFSTARTUP_FULL))
 }
 
-pub fn create_attributes(initial_size: u32) -> Attributes {
+pub fn shutdown() -> Result<()> {
+    hr!(MFShutdown())
+}
+
+pub fn create_attributes(initial_size: u32) -> Result<Attributes> {
     let mut attributes = ComPtr::<IMFAttributes>::default();
-    let hr = unsafe { MFCreateAttributes(attributes.getter_addrefs(), initial_size) };
-    assert!(hr == 0);
-    attributes.into()
+    hr!(MFCreateAttributes(
+        attributes.getter_addrefs(),
+        initial_size
+    ))?;
+    Ok(attributes.into())
 }
 
-pub fn create_dxgi_device_manager() -> DXGIDeviceManager {
+pub fn create_dxgi_device_manager() -> Result<DXGIDeviceManager> {
     let mut reset_token = 0u32;
     let mut manager = ComPtr::<IMFDXGIDeviceManager>::default();
-    let hr = unsafe { MFCreateDXGIDeviceManager(&mut reset_token, manager.getter_addrefs()) };
-    assert!(hr == 0);
-    (manager, reset_token).into()
+    hr!(MFCreateDXGIDeviceManager(
+        &mut reset_token,
+        manager.getter_addrefs()
+    ))?;
+    Ok((manager, reset_token).into())
 }
 
-pub fn create_media_type() -> MediaType {
+pub fn create_media_type() -> Result<MediaType> {
     let mut media_type = ComPtr::<IMFMediaType>::default();
-    let hr = unsafe { MFCreateMediaType(media_type.getter_addrefs()) };
-    assert!(hr == 0);
-    media_type.into()
+    hr!(MFCreateMediaType(media_type.getter_addrefs()))?;
+    Ok(media_type.into())
 }
 
-fn _create_source_reader_from_url(url: &[u16], attributes: &Attributes) -> SourceReader {
+fn _create_source_reader_from_url(url: &WideStr, attributes: &Attributes) -> Result<SourceReader> {
     let mut source_reader = ComPtr::<IMFSourceReader>::default();
-    let hr = unsafe {
-        MFCreateSourceReaderFromURL(
-            url.as_ptr(),
-            attributes.as_ptr(),
-            source_reader.getter_addrefs(),
-        )
-    };
-    assert!(hr == 0);
-    source_reader.into()
-}
-
-pub fn create_source_reader_from_url(url: impl ToUtf16, attributes: &Attributes) -> SourceReader {
-    let url = url.to_utf16();
+    hr!(MFCreateSourceReaderFromURL(
+        url.as_ptr(),
+        attributes.as_ptr(),
+        source_reader.getter_addrefs(),
+    ))?;
+    Ok(source_reader.into())
+}
+
+/// Fails with `E_INVALIDARG` if `url` contains a NUL.
+pub fn create_source_reader_from_url(
+    url: impl ToWideStr,
+    attributes: &Attributes,
+) -> Result<SourceReader> {
+    let url = url.to_wide_str()?;
     _create_source_reader_from_url(&url, attributes)
 }
 
@@ -167,6 +201,7 @@ impl ToAttributeKey for Attr {
 impl ToAttributeKey for SourceReaderAttr {
     fn to_key(self) -> REFGUID {
         match self {
+            SourceReaderAttr::AsyncCallback => &MF_SOURCE_READER_ASYNC_CALLBACK,
             SourceReaderAttr::D3dManager => &MF_SOURCE_READER_D3D_MANAGER,
             SourceReaderAttr::EnableAdvancedVideoProcessing => {
                 &MF_SOURCE_READER_ENABLE_ADVANCED_VIDEO_PROCESSING
@@ -188,7 +223,7 @@ pub enum AttributeValue {
     Uint32(u32),
     Uint64(u64),
     Double(f64),
-    Guid(REFGUID),
+    Guid(Guid),
     //String(LPWSTR),
     //Blob(&[u8]),
     Unknown(*mut IUnknown),
@@ -219,10 +254,10 @@ impl AttributeValue {
         }
     }
 
-    pub fn as_guid(&self) -> Option<&'static GUID> {
+    pub fn as_guid(&self) -> Option<Guid> {
         use self::AttributeValue::*;
         match self {
-            Guid(guid) => Some(unsafe { &**guid }),
+            Guid(guid) => Some(*guid),
             _ => None,
         }
     }
@@ -244,12 +279,18 @@ impl ToAttributeValue for u32 {
     }
 }
 
+impl ToAttributeValue for Guid {
+    fn to_value(self) -> AttributeValue {
+        AttributeValue::Guid(self)
+    }
+}
+
 impl ToAttributeValue for MajorTypeAttr {
     fn to_value(self) -> AttributeValue {
         use self::MajorTypeAttr::*;
         match self {
-            Audio => AttributeValue::Guid(&MFMediaType_Audio),
-            Video => AttributeValue::Guid(&MFMediaType_Video),
+            Audio => AttributeValue::Guid(MFMediaType_Audio.into()),
+            Video => AttributeValue::Guid(MFMediaType_Video.into()),
         }
     }
 }
@@ -258,7 +299,7 @@ impl ToAttributeValue for VideoFormat {
     fn to_value(self) -> AttributeValue {
         use self::VideoFormat::*;
         match self {
-            Nv12 => AttributeValue::Guid(&MFVideoFormat_NV12),
+            Nv12 => AttributeValue::Guid(MFVideoFormat_NV12.into()),
         }
     }
 }
@@ -278,7 +319,7 @@ impl ToAttributeValue for PROPVARIANT {
             MF_ATTRIBUTE_UINT32 => AttributeValue::Uint32(unsafe { *self.data.uintVal() }),
             MF_ATTRIBUTE_UINT64 => AttributeValue::Uint64(unsafe { *self.data.uhVal().QuadPart() }),
             MF_ATTRIBUTE_DOUBLE => AttributeValue::Double(unsafe { *self.data.dblVal() }),
-            MF_ATTRIBUTE_GUID => AttributeValue::Guid(unsafe { *self.data.puuid() }),
+            MF_ATTRIBUTE_GUID => AttributeValue::Guid(unsafe { Guid::from(&**self.data.puuid()) }),
             MF_ATTRIBUTE_STRING => AttributeValue::Uint32(0), // TODO
             MF_ATTRIBUTE_BLOB => AttributeValue::Uint32(0),   // TODO
             MF_ATTRIBUTE_IUNKNOWN => AttributeValue::Unknown(unsafe { *self.data.punkVal() }),
@@ -289,54 +330,47 @@ impl ToAttributeValue for PROPVARIANT {
 
 impl_interface! {
     impl [Attributes, MediaType, Sample] {
-        fn _get(&self, key: REFGUID) -> AttributeValue {
+        fn _get(&self, key: REFGUID) -> Result<AttributeValue> {
             let mut item = PROPVARIANT::default();
-            let hr = unsafe { self.0.GetItem(key, &mut item) };
-            assert!(hr == S_OK);
-            item.to_value()
+            hr!(self.0.GetItem(key, &mut item))?;
+            Ok(item.to_value())
         }
 
-        pub fn get(&self, key: impl ToAttributeKey) -> AttributeValue {
+        pub fn get(&self, key: impl ToAttributeKey) -> Result<AttributeValue> {
             self._get(key.to_key())
         }
 
-        fn _set(&mut self, key: REFGUID, value: AttributeValue) {
-            let hr = match value {
-                AttributeValue::Guid(guid) => unsafe { self.0.SetGUID(key, guid) },
-                AttributeValue::Uint32(value) => unsafe { self.0.SetUINT32(key, value) },
-                AttributeValue::Uint64(value) => unsafe { self.0.SetUINT64(key, value ) },
-                AttributeValue::Unknown(interface) => unsafe { self.0.SetUnknown(key, interface) },
-                _ => S_OK,
-            };
-            assert!(hr == S_OK);
+        fn _set(&mut self, key: REFGUID, value: AttributeValue) -> Result<()> {
+            match value {
+                AttributeValue::Guid(guid) => hr!(self.0.SetGUID(key, guid.as_ptr())),
+                AttributeValue::Uint32(value) => hr!(self.0.SetUINT32(key, value)),
+                AttributeValue::Uint64(value) => hr!(self.0.SetUINT64(key, value)),
+                AttributeValue::Unknown(interface) => hr!(self.0.SetUnknown(key, interface)),
+                _ => Ok(()),
+            }
         }
 
-        pub fn set(&mut self, key: impl ToAttributeKey, value: impl ToAttributeValue) {
+        pub fn set(&mut self, key: impl ToAttributeKey, value: impl ToAttributeValue) -> Result<()> {
             self._set(key.to_key(), value.to_value())
         }
 
-        pub fn len(&self) -> usize {
+        pub fn len(&self) -> Result<usize> {
             let mut items = 0;
-            let hr = unsafe { self.0.GetCount(&mut items) };
-            assert!(hr == S_OK);
-            items as _
+            hr!(self.0.GetCount(&mut items))?;
+            Ok(items as _)
         }
 
-        pub fn iter<'a>(&'a self) -> impl Iterator<Item=(GUID, AttributeValue)> + 'a {
-            let mut total = 0;
-            let hr = unsafe {
-                self.0.GetCount(&mut total)
-            };
-            assert!(hr == S_OK);
-            AttributeIter { attrs: &self.0, state: None }
+        /// Iterates over the attributes, holding the store's lock until the
+        /// iterator is dropped.
+        pub fn iter<'a>(&'a self) -> impl Iterator<Item=Result<(Guid, AttributeValue)>> + 'a {
+            AttributeIter { attrs: &self.0, state: None, locked: false }
         }
     }
 }
 
 impl DXGIDeviceManager {
-    pub fn reset_device(&self, device: &impl AsPtr<IUnknown>) {
-        let hr = unsafe { self.0.ResetDevice(device.as_ptr(), self.1) };
-        assert!(hr == 0);
+    pub fn reset_device(&self, device: &impl AsPtr<IUnknown>) -> Result<()> {
+        hr!(self.0.ResetDevice(device.as_ptr(), self.1))
     }
 }
 
@@ -387,7 +421,7 @@ impl SourceReader {
     pub fn native_media_types<'a>(
         &'a self,
         stream: impl Into<SourceReaderStream>,
-    ) -> impl Iterator<Item = MediaType> + 'a {
+    ) -> impl Iterator<Item = Result<MediaType>> + 'a {
         NativeMediaTypeIter {
             reader: self,
             stream: stream.into(),
@@ -405,30 +439,29 @@ impl SourceReader {
             _Out_  IMFMediaType **ppMediaType) = 0;
     */
 
-    pub fn current_media_type(&self, stream_index: impl Into<SourceReaderStream>) -> MediaType {
+    pub fn current_media_type(
+        &self,
+        stream_index: impl Into<SourceReaderStream>,
+    ) -> Result<MediaType> {
         let stream_index = stream_index.into();
         let mut media_type = ComPtr::<IMFMediaType>::default();
-        let hr = unsafe {
-            self.0
-                .GetCurrentMediaType(stream_index.into(), media_type.getter_addrefs())
-        };
-        assert!(hr == 0);
-        media_type.into()
+        hr!(
+            self.0.GetCurrentMediaType(stream_index.into(), media_type.getter_addrefs()),
+            stream = ?stream_index
+        )?;
+        Ok(media_type.into())
     }
     pub fn set_current_media_type(
         &self,
         stream_index: impl Into<SourceReaderStream>,
         media_type: &MediaType,
-    ) {
+    ) -> Result<()> {
         let stream_index = stream_index.into();
-        let hr = unsafe {
-            self.0.SetCurrentMediaType(
-                stream_index.into(),
-                std::ptr::null_mut(),
-                media_type.as_ptr(),
-            )
-        };
-        assert!(hr == 0);
+        hr!(self.0.SetCurrentMediaType(
+            stream_index.into(),
+            std::ptr::null_mut(),
+            media_type.as_ptr(),
+        ), stream = ?stream_index)
     }
 
     /*
@@ -442,56 +475,49 @@ impl SourceReader {
         &self,
         stream_index: impl Into<SourceReaderStream>,
         control_flags: SourceReaderControlFlag,
-    ) -> (SourceReaderStream, SourceReaderFlag, i64, Option<Sample>) {
+    ) -> Result<(SourceReaderStream, SourceReaderFlag, i64, Option<Sample>)> {
         let stream_index = stream_index.into();
         let mut actual_stream_index = 0;
         let mut stream_flags = 0;
         let mut timestamp = 0;
         let mut sample = ComPtr::<IMFSample>::default();
-        let hr = unsafe {
-            self.0.ReadSample(
-                stream_index.into(),
-                control_flags.into(),
-                &mut actual_stream_index,
-                &mut stream_flags,
-                &mut timestamp,
-                sample.getter_addrefs(),
-            )
-        };
-        if hr < 0 {
-            println!("hr = 0x{:x}", hr);
-        }
-        assert!(hr == 0);
+        hr!(self.0.ReadSample(
+            stream_index.into(),
+            control_flags.into(),
+            &mut actual_stream_index,
+            &mut stream_flags,
+            &mut timestamp,
+            sample.getter_addrefs(),
+        ), stream = ?stream_index)?;
         let sample = if sample.is_null() {
             None
         } else {
             Some(sample.into())
         };
-        (
+        Ok((
             actual_stream_index.into(),
             stream_flags.into(),
             timestamp,
             sample,
-        )
+        ))
     }
 
+    /// Requests a sample, delivered to the `SourceReaderCallback` the reader
+    /// was created with.
     pub fn read_sample_async(
         &self,
         stream_index: impl Into<SourceReaderStream>,
         control_flags: SourceReaderControlFlag,
-    ) {
+    ) -> Result<()> {
         let stream_index = stream_index.into();
-        let hr = unsafe {
-            self.0.ReadSample(
-                stream_index.into(),
-                control_flags.into(),
-                std::ptr::null_mut(),
-                std::ptr::null_mut(),
-                std::ptr::null_mut(),
-                std::ptr::null_mut(),
-            )
-        };
-        assert!(hr == 0);
+        hr!(self.0.ReadSample(
+            stream_index.into(),
+            control_flags.into(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        ), stream = ?stream_index)
     }
 
     /*
@@ -520,125 +546,119 @@ impl SourceReader {
 }
 
 impl MediaType {
-    pub fn major_type(&self) -> GUID {
+    pub fn major_type(&self) -> Result<Guid> {
         let mut major_type = GUID::default();
-        let hr = unsafe { self.0.GetMajorType(&mut major_type) };
-        assert!(hr == S_OK);
-        major_type
+        hr!(self.0.GetMajorType(&mut major_type))?;
+        Ok(major_type.into())
     }
 
-    pub fn is_compressed_format(&self) -> bool {
+    pub fn is_compressed_format(&self) -> Result<bool> {
         let mut compressed = 0;
-        let hr = unsafe { self.0.IsCompressedFormat(&mut compressed) };
-        assert!(hr == S_OK);
-        compressed != 0
+        hr!(self.0.IsCompressedFormat(&mut compressed))?;
+        Ok(compressed != 0)
     }
 }
 
 impl PartialEq for MediaType {
+    /// Types that can't be compared, which `IsEqual` reports as a failure,
+    /// are unequal.
     fn eq(&self, other: &Self) -> bool {
         let mut dummy = 0;
-        let hr = unsafe { self.0.IsEqual(other.as_ptr(), &mut dummy) };
-        assert!(hr == S_OK || hr == S_FALSE);
+        let hr = call!(self.0.IsEqual(other.as_ptr(), &mut dummy));
         hr == S_OK
     }
 }
 
 impl Sample {
-    pub fn buffer_count(&self) -> usize {
+    pub fn buffer_count(&self) -> Result<usize> {
         let mut count = 0;
-        let hr = unsafe { self.0.GetBufferCount(&mut count) };
-        assert!(hr == 0);
-        count as _
+        hr!(self.0.GetBufferCount(&mut count))?;
+        Ok(count as _)
     }
 
-    pub fn buffer(&self, index: usize) -> MediaBuffer {
-        debug_assert!(index < self.buffer_count());
+    /// Fails with `MF_E_INVALIDINDEX` if `index` is out of range.
+    pub fn buffer(&self, index: usize) -> Result<MediaBuffer> {
         let mut buffer = ComPtr::<IMFMediaBuffer>::default();
-        let hr = unsafe { self.0.GetBufferByIndex(index as _, buffer.getter_addrefs()) };
-        assert!(hr == 0);
-        buffer.into()
+        hr!(self.0.GetBufferByIndex(index as _, buffer.getter_addrefs()))?;
+        Ok(buffer.into())
     }
 
-    pub fn convert_to_contiguous_buffer(&self) -> MediaBuffer {
+    pub fn convert_to_contiguous_buffer(&self) -> Result<MediaBuffer> {
         let mut buffer = ComPtr::<IMFMediaBuffer>::default();
-        let hr = unsafe { self.0.ConvertToContiguousBuffer(buffer.getter_addrefs()) };
-        assert!(hr == 0);
-        buffer.into()
+        hr!(self.0.ConvertToContiguousBuffer(buffer.getter_addrefs()))?;
+        Ok(buffer.into())
     }
 }
 
 impl MediaBuffer {
-    fn _lock_read(&self) -> &[u8] {
+    fn _lock_read(&self) -> Result<&[u8]> {
         let mut length = 0;
         let mut buffer = std::ptr::null_mut();
-        let hr = unsafe { self.0.Lock(&mut buffer, std::ptr::null_mut(), &mut length) };
-        assert!(hr == 0);
-        unsafe { std::slice::from_raw_parts(buffer, length as _) }
+        hr!(self.0.Lock(&mut buffer, std::ptr::null_mut(), &mut length))?;
+        Ok(unsafe { std::slice::from_raw_parts(buffer, length as _) })
     }
-    fn _unlock(&self) {
-        let hr = unsafe { self.0.Unlock() };
-        assert!(hr == 0);
+    fn _unlock(&self) -> Result<()> {
+        hr!(self.0.Unlock())
     }
 
-    pub fn lock(&self, f: impl Fn(&[u8])) {
-        let buffer = self._lock_read();
+    pub fn lock(&self, f: impl Fn(&[u8])) -> Result<()> {
+        let buffer = self._lock_read()?;
         f(buffer);
-        self._unlock();
+        self._unlock()
     }
 
-    pub fn current_length(&self) -> usize {
+    pub fn current_length(&self) -> Result<usize> {
         let mut length = 0;
-        let hr = unsafe { self.0.GetCurrentLength(&mut length) };
-        assert!(hr == 0);
-        length as _
+        hr!(self.0.GetCurrentLength(&mut length))?;
+        Ok(length as _)
     }
-    pub fn max_length(&self) -> usize {
+    pub fn max_length(&self) -> Result<usize> {
         let mut length = 0;
-        let hr = unsafe { self.0.GetMaxLength(&mut length) };
-        assert!(hr == 0);
-        length as _
+        hr!(self.0.GetMaxLength(&mut length))?;
+        Ok(length as _)
     }
 }
 
 struct AttributeIter<'a> {
     attrs: &'a IMFAttributes,
     state: Option<(u32, u32)>,
+    locked: bool,
 }
 
 impl<'a> AttributeIter<'a> {
-    fn init_state(&mut self) -> bool {
+    fn init_state(&mut self) -> Result<u32> {
         assert!(self.state.is_none());
         // Lock the attributes so the call to the retrieve the item count is
         // correct. (As opposed to relying on getting the count when creating
         // the iterator)
-        let hr = unsafe { self.attrs.LockStore() };
-        if hr != S_OK {
-            return false;
-        }
+        hr!(self.attrs.LockStore())?;
+        self.locked = true;
         let mut total = 0;
-        let hr = unsafe { self.attrs.GetCount(&mut total) };
-        if hr != S_OK {
-            return false;
-        }
-        self.state = Some((0, total));
-        true
+        hr!(self.attrs.GetCount(&mut total))?;
+        Ok(total)
     }
 }
 
 impl<'a> Drop for AttributeIter<'a> {
     fn drop(&mut self) {
-        if self.state.is_some() {
-            unsafe { self.attrs.UnlockStore() };
+        if self.locked {
+            call!(self.attrs.UnlockStore());
         }
     }
 }
 
 impl<'a> Iterator for AttributeIter<'a> {
-    type Item = (GUID, AttributeValue);
+    type Item = Result<(Guid, AttributeValue)>;
+    /// A failure to lock the store is returned once, ending the iteration.
     fn next(&mut self) -> Option<Self::Item> {
-        if self.state.is_none() && !self.init_state() {
-            return None;
+        if self.state.is_none() {
+            match self.init_state() {
+                Ok(total) => self.state = Some((0, total)),
+                Err(error) => {
+                    self.state = Some((0, 0));
+                    return Some(Err(error));
+                }
+            }
         }
         match self.state {
             Some((ref mut index, total)) => {
@@ -647,10 +667,9 @@ impl<'a> Iterator for AttributeIter<'a> {
                 }
                 let mut key = GUID::default();
                 let mut value = PROPVARIANT::default();
-                let hr = unsafe { self.attrs.GetItemByIndex(*index, &mut key, &mut value) };
-                assert!(hr == S_OK);
+                let item = hr!(self.attrs.GetItemByIndex(*index, &mut key, &mut value));
                 *index += 1;
-                Some((key, value.to_value()))
+                Some(item.map(|()| (key.into(), value.to_value())))
             }
             _ => None,
         }
@@ -667,41 +686,40 @@ struct NativeMediaTypeIter<'a> {
 const MF_E_NO_MORE_TYPES: HRESULT = 0xc00d_36b9;
 
 impl<'a> Iterator for NativeMediaTypeIter<'a> {
-    type Item = MediaType;
+    type Item = Result<MediaType>;
     fn next(&mut self) -> Option<Self::Item> {
         let mut media_type = ComPtr::<IMFMediaType>::default();
-        let hr = unsafe {
+        let hr = call!(
             self.reader.0.GetNativeMediaType(
                 self.stream.into(),
                 self.index,
                 media_type.getter_addrefs(),
-            )
-        };
+            ),
+            stream = ?self.stream,
+            index = self.index
+        );
         self.index += 1;
         match hr {
-            S_OK => Some(media_type.into()),
             MF_E_NO_MORE_TYPES => None,
-            _ => panic!(),
+            hr => Some(Error::check(hr, "GetNativeMediaType").map(|()| media_type.into())),
         }
     }
 }
 
 impl BufferDxgi {
-    pub fn resource<T, I>(&self) -> T
+    pub fn resource<T, I>(&self) -> Result<T>
     where
         T: From<ComPtr<I>>,
         I: Interface,
     {
         let mut result = ComPtr::<I>::default();
-        let hr = unsafe { self.0.GetResource(&I::uuidof(), result.getter_addrefs()) };
-        assert!(hr == S_OK);
-        result.into()
+        hr!(self.0.GetResource(&I::uuidof(), result.getter_addrefs()))?;
+        Ok(result.into())
     }
 
-    pub fn subresource_index(&self) -> u32 {
+    pub fn subresource_index(&self) -> Result<u32> {
         let mut subresource = 0;
-        let hr = unsafe { self.0.GetSubresourceIndex(&mut subresource) };
-        assert!(hr == S_OK);
-        subresource
+        hr!(self.0.GetSubresourceIndex(&mut subresource))?;
+        Ok(subresource)
     }
 }
@@ -0,0 +1,608 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! COM objects implemented in Rust, for testing `ComPtr` and the wrappers on
+//! any platform.
+//!
+//! A mock object has a real vtable for each interface it implements, each
+//! starting with `IUnknown`'s methods, and follows the COM rules: every
+//! interface pointer answers `QueryInterface` for `IUnknown` with the same
+//! identity pointer, and the object is destroyed when the last reference is
+//! released. Each call is logged. `Mock` is the test's handle for inspecting
+//! the object; it keeps the memory alive after destruction, so a call made
+//! through a dangling pointer is reported rather than crashing.
+//!
+//! `IFoo`, `IBar` (derived from `IFoo`) and `IBaz` are interfaces for mocks
+//! to implement. Their methods read and write a single value.
+
+use crate::{
+    sys::{IUnknown, IUnknownVtbl, Interface, E_NOINTERFACE, GUID, HRESULT, S_OK, ULONG},
+    ComPtr, Guid,
+};
+use std::{
+    cell::{Cell, RefCell},
+    ffi::c_void,
+    ops::Deref,
+    ptr,
+    rc::Rc,
+};
+
+/// A call made on a mock object.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Call {
+    /// `AddRef`, with the count it returned.
+    AddRef(u32),
+    /// `Release`, with the count it returned.
+    Release(u32),
+    /// `QueryInterface` for the named interface, and whether it succeeded.
+    QueryInterface(&'static str, bool),
+    Method(&'static str),
+}
+
+/// An interface that mock objects can implement.
+pub trait MockInterface: Interface {
+    /// The IIDs one vtable answers: the interface's own, then its bases
+    /// other than `IUnknown`.
+    fn iids() -> Vec<GUID>;
+
+    /// The vtable entries after `IUnknown`'s, in order.
+    fn methods() -> Vec<*const c_void>;
+}
+
+/// Builds a mock object implementing the chosen interfaces.
+#[derive(Default)]
+pub struct MockBuilder {
+    vtables: Vec<Vtable>,
+    value: u32,
+}
+
+impl MockBuilder {
+    /// Adds a vtable for `I` and its bases. The first vtable is the one
+    /// that answers for `IUnknown`.
+    pub fn implements<I: MockInterface>(mut self) -> Self {
+        let unknown = [
+            query_interface as *const c_void,
+            add_ref as *const c_void,
+            release as *const c_void,
+        ];
+        self.vtables.push(Vtable {
+            iids: I::iids(),
+            entries: unknown.iter().copied().chain(I::methods()).collect(),
+        });
+        self
+    }
+
+    /// The initial value the methods read and write.
+    pub fn value(mut self, value: u32) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// Creates the object, returning its only reference, as `I`, and the
+    /// handle for inspecting it. The call log starts empty.
+    ///
+    /// Panics if the object doesn't implement `I`.
+    pub fn build<I: Interface>(self) -> (ComPtr<I>, Mock) {
+        assert!(
+            !self.vtables.is_empty(),
+            "a mock must implement an interface"
+        );
+        let object = Rc::new(Object {
+            state: State {
+                refs: Cell::new(1),
+                value: Cell::new(self.value),
+                ..Default::default()
+            },
+            vtables: self.vtables,
+            slots: RefCell::new(Vec::new()),
+            alive: RefCell::new(None),
+        });
+        let slots = object
+            .vtables
+            .iter()
+            .map(|vtable| Slot {
+                vtbl: vtable.entries.as_ptr(),
+                object: &*object,
+            })
+            .collect();
+        *object.slots.borrow_mut() = slots;
+        *object.alive.borrow_mut() = Some(object.clone());
+
+        let unknown = unsafe { ComPtr::already_addrefed(object.slot(0) as *mut IUnknown) };
+        let interface = unknown
+            .query_interface::<I>()
+            .expect("mock doesn't implement the requested interface");
+        drop(unknown);
+        object.state.calls.borrow_mut().clear();
+        (interface, Mock(object))
+    }
+}
+
+/// Handle for inspecting a mock object.
+pub struct Mock(Rc<Object>);
+
+impl Mock {
+    pub fn builder() -> MockBuilder {
+        MockBuilder::default()
+    }
+
+    pub fn ref_count(&self) -> u32 {
+        self.0.state.refs.get()
+    }
+
+    /// Whether the last reference was released.
+    pub fn is_destroyed(&self) -> bool {
+        self.0.state.destroyed.get()
+    }
+
+    /// Whether a call was made after the object was destroyed.
+    pub fn used_after_destroy(&self) -> bool {
+        self.0.state.used_after_destroy.get()
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.0.state.calls.borrow().clone()
+    }
+
+    pub fn value(&self) -> u32 {
+        self.0.state.value.get()
+    }
+
+    /// The object's identity, the pointer `QueryInterface` returns for
+    /// `IUnknown`.
+    pub fn identity(&self) -> *mut IUnknown {
+        self.0.slot(0) as *mut IUnknown
+    }
+}
+
+#[derive(Default)]
+struct State {
+    refs: Cell<u32>,
+    destroyed: Cell<bool>,
+    used_after_destroy: Cell<bool>,
+    value: Cell<u32>,
+    calls: RefCell<Vec<Call>>,
+}
+
+struct Vtable {
+    iids: Vec<GUID>,
+    entries: Box<[*const c_void]>,
+}
+
+/// An interface pointer points here: the vtable pointer that callers see,
+/// followed by the object it belongs to.
+#[repr(C)]
+struct Slot {
+    vtbl: *const *const c_void,
+    object: *const Object,
+}
+
+struct Object {
+    state: State,
+    vtables: Vec<Vtable>,
+    /// One per vtable. Filled once, so the addresses don't move.
+    slots: RefCell<Vec<Slot>>,
+    /// Keeps the object alive while it has references, even if the `Mock`
+    /// is dropped.
+    alive: RefCell<Option<Rc<Object>>>,
+}
+
+impl Object {
+    fn slot(&self, index: usize) -> *const Slot {
+        &self.slots.borrow()[index]
+    }
+
+    /// Logs `call`, noting whether the object was already destroyed.
+    fn record(&self, call: Call) {
+        if self.state.destroyed.get() {
+            self.state.used_after_destroy.set(true);
+        }
+        self.state.calls.borrow_mut().push(call);
+    }
+}
+
+/// The object behind an interface pointer.
+unsafe fn object<'a, T>(this: *mut T) -> &'a Object {
+    &*(*(this as *const Slot)).object
+}
+
+fn interface_name(iid: Guid) -> &'static str {
+    let known = [
+        (IUnknown::uuidof(), "IUnknown"),
+        (IFoo::uuidof(), "IFoo"),
+        (IBar::uuidof(), "IBar"),
+        (IBaz::uuidof(), "IBaz"),
+    ];
+    known
+        .iter()
+        .find(|(known, _)| iid == known.into())
+        .map_or("unknown", |&(_, name)| name)
+}
+
+unsafe extern "system" fn query_interface(
+    this: *mut IUnknown,
+    riid: *const GUID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    let object = object(this);
+    let iid = Guid::from(&*riid);
+    let index = if iid == IUnknown::uuidof().into() {
+        Some(0)
+    } else {
+        object
+            .vtables
+            .iter()
+            .position(|vtable| vtable.iids.iter().any(|known| iid == known.into()))
+    };
+    object.record(Call::QueryInterface(interface_name(iid), index.is_some()));
+    match index {
+        Some(index) => {
+            let slot = object.slot(index) as *mut IUnknown;
+            add_ref(slot);
+            *ppv = slot as *mut c_void;
+            S_OK
+        }
+        None => {
+            *ppv = ptr::null_mut();
+            E_NOINTERFACE
+        }
+    }
+}
+
+unsafe extern "system" fn add_ref(this: *mut IUnknown) -> ULONG {
+    let object = object(this);
+    let refs = object.state.refs.get() + 1;
+    object.state.refs.set(refs);
+    object.record(Call::AddRef(refs));
+    refs
+}
+
+unsafe extern "system" fn release(this: *mut IUnknown) -> ULONG {
+    let object = object(this);
+    let refs = object.state.refs.get().saturating_sub(1);
+    object.record(Call::Release(refs));
+    object.state.refs.set(refs);
+    if refs == 0 && !object.state.destroyed.get() {
+        object.state.destroyed.set(true);
+        // May free the object if the `Mock` is gone, so it's done last.
+        let alive = object.alive.borrow_mut().take();
+        drop(alive);
+    }
+    refs
+}
+
+macro_rules! mock_interface {
+    (
+        $name:ident($vtbl:ident): $parent:ident($parent_vtbl:ident) $iid:expr,
+        { $(fn $method:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)* }
+    ) => {
+        #[allow(non_snake_case)]
+        #[repr(C)]
+        pub struct $name {
+            pub lpVtbl: *const $vtbl,
+        }
+
+        #[allow(non_snake_case)]
+        #[repr(C)]
+        pub struct $vtbl {
+            pub parent: $parent_vtbl,
+            $(pub $method: unsafe extern "system" fn(This: *mut $name $(, $arg: $ty)*) -> $ret,)*
+        }
+
+        #[allow(non_snake_case)]
+        impl $name {
+            $(pub unsafe fn $method(&self $(, $arg: $ty)*) -> $ret {
+                ((*self.lpVtbl).$method)(self as *const _ as *mut _ $(, $arg)*)
+            })*
+        }
+
+        impl Deref for $name {
+            type Target = $parent;
+            fn deref(&self) -> &$parent {
+                unsafe { &*(self as *const _ as *const $parent) }
+            }
+        }
+
+        impl Interface for $name {
+            fn uuidof() -> GUID {
+                $iid
+            }
+        }
+    };
+}
+
+fn iid(data1: u32) -> GUID {
+    GUID {
+        Data1: data1,
+        Data2: 0x6e6f,
+        Data3: 0x6f64,
+        Data4: *b"le-mock!",
+    }
+}
+
+mock_interface! {
+    IFoo(IFooVtbl): IUnknown(IUnknownVtbl) iid(0xf00),
+    { fn Value() -> u32; }
+}
+
+mock_interface! {
+    IBar(IBarVtbl): IFoo(IFooVtbl) iid(0xba4),
+    { fn SetValue(value: u32) -> HRESULT; }
+}
+
+mock_interface! {
+    IBaz(IBazVtbl): IUnknown(IUnknownVtbl) iid(0xba2),
+    { fn Double() -> u32; }
+}
+
+unsafe extern "system" fn foo_value(this: *mut IFoo) -> u32 {
+    let object = object(this);
+    object.record(Call::Method("Value"));
+    object.state.value.get()
+}
+
+unsafe extern "system" fn bar_set_value(this: *mut IBar, value: u32) -> HRESULT {
+    let object = object(this);
+    object.record(Call::Method("SetValue"));
+    object.state.value.set(value);
+    S_OK
+}
+
+unsafe extern "system" fn baz_double(this: *mut IBaz) -> u32 {
+    let object = object(this);
+    object.record(Call::Method("Double"));
+    object.state.value.get() * 2
+}
+
+impl MockInterface for IFoo {
+    fn iids() -> Vec<GUID> {
+        vec![IFoo::uuidof()]
+    }
+
+    fn methods() -> Vec<*const c_void> {
+        vec![foo_value as *const c_void]
+    }
+}
+
+impl MockInterface for IBar {
+    fn iids() -> Vec<GUID> {
+        vec![IBar::uuidof(), IFoo::uuidof()]
+    }
+
+    fn methods() -> Vec<*const c_void> {
+        vec![foo_value as *const c_void, bar_set_value as *const c_void]
+    }
+}
+
+impl MockInterface for IBaz {
+    fn iids() -> Vec<GUID> {
+        vec![IBaz::uuidof()]
+    }
+
+    fn methods() -> Vec<*const c_void> {
+        vec![baz_double as *const c_void]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{impl_comptr, AsPtr};
+
+    impl_comptr! { Foo: [IFoo, IUnknown] }
+    impl_comptr! { Bar: [IBar, IFoo, IUnknown] }
+    impl_comptr! { Baz: [IBaz, IUnknown] }
+    impl_comptr! { AgileBar: [IBar, IFoo, IUnknown] in Agile }
+
+    /// Whether `$wrapper` upcasts to `$interface`, and with `&` whether it
+    /// lends one with `AsRef`, decided at compile time: the inherent constant
+    /// exists only when the impls do, and otherwise the trait's fallback is
+    /// used.
+    macro_rules! upcasts {
+        ($wrapper:ty => &$interface:ty) => {{
+            #[allow(dead_code)]
+            trait Fallback {
+                const UPCASTS: bool = false;
+            }
+            impl<T> Fallback for T {}
+            struct Check<W, I>(std::marker::PhantomData<(W, I)>);
+            #[allow(dead_code)]
+            impl<W, I> Check<W, I>
+            where
+                W: AsRef<ComPtr<I>>,
+                I: Interface,
+            {
+                const UPCASTS: bool = true;
+            }
+            <Check<$wrapper, $interface>>::UPCASTS
+        }};
+        ($wrapper:ty => $interface:ty) => {{
+            #[allow(dead_code)]
+            trait Fallback {
+                const UPCASTS: bool = false;
+            }
+            impl<T> Fallback for T {}
+            struct Check<W, I>(std::marker::PhantomData<(W, I)>);
+            #[allow(dead_code)]
+            impl<W, I> Check<W, I>
+            where
+                ComPtr<I>: From<W>,
+                I: Interface,
+            {
+                const UPCASTS: bool = true;
+            }
+            <Check<$wrapper, $interface>>::UPCASTS
+        }};
+    }
+
+    #[test]
+    fn methods() {
+        let (bar, mock) = Mock::builder()
+            .implements::<IBar>()
+            .value(3)
+            .build::<IBar>();
+        unsafe {
+            assert_eq!(bar.Value(), 3);
+            assert_eq!(bar.SetValue(4), S_OK);
+            assert_eq!(bar.Value(), 4);
+        }
+        assert_eq!(mock.value(), 4);
+        assert_eq!(
+            mock.calls(),
+            [
+                Call::Method("Value"),
+                Call::Method("SetValue"),
+                Call::Method("Value")
+            ]
+        );
+    }
+
+    #[test]
+    fn query_interface() {
+        let (foo, mock) = Mock::builder()
+            .implements::<IBar>()
+            .implements::<IBaz>()
+            .value(5)
+            .build::<IFoo>();
+        assert_eq!(mock.ref_count(), 1);
+
+        // IBar's vtable answers for IFoo and IUnknown, so they share a pointer.
+        let bar = foo.query_interface::<IBar>().unwrap();
+        let unknown = foo.query_interface::<IUnknown>().unwrap();
+        assert_eq!(bar.as_ptr() as usize, foo.as_ptr() as usize);
+        assert_eq!(unknown.as_ptr(), mock.identity());
+
+        // IBaz has its own vtable, but the same identity.
+        let baz = bar.query_interface::<IBaz>().unwrap();
+        assert_ne!(baz.as_ptr() as usize, foo.as_ptr() as usize);
+        let identity = baz.query_interface::<IUnknown>().unwrap();
+        assert_eq!(identity.as_ptr(), mock.identity());
+        assert_eq!(unsafe { baz.Double() }, 10);
+        assert_eq!(mock.ref_count(), 5);
+
+        let (other, other_mock) = Mock::builder().implements::<IFoo>().build::<IFoo>();
+        assert!(other.query_interface::<IBaz>().is_none());
+        assert_eq!(other_mock.calls(), [Call::QueryInterface("IBaz", false)]);
+
+        drop((bar, unknown, baz, identity));
+        assert_eq!(mock.ref_count(), 1);
+    }
+
+    #[test]
+    fn destruction() {
+        let (foo, mock) = Mock::builder().implements::<IFoo>().build::<IFoo>();
+        let raw = foo.as_ptr();
+        drop(foo);
+        assert!(mock.is_destroyed());
+        assert!(!mock.used_after_destroy());
+        assert_eq!(mock.calls(), [Call::Release(0)]);
+
+        // The memory outlives the object, so a dangling call is caught.
+        unsafe {
+            (*raw).Value();
+        }
+        assert!(mock.used_after_destroy());
+
+        // Without the handle, the object is freed on its last release.
+        let (foo, mock) = Mock::builder().implements::<IFoo>().build::<IFoo>();
+        let weak = Rc::downgrade(&mock.0);
+        drop(mock);
+        assert!(weak.upgrade().is_some());
+        drop(foo);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn wrappers() {
+        let (bar, mock) = Mock::builder()
+            .implements::<IBar>()
+            .implements::<IBaz>()
+            .build::<IBar>();
+        let bar = Bar::from(bar);
+        let foo: Foo = bar.as_().unwrap();
+        let baz: Baz = foo.as_().unwrap();
+        assert_eq!(
+            AsPtr::<IFoo>::as_ptr(&bar) as usize,
+            foo.0.as_ptr() as usize
+        );
+        assert_eq!(AsPtr::<IUnknown>::as_ptr(&bar), mock.identity());
+        assert_eq!(mock.ref_count(), 3);
+
+        let bar_again: Option<Bar> = baz.as_();
+        assert!(bar_again.is_some());
+        drop((bar, foo, baz, bar_again));
+        assert!(mock.is_destroyed());
+    }
+
+    #[test]
+    fn upcasts() {
+        const DECLARED: [bool; 6] = [
+            upcasts!(Bar => IBar),
+            upcasts!(Bar => IFoo),
+            upcasts!(Bar => IUnknown),
+            upcasts!(Foo => IUnknown),
+            upcasts!(Baz => IBaz),
+            upcasts!(Baz => IUnknown),
+        ];
+        assert_eq!(DECLARED, [true; 6]);
+        const UNDECLARED: [bool; 3] = [
+            upcasts!(Foo => IBar),
+            upcasts!(Baz => IFoo),
+            upcasts!(Foo => IBaz),
+        ];
+        assert_eq!(UNDECLARED, [false; 3]);
+        // Only agile wrappers lend a `ComPtr`, which has no affinity check.
+        const LENT: [bool; 3] = [
+            upcasts!(AgileBar => &IFoo),
+            upcasts!(AgileBar => &IUnknown),
+            upcasts!(Bar => &IFoo),
+        ];
+        assert_eq!(LENT, [true, true, false]);
+
+        let (agile, mock) = Mock::builder()
+            .implements::<IBar>()
+            .value(6)
+            .build::<IBar>();
+        let agile = AgileBar::from(agile);
+        let foo: &ComPtr<IFoo> = agile.as_ref();
+        assert_eq!(unsafe { foo.Value() }, 6);
+        let unknown: &ComPtr<IUnknown> = agile.as_ref();
+        assert_eq!(unknown.as_ptr(), mock.identity());
+        assert_eq!(mock.calls(), [Call::Method("Value")]);
+
+        let (bar, mock) = Mock::builder().implements::<IBar>().build::<IBar>();
+        let bar = Bar::from(bar);
+
+        // Upcasts neither query nor take a reference.
+        let foo: Foo = bar.clone().upcast();
+        let unknown = ComPtr::<IUnknown>::from(bar);
+        assert_eq!(AsPtr::<IFoo>::as_ptr(&foo), foo.0.as_ptr());
+        assert_eq!(mock.ref_count(), 2);
+        assert_eq!(mock.calls(), [Call::AddRef(2)]);
+        drop((foo, unknown));
+        assert!(mock.is_destroyed());
+    }
+
+    #[test]
+    fn downcasts() {
+        let (foo, mock) = Mock::builder().implements::<IBar>().build::<IFoo>();
+        let foo = Foo::from(foo);
+        let bar: Bar = foo.downcast().unwrap();
+        assert_eq!(bar.0.as_ptr() as usize, foo.0.as_ptr() as usize);
+
+        let error = foo.downcast::<Baz, _>().unwrap_err();
+        assert_eq!(error.hresult(), E_NOINTERFACE.into());
+        assert!(Foo::default().downcast::<Bar, _>().is_err());
+        assert_eq!(
+            mock.calls(),
+            [
+                Call::QueryInterface("IBar", true),
+                Call::AddRef(2),
+                Call::QueryInterface("IBaz", false),
+            ]
+        );
+        drop((foo, bar));
+        assert!(mock.is_destroyed());
+    }
+}
@@ -1,7 +1,6 @@
-use crate::{Offset, Size};
+use crate::{sys::RECT, Offset, Size};
 use derive_newtype::NewType;
 use std::ops::{Add, BitAnd, BitOr, Div, Mul, Sub};
-use winapi::shared::windef::RECT;
 
 #[derive(Clone, Copy, Default, NewType)]
 #[repr(transparent)]
@@ -229,8 +228,8 @@ fn subtract(lhs: Rect, rhs: Rect) -> Vec<Rect> {
             /* B */ rect!(lhs.left, i.bottom, lhs.right, lhs.bottom),
         ],
         0b1011 => vec![
-            /* L */ rect!(lhs.left, i.left, lhs.top, i.bottom),
-            /* R */ rect!(i.right, lhs.right, lhs.top, i.bottom),
+            /* L */ rect!(lhs.left, lhs.top, i.left, i.bottom),
+            /* R */ rect!(i.right, lhs.top, lhs.right, i.bottom),
             /* B */ rect!(lhs.left, i.bottom, lhs.right, lhs.bottom),
         ],
         0b0111 => vec![
@@ -339,6 +338,17 @@ mod tests {
         assert_eq!(v[0], rect!(0, -1, 2, 0));
     }
 
+    #[test]
+    fn notch() {
+        let d = rect!(1, 0, 2, 2);
+        let r = rect!(0, 0, 3, 3);
+        let v = r - d;
+        assert_eq!(v.len(), 3);
+        assert_eq!(v[0], rect!(0, 0, 1, 2));
+        assert_eq!(v[1], rect!(2, 0, 3, 2));
+        assert_eq!(v[2], rect!(0, 2, 3, 3));
+    }
+
     #[test]
     fn middle() {
         let d = rect!(1, 0, 2, 2);
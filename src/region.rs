@@ -0,0 +1,154 @@
+use crate::{Offset, Rect};
+
+/// A set of pixels, stored as a list of non-overlapping rectangles.
+#[derive(Clone, Debug, Default)]
+pub struct Region {
+    rects: Vec<Rect>,
+}
+
+impl From<Rect> for Region {
+    fn from(rect: Rect) -> Self {
+        let mut region = Region::new();
+        region.union_rect(rect);
+        region
+    }
+}
+
+impl Region {
+    pub fn new() -> Self {
+        Self { rects: Vec::new() }
+    }
+
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    /// Number of pixels covered by the region.
+    pub fn area(&self) -> i64 {
+        self.rects
+            .iter()
+            .map(|r| i64::from(r.width()) * i64::from(r.height()))
+            .sum()
+    }
+
+    /// Smallest rectangle containing the whole region.
+    pub fn bounds(&self) -> Rect {
+        let mut rects = self.rects.iter();
+        match rects.next() {
+            Some(first) => rects.fold(*first, |acc, r| acc | *r),
+            None => Rect::default(),
+        }
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.rects
+            .iter()
+            .any(|r| x >= r.left && x < r.right && y >= r.top && y < r.bottom)
+    }
+
+    pub fn intersects(&self, rect: &Rect) -> bool {
+        self.rects.iter().any(|r| !(*r & *rect).is_empty())
+    }
+
+    pub fn union_rect(&mut self, rect: Rect) {
+        if rect.is_empty() {
+            return;
+        }
+        let mut pieces = vec![rect];
+        for existing in &self.rects {
+            pieces = pieces.into_iter().flat_map(|p| p - *existing).collect();
+            if pieces.is_empty() {
+                return;
+            }
+        }
+        self.rects.extend(pieces);
+    }
+
+    pub fn union(&mut self, other: &Region) {
+        for rect in &other.rects {
+            self.union_rect(*rect);
+        }
+    }
+
+    pub fn subtract_rect(&mut self, rect: Rect) {
+        if rect.is_empty() {
+            return;
+        }
+        self.rects = self.rects.iter().flat_map(|r| *r - rect).collect();
+    }
+
+    pub fn subtract(&mut self, other: &Region) {
+        for rect in &other.rects {
+            self.subtract_rect(*rect);
+        }
+    }
+
+    pub fn intersect_rect(&mut self, rect: Rect) {
+        self.rects = self
+            .rects
+            .iter()
+            .map(|r| *r & rect)
+            .filter(|r| !r.is_empty())
+            .collect();
+    }
+
+    pub fn intersect(&mut self, other: &Region) {
+        let mut rects = Vec::new();
+        for a in &self.rects {
+            for b in &other.rects {
+                let i = *a & *b;
+                if !i.is_empty() {
+                    rects.push(i);
+                }
+            }
+        }
+        self.rects = rects;
+    }
+
+    pub fn translate(&mut self, offset: Offset) {
+        for rect in &mut self.rects {
+            *rect = *rect + offset;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Region;
+    use crate::{offset, rect};
+
+    #[test]
+    fn union_and_subtract() {
+        let mut region = Region::from(rect!(0, 0, 10, 10));
+        region.union_rect(rect!(5, 5, 15, 15));
+        assert_eq!(region.area(), 175);
+        assert_eq!(region.bounds(), rect!(0, 0, 15, 15));
+        assert!(region.contains(12, 12));
+        assert!(!region.contains(12, 2));
+
+        region.subtract_rect(rect!(0, 0, 15, 5));
+        assert_eq!(region.area(), 125);
+        assert!(!region.contains(2, 2));
+
+        region.intersect_rect(rect!(0, 0, 10, 10));
+        assert_eq!(region.area(), 50);
+
+        region.translate(offset!(100, 0));
+        assert_eq!(region.bounds(), rect!(100, 5, 110, 10));
+    }
+
+    #[test]
+    fn intersect_regions() {
+        let mut a = Region::from(rect!(0, 0, 4, 4));
+        a.union_rect(rect!(8, 0, 12, 4));
+        let b = Region::from(rect!(2, 2, 10, 10));
+        a.intersect(&b);
+        assert_eq!(a.area(), 8);
+        assert!(a.contains(3, 3) && a.contains(8, 3));
+        assert!(!a.contains(5, 3));
+    }
+}
@@ -0,0 +1,496 @@
+//! Frame loop driver.
+//!
+//! `FrameScheduler` replaces the hand-written
+//! `loop { draw; commit(); wait_for_commit_completion() }` pattern. It decides
+//! when a frame should run, commits it to a `Commit` device and keeps timing
+//! statistics. Time comes from a `Clock`, so tests can use a `ManualClock` and
+//! step through frames deterministically.
+
+use crate::{debug, trace_span, Result};
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Source of time for a `FrameScheduler`.
+pub trait Clock {
+    /// Time elapsed since an arbitrary, fixed origin.
+    fn now(&self) -> Duration;
+
+    /// Block until `now()` reaches `deadline`. Returns immediately if it
+    /// already has.
+    fn sleep_until(&self, deadline: Duration);
+}
+
+/// Wall clock backed by `std::time::Instant`.
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+
+    fn sleep_until(&self, deadline: Duration) {
+        let now = self.now();
+        if deadline > now {
+            thread::sleep(deadline - now);
+        }
+    }
+}
+
+/// Clock that only moves when told to. Clones share the same time, so a test
+/// can keep one handle and give another to the scheduler.
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock(Rc<Cell<Duration>>);
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.0.set(self.0.get() + by);
+    }
+
+    pub fn set(&self, now: Duration) {
+        self.0.set(now);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.0.get()
+    }
+
+    /// Jumps straight to `deadline` instead of blocking.
+    fn sleep_until(&self, deadline: Duration) {
+        if deadline > self.0.get() {
+            self.0.set(deadline);
+        }
+    }
+}
+
+/// Something a finished frame can be committed to, e.g. a `dcomp::Device`.
+pub trait Commit {
+    fn commit(&self) -> Result<()>;
+
+    /// Block until the last commit has been processed. For DirectComposition
+    /// this paces the loop to the display refresh.
+    fn wait_for_commit_completion(&self) -> Result<()>;
+}
+
+/// When frames run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Run a frame every time, then wait for the commit to complete.
+    VSync,
+    /// Only run a frame after `request_frame`. The caller is expected to
+    /// block on input between frames.
+    OnDemand,
+    /// Run frames at a fixed interval, sleeping on the clock in between.
+    /// Frames that fall behind are dropped rather than queued up. The
+    /// interval can't be zero.
+    FixedRate(Duration),
+}
+
+/// Passed to the draw callback of `FrameScheduler::frame`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameInfo {
+    /// Zero for the first frame.
+    pub index: u64,
+    /// Clock time at which the frame started.
+    pub time: Duration,
+    /// Time since the previous frame started, zero for the first frame.
+    pub delta: Duration,
+}
+
+/// Number of frames kept by `FrameStats`.
+pub const STATS_WINDOW: usize = 120;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Sample {
+    frame_time: Duration,
+    commit_latency: Duration,
+}
+
+/// Timing of the most recent `STATS_WINDOW` frames.
+#[derive(Clone, Debug, Default)]
+pub struct FrameStats {
+    frame_count: u64,
+    dropped_frames: u64,
+    samples: VecDeque<Sample>,
+}
+
+impl FrameStats {
+    /// Total number of frames run.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Number of `FixedRate` deadlines skipped because a frame overran.
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames
+    }
+
+    /// Intervals between consecutive frame starts, oldest first.
+    pub fn frame_times(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.samples.iter().map(|s| s.frame_time)
+    }
+
+    /// Time spent in `commit` and `wait_for_commit_completion`, oldest first.
+    pub fn commit_latencies(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.samples.iter().map(|s| s.commit_latency)
+    }
+
+    pub fn last_frame_time(&self) -> Option<Duration> {
+        self.samples.back().map(|s| s.frame_time)
+    }
+
+    pub fn min_frame_time(&self) -> Option<Duration> {
+        self.frame_times().min()
+    }
+
+    pub fn max_frame_time(&self) -> Option<Duration> {
+        self.frame_times().max()
+    }
+
+    pub fn average_frame_time(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let total: Duration = self.frame_times().sum();
+        Some(total / self.samples.len() as u32)
+    }
+
+    /// Frames per second derived from `average_frame_time`.
+    pub fn fps(&self) -> Option<f64> {
+        self.average_frame_time()
+            .filter(|t| *t > Duration::from_secs(0))
+            .map(|t| 1.0 / t.as_secs_f64())
+    }
+
+    fn push(&mut self, sample: Sample) {
+        if self.samples.len() == STATS_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+}
+
+pub struct FrameScheduler<C = SystemClock> {
+    clock: C,
+    mode: Mode,
+    frame_requested: bool,
+    last_frame_start: Option<Duration>,
+    next_deadline: Option<Duration>,
+    stats: FrameStats,
+}
+
+impl FrameScheduler<SystemClock> {
+    pub fn new(mode: Mode) -> Self {
+        Self::with_clock(mode, SystemClock::new())
+    }
+}
+
+impl<C: Clock> FrameScheduler<C> {
+    /// Panics if `mode` is `FixedRate` with a zero interval.
+    pub fn with_clock(mode: Mode, clock: C) -> Self {
+        check_mode(mode);
+        Self {
+            clock,
+            mode,
+            // Always draw the first frame.
+            frame_requested: true,
+            last_frame_start: None,
+            next_deadline: None,
+            stats: FrameStats::default(),
+        }
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Panics if `mode` is `FixedRate` with a zero interval.
+    pub fn set_mode(&mut self, mode: Mode) {
+        check_mode(mode);
+        self.mode = mode;
+        self.next_deadline = None;
+    }
+
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    /// Ask for a frame in `Mode::OnDemand`. Has no effect in other modes,
+    /// which run frames unconditionally.
+    pub fn request_frame(&mut self) {
+        self.frame_requested = true;
+    }
+
+    pub fn needs_frame(&self) -> bool {
+        match self.mode {
+            Mode::OnDemand => self.frame_requested,
+            Mode::VSync | Mode::FixedRate(_) => true,
+        }
+    }
+
+    /// Run one iteration of the frame loop: wait for the next frame if the
+    /// mode asks for it, call `draw`, then commit to `device`.
+    ///
+    /// Returns `false` without drawing when `Mode::OnDemand` has no pending
+    /// request. Errors from `draw` or the device abort the frame before it
+    /// is counted in the stats, and leave an on-demand request pending.
+    pub fn frame<D, F>(&mut self, device: &D, draw: F) -> Result<bool>
+    where
+        D: Commit + ?Sized,
+        F: FnOnce(&FrameInfo) -> Result<()>,
+    {
+        if !self.needs_frame() {
+            return Ok(false);
+        }
+
+        let _span = trace_span!("frame", index = self.stats.frame_count);
+
+        if let Mode::FixedRate(interval) = self.mode {
+            let now = self.clock.now();
+            let mut deadline = self.next_deadline.unwrap_or(now);
+            if deadline < now {
+                // Skip every deadline that has already passed.
+                let behind = (now - deadline).as_nanos();
+                let interval_ns = interval.as_nanos();
+                let skipped = (behind + interval_ns - 1) / interval_ns;
+                deadline += Duration::from_nanos((skipped * interval_ns) as u64);
+                self.stats.dropped_frames += skipped as u64;
+            }
+            self.clock.sleep_until(deadline);
+            self.next_deadline = Some(deadline + interval);
+        }
+
+        let start = self.clock.now();
+        let delta = self
+            .last_frame_start
+            .map(|last| start - last)
+            .unwrap_or_default();
+        let info = FrameInfo {
+            index: self.stats.frame_count,
+            time: start,
+            delta,
+        };
+        self.frame_requested = false;
+
+        let result = draw(&info).and_then(|()| {
+            let commit_start = self.clock.now();
+            device.commit()?;
+            if self.mode == Mode::VSync {
+                device.wait_for_commit_completion()?;
+            }
+            Ok(self.clock.now() - commit_start)
+        });
+        debug::end_frame();
+        let commit_latency = match result {
+            Ok(latency) => latency,
+            Err(err) => {
+                // Keep the request so an on-demand frame is retried.
+                self.frame_requested = true;
+                return Err(err);
+            }
+        };
+
+        if self.last_frame_start.is_some() {
+            self.stats.push(Sample {
+                frame_time: delta,
+                commit_latency,
+            });
+        }
+        self.stats.frame_count += 1;
+        self.last_frame_start = Some(start);
+        Ok(true)
+    }
+}
+
+fn check_mode(mode: Mode) {
+    if let Mode::FixedRate(interval) = mode {
+        assert!(
+            interval > Duration::from_secs(0),
+            "FixedRate needs a non-zero interval"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, Commit, FrameScheduler, ManualClock, Mode};
+    use crate::{sys::E_UNEXPECTED, Error, Result};
+    use std::{cell::Cell, time::Duration};
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// Device whose commit completes 16ms after it is waited on.
+    struct FakeDevice {
+        clock: ManualClock,
+        commits: Cell<u32>,
+        waits: Cell<u32>,
+    }
+
+    impl FakeDevice {
+        fn new(clock: &ManualClock) -> Self {
+            Self {
+                clock: clock.clone(),
+                commits: Cell::new(0),
+                waits: Cell::new(0),
+            }
+        }
+    }
+
+    impl Commit for FakeDevice {
+        fn commit(&self) -> Result<()> {
+            self.commits.set(self.commits.get() + 1);
+            Ok(())
+        }
+
+        fn wait_for_commit_completion(&self) -> Result<()> {
+            self.waits.set(self.waits.get() + 1);
+            self.clock.advance(ms(16));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn vsync() {
+        let clock = ManualClock::new();
+        let device = FakeDevice::new(&clock);
+        let mut scheduler = FrameScheduler::with_clock(Mode::VSync, clock.clone());
+
+        let mut times = Vec::new();
+        for _ in 0..4 {
+            let drawn = scheduler.frame(&device, |info| {
+                times.push((info.index, info.time));
+                Ok(())
+            });
+            assert_eq!(drawn, Ok(true));
+        }
+        assert_eq!(
+            times,
+            vec![(0, ms(0)), (1, ms(16)), (2, ms(32)), (3, ms(48))]
+        );
+        assert_eq!(device.commits.get(), 4);
+        assert_eq!(device.waits.get(), 4);
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.frame_count(), 4);
+        assert_eq!(stats.frame_times().count(), 3);
+        assert_eq!(stats.average_frame_time(), Some(ms(16)));
+        assert_eq!(stats.last_frame_time(), Some(ms(16)));
+        assert!(stats.commit_latencies().all(|t| t == ms(16)));
+    }
+
+    #[test]
+    fn on_demand() {
+        let clock = ManualClock::new();
+        let device = FakeDevice::new(&clock);
+        let mut scheduler = FrameScheduler::with_clock(Mode::OnDemand, clock.clone());
+
+        assert_eq!(scheduler.frame(&device, |_| Ok(())), Ok(true));
+        assert_eq!(
+            scheduler.frame(&device, |_| panic!("no frame requested")),
+            Ok(false)
+        );
+
+        clock.advance(ms(100));
+        scheduler.request_frame();
+        let mut delta = None;
+        let drawn = scheduler.frame(&device, |info| {
+            delta = Some(info.delta);
+            Ok(())
+        });
+        assert_eq!(drawn, Ok(true));
+        assert_eq!(delta, Some(ms(100)));
+        assert_eq!(device.commits.get(), 2);
+        assert_eq!(device.waits.get(), 0);
+    }
+
+    #[test]
+    fn failed_frame_is_retried() {
+        let clock = ManualClock::new();
+        let device = FakeDevice::new(&clock);
+        let mut scheduler = FrameScheduler::with_clock(Mode::OnDemand, clock.clone());
+
+        let error = Error::new(E_UNEXPECTED, "draw");
+        assert_eq!(scheduler.frame(&device, |_| Err(error.clone())), Err(error));
+        assert!(scheduler.needs_frame());
+        assert_eq!(device.commits.get(), 0);
+        assert_eq!(scheduler.stats().frame_count(), 0);
+
+        assert_eq!(scheduler.frame(&device, |_| Ok(())), Ok(true));
+        assert!(!scheduler.needs_frame());
+        assert_eq!(device.commits.get(), 1);
+        assert_eq!(scheduler.stats().frame_count(), 1);
+    }
+
+    #[test]
+    fn fixed_rate() {
+        let clock = ManualClock::new();
+        let device = FakeDevice::new(&clock);
+        let mut scheduler = FrameScheduler::with_clock(Mode::FixedRate(ms(10)), clock.clone());
+
+        // The fourth frame takes 25ms, skipping the deadlines at 40ms and 50ms.
+        let mut times = Vec::new();
+        for &duration in &[0, 0, 0, 25, 0] {
+            let drawn = scheduler.frame(&device, |info| {
+                times.push(info.time);
+                clock.advance(ms(duration));
+                Ok(())
+            });
+            assert_eq!(drawn, Ok(true));
+        }
+        assert_eq!(times[..3], [ms(0), ms(10), ms(20)]);
+        assert_eq!(times[3..], [ms(30), ms(60)]);
+        assert_eq!(scheduler.stats().dropped_frames(), 2);
+        assert_eq!(scheduler.stats().max_frame_time(), Some(ms(30)));
+        assert_eq!(scheduler.clock().now(), ms(60));
+
+        // A long stall skips all of its deadlines at once.
+        scheduler
+            .frame(&device, |_| {
+                clock.advance(Duration::from_secs(3600));
+                Ok(())
+            })
+            .unwrap();
+        scheduler.frame(&device, |_| Ok(())).unwrap();
+        assert_eq!(scheduler.stats().dropped_frames(), 2 + 359_999);
+        assert_eq!(scheduler.clock().now(), ms(3_600_070));
+    }
+
+    #[test]
+    #[should_panic(expected = "non-zero interval")]
+    fn zero_interval() {
+        FrameScheduler::with_clock(Mode::FixedRate(ms(0)), ManualClock::new());
+    }
+}
@@ -0,0 +1,324 @@
+use super::{GlyphCache, GlyphMask, SUBPIXEL_POSITIONS};
+use crate::{
+    text::{opentype::Face, TextLayout},
+    Canvas, Offset, Point, Rect, Size,
+};
+
+/// A CPU bitmap of premultiplied BGRA pixels, stored as `0xAARRGGBB`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    width: i32,
+    height: i32,
+    pixels: Vec<u32>,
+}
+
+impl std::fmt::Debug for Bitmap {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Bitmap")
+            .field("w", &self.width)
+            .field("h", &self.height)
+            .finish()
+    }
+}
+
+impl Bitmap {
+    pub fn new(width: i32, height: i32) -> Self {
+        assert!(width >= 0 && height >= 0);
+        Self {
+            width,
+            height,
+            pixels: vec![0; (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [u32] {
+        &mut self.pixels
+    }
+
+    pub fn row(&self, y: i32) -> &[u32] {
+        let start = (y * self.width) as usize;
+        &self.pixels[start..start + self.width as usize]
+    }
+
+    pub fn pixel(&self, x: i32, y: i32) -> u32 {
+        assert!(x >= 0 && x < self.width && y >= 0 && y < self.height);
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: u32) {
+        assert!(x >= 0 && x < self.width && y >= 0 && y < self.height);
+        self.pixels[(y * self.width + x) as usize] = color;
+    }
+
+    /// Fills `rect`, clipped to the bitmap, with `color`.
+    pub fn fill_rect(&mut self, rect: &Rect, color: u32) {
+        let rect = *rect & self.bounds();
+        if rect.is_empty() {
+            return;
+        }
+        for y in rect.top..rect.bottom {
+            let start = (y * self.width + rect.left) as usize;
+            let end = (y * self.width + rect.right) as usize;
+            for p in &mut self.pixels[start..end] {
+                *p = color;
+            }
+        }
+    }
+
+    /// Copies `src_rect` from `src` so that its top left corner lands on
+    /// `dst`. The copy is clipped against both bitmaps.
+    pub fn copy_from(&mut self, src: &Bitmap, src_rect: &Rect, dst: Point) {
+        let src_rect = *src_rect & src.bounds();
+        let dx = dst.x - src_rect.left;
+        let dy = dst.y - src_rect.top;
+        let dst_rect = (src_rect + Offset::new(dx, dy)) & self.bounds();
+        if dst_rect.is_empty() {
+            return;
+        }
+        for y in dst_rect.top..dst_rect.bottom {
+            let s = ((y - dy) * src.width + dst_rect.left - dx) as usize;
+            let d = (y * self.width + dst_rect.left) as usize;
+            let len = dst_rect.width() as usize;
+            self.pixels[d..d + len].copy_from_slice(&src.pixels[s..s + len]);
+        }
+    }
+
+    /// Blends `color` through the coverage of `mask`, with the mask's pen
+    /// position at `pen`.
+    pub fn fill_mask(&mut self, mask: &GlyphMask, pen: Point, color: u32) {
+        let left = pen.x + mask.left;
+        let top = pen.y + mask.top;
+        let rect = Rect::new(left, top, left + mask.width, top + mask.height) & self.bounds();
+        if rect.is_empty() {
+            return;
+        }
+        for y in rect.top..rect.bottom {
+            let coverage = &mask.row(y - top)[(rect.left - left) as usize..];
+            let start = (y * self.width + rect.left) as usize;
+            let end = (y * self.width + rect.right) as usize;
+            for (p, &c) in self.pixels[start..end].iter_mut().zip(coverage) {
+                if c != 0 {
+                    *p = blend(scale(color, c), *p);
+                }
+            }
+        }
+    }
+
+    /// Draws the text of `layout` in `color`, with the layout box's top left
+    /// corner at `origin`. Runs with a color of their own use it instead.
+    /// Glyphs come from `face`, which should be the face the layout was
+    /// measured with, and are positioned to a quarter pixel horizontally and
+    /// a whole pixel vertically. Underlines and strikethroughs sit at fixed
+    /// fractions of the font size.
+    pub fn draw_text_layout(
+        &mut self,
+        origin: Point,
+        layout: &TextLayout,
+        face: &Face,
+        cache: &mut GlyphCache,
+        color: [f32; 4],
+    ) {
+        let runs = layout.text_runs();
+        for line in layout.runs() {
+            let y = origin.y + line.baseline.round() as i32;
+            for glyph in &line.glyphs {
+                let style = &runs[glyph.run].style;
+                let color = premultiply(style.color.unwrap_or(color));
+                let x = origin.x as f32 + glyph.x;
+                let mut pen = x.floor() as i32;
+                let mut subpixel = ((x - x.floor()) * SUBPIXEL_POSITIONS as f32).round() as u32;
+                if subpixel == SUBPIXEL_POSITIONS {
+                    pen += 1;
+                    subpixel = 0;
+                }
+                let id = face.glyph_index(glyph.ch).unwrap_or(0);
+                if let Some(mask) = cache.glyph(face, id, style.size, subpixel) {
+                    self.fill_mask(&mask, Point::new(pen, y), color);
+                }
+
+                let thickness = (style.size / 16.0).round().max(1.0) as i32;
+                let left = x.round() as i32;
+                let right = (x + glyph.advance).round() as i32;
+                if style.underline {
+                    let top = y + thickness;
+                    self.blend_rect(&Rect::new(left, top, right, top + thickness), color);
+                }
+                if style.strikethrough {
+                    let top = y - (style.size * 0.3).round() as i32;
+                    self.blend_rect(&Rect::new(left, top, right, top + thickness), color);
+                }
+            }
+        }
+    }
+
+    fn blend_rect(&mut self, rect: &Rect, color: u32) {
+        let rect = *rect & self.bounds();
+        if rect.is_empty() {
+            return;
+        }
+        for y in rect.top..rect.bottom {
+            let start = (y * self.width + rect.left) as usize;
+            let end = (y * self.width + rect.right) as usize;
+            for p in &mut self.pixels[start..end] {
+                *p = blend(color, *p);
+            }
+        }
+    }
+}
+
+/// Converts a straight `[r, g, b, a]` color into a premultiplied pixel.
+pub fn premultiply(color: [f32; 4]) -> u32 {
+    let a = color[3].max(0.0).min(1.0);
+    let channel = |c: f32| (c.max(0.0).min(1.0) * a * 255.0).round() as u32;
+    let alpha = (a * 255.0).round() as u32;
+    alpha << 24 | channel(color[0]) << 16 | channel(color[1]) << 8 | channel(color[2])
+}
+
+/// Composites premultiplied `src` over `dst`.
+fn blend(src: u32, dst: u32) -> u32 {
+    let inv = 255 - (src >> 24);
+    let mut out = 0;
+    for shift in &[0, 8, 16, 24] {
+        let s = (src >> shift) & 0xFF;
+        let d = (dst >> shift) & 0xFF;
+        out |= (s + (d * inv + 127) / 255) << shift;
+    }
+    out
+}
+
+/// Scales premultiplied `color` by `coverage` out of 255.
+fn scale(color: u32, coverage: u8) -> u32 {
+    let coverage = u32::from(coverage);
+    let mut out = 0;
+    for shift in &[0, 8, 16, 24] {
+        let c = (color >> shift) & 0xFF;
+        out |= ((c * coverage + 127) / 255) << shift;
+    }
+    out
+}
+
+impl Canvas for Bitmap {
+    fn clear(&mut self) {
+        for p in &mut self.pixels {
+            *p = 0;
+        }
+    }
+
+    fn fill_rect(&mut self, rect: &Rect, color: [f32; 4]) {
+        let src = premultiply(color);
+        if src >> 24 == 0xFF {
+            Bitmap::fill_rect(self, rect, src);
+            return;
+        }
+        self.blend_rect(rect, src);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{premultiply, Bitmap};
+    use crate::{
+        soft::GlyphCache,
+        text::{
+            opentype::{builder::FontBuilder, Face},
+            FontStretch, FontStyle, FontWeight, RichText, TextAttribute, TextFormat, TextLayout,
+        },
+        Canvas, Point, Rect,
+    };
+    use std::rc::Rc;
+
+    #[test]
+    fn canvas() {
+        let mut bitmap = Bitmap::new(8, 8);
+        let bounds = bitmap.bounds();
+        Bitmap::fill_rect(&mut bitmap, &bounds, 0xFF00_00FF);
+        Canvas::fill_rect(&mut bitmap, &Rect::new(0, 0, 4, 8), [1.0, 0.0, 0.0, 0.5]);
+        assert_eq!(premultiply([1.0, 0.0, 0.0, 0.5]), 0x8080_0000);
+        assert_eq!(bitmap.pixel(0, 0), 0xFF80_007F);
+        assert_eq!(bitmap.pixel(4, 0), 0xFF00_00FF);
+
+        bitmap.clear();
+        bitmap.stroke_rect(&bounds, [1.0, 1.0, 1.0, 1.0], 2.0);
+        assert_eq!(bitmap.pixel(1, 4), 0xFFFF_FFFF);
+        assert_eq!(bitmap.pixel(6, 6), 0xFFFF_FFFF);
+        assert_eq!(bitmap.pixel(2, 2), 0);
+    }
+
+    #[test]
+    fn text() {
+        let square: &[_] = &[
+            (0, 0, true),
+            (0, 500, true),
+            (500, 500, true),
+            (500, 0, true),
+        ];
+        let face = FontBuilder::new("Squares")
+            .glyphs("a", 525)
+            .outline('a', &[square])
+            .build();
+        let face = Rc::new(Face::parse(face, 0).unwrap());
+        let mut format = TextFormat::new(
+            "Squares",
+            FontWeight::Normal,
+            FontStyle::Normal,
+            FontStretch::Normal,
+            20.0,
+            "en-us",
+        );
+        format.set_font_metrics(face.clone());
+        let layout = TextLayout::new("aa", &format, 100.0, 100.0);
+        assert_eq!(layout.font_size(), 20.0);
+
+        // The baseline is 16 pixels down and each square is 10 pixels wide,
+        // the second starting half way through a pixel.
+        let mut bitmap = Bitmap::new(32, 32);
+        let mut cache = GlyphCache::new(64, 64);
+        let white = [1.0, 1.0, 1.0, 1.0];
+        bitmap.draw_text_layout(Point::new(2, 3), &layout, &face, &mut cache, white);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(bitmap.pixel(2, 9), 0xFFFF_FFFF);
+        assert_eq!(bitmap.pixel(11, 18), 0xFFFF_FFFF);
+        assert_eq!(bitmap.pixel(12, 9), 0x8080_8080);
+        assert_eq!(bitmap.pixel(13, 9), 0xFFFF_FFFF);
+        assert_eq!(bitmap.pixel(22, 9), 0x8080_8080);
+        assert_eq!(bitmap.pixel(1, 9), 0);
+        assert_eq!(bitmap.pixel(2, 8), 0);
+        assert_eq!(bitmap.pixel(2, 19), 0);
+        assert_eq!(bitmap.pixel(23, 9), 0);
+
+        // Runs can change the color and add decorations.
+        let text = RichText::new("aa")
+            .set(1..2, TextAttribute::Color([1.0, 0.0, 0.0, 1.0]))
+            .set(1..2, TextAttribute::Underline(true));
+        let layout = TextLayout::with_rich_text(&text, &format, 100.0, 100.0);
+        bitmap.clear();
+        bitmap.draw_text_layout(Point::new(2, 3), &layout, &face, &mut cache, white);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(bitmap.pixel(2, 9), 0xFFFF_FFFF);
+        assert_eq!(bitmap.pixel(13, 9), 0xFFFF_0000);
+        assert_eq!(bitmap.pixel(12, 20), 0);
+        assert_eq!(bitmap.pixel(13, 20), 0xFFFF_0000);
+        assert_eq!(bitmap.pixel(22, 20), 0xFFFF_0000);
+        assert_eq!(bitmap.pixel(23, 20), 0);
+    }
+}
@@ -0,0 +1,371 @@
+use super::raster::{rasterize_glyph, GlyphImage, SUBPIXEL_POSITIONS};
+use crate::{text::opentype::Face, Rect};
+use std::collections::HashMap;
+
+/// Identifies one rasterization of a glyph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    face: usize,
+    glyph: u16,
+    /// Bits of the `f32` size, which is never NaN.
+    size: u32,
+    subpixel: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    /// Where the coverage lives in the atlas; empty for blank glyphs.
+    rect: Rect,
+    left: i32,
+    top: i32,
+    last_used: u64,
+}
+
+/// A row of the atlas holding glyphs no taller than it.
+#[derive(Clone, Copy, Debug)]
+struct Shelf {
+    top: i32,
+    height: i32,
+    /// Left edge of the free space.
+    right: i32,
+}
+
+/// The coverage of a cached glyph, placed relative to the pen position on
+/// the baseline.
+#[derive(Clone, Copy, Debug)]
+pub struct GlyphMask<'a> {
+    pub left: i32,
+    pub top: i32,
+    pub width: i32,
+    pub height: i32,
+    /// Distance between rows in `coverage`.
+    pub stride: usize,
+    pub coverage: &'a [u8],
+}
+
+impl GlyphMask<'_> {
+    pub fn row(&self, y: i32) -> &[u8] {
+        let start = y as usize * self.stride;
+        &self.coverage[start..start + self.width as usize]
+    }
+}
+
+/// Rasterized glyphs packed into an 8-bit coverage atlas.
+///
+/// Glyphs are keyed by face, size, glyph id and subpixel position. When the
+/// atlas fills up, the least recently used glyphs are evicted until the
+/// survivors take up at most half of it, and the survivors are packed again.
+/// If the new glyph still has no room, the whole atlas is dropped instead.
+/// Glyphs too big for the atlas are rasterized on every use.
+pub struct GlyphCache {
+    width: i32,
+    height: i32,
+    atlas: Vec<u8>,
+    shelves: Vec<Shelf>,
+    entries: HashMap<GlyphKey, Entry>,
+    /// Counts lookups, to order entries by their last use.
+    clock: u64,
+    uncached: GlyphImage,
+}
+
+impl std::fmt::Debug for GlyphCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("GlyphCache")
+            .field("w", &self.width)
+            .field("h", &self.height)
+            .field("len", &self.entries.len())
+            .finish()
+    }
+}
+
+impl GlyphCache {
+    /// Creates a cache with an atlas of `width` by `height` pixels.
+    pub fn new(width: i32, height: i32) -> Self {
+        assert!(width > 0 && height > 0);
+        Self {
+            width,
+            height,
+            atlas: vec![0; (width * height) as usize],
+            shelves: Vec::new(),
+            entries: HashMap::new(),
+            clock: 0,
+            uncached: GlyphImage::default(),
+        }
+    }
+
+    /// Number of cached glyphs.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.shelves.clear();
+    }
+
+    /// Returns `glyph` of `face` at `size` pixels per em, shifted right by
+    /// `subpixel` of `SUBPIXEL_POSITIONS` parts of a pixel, rasterizing it if
+    /// it is not cached. Returns `None` if the face has no outline for the
+    /// glyph.
+    pub fn glyph(
+        &mut self,
+        face: &Face,
+        glyph: u16,
+        size: f32,
+        subpixel: u32,
+    ) -> Option<GlyphMask<'_>> {
+        assert!(!size.is_nan() && subpixel < SUBPIXEL_POSITIONS);
+        self.clock += 1;
+        let key = GlyphKey {
+            face: face.id(),
+            glyph,
+            size: size.to_bits(),
+            subpixel,
+        };
+
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.last_used = self.clock;
+            let entry = *entry;
+            return Some(self.mask(&entry));
+        }
+
+        let offset = subpixel as f32 / SUBPIXEL_POSITIONS as f32;
+        let image = rasterize_glyph(face, glyph, size, offset)?;
+        if image.width > self.width || image.height > self.height {
+            self.uncached = image;
+            let image = &self.uncached;
+            return Some(GlyphMask {
+                left: image.left,
+                top: image.top,
+                width: image.width,
+                height: image.height,
+                stride: image.width as usize,
+                coverage: &image.coverage,
+            });
+        }
+
+        let rect = match self.allocate(image.width, image.height) {
+            Some(rect) => rect,
+            None => {
+                self.evict();
+                match self.allocate(image.width, image.height) {
+                    Some(rect) => rect,
+                    None => {
+                        // The survivors' shelves can leave no row tall
+                        // enough, but the glyph fits in an empty atlas.
+                        self.clear();
+                        self.allocate(image.width, image.height)
+                            .expect("glyph fits in an empty atlas")
+                    }
+                }
+            }
+        };
+        for y in 0..rect.height() {
+            let src = (y * image.width) as usize;
+            let dst = ((rect.top + y) * self.width + rect.left) as usize;
+            let len = image.width as usize;
+            self.atlas[dst..dst + len].copy_from_slice(&image.coverage[src..src + len]);
+        }
+        let entry = Entry {
+            rect,
+            left: image.left,
+            top: image.top,
+            last_used: self.clock,
+        };
+        self.entries.insert(key, entry);
+        Some(self.mask(&entry))
+    }
+
+    fn mask(&self, entry: &Entry) -> GlyphMask<'_> {
+        let start = (entry.rect.top * self.width + entry.rect.left) as usize;
+        GlyphMask {
+            left: entry.left,
+            top: entry.top,
+            width: entry.rect.width(),
+            height: entry.rect.height(),
+            stride: self.width as usize,
+            coverage: &self.atlas[start.min(self.atlas.len())..],
+        }
+    }
+
+    /// Finds room for a glyph on the shortest shelf it fits, or on a new
+    /// shelf below the others.
+    fn allocate(&mut self, width: i32, height: i32) -> Option<Rect> {
+        if width == 0 || height == 0 {
+            return Some(Rect::default());
+        }
+        let atlas_width = self.width;
+        let shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && atlas_width - shelf.right >= width)
+            .min_by_key(|shelf| shelf.height);
+        let shelf = match shelf {
+            Some(shelf) => shelf,
+            None => {
+                let top = self.shelves.last().map_or(0, |s| s.top + s.height);
+                if self.height - top < height {
+                    return None;
+                }
+                self.shelves.push(Shelf {
+                    top,
+                    height,
+                    right: 0,
+                });
+                self.shelves.last_mut().unwrap()
+            }
+        };
+        let rect = Rect::new(
+            shelf.right,
+            shelf.top,
+            shelf.right + width,
+            shelf.top + height,
+        );
+        shelf.right += width;
+        Some(rect)
+    }
+
+    /// Drops the least recently used glyphs until the rest cover at most
+    /// half the atlas, and packs the rest again from the top.
+    fn evict(&mut self) {
+        let mut entries: Vec<_> = self.entries.drain().collect();
+        entries.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.last_used));
+        let old = std::mem::replace(
+            &mut self.atlas,
+            vec![0; (self.width * self.height) as usize],
+        );
+        self.shelves.clear();
+
+        // Packing the tallest glyphs first wastes less space.
+        let budget = self.width * self.height / 2;
+        let mut area = 0;
+        let mut survivors: Vec<_> = entries
+            .into_iter()
+            .take_while(|(_, entry)| {
+                area += entry.rect.width() * entry.rect.height();
+                area <= budget
+            })
+            .collect();
+        survivors.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.rect.height()));
+
+        for (key, mut entry) in survivors {
+            let rect = match self.allocate(entry.rect.width(), entry.rect.height()) {
+                Some(rect) => rect,
+                None => continue,
+            };
+            for y in 0..rect.height() {
+                let src = ((entry.rect.top + y) * self.width + entry.rect.left) as usize;
+                let dst = ((rect.top + y) * self.width + rect.left) as usize;
+                let len = rect.width() as usize;
+                self.atlas[dst..dst + len].copy_from_slice(&old[src..src + len]);
+            }
+            entry.rect = rect;
+            self.entries.insert(key, entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GlyphCache;
+    use crate::text::opentype::{builder::FontBuilder, Face};
+
+    const LETTERS: &str = "abcdefghijklmnopq";
+
+    fn face() -> Face {
+        let square: &[_] = &[
+            (0, 0, true),
+            (0, 1000, true),
+            (1000, 1000, true),
+            (1000, 0, true),
+        ];
+        let builder = LETTERS.chars().fold(
+            FontBuilder::new("Squares")
+                .glyphs(" ", 1000)
+                .glyphs(LETTERS, 1000),
+            |builder, ch| builder.outline(ch, &[square]),
+        );
+        Face::parse(builder.build(), 0).unwrap()
+    }
+
+    #[test]
+    fn lookup() {
+        let face = face();
+        let mut cache = GlyphCache::new(64, 64);
+        let mask = cache.glyph(&face, 2, 10.0, 0).unwrap();
+        assert_eq!(
+            (mask.left, mask.top, mask.width, mask.height),
+            (0, -10, 10, 10)
+        );
+        assert_eq!(mask.row(9), [255; 10]);
+
+        // The same glyph is found again; other sizes, positions and faces
+        // are cached separately.
+        cache.glyph(&face, 2, 10.0, 0).unwrap();
+        assert_eq!(cache.len(), 1);
+        let mask = cache.glyph(&face, 2, 10.0, 2).unwrap();
+        assert_eq!(mask.width, 11);
+        assert_eq!(mask.row(0)[0], 128);
+        cache.glyph(&face, 2, 12.0, 0).unwrap();
+        let copy = Face::parse(face.data().clone(), 0).unwrap();
+        cache.glyph(&copy, 2, 10.0, 0).unwrap();
+        assert_eq!(cache.len(), 4);
+
+        // Blank glyphs take no room and missing ones are not cached.
+        assert_eq!(cache.glyph(&face, 1, 10.0, 0).unwrap().width, 0);
+        assert!(cache.glyph(&face, 99, 10.0, 0).is_none());
+        assert_eq!(cache.len(), 5);
+
+        let mask = cache.glyph(&face, 3, 100.0, 0).unwrap();
+        assert_eq!((mask.width, mask.height), (100, 100));
+        assert_eq!(mask.row(99), &[255; 100][..]);
+        assert_eq!(cache.len(), 5);
+    }
+
+    #[test]
+    fn eviction() {
+        let face = face();
+        let mut cache = GlyphCache::new(64, 64);
+        // Sixteen glyphs of 16 by 16 pixels fill the atlas. The first is
+        // used again last.
+        for glyph in 2..18 {
+            cache.glyph(&face, glyph, 16.0, 0).unwrap();
+        }
+        cache.glyph(&face, 2, 16.0, 0).unwrap();
+        assert_eq!(cache.len(), 16);
+
+        // The next glyph keeps the most recently used half.
+        cache.glyph(&face, 18, 16.0, 0).unwrap();
+        assert_eq!(cache.len(), 9);
+        let mask = cache.glyph(&face, 2, 16.0, 0).unwrap();
+        assert_eq!(mask.row(15), [255; 16]);
+        cache.glyph(&face, 17, 16.0, 0).unwrap();
+        cache.glyph(&face, 11, 16.0, 0).unwrap();
+        assert_eq!(cache.len(), 9);
+        cache.glyph(&face, 10, 16.0, 0).unwrap();
+        assert_eq!(cache.len(), 10);
+    }
+    #[test]
+    fn eviction_with_mixed_heights() {
+        let face = face();
+        let mut cache = GlyphCache::new(64, 64);
+        // Shelves 33 and 31 pixels tall fill the atlas, and the taller glyph
+        // is used again last.
+        cache.glyph(&face, 2, 33.0, 0).unwrap();
+        cache.glyph(&face, 3, 31.0, 0).unwrap();
+        cache.glyph(&face, 2, 33.0, 0).unwrap();
+
+        // Only the 33 pixel glyph survives eviction, and its shelf leaves no
+        // room for a 32 pixel one, so everything goes.
+        let mask = cache.glyph(&face, 4, 32.0, 0).unwrap();
+        assert_eq!((mask.width, mask.height), (32, 32));
+        assert_eq!(mask.row(31), &[255; 32][..]);
+        assert_eq!(cache.len(), 1);
+        cache.glyph(&face, 3, 31.0, 0).unwrap();
+        assert_eq!(cache.len(), 2);
+    }
+}
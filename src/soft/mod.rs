@@ -0,0 +1,15 @@
+//! Software implementations of noodle's surfaces, for running and testing
+//! drawing code without a GPU.
+//!
+//! Text is drawn from the outlines of `text::opentype` faces, rasterized on
+//! the CPU and kept in a `GlyphCache` atlas.
+
+mod bitmap;
+mod glyph_cache;
+mod raster;
+mod surface;
+
+pub use bitmap::{premultiply, Bitmap};
+pub use glyph_cache::{GlyphCache, GlyphMask};
+pub use raster::{rasterize_glyph, GlyphImage, SUBPIXEL_POSITIONS};
+pub use surface::{VirtualSurface, TILE_SIZE};
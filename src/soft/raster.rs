@@ -0,0 +1,357 @@
+//! Anti-aliased rasterization of glyph outlines.
+//!
+//! Edges accumulate signed area into a buffer, which a running sum turns
+//! into coverage, so each pixel's coverage is exact for the flattened
+//! outline. Overlapping contours of the same direction saturate rather than
+//! cancel, which matches the nonzero fill rule for real fonts.
+
+use crate::text::opentype::{Face, PathCommand};
+
+/// Glyphs are rasterized at this many horizontal offsets within a pixel, so
+/// text can be positioned more finely than whole pixels while each glyph is
+/// cached at most this many times per size.
+pub const SUBPIXEL_POSITIONS: u32 = 4;
+
+/// An anti-aliased glyph: one byte of coverage per pixel, placed relative to
+/// the pen position on the baseline.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GlyphImage {
+    /// Offset of the image's left edge from the pen.
+    pub left: i32,
+    /// Offset of the image's top edge from the baseline, negative above it.
+    pub top: i32,
+    pub width: i32,
+    pub height: i32,
+    pub coverage: Vec<u8>,
+}
+
+/// Rasterizes `glyph` at `size` pixels per em, with the pen `x_offset`
+/// pixels right of a pixel boundary. Returns `None` if the face has no
+/// outline for the glyph. Glyphs without contours give an empty image.
+pub fn rasterize_glyph(face: &Face, glyph: u16, size: f32, x_offset: f32) -> Option<GlyphImage> {
+    let outline = face.glyph_outline(glyph)?;
+    let scale = size / f32::from(face.metrics().design_units_per_em.max(1));
+    // Pixel space has y pointing down.
+    let transform = |x: f32, y: f32| (x * scale + x_offset, -y * scale);
+
+    let mut points = Vec::new();
+    for command in &outline {
+        match *command {
+            PathCommand::MoveTo(x, y) | PathCommand::LineTo(x, y) => points.push(transform(x, y)),
+            PathCommand::QuadTo(x1, y1, x, y) => {
+                points.push(transform(x1, y1));
+                points.push(transform(x, y));
+            }
+            PathCommand::CubicTo(x1, y1, x2, y2, x, y) => {
+                points.push(transform(x1, y1));
+                points.push(transform(x2, y2));
+                points.push(transform(x, y));
+            }
+            PathCommand::Close => {}
+        }
+    }
+    if points.is_empty() {
+        return Some(GlyphImage::default());
+    }
+
+    // Curves lie within the hull of their control points.
+    let min_x = points
+        .iter()
+        .map(|p| p.0)
+        .fold(std::f32::INFINITY, f32::min);
+    let min_y = points
+        .iter()
+        .map(|p| p.1)
+        .fold(std::f32::INFINITY, f32::min);
+    let max_x = points
+        .iter()
+        .map(|p| p.0)
+        .fold(std::f32::NEG_INFINITY, f32::max);
+    let max_y = points
+        .iter()
+        .map(|p| p.1)
+        .fold(std::f32::NEG_INFINITY, f32::max);
+    let left = min_x.floor() as i32;
+    let top = min_y.floor() as i32;
+    let width = max_x.ceil() as i32 - left;
+    let height = max_y.ceil() as i32 - top;
+    if width <= 0 || height <= 0 {
+        return Some(GlyphImage::default());
+    }
+
+    let mut raster = Rasterizer::new(width as usize, height as usize);
+    let origin = (left as f32, top as f32);
+    let local = |x: f32, y: f32| {
+        let (x, y) = transform(x, y);
+        (x - origin.0, y - origin.1)
+    };
+    let mut start = (0.0, 0.0);
+    let mut current = (0.0, 0.0);
+    for command in &outline {
+        match *command {
+            PathCommand::MoveTo(x, y) => {
+                start = local(x, y);
+                current = start;
+            }
+            PathCommand::LineTo(x, y) => {
+                let p = local(x, y);
+                raster.line(current, p);
+                current = p;
+            }
+            PathCommand::QuadTo(x1, y1, x, y) => {
+                let p = local(x, y);
+                raster.quad(current, local(x1, y1), p);
+                current = p;
+            }
+            PathCommand::CubicTo(x1, y1, x2, y2, x, y) => {
+                let p = local(x, y);
+                raster.cubic(current, local(x1, y1), local(x2, y2), p);
+                current = p;
+            }
+            PathCommand::Close => {
+                raster.line(current, start);
+                current = start;
+            }
+        }
+    }
+
+    Some(GlyphImage {
+        left,
+        top,
+        width,
+        height,
+        coverage: raster.coverage(),
+    })
+}
+
+type Point = (f32, f32);
+
+struct Rasterizer {
+    width: usize,
+    height: usize,
+    /// Signed area added at each pixel, plus slack for edges on the right
+    /// border.
+    area: Vec<f32>,
+}
+
+impl Rasterizer {
+    fn new(width: usize, height: usize) -> Self {
+        Rasterizer {
+            width,
+            height,
+            area: vec![0.0; width * height + 4],
+        }
+    }
+
+    fn line(&mut self, p0: Point, p1: Point) {
+        if (p0.1 - p1.1).abs() < std::f32::EPSILON {
+            return;
+        }
+        let (dir, p0, p1) = if p0.1 < p1.1 {
+            (1.0, p0, p1)
+        } else {
+            (-1.0, p1, p0)
+        };
+        let dxdy = (p1.0 - p0.0) / (p1.1 - p0.1);
+        let width = self.width as f32;
+        let clamp = |x: f32| x.max(0.0).min(width);
+        let mut x = p0.0;
+        let y0 = p0.1.max(0.0);
+        if p0.1 < 0.0 {
+            x -= p0.1 * dxdy;
+        }
+        let y_end = (p1.1.ceil() as usize).min(self.height);
+        for y in y0 as usize..y_end {
+            let row = y * self.width;
+            let dy = ((y + 1) as f32).min(p1.1) - (y as f32).max(p0.1);
+            let x_next = x + dxdy * dy;
+            let d = dy * dir;
+            let (x0, x1) = if x < x_next { (x, x_next) } else { (x_next, x) };
+            let (x0, x1) = (clamp(x0), clamp(x1));
+            let x0_floor = x0.floor();
+            let x0i = x0_floor as usize;
+            let x1_ceil = x1.ceil();
+            let x1i = x1_ceil as usize;
+            if x1i <= x0i + 1 {
+                // The edge stays within one pixel in this row.
+                let xmf = 0.5 * (x0 + x1) - x0_floor;
+                self.area[row + x0i] += d - d * xmf;
+                self.area[row + x0i + 1] += d * xmf;
+            } else {
+                let s = (x1 - x0).recip();
+                let x0f = x0 - x0_floor;
+                let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+                let x1f = x1 - x1_ceil + 1.0;
+                let am = 0.5 * s * x1f * x1f;
+                self.area[row + x0i] += d * a0;
+                if x1i == x0i + 2 {
+                    self.area[row + x0i + 1] += d * (1.0 - a0 - am);
+                } else {
+                    let a1 = s * (1.5 - x0f);
+                    self.area[row + x0i + 1] += d * (a1 - a0);
+                    for xi in x0i + 2..x1i - 1 {
+                        self.area[row + xi] += d * s;
+                    }
+                    let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+                    self.area[row + x1i - 1] += d * (1.0 - a2 - am);
+                }
+                self.area[row + x1i] += d * am;
+            }
+            x = x_next;
+        }
+    }
+
+    fn quad(&mut self, p0: Point, p1: Point, p2: Point) {
+        let deviation = (p0.0 - 2.0 * p1.0 + p2.0).hypot(p0.1 - 2.0 * p1.1 + p2.1) / 4.0;
+        self.flatten(deviation, |t| {
+            let mt = 1.0 - t;
+            (
+                mt * mt * p0.0 + 2.0 * mt * t * p1.0 + t * t * p2.0,
+                mt * mt * p0.1 + 2.0 * mt * t * p1.1 + t * t * p2.1,
+            )
+        });
+    }
+
+    fn cubic(&mut self, p0: Point, p1: Point, p2: Point, p3: Point) {
+        let deviation = (p0.0 - 2.0 * p1.0 + p2.0)
+            .hypot(p0.1 - 2.0 * p1.1 + p2.1)
+            .max((p1.0 - 2.0 * p2.0 + p3.0).hypot(p1.1 - 2.0 * p2.1 + p3.1))
+            * 0.75;
+        self.flatten(deviation, |t| {
+            let mt = 1.0 - t;
+            let (a, b, c, d) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+            (
+                a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+                a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+            )
+        });
+    }
+
+    /// Draws a curve as lines. A curve strays at most `deviation / n²`
+    /// pixels from `n` equal steps along it, so this takes enough steps to
+    /// keep within a tenth of a pixel.
+    fn flatten(&mut self, deviation: f32, point: impl Fn(f32) -> Point) {
+        let segments = 1 + (10.0 * deviation).sqrt().floor() as usize;
+        let mut previous = point(0.0);
+        for i in 1..=segments {
+            let next = point(i as f32 / segments as f32);
+            self.line(previous, next);
+            previous = next;
+        }
+    }
+
+    fn coverage(&self) -> Vec<u8> {
+        let mut sum = 0.0;
+        self.area[..self.width * self.height]
+            .iter()
+            .map(|area| {
+                sum += area;
+                (sum.abs().min(1.0) * 255.0).round() as u8
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::opentype::builder::FontBuilder;
+
+    fn face() -> Face {
+        let square: &[_] = &[
+            (0, 0, true),
+            (0, 500, true),
+            (500, 500, true),
+            (500, 0, true),
+        ];
+        let hole: &[_] = &[
+            (100, 100, true),
+            (400, 100, true),
+            (400, 400, true),
+            (100, 400, true),
+        ];
+        let diamond: &[_] = &[
+            (0, 250, true),
+            (250, 500, true),
+            (500, 250, true),
+            (250, 0, true),
+        ];
+        let round: &[_] = &[
+            (0, 0, true),
+            (500, 0, false),
+            (500, 500, true),
+            (0, 500, false),
+        ];
+        Face::parse(
+            FontBuilder::new("Shapes")
+                .glyphs(" sodq", 600)
+                .outline('s', &[square])
+                .outline('o', &[square, hole])
+                .outline('d', &[diamond])
+                .outline('q', &[round])
+                .build(),
+            0,
+        )
+        .unwrap()
+    }
+
+    fn rows(image: &GlyphImage) -> Vec<&[u8]> {
+        image.coverage.chunks(image.width as usize).collect()
+    }
+
+    #[test]
+    fn squares() {
+        let face = face();
+        // Half an em square at 20 pixels per em covers 10 by 10 pixels
+        // sitting on the baseline.
+        let image = rasterize_glyph(&face, 2, 20.0, 0.0).unwrap();
+        assert_eq!(
+            (image.left, image.top, image.width, image.height),
+            (0, -10, 10, 10)
+        );
+        assert!(image.coverage.iter().all(|&c| c == 255));
+
+        // A quarter pixel offset spreads the square over 11 columns.
+        let image = rasterize_glyph(&face, 2, 20.0, 0.25).unwrap();
+        assert_eq!((image.left, image.width), (0, 11));
+        for row in rows(&image) {
+            assert_eq!(row[0], 191);
+            assert!(row[1..10].iter().all(|&c| c == 255));
+            assert_eq!(row[10], 64);
+        }
+
+        // The hole winds the other way and cancels.
+        let image = rasterize_glyph(&face, 3, 20.0, 0.0).unwrap();
+        let rows = rows(&image);
+        assert_eq!(rows[0], [255; 10]);
+        assert_eq!(rows[5], [255, 255, 0, 0, 0, 0, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn edges_and_curves() {
+        let face = face();
+        let image = rasterize_glyph(&face, 4, 20.0, 0.0).unwrap();
+        let rows = rows(&image);
+        // Each corner pixel of the diamond is cut by its diagonal.
+        assert_eq!(rows[0][0], 0);
+        assert_eq!(rows[0][4], 128);
+        assert_eq!(rows[4][0], 128);
+        assert_eq!(rows[4][4], 255);
+        let total: u32 = image.coverage.iter().map(|&c| u32::from(c)).sum();
+        assert_eq!((total as f32 / 255.0).round(), 50.0);
+
+        // A lens of two parabolas has two thirds of its square's area, less
+        // a little lost where flattening cuts its edges.
+        let image = rasterize_glyph(&face, 5, 20.0, 0.0).unwrap();
+        let total: u32 = image.coverage.iter().map(|&c| u32::from(c)).sum();
+        let area = total as f32 / 255.0;
+        assert!(area < 100.0 * 2.0 / 3.0 && area > 64.0);
+        assert_eq!(image.coverage[0], 0);
+        assert_eq!(image.coverage[44], 255);
+
+        assert_eq!(
+            rasterize_glyph(&face, 1, 20.0, 0.0),
+            Some(GlyphImage::default())
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use super::Bitmap;
+use crate::{debug, Offset, Point, Rect, Region, Size};
+use std::{cell::RefCell, collections::HashMap};
+
+/// Side length of the square tiles backing a `VirtualSurface`.
+pub const TILE_SIZE: i32 = 256;
+
+const BYTES_PER_PIXEL: usize = 4;
+
+/// Gap between the edge of the bitmap handed to a `draw` callback and the
+/// update rect. DirectComposition hands out an offset into a shared atlas, so
+/// a non-zero offset catches drawing code that forgets to apply it.
+const DRAW_MARGIN: i32 = 16;
+
+struct Inner {
+    size: Size,
+    tiles: HashMap<(i32, i32), Bitmap>,
+    valid: Region,
+}
+
+/// Software emulation of `dcomp::VirtualSurface`.
+///
+/// Content is stored in sparse `TILE_SIZE` tiles that are only allocated
+/// for pixels that have been drawn and are released as soon as trimming,
+/// resizing or scrolling leaves them without any valid pixels.
+pub struct VirtualSurface(RefCell<Inner>);
+
+impl std::fmt::Debug for VirtualSurface {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let inner = self.0.borrow();
+        f.debug_struct("VirtualSurface")
+            .field("size", &inner.size)
+            .field("tiles", &inner.tiles.len())
+            .finish()
+    }
+}
+
+impl VirtualSurface {
+    pub fn new(initial_width: u32, initial_height: u32) -> Self {
+        Self(RefCell::new(Inner {
+            size: Size::new(initial_width as i32, initial_height as i32),
+            tiles: HashMap::new(),
+            valid: Region::new(),
+        }))
+    }
+
+    pub fn size(&self) -> Size {
+        self.0.borrow().size
+    }
+
+    /// Pixels that have been drawn and not discarded since.
+    pub fn valid_region(&self) -> Region {
+        self.0.borrow().valid.clone()
+    }
+
+    pub fn tile_count(&self) -> usize {
+        self.0.borrow().tiles.len()
+    }
+
+    /// Bytes of tile storage currently allocated.
+    pub fn memory_usage(&self) -> usize {
+        self.tile_count() * (TILE_SIZE * TILE_SIZE) as usize * BYTES_PER_PIXEL
+    }
+
+    /// The pixel at `(x, y)`, or `None` if that pixel isn't valid.
+    pub fn pixel(&self, x: i32, y: i32) -> Option<u32> {
+        let inner = self.0.borrow();
+        if !inner.valid.contains(x, y) {
+            return None;
+        }
+        let tile = &inner.tiles[&(x / TILE_SIZE, y / TILE_SIZE)];
+        Some(tile.pixel(x % TILE_SIZE, y % TILE_SIZE))
+    }
+
+    pub fn draw<'a>(
+        &self,
+        update_rect: impl Into<Option<&'a Rect>>,
+        mut f: impl FnMut(&mut Bitmap, Offset),
+    ) {
+        let bounds = self.bounds();
+        let update_rect = update_rect.into().copied().unwrap_or(bounds);
+        assert!(
+            !update_rect.is_empty() && (update_rect & bounds) == update_rect,
+            "Update rect {:?} must be inside the surface {:?}",
+            update_rect,
+            bounds
+        );
+
+        let mut bitmap = Bitmap::new(
+            update_rect.width() + 2 * DRAW_MARGIN,
+            update_rect.height() + 2 * DRAW_MARGIN,
+        );
+        let offset = Offset::new(DRAW_MARGIN, DRAW_MARGIN);
+        let to_bitmap = offset - [update_rect.left, update_rect.top];
+        self.read(&update_rect, &mut bitmap, to_bitmap);
+        f(&mut bitmap, offset);
+
+        let mut inner = self.0.borrow_mut();
+        inner.write(&bitmap, &update_rect, to_bitmap);
+        inner.valid.union_rect(update_rect);
+        debug::record_paint(self as *const _ as usize, update_rect);
+    }
+
+    pub fn scroll<'a>(
+        &self,
+        scroll_rect: impl Into<Option<&'a Rect>>,
+        clip_rect: impl Into<Option<&'a Rect>>,
+        offset: (i32, i32),
+    ) {
+        let bounds = self.bounds();
+        let scroll_rect = scroll_rect.into().map_or(bounds, |r| *r & bounds);
+        let clip_rect = clip_rect.into().map_or(bounds, |r| *r & bounds);
+        let offset = Offset::new(offset.0, offset.1);
+
+        // Only the part of the destination that lands inside the clip changes.
+        let dst_rect = (scroll_rect + offset) & clip_rect;
+        if dst_rect.is_empty() {
+            return;
+        }
+        let src_rect = dst_rect + -offset;
+
+        // Snapshot the source first since it can overlap the destination.
+        let mut scratch = Bitmap::new(src_rect.width(), src_rect.height());
+        let to_scratch = Offset::new(-src_rect.left, -src_rect.top);
+        self.read(&src_rect, &mut scratch, to_scratch);
+
+        let mut inner = self.0.borrow_mut();
+        inner.write(&scratch, &dst_rect, to_scratch - offset);
+
+        let mut moved = inner.valid.clone();
+        moved.intersect_rect(src_rect);
+        moved.translate(offset);
+        inner.valid.subtract_rect(dst_rect);
+        inner.valid.union(&moved);
+        inner.release_unused_tiles();
+    }
+
+    /// Changes the logical size of the surface, discarding any content that
+    /// falls outside the new bounds.
+    pub fn resize(&self, width: u32, height: u32) {
+        let mut inner = self.0.borrow_mut();
+        inner.size = Size::new(width as i32, height as i32);
+        inner
+            .valid
+            .intersect_rect(Rect::new(0, 0, width as i32, height as i32));
+        inner.release_unused_tiles();
+    }
+
+    /// Discards every pixel outside `rects`. An empty slice discards the
+    /// whole surface.
+    pub fn trim(&self, rects: &[Rect]) {
+        let mut keep = Region::new();
+        for rect in rects {
+            keep.union_rect(*rect);
+        }
+        let mut inner = self.0.borrow_mut();
+        inner.valid.intersect(&keep);
+        inner.release_unused_tiles();
+    }
+
+    fn bounds(&self) -> Rect {
+        let size = self.size();
+        Rect::new(0, 0, size.width(), size.height())
+    }
+
+    /// Copies `rect` from the surface into `dst`, moved by `offset`.
+    fn read(&self, rect: &Rect, dst: &mut Bitmap, offset: Offset) {
+        let inner = self.0.borrow();
+        for (tile_rect, tile) in inner.tiles_in(rect) {
+            if let Some(tile) = tile {
+                let area = *rect & tile_rect;
+                let src = area + -Offset::new(tile_rect.left, tile_rect.top);
+                dst.copy_from(tile, &src, Point::new(area.left, area.top) + offset);
+            }
+        }
+    }
+}
+
+impl Inner {
+    fn tile_rect(column: i32, row: i32) -> Rect {
+        Rect::new(
+            column * TILE_SIZE,
+            row * TILE_SIZE,
+            (column + 1) * TILE_SIZE,
+            (row + 1) * TILE_SIZE,
+        )
+    }
+
+    fn tile_range(rect: &Rect) -> impl Iterator<Item = (i32, i32)> {
+        let (left, top) = (rect.left / TILE_SIZE, rect.top / TILE_SIZE);
+        let right = (rect.right + TILE_SIZE - 1) / TILE_SIZE;
+        let bottom = (rect.bottom + TILE_SIZE - 1) / TILE_SIZE;
+        (top..bottom).flat_map(move |row| (left..right).map(move |column| (column, row)))
+    }
+
+    fn tiles_in<'a>(&'a self, rect: &Rect) -> impl Iterator<Item = (Rect, Option<&'a Bitmap>)> {
+        Self::tile_range(rect).map(move |(column, row)| {
+            (Self::tile_rect(column, row), self.tiles.get(&(column, row)))
+        })
+    }
+
+    /// Copies the pixels of `src` that map to `rect` on the surface, where
+    /// `offset` moves surface coordinates into `src`.
+    fn write(&mut self, src: &Bitmap, rect: &Rect, offset: Offset) {
+        for (column, row) in Self::tile_range(rect) {
+            let tile_rect = Self::tile_rect(column, row);
+            let area = *rect & tile_rect;
+            let tile = self
+                .tiles
+                .entry((column, row))
+                .or_insert_with(|| Bitmap::new(TILE_SIZE, TILE_SIZE));
+            tile.copy_from(
+                src,
+                &(area + offset),
+                Point::new(area.left - tile_rect.left, area.top - tile_rect.top),
+            );
+        }
+    }
+
+    fn release_unused_tiles(&mut self) {
+        let valid = &self.valid;
+        self.tiles
+            .retain(|&(column, row), _| valid.intersects(&Self::tile_rect(column, row)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VirtualSurface, TILE_SIZE};
+    use crate::{rect, soft::Bitmap, Offset, Rect};
+
+    fn fill(surface: &VirtualSurface, rect: Rect, color: u32) {
+        surface.draw(&rect, |bitmap: &mut Bitmap, offset: Offset| {
+            bitmap.fill_rect(&(rect!(rect.width(), rect.height()) + offset), color);
+        });
+    }
+
+    #[test]
+    fn draw_allocates_touched_tiles() {
+        let surface = VirtualSurface::new(4096, 4096);
+        assert_eq!(surface.memory_usage(), 0);
+
+        fill(&surface, rect!(250, 10, 260, 20), 0xff00_00ff);
+        assert_eq!(surface.tile_count(), 2);
+        assert_eq!(surface.pixel(250, 10), Some(0xff00_00ff));
+        assert_eq!(surface.pixel(259, 19), Some(0xff00_00ff));
+        assert_eq!(surface.pixel(260, 19), None);
+        assert_eq!(surface.valid_region().area(), 100);
+    }
+
+    #[test]
+    fn draw_preserves_previous_content() {
+        let surface = VirtualSurface::new(512, 512);
+        fill(&surface, rect!(0, 0, 20, 20), 1);
+        surface.draw(&rect!(10, 10, 30, 30), |bitmap, offset| {
+            bitmap.fill_rect(&(rect!(10, 10, 20, 20) + offset), 2);
+        });
+        assert_eq!(surface.pixel(5, 5), Some(1));
+        assert_eq!(surface.pixel(15, 15), Some(1));
+        assert_eq!(surface.pixel(25, 25), Some(2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn update_rect_outside_surface() {
+        let surface = VirtualSurface::new(100, 100);
+        fill(&surface, rect!(90, 90, 110, 110), 1);
+    }
+
+    #[test]
+    fn trim_releases_tiles() {
+        let surface = VirtualSurface::new(2048, 2048);
+        fill(&surface, rect!(0, 0, 2048, 2048), 7);
+        assert_eq!(surface.tile_count(), 64);
+
+        surface.trim(&[rect!(0, 0, 300, 300)]);
+        assert_eq!(surface.tile_count(), 4);
+        assert_eq!(
+            surface.memory_usage(),
+            4 * (TILE_SIZE * TILE_SIZE * 4) as usize
+        );
+        assert_eq!(surface.pixel(299, 299), Some(7));
+        assert_eq!(surface.pixel(300, 10), None);
+
+        surface.trim(&[]);
+        assert_eq!(surface.memory_usage(), 0);
+    }
+
+    #[test]
+    fn resize_discards_outside() {
+        let surface = VirtualSurface::new(1024, 1024);
+        fill(&surface, rect!(0, 0, 1024, 1024), 3);
+        surface.resize(100, 600);
+        assert_eq!(surface.tile_count(), 3);
+        assert_eq!(surface.valid_region().area(), 100 * 600);
+        surface.resize(1024, 1024);
+        assert_eq!(surface.pixel(500, 500), None);
+    }
+
+    #[test]
+    fn scroll_is_pixel_exact() {
+        let surface = VirtualSurface::new(300, 300);
+        surface.draw(None, |bitmap, offset| {
+            for y in 0..300 {
+                for x in 0..300 {
+                    bitmap.set_pixel(x + offset.x, y + offset.y, (y * 1000 + x) as u32);
+                }
+            }
+        });
+
+        // Scroll the whole surface up and left, overlapping itself.
+        surface.scroll(None, None, (-10, -20));
+        assert_eq!(surface.pixel(0, 0), Some(20 * 1000 + 10));
+        assert_eq!(surface.pixel(289, 279), Some(299 * 1000 + 299));
+        // Pixels that nothing scrolled into keep their old content.
+        assert_eq!(surface.pixel(295, 295), Some(295 * 1000 + 295));
+        assert_eq!(surface.valid_region().area(), 300 * 300);
+    }
+
+    #[test]
+    fn scroll_respects_clip() {
+        let surface = VirtualSurface::new(100, 100);
+        fill(&surface, rect!(0, 0, 100, 100), 1);
+        fill(&surface, rect!(0, 0, 10, 10), 2);
+
+        let clip = rect!(0, 0, 15, 100);
+        surface.scroll(&rect!(0, 0, 10, 10), &clip, (10, 0));
+        assert_eq!(surface.pixel(12, 5), Some(2));
+        // Outside the clip nothing changes.
+        assert_eq!(surface.pixel(17, 5), Some(1));
+        assert_eq!(surface.pixel(5, 5), Some(2));
+    }
+
+    #[test]
+    fn scroll_moves_valid_region() {
+        let surface = VirtualSurface::new(1000, 1000);
+        fill(&surface, rect!(0, 0, 100, 100), 9);
+        fill(&surface, rect!(600, 0, 700, 100), 5);
+        // Invalid pixels scrolled over the destination invalidate it.
+        surface.scroll(&rect!(300, 0, 400, 100), None, (300, 0));
+        assert_eq!(surface.pixel(650, 50), None);
+        assert_eq!(surface.pixel(50, 50), Some(9));
+
+        surface.scroll(&rect!(0, 0, 100, 100), None, (600, 0));
+        assert_eq!(surface.pixel(650, 50), Some(9));
+        assert_eq!(surface.valid_region().area(), 2 * 100 * 100);
+    }
+}
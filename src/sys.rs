@@ -0,0 +1,121 @@
+//! Plain data types shared with the Windows API.
+//!
+//! `winapi` is empty on other platforms, so identical `#[repr(C)]`
+//! definitions stand in for them there. This keeps the portable parts of the
+//! crate, such as geometry, text and the software renderer, buildable and
+//! testable everywhere. The COM basics, `IUnknown`, `GUID` and `Interface`,
+//! are included so `ComPtr` can be tested against mock objects.
+
+#[cfg(windows)]
+pub use winapi::{
+    shared::{
+        guiddef::GUID,
+        minwindef::ULONG,
+        ntdef::HRESULT,
+        windef::{POINT, RECT, SIZE},
+        winerror::{E_INVALIDARG, E_NOINTERFACE, E_POINTER, E_UNEXPECTED, S_OK},
+    },
+    um::unknwnbase::{IUnknown, IUnknownVtbl},
+    Interface,
+};
+
+#[cfg(not(windows))]
+pub use self::fallback::*;
+
+#[cfg(not(windows))]
+#[allow(non_snake_case)]
+mod fallback {
+    use std::ffi::c_void;
+
+    pub type HRESULT = i32;
+    pub type ULONG = u32;
+
+    pub const S_OK: HRESULT = 0;
+    pub const E_NOINTERFACE: HRESULT = 0x8000_4002_u32 as HRESULT;
+    pub const E_INVALIDARG: HRESULT = 0x8007_0057_u32 as HRESULT;
+    pub const E_POINTER: HRESULT = 0x8000_4003_u32 as HRESULT;
+    pub const E_UNEXPECTED: HRESULT = 0x8000_ffff_u32 as HRESULT;
+
+    #[derive(Clone, Copy, Debug, Default)]
+    #[repr(C)]
+    pub struct GUID {
+        pub Data1: u32,
+        pub Data2: u16,
+        pub Data3: u16,
+        pub Data4: [u8; 8],
+    }
+
+    /// A COM interface, identified by its IID.
+    pub trait Interface {
+        fn uuidof() -> GUID;
+    }
+
+    #[repr(C)]
+    pub struct IUnknownVtbl {
+        pub QueryInterface: unsafe extern "system" fn(
+            This: *mut IUnknown,
+            riid: *const GUID,
+            ppvObject: *mut *mut c_void,
+        ) -> HRESULT,
+        pub AddRef: unsafe extern "system" fn(This: *mut IUnknown) -> ULONG,
+        pub Release: unsafe extern "system" fn(This: *mut IUnknown) -> ULONG,
+    }
+
+    #[repr(C)]
+    pub struct IUnknown {
+        pub lpVtbl: *const IUnknownVtbl,
+    }
+
+    impl IUnknown {
+        pub unsafe fn QueryInterface(
+            &self,
+            riid: *const GUID,
+            ppvObject: *mut *mut c_void,
+        ) -> HRESULT {
+            ((*self.lpVtbl).QueryInterface)(self as *const _ as *mut _, riid, ppvObject)
+        }
+
+        pub unsafe fn AddRef(&self) -> ULONG {
+            ((*self.lpVtbl).AddRef)(self as *const _ as *mut _)
+        }
+
+        pub unsafe fn Release(&self) -> ULONG {
+            ((*self.lpVtbl).Release)(self as *const _ as *mut _)
+        }
+    }
+
+    impl Interface for IUnknown {
+        fn uuidof() -> GUID {
+            // 00000000-0000-0000-C000-000000000046
+            GUID {
+                Data1: 0,
+                Data2: 0,
+                Data3: 0,
+                Data4: [0xc0, 0, 0, 0, 0, 0, 0, 0x46],
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, Default)]
+    #[repr(C)]
+    pub struct POINT {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    #[derive(Clone, Copy, Debug, Default)]
+    #[repr(C)]
+    pub struct SIZE {
+        pub cx: i32,
+        pub cy: i32,
+    }
+
+    #[derive(Clone, Copy, Debug, Default)]
+    #[repr(C)]
+    pub struct RECT {
+        pub left: i32,
+        pub top: i32,
+        pub right: i32,
+        pub bottom: i32,
+    }
+}
@@ -0,0 +1,979 @@
+//! The Unicode Bidirectional Algorithm (UAX #9), which orders mixed
+//! left-to-right and right-to-left text for display.
+//!
+//! The rules are implemented in full through L2, including isolates and
+//! paired brackets; characters removed by rule X9 take the level of the
+//! character before them. Character classes come from a compact table
+//! covering Latin, Greek, Cyrillic, Hebrew, Arabic, Syriac, Thaana, N'Ko and
+//! the punctuation, digits and formatting characters used with them; other
+//! characters are treated as left-to-right.
+
+/// Bidirectional character types, named as in UAX #9.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
+crate enum BidiClass {
+    L,
+    R,
+    AL,
+    EN,
+    ES,
+    ET,
+    AN,
+    CS,
+    NSM,
+    BN,
+    B,
+    S,
+    WS,
+    ON,
+    LRE,
+    LRO,
+    RLE,
+    RLO,
+    PDF,
+    LRI,
+    RLI,
+    FSI,
+    PDI,
+}
+
+use self::BidiClass::*;
+
+/// Embedding levels deeper than this overflow, as in rule BD2.
+const MAX_DEPTH: u8 = 125;
+
+/// Bracket pairs deeper than this stop the search for pairs, as in rule
+/// BD16.
+const MAX_BRACKET_DEPTH: usize = 63;
+
+/// A paired bracket, identified by its opening bracket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+crate enum Bracket {
+    Open(char),
+    Close(char),
+}
+
+/// Whether `class` is removed by rule X9.
+fn removed(class: BidiClass) -> bool {
+    matches!(class, RLE | LRE | RLO | LRO | PDF | BN)
+}
+
+fn is_isolate_initiator(class: BidiClass) -> bool {
+    matches!(class, LRI | RLI | FSI)
+}
+
+/// Neutral and isolate formatting types, which rules N1 and N2 resolve.
+fn is_neutral(class: BidiClass) -> bool {
+    matches!(class, B | S | WS | ON | LRI | RLI | FSI | PDI)
+}
+
+fn direction(level: u8) -> BidiClass {
+    if level % 2 == 0 {
+        L
+    } else {
+        R
+    }
+}
+
+/// Finds the matching PDI of every isolate initiator, as in rule BD9.
+fn matching_pdis(classes: &[BidiClass]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; classes.len()];
+    let mut open = Vec::new();
+    for (i, &class) in classes.iter().enumerate() {
+        match class {
+            LRI | RLI | FSI => open.push(i),
+            PDI => {
+                if let Some(initiator) = open.pop() {
+                    matches[initiator] = Some(i);
+                }
+            }
+            B => open.clear(),
+            _ => {}
+        }
+    }
+    matches
+}
+
+/// Finds the paragraph level from the first strong character, skipping
+/// isolates, as in rules P2 and P3. Returns `None` if there is none.
+fn first_strong_level(classes: &[BidiClass], matches: &[Option<usize>]) -> Option<u8> {
+    let mut i = 0;
+    while i < classes.len() {
+        match classes[i] {
+            L => return Some(0),
+            R | AL => return Some(1),
+            LRI | RLI | FSI => match matches[i] {
+                Some(pdi) => i = pdi,
+                None => return None,
+            },
+            B => return None,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Resolves the level of each character of a paragraph through rule I2,
+/// before any line breaking. The paragraph level comes from the text if
+/// `paragraph_level` is `None`. `brackets` is either empty or holds each
+/// character's bracket pairing. Returns the paragraph level and the levels.
+crate fn resolve_levels(
+    classes: &[BidiClass],
+    brackets: &[Option<Bracket>],
+    paragraph_level: Option<u8>,
+) -> (u8, Vec<u8>) {
+    let matches = matching_pdis(classes);
+    let paragraph_level =
+        paragraph_level.unwrap_or_else(|| first_strong_level(classes, &matches).unwrap_or(0));
+    let mut types = classes.to_vec();
+    let mut levels = vec![paragraph_level; classes.len()];
+    explicit_levels(&mut types, &mut levels, &matches, paragraph_level);
+
+    let explicit = levels.clone();
+    for sequence in isolating_run_sequences(classes, &explicit, &matches) {
+        let level = explicit[sequence[0]];
+        let neighbor = |i: Option<usize>| i.map_or(paragraph_level, |i| explicit[i]);
+        let before = classes[..sequence[0]].iter().rposition(|&c| !removed(c));
+        let last = sequence[sequence.len() - 1];
+        let after = if is_isolate_initiator(classes[last]) {
+            None
+        } else {
+            classes[last + 1..]
+                .iter()
+                .position(|&c| !removed(c))
+                .map(|i| last + 1 + i)
+        };
+        let sos = direction(level.max(neighbor(before)));
+        let eos = direction(level.max(neighbor(after)));
+
+        let mut seq_types: Vec<BidiClass> = sequence.iter().map(|&i| types[i]).collect();
+        weak_types(&mut seq_types, sos);
+        if !brackets.is_empty() {
+            let seq_brackets: Vec<_> = sequence.iter().map(|&i| brackets[i]).collect();
+            let originals: Vec<_> = sequence.iter().map(|&i| classes[i]).collect();
+            paired_brackets(&mut seq_types, &seq_brackets, &originals, level, sos);
+        }
+        neutral_types(&mut seq_types, level, sos, eos);
+
+        for (&i, &class) in sequence.iter().zip(&seq_types) {
+            levels[i] = match (levels[i] % 2, class) {
+                (0, R) => levels[i] + 1,
+                (0, AN) | (0, EN) => levels[i] + 2,
+                (1, L) | (1, AN) | (1, EN) => levels[i] + 1,
+                _ => levels[i],
+            };
+        }
+    }
+
+    // Removed characters take the level of the character before them.
+    let mut previous = paragraph_level;
+    for (level, &class) in levels.iter_mut().zip(classes) {
+        if removed(class) {
+            *level = previous;
+        }
+        previous = *level;
+    }
+    (paragraph_level, levels)
+}
+
+/// Applies rules X1 to X8, setting explicit levels and overridden types.
+fn explicit_levels(
+    types: &mut [BidiClass],
+    levels: &mut [u8],
+    matches: &[Option<usize>],
+    paragraph_level: u8,
+) {
+    #[derive(Clone, Copy)]
+    struct Status {
+        level: u8,
+        overridden: Option<BidiClass>,
+        isolate: bool,
+    }
+
+    let mut stack = vec![Status {
+        level: paragraph_level,
+        overridden: None,
+        isolate: false,
+    }];
+    let mut overflow_isolates = 0;
+    let mut overflow_embeddings = 0;
+    let mut valid_isolates = 0;
+    let next_level = |level: u8, rtl: bool| {
+        if rtl {
+            (level + 1) | 1
+        } else {
+            (level + 2) & !1
+        }
+    };
+
+    for i in 0..types.len() {
+        let top = stack[stack.len() - 1];
+        match types[i] {
+            RLE | LRE | RLO | LRO => {
+                let class = types[i];
+                let level = next_level(top.level, class == RLE || class == RLO);
+                levels[i] = top.level;
+                if level <= MAX_DEPTH && overflow_isolates == 0 && overflow_embeddings == 0 {
+                    stack.push(Status {
+                        level,
+                        overridden: match class {
+                            RLO => Some(R),
+                            LRO => Some(L),
+                            _ => None,
+                        },
+                        isolate: false,
+                    });
+                } else if overflow_isolates == 0 {
+                    overflow_embeddings += 1;
+                }
+            }
+            RLI | LRI | FSI => {
+                levels[i] = top.level;
+                let rtl = match types[i] {
+                    RLI => true,
+                    LRI => false,
+                    _ => {
+                        let end = matches[i].unwrap_or(types.len());
+                        let inner = &types[i + 1..end];
+                        first_strong_level(inner, &matching_pdis(inner)) == Some(1)
+                    }
+                };
+                if let Some(class) = top.overridden {
+                    types[i] = class;
+                }
+                let level = next_level(top.level, rtl);
+                if level <= MAX_DEPTH && overflow_isolates == 0 && overflow_embeddings == 0 {
+                    valid_isolates += 1;
+                    stack.push(Status {
+                        level,
+                        overridden: None,
+                        isolate: true,
+                    });
+                } else {
+                    overflow_isolates += 1;
+                }
+            }
+            PDI => {
+                if overflow_isolates > 0 {
+                    overflow_isolates -= 1;
+                } else if valid_isolates > 0 {
+                    overflow_embeddings = 0;
+                    while !stack[stack.len() - 1].isolate {
+                        stack.pop();
+                    }
+                    stack.pop();
+                    valid_isolates -= 1;
+                }
+                let top = stack[stack.len() - 1];
+                levels[i] = top.level;
+                if let Some(class) = top.overridden {
+                    types[i] = class;
+                }
+            }
+            PDF => {
+                levels[i] = top.level;
+                if overflow_isolates > 0 {
+                } else if overflow_embeddings > 0 {
+                    overflow_embeddings -= 1;
+                } else if !top.isolate && stack.len() >= 2 {
+                    stack.pop();
+                }
+            }
+            B => levels[i] = paragraph_level,
+            BN => levels[i] = top.level,
+            _ => {
+                levels[i] = top.level;
+                if let Some(class) = top.overridden {
+                    types[i] = class;
+                }
+            }
+        }
+    }
+}
+
+/// Splits the characters that rule X9 keeps into isolating run sequences,
+/// as in rule X10.
+fn isolating_run_sequences(
+    classes: &[BidiClass],
+    levels: &[u8],
+    matches: &[Option<usize>],
+) -> Vec<Vec<usize>> {
+    let mut runs: Vec<Vec<usize>> = Vec::new();
+    let mut run_of = vec![0; classes.len()];
+    let mut previous_level = None;
+    for i in (0..classes.len()).filter(|&i| !removed(classes[i])) {
+        if previous_level != Some(levels[i]) {
+            runs.push(Vec::new());
+        }
+        previous_level = Some(levels[i]);
+        run_of[i] = runs.len() - 1;
+        runs.last_mut().unwrap().push(i);
+    }
+
+    let mut chained = vec![false; runs.len()];
+    let mut sequences = Vec::new();
+    for start in 0..runs.len() {
+        if chained[start] {
+            continue;
+        }
+        let mut sequence = Vec::new();
+        let mut run = start;
+        loop {
+            sequence.extend_from_slice(&runs[run]);
+            let last = runs[run][runs[run].len() - 1];
+            match matches[last] {
+                Some(pdi) if is_isolate_initiator(classes[last]) && run_of[pdi] != run => {
+                    run = run_of[pdi];
+                    chained[run] = true;
+                }
+                _ => break,
+            }
+        }
+        sequences.push(sequence);
+    }
+    sequences
+}
+
+/// Applies rules W1 to W7 to an isolating run sequence.
+fn weak_types(types: &mut [BidiClass], sos: BidiClass) {
+    // W1
+    let mut previous = sos;
+    for class in types.iter_mut() {
+        if *class == NSM {
+            *class = match previous {
+                LRI | RLI | FSI | PDI => ON,
+                _ => previous,
+            };
+        }
+        previous = *class;
+    }
+
+    // W2 and W3
+    let mut strong = sos;
+    for class in types.iter_mut() {
+        match *class {
+            L | R => strong = *class,
+            AL => {
+                strong = AL;
+                *class = R;
+            }
+            EN if strong == AL => *class = AN,
+            _ => {}
+        }
+    }
+
+    // W4
+    for i in 1..types.len().saturating_sub(1) {
+        let (before, after) = (types[i - 1], types[i + 1]);
+        types[i] = match (before, types[i], after) {
+            (EN, ES, EN) | (EN, CS, EN) => EN,
+            (AN, CS, AN) => AN,
+            _ => types[i],
+        };
+    }
+
+    // W5
+    let mut i = 0;
+    while i < types.len() {
+        if types[i] != ET {
+            i += 1;
+            continue;
+        }
+        let end = types[i..]
+            .iter()
+            .position(|&c| c != ET)
+            .map_or(types.len(), |n| i + n);
+        let next_to_number =
+            (i > 0 && types[i - 1] == EN) || (end < types.len() && types[end] == EN);
+        if next_to_number {
+            for class in &mut types[i..end] {
+                *class = EN;
+            }
+        }
+        i = end;
+    }
+
+    // W6
+    for class in types.iter_mut() {
+        if let ES | ET | CS = *class {
+            *class = ON;
+        }
+    }
+
+    // W7
+    let mut strong = sos;
+    for class in types.iter_mut() {
+        match *class {
+            L | R => strong = *class,
+            EN if strong == L => *class = L,
+            _ => {}
+        }
+    }
+}
+
+/// Resolves paired brackets as in rule N0.
+fn paired_brackets(
+    types: &mut [BidiClass],
+    brackets: &[Option<Bracket>],
+    originals: &[BidiClass],
+    level: u8,
+    sos: BidiClass,
+) {
+    // BD16
+    let mut pairs = Vec::new();
+    let mut open: Vec<(char, usize)> = Vec::new();
+    for (i, bracket) in brackets.iter().enumerate() {
+        if types[i] != ON {
+            continue;
+        }
+        match *bracket {
+            Some(Bracket::Open(id)) => {
+                if open.len() == MAX_BRACKET_DEPTH {
+                    break;
+                }
+                open.push((id, i));
+            }
+            Some(Bracket::Close(id)) => {
+                if let Some(depth) = open.iter().rposition(|&(opener, _)| opener == id) {
+                    pairs.push((open[depth].1, i));
+                    open.truncate(depth);
+                }
+            }
+            None => {}
+        }
+    }
+    pairs.sort();
+
+    let embedding = direction(level);
+    let strong = |class: BidiClass| match class {
+        L => Some(L),
+        R | AN | EN => Some(R),
+        _ => None,
+    };
+    for (open, close) in pairs {
+        let inside: Vec<_> = types[open + 1..close]
+            .iter()
+            .filter_map(|&c| strong(c))
+            .collect();
+        let resolved = if inside.contains(&embedding) {
+            embedding
+        } else if inside.is_empty() {
+            continue;
+        } else {
+            let context = types[..open]
+                .iter()
+                .rev()
+                .find_map(|&c| strong(c))
+                .unwrap_or(sos);
+            if context != embedding {
+                context
+            } else {
+                embedding
+            }
+        };
+        for &bracket in &[open, close] {
+            types[bracket] = resolved;
+            // Marks following a bracket take its new direction.
+            for i in bracket + 1..types.len() {
+                if originals[i] != NSM {
+                    break;
+                }
+                types[i] = resolved;
+            }
+        }
+    }
+}
+
+/// Applies rules N1 and N2 to an isolating run sequence.
+fn neutral_types(types: &mut [BidiClass], level: u8, sos: BidiClass, eos: BidiClass) {
+    let strong = |class: BidiClass| match class {
+        L => L,
+        _ => R,
+    };
+    let mut i = 0;
+    while i < types.len() {
+        if !is_neutral(types[i]) {
+            i += 1;
+            continue;
+        }
+        let end = types[i..]
+            .iter()
+            .position(|&c| !is_neutral(c))
+            .map_or(types.len(), |n| i + n);
+        let before = if i == 0 { sos } else { strong(types[i - 1]) };
+        let after = if end == types.len() {
+            eos
+        } else {
+            strong(types[end])
+        };
+        let resolved = if before == after {
+            before
+        } else {
+            direction(level)
+        };
+        for class in &mut types[i..end] {
+            *class = resolved;
+        }
+        i = end;
+    }
+}
+
+/// Resets separators and trailing whitespace on a line to the paragraph
+/// level, as in rule L1. `classes` and `levels` cover just the line.
+crate fn reset_whitespace(classes: &[BidiClass], levels: &mut [u8], paragraph_level: u8) {
+    let whitespace = |class: BidiClass| match class {
+        WS | LRI | RLI | FSI | PDI => true,
+        class => removed(class),
+    };
+    let mut trailing = true;
+    for i in (0..classes.len()).rev() {
+        match classes[i] {
+            S | B => {
+                levels[i] = paragraph_level;
+                trailing = true;
+            }
+            class if whitespace(class) => {
+                if trailing {
+                    levels[i] = paragraph_level;
+                }
+            }
+            _ => trailing = false,
+        }
+    }
+}
+
+/// Returns the indices of a line's characters from left to right, reversing
+/// runs at each level as in rule L2.
+crate fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let highest = levels.iter().copied().max().unwrap_or(0);
+    let lowest_odd = levels.iter().copied().filter(|l| l % 2 == 1).min();
+    let lowest_odd = match lowest_odd {
+        Some(level) => level,
+        None => return order,
+    };
+    for level in (lowest_odd..=highest).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+            let end = order[i..]
+                .iter()
+                .position(|&j| levels[j] < level)
+                .map_or(order.len(), |n| i + n);
+            order[i..end].reverse();
+            i = end;
+        }
+    }
+    order
+}
+
+/// Opening and closing brackets, as paired in `BidiBrackets.txt`.
+const BRACKETS: &[(char, char)] = &[
+    ('\u{28}', '\u{29}'),
+    ('\u{5b}', '\u{5d}'),
+    ('\u{7b}', '\u{7d}'),
+    ('\u{f3c}', '\u{f3d}'),
+    ('\u{169b}', '\u{169c}'),
+    ('\u{207d}', '\u{207e}'),
+    ('\u{208d}', '\u{208e}'),
+    ('\u{230a}', '\u{230b}'),
+    ('\u{2329}', '\u{232a}'),
+    ('\u{2768}', '\u{2769}'),
+    ('\u{276a}', '\u{276b}'),
+    ('\u{276c}', '\u{276d}'),
+    ('\u{276e}', '\u{276f}'),
+    ('\u{2770}', '\u{2771}'),
+    ('\u{2772}', '\u{2773}'),
+    ('\u{2774}', '\u{2775}'),
+    ('\u{27c5}', '\u{27c6}'),
+    ('\u{27e6}', '\u{27e7}'),
+    ('\u{27e8}', '\u{27e9}'),
+    ('\u{27ea}', '\u{27eb}'),
+    ('\u{27ec}', '\u{27ed}'),
+    ('\u{27ee}', '\u{27ef}'),
+    ('\u{2983}', '\u{2984}'),
+    ('\u{2985}', '\u{2986}'),
+    ('\u{2987}', '\u{2988}'),
+    ('\u{2989}', '\u{298a}'),
+    ('\u{298b}', '\u{298c}'),
+    ('\u{298d}', '\u{2990}'),
+    ('\u{298f}', '\u{298e}'),
+    ('\u{2991}', '\u{2992}'),
+    ('\u{2993}', '\u{2994}'),
+    ('\u{2995}', '\u{2996}'),
+    ('\u{2997}', '\u{2998}'),
+    ('\u{29d8}', '\u{29d9}'),
+    ('\u{29da}', '\u{29db}'),
+    ('\u{29fc}', '\u{29fd}'),
+    ('\u{2e22}', '\u{2e23}'),
+    ('\u{2e24}', '\u{2e25}'),
+    ('\u{2e26}', '\u{2e27}'),
+    ('\u{2e28}', '\u{2e29}'),
+    ('\u{2e55}', '\u{2e56}'),
+    ('\u{2e57}', '\u{2e58}'),
+    ('\u{2e59}', '\u{2e5a}'),
+    ('\u{2e5b}', '\u{2e5c}'),
+    ('\u{3008}', '\u{3009}'),
+    ('\u{300a}', '\u{300b}'),
+    ('\u{300c}', '\u{300d}'),
+    ('\u{300e}', '\u{300f}'),
+    ('\u{3010}', '\u{3011}'),
+    ('\u{3014}', '\u{3015}'),
+    ('\u{3016}', '\u{3017}'),
+    ('\u{3018}', '\u{3019}'),
+    ('\u{301a}', '\u{301b}'),
+    ('\u{fe59}', '\u{fe5a}'),
+    ('\u{fe5b}', '\u{fe5c}'),
+    ('\u{fe5d}', '\u{fe5e}'),
+    ('\u{ff08}', '\u{ff09}'),
+    ('\u{ff3b}', '\u{ff3d}'),
+    ('\u{ff5b}', '\u{ff5d}'),
+    ('\u{ff5f}', '\u{ff60}'),
+    ('\u{ff62}', '\u{ff63}'),
+];
+
+/// Returns the bracket pairing of `ch`.
+crate fn bracket(ch: char) -> Option<Bracket> {
+    // Angle brackets are canonically equivalent to the CJK ones.
+    let ch = match ch {
+        '\u{2329}' => '\u{3008}',
+        '\u{232a}' => '\u{3009}',
+        _ => ch,
+    };
+    BRACKETS.iter().find_map(|&(open, close)| {
+        if ch == open {
+            Some(Bracket::Open(open))
+        } else if ch == close {
+            Some(Bracket::Close(open))
+        } else {
+            None
+        }
+    })
+}
+
+/// Returns the character to display for `ch` at a right-to-left level, as
+/// in rule L4. Brackets and the common mirrored symbols are swapped.
+crate fn mirror(ch: char) -> char {
+    let mirrored = match ch {
+        '<' => '>',
+        '>' => '<',
+        '\u{ab}' => '\u{bb}',
+        '\u{bb}' => '\u{ab}',
+        '\u{2039}' => '\u{203a}',
+        '\u{203a}' => '\u{2039}',
+        '\u{2208}' => '\u{220b}',
+        '\u{2209}' => '\u{220c}',
+        '\u{220a}' => '\u{220d}',
+        '\u{220b}' => '\u{2208}',
+        '\u{220c}' => '\u{2209}',
+        '\u{220d}' => '\u{220a}',
+        '\u{2264}' => '\u{2265}',
+        '\u{2265}' => '\u{2264}',
+        '\u{2266}' => '\u{2267}',
+        '\u{2267}' => '\u{2266}',
+        '\u{226a}' => '\u{226b}',
+        '\u{226b}' => '\u{226a}',
+        '\u{226e}' => '\u{226f}',
+        '\u{226f}' => '\u{226e}',
+        '\u{2282}' => '\u{2283}',
+        '\u{2283}' => '\u{2282}',
+        '\u{2286}' => '\u{2287}',
+        '\u{2287}' => '\u{2286}',
+        '\u{ff1c}' => '\u{ff1e}',
+        '\u{ff1e}' => '\u{ff1c}',
+        _ => ch,
+    };
+    if mirrored != ch {
+        return mirrored;
+    }
+    BRACKETS
+        .iter()
+        .find_map(|&(open, close)| match ch {
+            _ if ch == open => Some(close),
+            _ if ch == close => Some(open),
+            _ => None,
+        })
+        .unwrap_or(ch)
+}
+
+/// Looks up the bidirectional type of `ch`.
+crate fn class(ch: char) -> BidiClass {
+    match ch {
+        '\t' | '\u{b}' | '\u{1f}' => S,
+        '\n' | '\r' | '\u{1c}'..='\u{1e}' | '\u{85}' | '\u{2029}' => B,
+        '\u{0}'..='\u{8}' | '\u{e}'..='\u{1b}' | '\u{7f}'..='\u{84}' | '\u{86}'..='\u{9f}' => BN,
+        '\u{c}' | ' ' => WS,
+        '!' | '"' | '&' | '\'' | '(' | ')' | '*' | ';' | '<' | '=' | '>' | '?' | '@' => ON,
+        '[' | '\\' | ']' | '^' | '_' | '`' | '{' | '|' | '}' | '~' => ON,
+        '#' | '$' | '%' => ET,
+        '+' | '-' => ES,
+        ',' | '.' | '/' | ':' => CS,
+        '0'..='9' => EN,
+        '\u{a0}' => CS,
+        '\u{a1}' | '\u{a6}'..='\u{a9}' | '\u{ab}' | '\u{ac}' | '\u{ae}' | '\u{af}' => ON,
+        '\u{a2}'..='\u{a5}' | '\u{b0}' | '\u{b1}' => ET,
+        '\u{ad}' => BN,
+        '\u{b2}' | '\u{b3}' | '\u{b9}' => EN,
+        '\u{b4}' | '\u{b6}'..='\u{b8}' | '\u{bb}'..='\u{bf}' | '\u{d7}' | '\u{f7}' => ON,
+
+        '\u{2b9}'..='\u{2ba}' | '\u{2c2}'..='\u{2cf}' | '\u{2d2}'..='\u{2df}' => ON,
+        '\u{2e5}'..='\u{2ed}' | '\u{2ef}'..='\u{2ff}' | '\u{374}'..='\u{375}' | '\u{37e}' => ON,
+        '\u{384}'..='\u{385}' | '\u{387}' | '\u{3f6}' | '\u{58a}' | '\u{58d}'..='\u{58e}' => ON,
+        '\u{58f}' => ET,
+        '\u{300}'..='\u{36f}' | '\u{483}'..='\u{489}' => NSM,
+
+        '\u{591}'..='\u{5bd}' | '\u{5bf}' | '\u{5c1}'..='\u{5c2}' | '\u{5c4}'..='\u{5c5}' => NSM,
+        '\u{5c7}' => NSM,
+        '\u{590}'..='\u{5ff}' => R,
+
+        '\u{600}'..='\u{605}' | '\u{660}'..='\u{669}' | '\u{66b}'..='\u{66c}' | '\u{6dd}' => AN,
+        '\u{606}'..='\u{607}' | '\u{60e}'..='\u{60f}' | '\u{6de}' | '\u{6e9}' => ON,
+        '\u{609}'..='\u{60a}' | '\u{66a}' => ET,
+        '\u{60c}' => CS,
+        '\u{610}'..='\u{61a}'
+        | '\u{64b}'..='\u{65f}'
+        | '\u{670}'
+        | '\u{6d6}'..='\u{6dc}'
+        | '\u{6df}'..='\u{6e4}'
+        | '\u{6e7}'..='\u{6e8}'
+        | '\u{6ea}'..='\u{6ed}' => NSM,
+        '\u{6f0}'..='\u{6f9}' => EN,
+        '\u{711}' | '\u{730}'..='\u{74a}' | '\u{7a6}'..='\u{7b0}' => NSM,
+        '\u{600}'..='\u{7bf}' => AL,
+        '\u{7eb}'..='\u{7f3}' | '\u{7fd}' => NSM,
+        '\u{7f6}'..='\u{7f9}' => ON,
+        '\u{816}'..='\u{819}'
+        | '\u{81b}'..='\u{823}'
+        | '\u{825}'..='\u{827}'
+        | '\u{829}'..='\u{82d}'
+        | '\u{859}'..='\u{85b}' => NSM,
+        '\u{7c0}'..='\u{85f}' => R,
+        '\u{898}'..='\u{89f}' | '\u{8ca}'..='\u{8e1}' | '\u{8e3}'..='\u{8ff}' => NSM,
+        '\u{890}'..='\u{891}' | '\u{8e2}' => AN,
+        '\u{860}'..='\u{8ff}' => AL,
+
+        '\u{1680}' | '\u{2000}'..='\u{200a}' | '\u{2028}' | '\u{205f}' | '\u{3000}' => WS,
+        '\u{180e}'
+        | '\u{200b}'..='\u{200d}'
+        | '\u{2060}'..='\u{2064}'
+        | '\u{206a}'..='\u{206f}' => BN,
+        '\u{feff}' => BN,
+        '\u{200e}' => L,
+        '\u{200f}' => R,
+        '\u{202a}' => LRE,
+        '\u{202b}' => RLE,
+        '\u{202c}' => PDF,
+        '\u{202d}' => LRO,
+        '\u{202e}' => RLO,
+        '\u{2066}' => LRI,
+        '\u{2067}' => RLI,
+        '\u{2068}' => FSI,
+        '\u{2069}' => PDI,
+        '\u{202f}' | '\u{2044}' => CS,
+        '\u{2030}'..='\u{2034}' | '\u{20a0}'..='\u{20cf}' | '\u{2213}' | '\u{212e}' => ET,
+        '\u{2010}'..='\u{2027}' | '\u{2035}'..='\u{2043}' | '\u{2045}'..='\u{205e}' => ON,
+        '\u{2070}' | '\u{2074}'..='\u{2079}' | '\u{2080}'..='\u{2089}' => EN,
+        '\u{207a}'..='\u{207b}' | '\u{208a}'..='\u{208b}' | '\u{2212}' => ES,
+        '\u{207c}'..='\u{207e}' | '\u{208c}'..='\u{208e}' => ON,
+        '\u{20d0}'..='\u{20f0}' | '\u{302a}'..='\u{302d}' | '\u{3099}'..='\u{309a}' => NSM,
+        '\u{2100}'..='\u{2101}' | '\u{2103}'..='\u{2106}' | '\u{2108}'..='\u{2109}' => ON,
+        '\u{2114}' | '\u{2116}'..='\u{2118}' | '\u{211e}'..='\u{2123}' | '\u{2125}' => ON,
+        '\u{2127}' | '\u{2129}' | '\u{213a}'..='\u{213b}' | '\u{2140}'..='\u{2144}' => ON,
+        '\u{214a}'..='\u{214d}' | '\u{2150}'..='\u{215f}' | '\u{2189}'..='\u{218b}' => ON,
+        '\u{2460}'..='\u{2487}' | '\u{24ea}'..='\u{24ff}' => ON,
+        '\u{2190}'..='\u{2211}' | '\u{2214}'..='\u{2335}' | '\u{237b}'..='\u{2394}' => ON,
+        '\u{2396}'..='\u{2426}' | '\u{2440}'..='\u{244a}' | '\u{2500}'..='\u{26ab}' => ON,
+        '\u{2488}'..='\u{249b}' | '\u{1f100}'..='\u{1f10a}' => EN,
+        '\u{26ad}'..='\u{27ff}' | '\u{2900}'..='\u{2b73}' | '\u{2b76}'..='\u{2b95}' => ON,
+        '\u{2b97}'..='\u{2bff}' | '\u{2e00}'..='\u{2e5d}' | '\u{2e80}'..='\u{2ef3}' => ON,
+        '\u{2f00}'..='\u{2fd5}' | '\u{2ff0}'..='\u{2ffb}' | '\u{3036}'..='\u{3037}' => ON,
+        '\u{309b}'..='\u{309c}' | '\u{30a0}' | '\u{30fb}' => ON,
+        '\u{3001}'..='\u{3004}'
+        | '\u{3008}'..='\u{3020}'
+        | '\u{3030}'
+        | '\u{303d}'..='\u{303f}' => ON,
+
+        '\u{fb1e}' => NSM,
+        '\u{fb29}' => ES,
+        '\u{fb1d}'..='\u{fb4f}' => R,
+        '\u{fd3e}'..='\u{fd4f}' | '\u{fdcf}' | '\u{fdfd}'..='\u{fdff}' => ON,
+        '\u{fb50}'..='\u{fdff}' | '\u{fe70}'..='\u{fefe}' => AL,
+        '\u{fe00}'..='\u{fe0f}' | '\u{fe20}'..='\u{fe2f}' => NSM,
+        '\u{fe10}'..='\u{fe19}' | '\u{fe30}'..='\u{fe4f}' => ON,
+        '\u{fe50}' | '\u{fe52}' | '\u{fe55}' => CS,
+        '\u{fe5f}' | '\u{fe69}'..='\u{fe6a}' => ET,
+        '\u{fe62}'..='\u{fe63}' => ES,
+        '\u{fe51}' | '\u{fe54}' | '\u{fe56}'..='\u{fe5e}' | '\u{fe60}'..='\u{fe61}' => ON,
+        '\u{fe64}'..='\u{fe66}' | '\u{fe68}' | '\u{fe6b}' => ON,
+        '\u{ff03}'..='\u{ff05}' | '\u{ffe0}'..='\u{ffe1}' | '\u{ffe5}'..='\u{ffe6}' => ET,
+        '\u{ff0b}' | '\u{ff0d}' => ES,
+        '\u{ff0c}' | '\u{ff0e}'..='\u{ff0f}' | '\u{ff1a}' => CS,
+        '\u{ff10}'..='\u{ff19}' => EN,
+        '\u{ff01}'..='\u{ff02}' | '\u{ff06}'..='\u{ff0a}' | '\u{ff1b}'..='\u{ff20}' => ON,
+        '\u{ff3b}'..='\u{ff40}' | '\u{ff5b}'..='\u{ff65}' => ON,
+        '\u{ffe2}'..='\u{ffe4}' | '\u{ffe8}'..='\u{ffee}' | '\u{fff9}'..='\u{fffd}' => ON,
+        '\u{1e8d0}'..='\u{1e8d6}' | '\u{1e944}'..='\u{1e94a}' => NSM,
+        '\u{1e800}'..='\u{1e8cf}' | '\u{1e900}'..='\u{1e95f}' => R,
+        '\u{1eef0}'..='\u{1eef1}' => ON,
+        '\u{1ec70}'..='\u{1ecbf}' | '\u{1ed00}'..='\u{1ed4f}' | '\u{1ee00}'..='\u{1eeff}' => AL,
+        '\u{e0001}' | '\u{e0020}'..='\u{e007f}' => BN,
+        '\u{e0100}'..='\u{e01ef}' => NSM,
+        _ => L,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! The conformance tests run on excerpts of the Unicode 8.0.0
+    //! `BidiTest.txt` and `BidiCharacterTest.txt`, not the full files, so
+    //! passing them is evidence rather than proof of conformance. Each
+    //! excerpt's header says which cases it keeps.
+    //!
+    //! The class table isn't tied to a Unicode version, and the excerpts are
+    //! older than most; `BidiTest.txt` gives classes rather than characters,
+    //! so only `BidiCharacterTest.txt` depends on the table, and its excerpt
+    //! only uses characters the table covers, whose classes and brackets
+    //! haven't changed since 8.0.0. Cases added to the files since then
+    //! aren't tested.
+
+    use super::{bracket, class, mirror, removed, reset_whitespace, resolve_levels, visual_order};
+    use super::{BidiClass, BidiClass::*};
+
+    fn parse_class(name: &str) -> BidiClass {
+        [
+            L, R, AL, EN, ES, ET, AN, CS, NSM, BN, B, S, WS, ON, LRE, LRO, RLE, RLO, PDF, LRI, RLI,
+            FSI, PDI,
+        ]
+        .iter()
+        .copied()
+        .find(|class| format!("{:?}", class) == name)
+        .unwrap()
+    }
+
+    /// Runs the algorithm on one line of text and returns the levels, with
+    /// `None` for removed characters, and the visual order of the rest.
+    fn run(
+        classes: &[BidiClass],
+        text: &[char],
+        paragraph_level: Option<u8>,
+    ) -> (u8, Vec<Option<u8>>, Vec<usize>) {
+        let brackets: Vec<_> = text.iter().map(|&ch| bracket(ch)).collect();
+        let (paragraph_level, mut levels) = resolve_levels(classes, &brackets, paragraph_level);
+        reset_whitespace(classes, &mut levels, paragraph_level);
+        let order = visual_order(&levels)
+            .into_iter()
+            .filter(|&i| !removed(classes[i]))
+            .collect();
+        let levels = levels
+            .iter()
+            .zip(classes)
+            .map(|(&level, &class)| if removed(class) { None } else { Some(level) })
+            .collect();
+        (paragraph_level, levels, order)
+    }
+
+    fn parse_levels(levels: &str) -> Vec<Option<u8>> {
+        levels
+            .split_whitespace()
+            .map(|level| level.parse().ok())
+            .collect()
+    }
+
+    fn parse_order(order: &str) -> Vec<usize> {
+        order
+            .split_whitespace()
+            .map(|i| i.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn conformance() {
+        let mut levels = Vec::new();
+        let mut order = Vec::new();
+        let mut cases = 0;
+        for line in include_str!("testdata/BidiTest.txt").lines() {
+            if line.starts_with('#') || line.is_empty() {
+            } else if let Some(rest) = line.strip_prefix("@Levels:") {
+                levels = parse_levels(rest);
+            } else if let Some(rest) = line.strip_prefix("@Reorder:") {
+                order = parse_order(rest);
+            } else {
+                let mut fields = line.split(';');
+                let classes: Vec<_> = fields
+                    .next()
+                    .unwrap()
+                    .split_whitespace()
+                    .map(parse_class)
+                    .collect();
+                let bits: u8 = fields.next().unwrap().trim().parse().unwrap();
+                for &(bit, paragraph_level) in &[(1, None), (2, Some(0)), (4, Some(1))] {
+                    if bits & bit != 0 {
+                        let (_, actual_levels, actual_order) = run(&classes, &[], paragraph_level);
+                        assert_eq!(actual_levels, levels, "{} at {:?}", line, paragraph_level);
+                        assert_eq!(actual_order, order, "{} at {:?}", line, paragraph_level);
+                        cases += 1;
+                    }
+                }
+            }
+        }
+        assert!(cases > 10_000);
+    }
+
+    #[test]
+    fn character_conformance() {
+        let mut cases = 0;
+        for line in include_str!("testdata/BidiCharacterTest.txt").lines() {
+            if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+            let fields: Vec<_> = line.split(';').collect();
+            let text: Vec<char> = fields[0]
+                .split_whitespace()
+                .map(|code| std::char::from_u32(u32::from_str_radix(code, 16).unwrap()).unwrap())
+                .collect();
+            let classes: Vec<_> = text.iter().map(|&ch| class(ch)).collect();
+            let paragraph_level = match fields[1] {
+                "0" => Some(0),
+                "1" => Some(1),
+                _ => None,
+            };
+            let (level, levels, order) = run(&classes, &text, paragraph_level);
+            assert_eq!(level.to_string(), fields[2], "{}", line);
+            assert_eq!(levels, parse_levels(fields[3]), "{}", line);
+            assert_eq!(order, parse_order(fields[4]), "{}", line);
+            cases += 1;
+        }
+        assert!(cases > 1_000);
+    }
+
+    #[test]
+    fn lines() {
+        // Whitespace at the end of a line and before tabs goes back to the
+        // paragraph level.
+        let text: Vec<char> = "אב  \tגד  ".chars().collect();
+        let classes: Vec<_> = text.iter().map(|&ch| class(ch)).collect();
+        let (_, mut levels) = resolve_levels(&classes, &[], Some(0));
+        assert_eq!(levels, [1, 1, 1, 1, 1, 1, 1, 0, 0]);
+        reset_whitespace(&classes, &mut levels, 0);
+        assert_eq!(levels, [1, 1, 0, 0, 0, 1, 1, 0, 0]);
+        assert_eq!(visual_order(&levels), [1, 0, 2, 3, 4, 6, 5, 7, 8]);
+        assert_eq!(visual_order(&[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn mirroring() {
+        assert_eq!(mirror('('), ')');
+        assert_eq!(mirror(']'), '[');
+        assert_eq!(mirror('\u{ab}'), '\u{bb}');
+        assert_eq!(mirror('\u{2264}'), '\u{2265}');
+        assert_eq!(mirror('\u{2983}'), '\u{2984}');
+        assert_eq!(mirror('\u{298d}'), '\u{2990}');
+        assert_eq!(mirror('a'), 'a');
+        assert_eq!(bracket('\u{2329}'), bracket('\u{3008}'));
+        assert_eq!(bracket('x'), None);
+    }
+}
@@ -0,0 +1,444 @@
+//! A set of font faces loaded by the application, matched the way CSS
+//! matches `font-family`, `font-weight`, `font-style` and `font-stretch`.
+
+use super::{
+    opentype::{face_count, Face, FontError},
+    FontStretch, FontStyle, FontWeight, TextFormat,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+#[derive(Clone, Debug)]
+struct Entry {
+    face: Rc<Face>,
+    /// The file the face was read from, if it did not come from memory.
+    path: Option<PathBuf>,
+}
+
+/// Fonts loaded from files or memory, with a chain of fallback families for
+/// when a family is missing or lacks a character.
+#[derive(Clone, Debug, Default)]
+pub struct FontCollection {
+    entries: Vec<Entry>,
+    fallback: Vec<String>,
+}
+
+impl FontCollection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads every `.ttf`, `.otf`, `.ttc` and `.otc` file in `dir`, in file
+    /// name order. Subdirectories are not searched.
+    pub fn from_directory(dir: impl AsRef<Path>) -> Result<Self, FontError> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let is_font = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map_or(false, |ext| {
+                    ["ttf", "otf", "ttc", "otc"]
+                        .iter()
+                        .any(|font| ext.eq_ignore_ascii_case(font))
+                });
+            if is_font && path.is_file() {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut collection = Self::new();
+        for path in paths {
+            collection.add_file(path)?;
+        }
+        Ok(collection)
+    }
+
+    /// Adds every face in a font or collection file. Returns the number of
+    /// faces added.
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> Result<usize, FontError> {
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+        self.add(data.into(), Some(path))
+    }
+
+    /// Adds every face in an in-memory font or collection. Returns the number
+    /// of faces added.
+    pub fn add_data(&mut self, data: impl Into<Rc<[u8]>>) -> Result<usize, FontError> {
+        self.add(data.into(), None)
+    }
+
+    fn add(&mut self, data: Rc<[u8]>, path: Option<&Path>) -> Result<usize, FontError> {
+        let count = face_count(&data)?;
+        // Parse every face before adding any, so a bad face leaves the
+        // collection unchanged.
+        let faces = (0..count)
+            .map(|index| Face::parse(data.clone(), index))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.entries.extend(faces.into_iter().map(|face| Entry {
+            face: Rc::new(face),
+            path: path.map(Path::to_owned),
+        }));
+        Ok(count as usize)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The faces in the order they were added.
+    pub fn faces(&self) -> impl Iterator<Item = &Rc<Face>> {
+        self.entries.iter().map(|entry| &entry.face)
+    }
+
+    /// Each face with the file it was loaded from, or `None` for faces added
+    /// from memory.
+    pub fn sources(&self) -> impl Iterator<Item = (&Rc<Face>, Option<&Path>)> {
+        self.entries
+            .iter()
+            .map(|entry| (&entry.face, entry.path.as_deref()))
+    }
+
+    /// The distinct family names, in the order they were first added.
+    pub fn family_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for face in self.faces() {
+            if !names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(face.family_name()))
+            {
+                names.push(face.family_name());
+            }
+        }
+        names
+    }
+
+    /// Families tried, in order, after the requested one.
+    pub fn fallback(&self) -> &[String] {
+        &self.fallback
+    }
+
+    pub fn set_fallback(&mut self, families: &[&str]) {
+        self.fallback = families.iter().map(|&family| family.to_owned()).collect();
+    }
+
+    /// Picks the face of `family` closest to the requested weight, style and
+    /// stretch, using the CSS font matching algorithm. Family names are
+    /// compared case-insensitively. Returns `None` if the collection has no
+    /// face of that family.
+    pub fn match_family(
+        &self,
+        family: &str,
+        weight: FontWeight,
+        style: FontStyle,
+        stretch: FontStretch,
+    ) -> Option<&Rc<Face>> {
+        let faces: Vec<&Rc<Face>> = self
+            .faces()
+            .filter(|face| face.family_name().eq_ignore_ascii_case(family))
+            .collect();
+
+        // Stretch narrows the candidates first, then style, then weight.
+        let faces = closest(faces, |face| stretch_distance(stretch, face.stretch()));
+        let faces = closest(faces, |face| style_distance(style, face.style()));
+        let faces = closest(faces, |face| weight_distance(weight, face.weight()));
+        faces.into_iter().next()
+    }
+
+    /// Like `match_family`, but tries the fallback families in turn when the
+    /// collection has no face of `family`.
+    pub fn match_font(
+        &self,
+        family: &str,
+        weight: FontWeight,
+        style: FontStyle,
+        stretch: FontStretch,
+    ) -> Option<&Rc<Face>> {
+        self.families(family)
+            .find_map(|family| self.match_family(family, weight, style, stretch))
+    }
+
+    /// The first face along the fallback chain that has a glyph for `ch`.
+    pub fn match_char(
+        &self,
+        family: &str,
+        weight: FontWeight,
+        style: FontStyle,
+        stretch: FontStretch,
+        ch: char,
+    ) -> Option<&Rc<Face>> {
+        self.families(family)
+            .filter_map(|family| self.match_family(family, weight, style, stretch))
+            .find(|face| face.glyph_index(ch).is_some())
+    }
+
+    /// Counterpart of `dwrite::Factory::create_text_format` that measures
+    /// with the face `match_font` picks. The format keeps
+    /// `ApproximateMetrics` if no face matches.
+    pub fn create_text_format(
+        &self,
+        family_name: &str,
+        weight: FontWeight,
+        style: FontStyle,
+        stretch: FontStretch,
+        size: f32,
+        locale_name: &str,
+    ) -> TextFormat {
+        let mut format = TextFormat::new(family_name, weight, style, stretch, size, locale_name);
+        if let Some(face) = self.match_font(family_name, weight, style, stretch) {
+            format.set_font_metrics(face.clone());
+        }
+        format
+    }
+
+    fn families<'a>(&'a self, family: &'a str) -> impl Iterator<Item = &'a str> {
+        Some(family)
+            .into_iter()
+            .chain(self.fallback.iter().map(String::as_str))
+    }
+}
+
+/// Keeps the faces with the smallest key.
+fn closest<K: Ord>(faces: Vec<&Rc<Face>>, key: impl Fn(&Face) -> K) -> Vec<&Rc<Face>> {
+    let best = match faces.iter().map(|face| key(face)).min() {
+        Some(best) => best,
+        None => return faces,
+    };
+    faces.into_iter().filter(|face| key(face) == best).collect()
+}
+
+/// Exact matches first. Then, for normal or narrower requests, narrower
+/// widths from closest to furthest followed by wider ones; wider requests
+/// search the other way round.
+fn stretch_distance(wanted: FontStretch, stretch: FontStretch) -> (u8, u8) {
+    let ordinal = |stretch| match stretch {
+        FontStretch::Undefined => FontStretch::Normal as u8,
+        stretch => stretch as u8,
+    };
+    let (wanted, stretch) = (ordinal(wanted), ordinal(stretch));
+    let narrow_first = wanted <= FontStretch::Normal as u8;
+    let distance = stretch.max(wanted) - stretch.min(wanted);
+    if stretch == wanted {
+        (0, 0)
+    } else if (stretch < wanted) == narrow_first {
+        (1, distance)
+    } else {
+        (2, distance)
+    }
+}
+
+/// Italic falls back to oblique, oblique to italic, and either to normal.
+/// Normal prefers oblique over italic.
+fn style_distance(wanted: FontStyle, style: FontStyle) -> u8 {
+    use FontStyle::*;
+    let order = match wanted {
+        Normal => [Normal, Oblique, Italic],
+        Oblique => [Oblique, Italic, Normal],
+        Italic => [Italic, Oblique, Normal],
+    };
+    order.iter().position(|&s| s == style).unwrap() as u8
+}
+
+/// Between 400 and 500, heavier weights up to 500 are tried first, then
+/// lighter weights, then weights above 500. Below 400 lighter weights come
+/// first and above 500 heavier ones do.
+fn weight_distance(wanted: FontWeight, weight: FontWeight) -> (u8, u16) {
+    let (wanted, weight) = (wanted.to_numeric(), weight.to_numeric());
+    let distance = weight.max(wanted) - weight.min(wanted);
+    let group = if wanted < 400 {
+        if weight <= wanted {
+            0
+        } else {
+            1
+        }
+    } else if wanted > 500 {
+        if weight >= wanted {
+            0
+        } else {
+            1
+        }
+    } else if weight >= wanted && weight <= 500 {
+        0
+    } else if weight < wanted {
+        1
+    } else {
+        2
+    };
+    (group, distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::opentype::builder::FontBuilder;
+
+    fn collection(fonts: &[FontBuilder]) -> FontCollection {
+        let mut collection = FontCollection::new();
+        for font in fonts {
+            collection.add_data(font.build()).unwrap();
+        }
+        collection
+    }
+
+    fn weight_of(collection: &FontCollection, weight: FontWeight) -> FontWeight {
+        collection
+            .match_family("Body", weight, FontStyle::Normal, FontStretch::Normal)
+            .unwrap()
+            .weight()
+    }
+
+    #[test]
+    fn weight_matching() {
+        let fonts = collection(&[
+            FontBuilder::new("Body").weight(300),
+            FontBuilder::new("Body").weight(400),
+            FontBuilder::new("Body").weight(700),
+        ]);
+        assert_eq!(weight_of(&fonts, FontWeight::Normal), FontWeight::Normal);
+        assert_eq!(weight_of(&fonts, FontWeight::Medium), FontWeight::Normal);
+        assert_eq!(weight_of(&fonts, FontWeight::SemiBold), FontWeight::Bold);
+        assert_eq!(weight_of(&fonts, FontWeight::Black), FontWeight::Bold);
+        assert_eq!(weight_of(&fonts, FontWeight::SemiLight), FontWeight::Light);
+        assert_eq!(weight_of(&fonts, FontWeight::Thin), FontWeight::Light);
+
+        let fonts = collection(&[
+            FontBuilder::new("Body").weight(300),
+            FontBuilder::new("Body").weight(500),
+            FontBuilder::new("Body").weight(600),
+        ]);
+        assert_eq!(weight_of(&fonts, FontWeight::Normal), FontWeight::Medium);
+        let fonts = collection(&[
+            FontBuilder::new("Body").weight(300),
+            FontBuilder::new("Body").weight(600),
+        ]);
+        assert_eq!(weight_of(&fonts, FontWeight::Normal), FontWeight::Light);
+    }
+
+    #[test]
+    fn style_and_stretch_matching() {
+        let fonts = collection(&[
+            FontBuilder::new("Body"),
+            FontBuilder::new("Body").oblique(),
+            FontBuilder::new("Body").width(3),
+            FontBuilder::new("Body").width(9).italic(),
+        ]);
+        let style_of = |style| {
+            fonts
+                .match_family("body", FontWeight::Normal, style, FontStretch::Normal)
+                .unwrap()
+                .style()
+        };
+        assert_eq!(style_of(FontStyle::Italic), FontStyle::Oblique);
+        assert_eq!(style_of(FontStyle::Normal), FontStyle::Normal);
+
+        let stretch_of = |stretch| {
+            let face = fonts
+                .match_family("Body", FontWeight::Normal, FontStyle::Italic, stretch)
+                .unwrap();
+            (face.stretch(), face.style())
+        };
+        // Stretch is matched before style.
+        assert_eq!(
+            stretch_of(FontStretch::SemiCondensed),
+            (FontStretch::Condensed, FontStyle::Normal)
+        );
+        assert_eq!(
+            stretch_of(FontStretch::UltraCondensed),
+            (FontStretch::Condensed, FontStyle::Normal)
+        );
+        assert_eq!(
+            stretch_of(FontStretch::SemiExpanded),
+            (FontStretch::UltraExpanded, FontStyle::Italic)
+        );
+        assert_eq!(
+            stretch_of(FontStretch::Undefined),
+            (FontStretch::Normal, FontStyle::Oblique)
+        );
+    }
+
+    #[test]
+    fn fallback() {
+        let mut fonts = collection(&[
+            FontBuilder::new("Body").glyphs("abc", 500),
+            FontBuilder::new("Symbols").glyphs("\u{2603}", 1000),
+            FontBuilder::new("Emoji").glyphs("\u{1f600}", 1200),
+        ]);
+        assert_eq!(fonts.family_names(), ["Body", "Symbols", "Emoji"]);
+        let (weight, style, stretch) = (FontWeight::Bold, FontStyle::Normal, FontStretch::Normal);
+        assert!(fonts.match_font("Serif", weight, style, stretch).is_none());
+
+        fonts.set_fallback(&["Serif", "Emoji", "Symbols"]);
+        let family = |face: Option<&Rc<Face>>| face.map(|face| face.family_name().to_owned());
+        assert_eq!(
+            family(fonts.match_font("Serif", weight, style, stretch)),
+            Some("Emoji".to_owned())
+        );
+        assert_eq!(
+            family(fonts.match_char("Body", weight, style, stretch, 'a')),
+            Some("Body".to_owned())
+        );
+        assert_eq!(
+            family(fonts.match_char("Body", weight, style, stretch, '\u{2603}')),
+            Some("Symbols".to_owned())
+        );
+        assert_eq!(
+            family(fonts.match_char("Body", weight, style, stretch, '\u{1f600}')),
+            Some("Emoji".to_owned())
+        );
+        assert_eq!(
+            family(fonts.match_char("Body", weight, style, stretch, 'z')),
+            None
+        );
+
+        let format = fonts.create_text_format("Body", weight, style, stretch, 10.0, "en-us");
+        assert_eq!(format.font_metrics().advance('a'), 0.5);
+        assert_eq!(format.font_metrics().ascent(), 0.8);
+    }
+
+    #[test]
+    fn directory() {
+        let dir = std::env::temp_dir().join(format!("noodle-fonts-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("b.TTF"),
+            FontBuilder::new("Bold").weight(700).build(),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("a.ttc"),
+            crate::text::opentype::builder::collection(&[
+                FontBuilder::new("Body"),
+                FontBuilder::new("Body").italic(),
+            ]),
+        )
+        .unwrap();
+        fs::write(dir.join("readme.txt"), "not a font").unwrap();
+
+        let fonts = FontCollection::from_directory(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let fonts = fonts.unwrap();
+
+        assert_eq!(fonts.len(), 3);
+        assert_eq!(fonts.family_names(), ["Body", "Bold"]);
+        let sources: Vec<_> = fonts
+            .sources()
+            .map(|(face, path)| {
+                let name = path.unwrap().file_name().unwrap();
+                (face.index(), name.to_str().unwrap())
+            })
+            .collect();
+        assert_eq!(sources, [(0, "a.ttc"), (1, "a.ttc"), (0, "b.TTF")]);
+
+        let mut fonts = fonts;
+        assert!(fonts.add_data(b"junk".to_vec()).is_err());
+        assert_eq!(fonts.len(), 3);
+    }
+}
@@ -0,0 +1,655 @@
+use std::{fmt, rc::Rc, str::FromStr};
+
+/// The font weight enumeration describes common values for degree of blackness or thickness of strokes of characters in a font.
+/// Font weight values less than 1 or greater than 999 are considered to be invalid, and they are rejected by font API functions.
+///
+/// Weights serialize as their numeric values, and any value from 1 to 1000
+/// deserializes to the nearest named weight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "u16", into = "u16")
+)]
+pub enum FontWeight {
+    Thin,
+    ExtraLight,
+    Light,
+    SemiLight,
+    Normal,
+    Medium,
+    SemiBold,
+    Bold,
+    ExtraBold,
+    Black,
+    ExtraBlack,
+}
+
+/// The font stretch enumeration describes relative change from the normal aspect ratio
+/// as specified by a font designer for the glyphs in a font.
+/// Values less than 1 or greater than 9 are considered to be invalid, and they are rejected by font API functions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FontStretch {
+    Undefined,
+    UltraCondensed,
+    ExtraCondensed,
+    Condensed,
+    SemiCondensed,
+    Normal,
+    SemiExpanded,
+    Expanded,
+    ExtraExpanded,
+    UltraExpanded,
+}
+
+/// The font style enumeration describes the slope style of a font face, such as Normal, Italic or Oblique.
+/// Values other than the ones defined in the enumeration are considered to be invalid, and they are rejected by font API functions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FontStyle {
+    Normal,
+    Oblique,
+    Italic,
+}
+
+impl FontWeight {
+    /// The named weight nearest to a numeric weight, as used by CSS and the
+    /// OpenType OS/2 table. Values halfway between two names take the
+    /// heavier one.
+    pub fn from_numeric(weight: u16) -> Self {
+        use FontWeight::*;
+        match weight {
+            0..=149 => Thin,
+            150..=249 => ExtraLight,
+            250..=324 => Light,
+            325..=374 => SemiLight,
+            375..=449 => Normal,
+            450..=549 => Medium,
+            550..=649 => SemiBold,
+            650..=749 => Bold,
+            750..=849 => ExtraBold,
+            850..=924 => Black,
+            _ => ExtraBlack,
+        }
+    }
+
+    /// The numeric weight, from 100 for `Thin` to 950 for `ExtraBlack`.
+    pub fn to_numeric(self) -> u16 {
+        use FontWeight::*;
+        match self {
+            Thin => 100,
+            ExtraLight => 200,
+            Light => 300,
+            SemiLight => 350,
+            Normal => 400,
+            Medium => 500,
+            SemiBold => 600,
+            Bold => 700,
+            ExtraBold => 800,
+            Black => 900,
+            ExtraBlack => 950,
+        }
+    }
+}
+
+impl From<u16> for FontWeight {
+    fn from(weight: u16) -> Self {
+        FontWeight::from_numeric(weight)
+    }
+}
+
+impl From<FontWeight> for u16 {
+    fn from(weight: FontWeight) -> Self {
+        weight.to_numeric()
+    }
+}
+
+/// Parses a CSS `font-weight`: `normal`, `bold` or a number from 1 to 1000,
+/// which goes to the nearest named weight. The relative `bolder` and
+/// `lighter` are rejected as they need the parent's weight.
+impl FromStr for FontWeight {
+    type Err = ParseFontError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("normal") {
+            Ok(FontWeight::Normal)
+        } else if s.eq_ignore_ascii_case("bold") {
+            Ok(FontWeight::Bold)
+        } else {
+            match s.parse::<f32>() {
+                Ok(weight) if (1.0..=1000.0).contains(&weight) => {
+                    Ok(FontWeight::from_numeric(weight.round() as u16))
+                }
+                _ => Err(ParseFontError("font-weight")),
+            }
+        }
+    }
+}
+
+/// Keywords of `FontStretch` in CSS, with their percentages.
+const STRETCHES: [(FontStretch, &str, f32); 9] = [
+    (FontStretch::UltraCondensed, "ultra-condensed", 50.0),
+    (FontStretch::ExtraCondensed, "extra-condensed", 62.5),
+    (FontStretch::Condensed, "condensed", 75.0),
+    (FontStretch::SemiCondensed, "semi-condensed", 87.5),
+    (FontStretch::Normal, "normal", 100.0),
+    (FontStretch::SemiExpanded, "semi-expanded", 112.5),
+    (FontStretch::Expanded, "expanded", 125.0),
+    (FontStretch::ExtraExpanded, "extra-expanded", 150.0),
+    (FontStretch::UltraExpanded, "ultra-expanded", 200.0),
+];
+
+impl FontStretch {
+    /// The stretch with a numeric value from 1 for `UltraCondensed` to 9 for
+    /// `UltraExpanded`, as in DirectWrite and the OpenType OS/2 table. Other
+    /// values are `Undefined`.
+    pub fn from_numeric(stretch: u16) -> Self {
+        match stretch {
+            1..=9 => STRETCHES[usize::from(stretch) - 1].0,
+            _ => FontStretch::Undefined,
+        }
+    }
+
+    /// The numeric value, zero for `Undefined`.
+    pub fn to_numeric(self) -> u16 {
+        self as u16
+    }
+
+    /// The stretch nearest to a CSS width percentage. Values halfway
+    /// between two keywords take the wider one.
+    pub fn from_percentage(percentage: f32) -> Self {
+        let mut nearest = STRETCHES[0];
+        for &stretch in &STRETCHES[1..] {
+            if (percentage - stretch.2).abs() <= (percentage - nearest.2).abs() {
+                nearest = stretch;
+            }
+        }
+        nearest.0
+    }
+
+    /// The CSS width percentage, 100 for `Undefined`.
+    pub fn to_percentage(self) -> f32 {
+        match self {
+            FontStretch::Undefined => 100.0,
+            stretch => STRETCHES[stretch as usize - 1].2,
+        }
+    }
+}
+
+/// Parses a CSS `font-stretch`: a keyword such as `semi-condensed`, or a
+/// non-negative percentage, which goes to the nearest keyword.
+impl FromStr for FontStretch {
+    type Err = ParseFontError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(percentage) = s.strip_suffix('%') {
+            return match percentage.parse::<f32>() {
+                Ok(percentage) if percentage >= 0.0 => Ok(FontStretch::from_percentage(percentage)),
+                _ => Err(ParseFontError("font-stretch")),
+            };
+        }
+        STRETCHES
+            .iter()
+            .find(|(_, keyword, _)| s.eq_ignore_ascii_case(keyword))
+            .map(|&(stretch, _, _)| stretch)
+            .ok_or(ParseFontError("font-stretch"))
+    }
+}
+
+/// Parses a CSS `font-style`: `normal`, `italic` or `oblique`. An angle
+/// after `oblique` is accepted and ignored.
+impl FromStr for FontStyle {
+    type Err = ParseFontError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<_> = s.split_whitespace().map(str::to_ascii_lowercase).collect();
+        let words: Vec<_> = words.iter().map(String::as_str).collect();
+        let is_angle = |angle: &str| {
+            angle
+                .strip_suffix("deg")
+                .map_or(false, |degrees| degrees.parse::<f32>().is_ok())
+        };
+        match words[..] {
+            ["normal"] => Ok(FontStyle::Normal),
+            ["italic"] => Ok(FontStyle::Italic),
+            ["oblique"] => Ok(FontStyle::Oblique),
+            ["oblique", angle] if is_angle(angle) => Ok(FontStyle::Oblique),
+            _ => Err(ParseFontError("font-style")),
+        }
+    }
+}
+
+/// A string is not a valid value of the CSS font property it names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseFontError(&'static str);
+
+impl ParseFontError {
+    /// The CSS property, such as `font-weight`.
+    pub fn property(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl fmt::Display for ParseFontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid {} value", self.0)
+    }
+}
+
+impl std::error::Error for ParseFontError {}
+
+/// Alignment of paragraph text along the reading direction axis relative to
+/// the leading and trailing edge of the layout box.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextAlignment {
+    /// The leading edge of the paragraph text is aligned to the layout box's leading edge.
+    Leading,
+    /// The trailing edge of the paragraph text is aligned to the layout box's trailing edge.
+    Trailing,
+    /// The center of the paragraph text is aligned to the center of the layout box.
+    Center,
+    /// Align text to the leading side, and also justify text to fill the lines.
+    Justified,
+}
+
+/// Alignment of paragraph text along the flow direction axis relative to the
+/// flow's beginning and ending edge of the layout box.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ParagraphAlignment {
+    /// The first line of paragraph is aligned to the flow's beginning edge of the layout box.
+    Near,
+    /// The last line of paragraph is aligned to the flow's ending edge of the layout box.
+    Far,
+    /// The center of the paragraph is aligned to the center of the flow of the layout box.
+    Center,
+}
+
+/// Direction in which characters of a paragraph progress along a line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReadingDirection {
+    LeftToRight,
+    RightToLeft,
+}
+
+impl ReadingDirection {
+    /// The direction of the script a locale is usually written in, such as
+    /// right-to-left for `ar-eg` or `he`. A script subtag, as in `az-arab`,
+    /// takes precedence over the language.
+    pub fn for_locale(locale_name: &str) -> Self {
+        let mut subtags = locale_name
+            .split(&['-', '_'][..])
+            .map(|subtag| subtag.to_ascii_lowercase());
+        let language = subtags.next().unwrap_or_default();
+        let script = subtags.find(|subtag| subtag.len() == 4);
+        let rtl = match script.as_deref() {
+            Some("arab") | Some("hebr") | Some("syrc") | Some("thaa") | Some("nkoo")
+            | Some("adlm") | Some("rohg") | Some("mand") | Some("samr") => true,
+            Some(_) => false,
+            None => matches!(
+                language.as_str(),
+                "ar" | "arc"
+                    | "ckb"
+                    | "dv"
+                    | "fa"
+                    | "he"
+                    | "iw"
+                    | "ks"
+                    | "ku"
+                    | "ps"
+                    | "sd"
+                    | "syr"
+                    | "ug"
+                    | "ur"
+                    | "yi"
+            ),
+        };
+        if rtl {
+            ReadingDirection::RightToLeft
+        } else {
+            ReadingDirection::LeftToRight
+        }
+    }
+}
+
+/// Where text that overflows the layout box may be cut.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TrimmingGranularity {
+    /// No trimming occurs; text overflows the layout box.
+    None,
+    /// Trimming occurs at character cluster boundaries.
+    Character,
+    /// Trimming occurs at word boundaries.
+    Word,
+}
+
+/// How text that overflows the layout box is trimmed, mirroring
+/// `DWRITE_TRIMMING`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Trimming {
+    pub granularity: TrimmingGranularity,
+    /// Whether an ellipsis marks where text was cut. DirectWrite draws the
+    /// trimming sign passed along with the options instead.
+    pub ellipsis: bool,
+    /// Character separating the parts of a path. When set, the text is cut
+    /// in the middle and everything from the `delimiter_count`th delimiter
+    /// from the end is kept, so `C:\Users\a\file.txt` might become
+    /// `C:\Us…\file.txt`.
+    pub delimiter: Option<char>,
+    pub delimiter_count: u32,
+}
+
+impl Trimming {
+    /// Cuts the end of the text at `granularity` and shows an ellipsis.
+    pub fn ellipsis(granularity: TrimmingGranularity) -> Self {
+        Self {
+            granularity,
+            ellipsis: true,
+            delimiter: None,
+            delimiter_count: 0,
+        }
+    }
+}
+
+impl Default for Trimming {
+    fn default() -> Self {
+        Self {
+            granularity: TrimmingGranularity::None,
+            ellipsis: false,
+            delimiter: None,
+            delimiter_count: 0,
+        }
+    }
+}
+
+/// Supplies glyph advances and vertical metrics to `TextLayout`.
+///
+/// All values are in ems, so one provider serves every font size.
+pub trait FontMetrics {
+    /// Horizontal advance of the glyph for `ch`.
+    fn advance(&self, ch: char) -> f32;
+
+    /// Distance from the baseline to the top of a line.
+    fn ascent(&self) -> f32;
+
+    /// Distance from the baseline to the bottom of a line.
+    fn descent(&self) -> f32;
+
+    /// Extra space between lines, added below the descent.
+    fn line_gap(&self) -> f32 {
+        0.0
+    }
+
+    /// Adjustment to the advance of `left` when `right` follows it.
+    fn kerning(&self, _left: char, _right: char) -> f32 {
+        0.0
+    }
+}
+
+/// Metrics that need no font file: spaces are a quarter em, East Asian wide
+/// characters a full em and everything else half an em.
+///
+/// Good enough to exercise layout logic; use real font metrics to match what
+/// DirectWrite draws.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ApproximateMetrics;
+
+impl FontMetrics for ApproximateMetrics {
+    fn advance(&self, ch: char) -> f32 {
+        match ch {
+            ' ' | '\u{a0}' => 0.25,
+            '\t' => 1.0,
+            c if c.is_control() => 0.0,
+            '\u{1100}'..='\u{115f}'
+            | '\u{2e80}'..='\u{a4cf}'
+            | '\u{ac00}'..='\u{d7a3}'
+            | '\u{f900}'..='\u{faff}'
+            | '\u{ff00}'..='\u{ff60}' => 1.0,
+            _ => 0.5,
+        }
+    }
+
+    fn ascent(&self) -> f32 {
+        0.8
+    }
+
+    fn descent(&self) -> f32 {
+        0.2
+    }
+}
+
+/// Portable counterpart of `dwrite::TextFormat`, used by `TextLayout`.
+#[derive(Clone)]
+pub struct TextFormat {
+    family_name: String,
+    weight: FontWeight,
+    style: FontStyle,
+    stretch: FontStretch,
+    size: f32,
+    locale_name: String,
+    text_alignment: TextAlignment,
+    paragraph_alignment: ParagraphAlignment,
+    reading_direction: ReadingDirection,
+    trimming: Trimming,
+    max_lines: Option<u32>,
+    metrics: Rc<dyn FontMetrics>,
+}
+
+impl TextFormat {
+    /// Takes the same arguments as `dwrite::Factory::create_text_format`.
+    /// Until `set_font_metrics` is called the format measures with
+    /// `ApproximateMetrics`. Like DirectWrite, the reading direction starts
+    /// out left-to-right whatever the locale.
+    pub fn new(
+        family_name: &str,
+        weight: FontWeight,
+        style: FontStyle,
+        stretch: FontStretch,
+        size: f32,
+        locale_name: &str,
+    ) -> Self {
+        Self {
+            family_name: family_name.to_owned(),
+            weight,
+            style,
+            stretch,
+            size,
+            locale_name: locale_name.to_owned(),
+            text_alignment: TextAlignment::Leading,
+            paragraph_alignment: ParagraphAlignment::Near,
+            reading_direction: ReadingDirection::LeftToRight,
+            trimming: Trimming::default(),
+            max_lines: None,
+            metrics: Rc::new(ApproximateMetrics),
+        }
+    }
+
+    pub fn family_name(&self) -> &str {
+        &self.family_name
+    }
+
+    pub fn weight(&self) -> FontWeight {
+        self.weight
+    }
+
+    pub fn style(&self) -> FontStyle {
+        self.style
+    }
+
+    pub fn stretch(&self) -> FontStretch {
+        self.stretch
+    }
+
+    /// Font size in DIPs.
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+
+    pub fn locale_name(&self) -> &str {
+        &self.locale_name
+    }
+
+    pub fn text_alignment(&self) -> TextAlignment {
+        self.text_alignment
+    }
+
+    /// Set alignment option of text relative to layout box's leading and trailing edge.
+    pub fn set_text_alignment(&mut self, alignment: TextAlignment) {
+        self.text_alignment = alignment;
+    }
+
+    pub fn paragraph_alignment(&self) -> ParagraphAlignment {
+        self.paragraph_alignment
+    }
+
+    /// Set alignment option of paragraph relative to layout box's top and bottom edge.
+    pub fn set_paragraph_alignment(&mut self, alignment: ParagraphAlignment) {
+        self.paragraph_alignment = alignment;
+    }
+
+    pub fn reading_direction(&self) -> ReadingDirection {
+        self.reading_direction
+    }
+
+    /// Set the base direction of paragraphs, which also decides which edge
+    /// of the layout box is leading.
+    pub fn set_reading_direction(&mut self, direction: ReadingDirection) {
+        self.reading_direction = direction;
+    }
+
+    pub fn trimming(&self) -> Trimming {
+        self.trimming
+    }
+
+    /// Set how text is trimmed when it overflows the layout box, either by
+    /// running past its bottom edge or past `max_lines`.
+    pub fn set_trimming(&mut self, trimming: Trimming) {
+        self.trimming = trimming;
+    }
+
+    pub fn max_lines(&self) -> Option<u32> {
+        self.max_lines
+    }
+
+    /// Limit layouts to `max_lines` lines, dropping the text after them.
+    /// DirectWrite has no such option; the portable layout trims the last
+    /// line as if the text had overflowed the box there.
+    pub fn set_max_lines(&mut self, max_lines: Option<u32>) {
+        self.max_lines = max_lines;
+    }
+
+    pub fn font_metrics(&self) -> &dyn FontMetrics {
+        &*self.metrics
+    }
+
+    pub fn set_font_metrics(&mut self, metrics: Rc<dyn FontMetrics>) {
+        self.metrics = metrics;
+    }
+}
+
+impl fmt::Debug for TextFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TextFormat")
+            .field("family_name", &self.family_name)
+            .field("weight", &self.weight)
+            .field("style", &self.style)
+            .field("stretch", &self.stretch)
+            .field("size", &self.size)
+            .field("locale_name", &self.locale_name)
+            .field("text_alignment", &self.text_alignment)
+            .field("paragraph_alignment", &self.paragraph_alignment)
+            .field("reading_direction", &self.reading_direction)
+            .field("trimming", &self.trimming)
+            .field("max_lines", &self.max_lines)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_weights() {
+        for weight in (1..=1000).map(FontWeight::from_numeric) {
+            assert_eq!(FontWeight::from_numeric(weight.to_numeric()), weight);
+        }
+        assert_eq!(FontWeight::from_numeric(350), FontWeight::SemiLight);
+        assert_eq!(FontWeight::from_numeric(450), FontWeight::Medium);
+        assert_eq!(FontWeight::from_numeric(449), FontWeight::Normal);
+        assert_eq!(FontWeight::from_numeric(1000), FontWeight::ExtraBlack);
+        assert_eq!(FontWeight::Bold.to_numeric(), 700);
+    }
+
+    #[test]
+    fn numeric_stretches() {
+        for n in 0..=9 {
+            assert_eq!(FontStretch::from_numeric(n).to_numeric(), n);
+        }
+        assert_eq!(FontStretch::from_numeric(10), FontStretch::Undefined);
+        assert_eq!(FontStretch::from_percentage(80.0), FontStretch::Condensed);
+        assert_eq!(
+            FontStretch::from_percentage(81.25),
+            FontStretch::SemiCondensed
+        );
+        assert_eq!(
+            FontStretch::from_percentage(0.0),
+            FontStretch::UltraCondensed
+        );
+        assert_eq!(
+            FontStretch::from_percentage(400.0),
+            FontStretch::UltraExpanded
+        );
+        assert_eq!(FontStretch::SemiExpanded.to_percentage(), 112.5);
+    }
+
+    #[test]
+    fn css_parsing() {
+        assert_eq!("bold".parse(), Ok(FontWeight::Bold));
+        assert_eq!(" Normal ".parse(), Ok(FontWeight::Normal));
+        assert_eq!("350".parse(), Ok(FontWeight::SemiLight));
+        assert_eq!("649.5".parse(), Ok(FontWeight::Bold));
+        let invalid = Err(ParseFontError("font-weight"));
+        assert_eq!("0".parse::<FontWeight>(), invalid);
+        assert_eq!("1001".parse::<FontWeight>(), invalid);
+        assert_eq!("bolder".parse::<FontWeight>(), invalid);
+
+        assert_eq!("semi-condensed".parse(), Ok(FontStretch::SemiCondensed));
+        assert_eq!("ULTRA-EXPANDED".parse(), Ok(FontStretch::UltraExpanded));
+        assert_eq!("87.5%".parse(), Ok(FontStretch::SemiCondensed));
+        assert_eq!("130%".parse(), Ok(FontStretch::Expanded));
+        let invalid = Err(ParseFontError("font-stretch"));
+        assert_eq!("-10%".parse::<FontStretch>(), invalid);
+        assert_eq!("wide".parse::<FontStretch>(), invalid);
+
+        assert_eq!("italic".parse(), Ok(FontStyle::Italic));
+        assert_eq!("oblique".parse(), Ok(FontStyle::Oblique));
+        assert_eq!("oblique 10deg".parse(), Ok(FontStyle::Oblique));
+        let invalid = Err(ParseFontError("font-style"));
+        assert_eq!("oblique 10".parse::<FontStyle>(), invalid);
+        assert_eq!("italic bold".parse::<FontStyle>(), invalid);
+        let error = "italic bold".parse::<FontStyle>().unwrap_err();
+        assert_eq!(error.to_string(), "invalid font-style value");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        assert_eq!(
+            serde_json::to_string(&FontWeight::SemiLight).unwrap(),
+            "350"
+        );
+        let weight: FontWeight = serde_json::from_str("580").unwrap();
+        assert_eq!(weight, FontWeight::SemiBold);
+        let stretch = serde_json::to_string(&FontStretch::Condensed).unwrap();
+        assert_eq!(
+            serde_json::from_str::<FontStretch>(&stretch).unwrap(),
+            FontStretch::Condensed
+        );
+    }
+}
@@ -0,0 +1,1522 @@
+//! A portable implementation of DirectWrite's text layout queries.
+//!
+//! Positions and lengths count UTF-16 code units, as in DirectWrite, so
+//! results can be compared with `dwrite::TextLayout` directly. Lines break
+//! where UAX #14 allows, or between characters when a word does not fit on
+//! its own, and are placed in the layout box as the format's text and
+//! paragraph alignment ask. Whitespace at the end of a line hangs past the
+//! edge and is ignored when aligning.
+//!
+//! Paragraphs take their base direction from the format's reading direction
+//! and mixed directions are resolved with the Unicode Bidirectional
+//! Algorithm. Lines are broken in logical order, then each line is reordered
+//! for display and right-to-left characters are drawn with their mirrored
+//! forms. Leading and trailing alignment follow the reading direction.
+//!
+//! Lines past the format's line limit, or past the bottom of the box when
+//! the format trims, are dropped. The last line kept is cut to make room for
+//! an ellipsis and takes over the dropped text, hidden, so every text
+//! position still belongs to a line.
+
+use super::{
+    bidi::{self, BidiClass},
+    line_breaks, BreakOpportunity, ParagraphAlignment, ReadingDirection, RichText, TextAlignment,
+    TextFormat, TextRun, TrimmingGranularity,
+};
+use std::ops::Range;
+
+/// Information about a formatted line of text.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LineMetrics {
+    /// Number of text positions in the line, including trailing whitespace
+    /// and newline characters.
+    pub length: u32,
+    /// Number of whitespace positions at the end of the line. Newline
+    /// sequences count as whitespace.
+    pub trailing_whitespace_length: u32,
+    /// Number of positions taken by the newline sequence ending the line, or
+    /// zero if the line was wrapped or ends the text.
+    pub newline_length: u32,
+    /// Height of the line, from the top of the highest glyph to the bottom
+    /// of the lowest, plus the line gap.
+    pub height: f32,
+    /// Distance from the top of the line to its baseline.
+    pub baseline: f32,
+    /// Whether the line is trimmed.
+    pub is_trimmed: bool,
+}
+
+/// Overall metrics of a formatted block of text.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextMetrics {
+    /// Left-most point of the text, relative to the layout box.
+    pub left: f32,
+    /// Top-most point of the text, relative to the layout box.
+    pub top: f32,
+    /// Width of the widest line, ignoring trailing whitespace.
+    pub width: f32,
+    /// Width of the widest line, including trailing whitespace.
+    pub width_including_trailing_whitespace: f32,
+    /// Height of all lines together.
+    pub height: f32,
+    /// Width of the layout box.
+    pub layout_width: f32,
+    /// Height of the layout box.
+    pub layout_height: f32,
+    /// Number of lines, which is never zero.
+    pub line_count: u32,
+}
+
+/// Geometry of a range of text, as returned by hit testing.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HitTestMetrics {
+    /// First text position of the range.
+    pub text_position: u32,
+    /// Number of text positions in the range.
+    pub length: u32,
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Bidi level of the range; even levels are left-to-right.
+    pub bidi_level: u32,
+    /// Whether the range holds text rather than an inline object.
+    pub is_text: bool,
+    /// Whether the range is trimmed.
+    pub is_trimmed: bool,
+}
+
+/// A glyph placed on a line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionedGlyph {
+    /// The character to draw, which is mirrored at right-to-left levels.
+    pub ch: char,
+    pub text_position: u32,
+    /// Left edge of the glyph, relative to the layout box.
+    pub x: f32,
+    /// Advance of the glyph, which includes any space added by
+    /// justification.
+    pub advance: f32,
+    /// Index of the glyph's style in `TextLayout::text_runs`.
+    pub run: usize,
+    /// Bidi level of the glyph; even levels are left-to-right.
+    pub bidi_level: u32,
+}
+
+/// A line of text placed in the layout box, ready for drawing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineRun {
+    pub text_position: u32,
+    /// Number of text positions in the line, as in `LineMetrics`.
+    pub length: u32,
+    pub left: f32,
+    pub top: f32,
+    /// Position of the baseline, relative to the layout box.
+    pub baseline: f32,
+    /// Width of the line, ignoring trailing whitespace.
+    pub width: f32,
+    /// Glyphs of the line from left to right, not including the newline.
+    pub glyphs: Vec<PositionedGlyph>,
+}
+
+/// Result of `TextLayout::hit_test_point`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HitTestPoint {
+    /// Whether the point is nearer the trailing edge of the character it hit,
+    /// meaning a caret belongs after that character.
+    pub is_trailing_hit: bool,
+    /// Whether the point is on the text, rather than above, below or beside
+    /// it.
+    pub is_inside: bool,
+    /// The character nearest to the point.
+    pub metrics: HitTestMetrics,
+}
+
+/// A step for `TextLayout::move_caret`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaretMovement {
+    /// To the next position in the text.
+    Next,
+    /// To the previous position in the text.
+    Previous,
+    /// To the position displayed next to the left, continuing on the
+    /// adjacent line at the end of a line.
+    Left,
+    /// To the position displayed next to the right, continuing on the
+    /// adjacent line at the end of a line.
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ClusterKind {
+    Text,
+    Whitespace,
+    Newline,
+}
+
+/// One character, or a CR LF pair, with its position in the text.
+#[derive(Clone, Copy, Debug)]
+struct Cluster {
+    /// The character to draw, mirrored if the level is right-to-left.
+    ch: char,
+    position: u32,
+    length: u32,
+    kind: ClusterKind,
+    /// Whether a line may break after this cluster.
+    break_after: bool,
+    /// Index into the layout's text runs.
+    run: usize,
+    class: BidiClass,
+    level: u8,
+    advance: f32,
+    /// Distance from the left edge of the line.
+    x: f32,
+    /// Whether trimming hid the cluster.
+    trimmed: bool,
+}
+
+#[derive(Clone, Debug)]
+struct Line {
+    clusters: Range<usize>,
+    /// Indices of the line's clusters from left to right.
+    order: Vec<usize>,
+    left: f32,
+    top: f32,
+    width: f32,
+    width_including_trailing_whitespace: f32,
+    metrics: LineMetrics,
+}
+
+/// Text formatted into lines within a layout box.
+#[derive(Clone, Debug)]
+pub struct TextLayout {
+    clusters: Vec<Cluster>,
+    /// Always holds at least one line, which may be empty.
+    lines: Vec<Line>,
+    text_length: u32,
+    text_runs: Vec<TextRun>,
+    font_size: f32,
+    paragraph_level: u8,
+    max_width: f32,
+    max_height: f32,
+}
+
+impl TextLayout {
+    /// Lays out `text` with `format` in a box of `max_width` by `max_height`
+    /// DIPs. Text that does not fit vertically is kept and overflows the box,
+    /// unless the format trims it.
+    pub fn new(text: &str, format: &TextFormat, max_width: f32, max_height: f32) -> Self {
+        Self::with_rich_text(&RichText::new(text), format, max_width, max_height)
+    }
+
+    /// Lays out rich text, measuring every run with the format's font
+    /// metrics at the run's size, plus its letter spacing. Each line is as
+    /// tall as its largest run.
+    pub fn with_rich_text(
+        text: &RichText,
+        format: &TextFormat,
+        max_width: f32,
+        max_height: f32,
+    ) -> Self {
+        let text_runs = text.resolve(format);
+        let mut clusters = clusters(text.text(), format, &text_runs);
+        let text_length = clusters.last().map_or(0, |c| c.position + c.length);
+
+        let font = format.font_metrics();
+        let alignment = format.text_alignment();
+        let paragraph_level = paragraph_level(format.reading_direction());
+        let rtl = paragraph_level == 1;
+
+        let mut ranges = break_lines(&clusters, max_width);
+        let trimmed = trim(
+            &mut clusters,
+            &mut ranges,
+            format,
+            &text_runs,
+            paragraph_level,
+            max_width,
+            max_height,
+        );
+
+        let mut lines = Vec::new();
+        let mut top = 0.0;
+        for range in ranges {
+            // Only the last line takes over dropped text.
+            let is_trimmed = trimmed && range.end == clusters.len();
+
+            // Justification stretches the spaces between words on every
+            // line but the last of a paragraph.
+            let ends_paragraph =
+                range.end == clusters.len() || clusters[range.end - 1].kind == ClusterKind::Newline;
+            if alignment == TextAlignment::Justified && !ends_paragraph && !is_trimmed {
+                justify(&mut clusters[range.clone()], max_width);
+            }
+
+            // Whitespace at the end of the line goes back to the paragraph
+            // level before the line is reordered.
+            let line_clusters = &mut clusters[range.clone()];
+            let classes: Vec<_> = line_clusters.iter().map(|c| c.class).collect();
+            let mut levels: Vec<_> = line_clusters.iter().map(|c| c.level).collect();
+            bidi::reset_whitespace(&classes, &mut levels, paragraph_level);
+            for (cluster, &level) in line_clusters.iter_mut().zip(&levels) {
+                cluster.level = level;
+            }
+            let order: Vec<usize> = bidi::visual_order(&levels)
+                .into_iter()
+                .map(|i| range.start + i)
+                .collect();
+
+            let trailing = line_clusters
+                .iter()
+                .rev()
+                .take_while(|c| c.kind != ClusterKind::Text)
+                .count();
+            let text_end = line_clusters.len() - trailing;
+            let width: f32 = line_clusters[..text_end].iter().map(|c| c.advance).sum();
+            let trailing_width: f32 = line_clusters[text_end..].iter().map(|c| c.advance).sum();
+            let trailing_whitespace_length =
+                line_clusters[text_end..].iter().map(|c| c.length).sum();
+
+            // Trailing whitespace hangs past the trailing edge, which is on
+            // the left in right-to-left paragraphs.
+            let mut x = if rtl { -trailing_width } else { 0.0 };
+            for &i in &order {
+                clusters[i].x = x;
+                x += clusters[i].advance;
+            }
+
+            let line_clusters = &clusters[range.clone()];
+            let newline_length = line_clusters
+                .last()
+                .filter(|c| c.kind == ClusterKind::Newline)
+                .map_or(0, |c| c.length);
+
+            let size = line_size(line_clusters, &text_runs);
+            let height = (font.ascent() + font.descent() + font.line_gap()) * size;
+            let baseline = font.ascent() * size;
+
+            let left = match alignment {
+                TextAlignment::Leading | TextAlignment::Justified if rtl => max_width - width,
+                TextAlignment::Trailing if !rtl => max_width - width,
+                TextAlignment::Center => (max_width - width) / 2.0,
+                _ => 0.0,
+            };
+
+            lines.push(Line {
+                clusters: range,
+                order,
+                left,
+                top,
+                width,
+                width_including_trailing_whitespace: width + trailing_width,
+                metrics: LineMetrics {
+                    length: line_clusters.iter().map(|c| c.length).sum(),
+                    trailing_whitespace_length,
+                    newline_length,
+                    height,
+                    baseline,
+                    is_trimmed,
+                },
+            });
+            top += height;
+        }
+
+        let offset = match format.paragraph_alignment() {
+            ParagraphAlignment::Near => 0.0,
+            ParagraphAlignment::Far => max_height - top,
+            ParagraphAlignment::Center => (max_height - top) / 2.0,
+        };
+        for line in &mut lines {
+            line.top += offset;
+        }
+
+        Self {
+            clusters,
+            lines,
+            text_length,
+            text_runs,
+            font_size: format.size(),
+            paragraph_level,
+            max_width,
+            max_height,
+        }
+    }
+
+    pub fn max_width(&self) -> f32 {
+        self.max_width
+    }
+
+    pub fn max_height(&self) -> f32 {
+        self.max_height
+    }
+
+    /// Size of the format's font in DIPs.
+    pub fn font_size(&self) -> f32 {
+        self.font_size
+    }
+
+    /// Runs of uniformly styled text, in order.
+    pub fn text_runs(&self) -> &[TextRun] {
+        &self.text_runs
+    }
+
+    /// Metrics for each line, in order.
+    pub fn line_metrics(&self) -> Vec<LineMetrics> {
+        self.lines.iter().map(|line| line.metrics).collect()
+    }
+
+    /// The lines with their glyphs, in order.
+    pub fn runs(&self) -> Vec<LineRun> {
+        self.lines
+            .iter()
+            .map(|line| LineRun {
+                text_position: self
+                    .clusters
+                    .get(line.clusters.start)
+                    .map_or(self.text_length, |c| c.position),
+                length: line.metrics.length,
+                left: line.left,
+                top: line.top,
+                baseline: line.top + line.metrics.baseline,
+                width: line.width,
+                glyphs: self
+                    .visible_order(line)
+                    .map(|c| PositionedGlyph {
+                        ch: c.ch,
+                        text_position: c.position,
+                        x: line.left + c.x,
+                        advance: c.advance,
+                        run: c.run,
+                        bidi_level: u32::from(c.level),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Whether text was cut to fit, in which case a UI might offer the full
+    /// text in a tooltip.
+    pub fn is_trimmed(&self) -> bool {
+        self.lines.iter().any(|line| line.metrics.is_trimmed)
+    }
+
+    pub fn metrics(&self) -> TextMetrics {
+        let first = &self.lines[0];
+        let last = &self.lines[self.lines.len() - 1];
+        let max = |f: fn(&Line) -> f32| self.lines.iter().map(f).fold(0.0, f32::max);
+        TextMetrics {
+            left: self
+                .lines
+                .iter()
+                .map(|line| line.left)
+                .fold(std::f32::INFINITY, f32::min),
+            top: first.top,
+            width: max(|line| line.width),
+            width_including_trailing_whitespace: max(|line| {
+                line.width_including_trailing_whitespace
+            }),
+            height: last.top + last.metrics.height - first.top,
+            layout_width: self.max_width,
+            layout_height: self.max_height,
+            line_count: self.lines.len() as u32,
+        }
+    }
+
+    /// Finds the character nearest to a point relative to the layout box.
+    /// Points outside the text snap to the nearest line and to the first or
+    /// last character on it.
+    pub fn hit_test_point(&self, x: f32, y: f32) -> HitTestPoint {
+        let first = &self.lines[0];
+        let last = &self.lines[self.lines.len() - 1];
+        let inside_y = y >= first.top && y < last.top + last.metrics.height;
+        let line = self
+            .lines
+            .iter()
+            .find(|line| y < line.top + line.metrics.height)
+            .unwrap_or(last);
+
+        let visible: Vec<_> = self.visible_order(line).collect();
+        let (first, last_visible) = match (visible.first(), visible.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => {
+                let position = self
+                    .clusters
+                    .get(line.clusters.start)
+                    .map_or(self.text_length, |c| c.position);
+                let metrics = self.caret_metrics(line, position, line.left, self.paragraph_level);
+                return HitTestPoint {
+                    is_trailing_hit: false,
+                    is_inside: false,
+                    metrics,
+                };
+            }
+        };
+
+        // The trailing edge of a right-to-left character is its left edge.
+        let x = x - line.left;
+        let (cluster, is_trailing_hit, inside_x) = if x < first.x {
+            (first, first.level % 2 == 1, false)
+        } else {
+            match visible.iter().find(|c| x < c.x + c.advance) {
+                Some(&cluster) => {
+                    let right_half = x >= cluster.x + cluster.advance / 2.0;
+                    (cluster, right_half != (cluster.level % 2 == 1), true)
+                }
+                None => (last_visible, last_visible.level % 2 == 0, false),
+            }
+        };
+
+        HitTestPoint {
+            is_trailing_hit,
+            is_inside: inside_x && inside_y,
+            metrics: self.cluster_metrics(line, cluster),
+        }
+    }
+
+    /// Returns the caret position for a text position, along with the
+    /// metrics of the character there. The caret is on the trailing edge of
+    /// the character if `is_trailing_hit` is set, which is its left edge if
+    /// the character is right-to-left. Positions past the end of the text
+    /// place the caret on the trailing edge of the last character.
+    pub fn hit_test_text_position(
+        &self,
+        text_position: u32,
+        is_trailing_hit: bool,
+    ) -> (f32, f32, HitTestMetrics) {
+        match self
+            .clusters
+            .iter()
+            .position(|c| text_position < c.position + c.length)
+        {
+            Some(index) => {
+                let line = self.line_of(index);
+                let metrics = self.cluster_metrics(line, &self.clusters[index]);
+                let x = if is_trailing_hit != (metrics.bidi_level % 2 == 1) {
+                    metrics.left + metrics.width
+                } else {
+                    metrics.left
+                };
+                (x, metrics.top, metrics)
+            }
+            None => {
+                let line = &self.lines[self.lines.len() - 1];
+                let (x, level) = match self.clusters[self.visible_clusters(line)]
+                    .iter()
+                    .rev()
+                    .find(|c| !c.trimmed)
+                {
+                    Some(c) if c.level % 2 == 1 => (line.left + c.x, c.level),
+                    Some(c) => (line.left + c.x + c.advance, c.level),
+                    None => (line.left, self.paragraph_level),
+                };
+                let metrics = self.caret_metrics(line, self.text_length, x, level);
+                (x, line.top, metrics)
+            }
+        }
+    }
+
+    /// Returns the rectangles covered by the range, such as for drawing a
+    /// selection, offset by the origin. Each rectangle is a run of adjacent
+    /// characters at one bidi level, so a line of one direction gets a
+    /// single rectangle. An empty range covers nothing.
+    pub fn hit_test_text_range(
+        &self,
+        text_position: u32,
+        text_length: u32,
+        origin_x: f32,
+        origin_y: f32,
+    ) -> Vec<HitTestMetrics> {
+        let end = text_position.saturating_add(text_length);
+        let mut rects = Vec::new();
+        for line in &self.lines {
+            let mut current: Option<HitTestMetrics> = None;
+            for cluster in line.order.iter().map(|&i| &self.clusters[i]) {
+                // The ellipsis is selected with the first character it hides.
+                let hit = !cluster.trimmed
+                    && cluster.position < end
+                    && cluster.position + cluster.length.max(1) > text_position;
+                match current.as_mut() {
+                    Some(rect) if hit && rect.bidi_level == u32::from(cluster.level) => {
+                        let start = rect.text_position.min(cluster.position);
+                        let stop = (rect.text_position + rect.length)
+                            .max(cluster.position + cluster.length);
+                        rect.text_position = start;
+                        rect.length = stop - start;
+                        rect.width += cluster.advance;
+                    }
+                    _ => {
+                        rects.extend(current.take());
+                        if hit {
+                            let mut rect = self.cluster_metrics(line, cluster);
+                            rect.left += origin_x;
+                            rect.top += origin_y;
+                            current = Some(rect);
+                        }
+                    }
+                }
+            }
+            rects.extend(current);
+        }
+        rects
+    }
+
+    /// Moves a caret at `text_position` by one step and returns its new
+    /// position. Positions inside a character count as its start, and the
+    /// caret stays put at either end of the text.
+    pub fn move_caret(&self, text_position: u32, movement: CaretMovement) -> u32 {
+        let index = self
+            .clusters
+            .iter()
+            .position(|c| text_position < c.position + c.length);
+        let text_position = index.map_or(self.text_length, |i| self.clusters[i].position);
+        let right = match movement {
+            CaretMovement::Next => {
+                return index.map_or(self.text_length, |i| {
+                    self.clusters[i].position + self.clusters[i].length
+                })
+            }
+            CaretMovement::Previous => {
+                return self
+                    .clusters
+                    .iter()
+                    .rev()
+                    .find(|c| c.position < text_position)
+                    .map_or(0, |c| c.position)
+            }
+            CaretMovement::Left => false,
+            CaretMovement::Right => true,
+        };
+
+        let line = index.map_or(self.lines.len() - 1, |i| {
+            self.lines
+                .iter()
+                .position(|line| line.clusters.contains(&i))
+                .expect("cluster outside every line")
+        });
+        let stops = self.caret_stops(line);
+        let current = stops
+            .iter()
+            .position(|&(_, position)| position == text_position)
+            .expect("caret position is a stop on its line");
+        let next = if right {
+            stops.get(current + 1)
+        } else {
+            current.checked_sub(1).map(|i| &stops[i])
+        };
+        if let Some(&(_, position)) = next {
+            return position;
+        }
+
+        // Off the end of the line, continue on the next line when moving
+        // with the reading direction and the previous one otherwise.
+        let forward = right == (self.paragraph_level % 2 == 0);
+        let target = if forward {
+            Some(line + 1).filter(|&l| l < self.lines.len())
+        } else {
+            line.checked_sub(1)
+        };
+        match target {
+            Some(target) => {
+                let stops = self.caret_stops(target);
+                let (_, position) = if right {
+                    stops[0]
+                } else {
+                    stops[stops.len() - 1]
+                };
+                position
+            }
+            None => text_position,
+        }
+    }
+
+    fn line_of(&self, cluster: usize) -> &Line {
+        self.lines
+            .iter()
+            .find(|line| line.clusters.contains(&cluster))
+            .expect("cluster outside every line")
+    }
+
+    /// The clusters of a line that a point can hit, which excludes the
+    /// newline. Trimmed clusters are still in the range.
+    fn visible_clusters(&self, line: &Line) -> Range<usize> {
+        let newline = line.metrics.newline_length > 0;
+        line.clusters.start..line.clusters.end - newline as usize
+    }
+
+    /// The visible clusters of a line from left to right.
+    fn visible_order<'a>(&'a self, line: &'a Line) -> impl Iterator<Item = &'a Cluster> {
+        let visible = self.visible_clusters(line);
+        line.order
+            .iter()
+            .filter(move |&i| visible.contains(i))
+            .map(move |&i| &self.clusters[i])
+            .filter(|c| !c.trimmed)
+    }
+
+    /// The positions a caret can take on a line, with their offsets, from
+    /// left to right. A line's end is only a stop at the end of the text,
+    /// as elsewhere it is the start of the next line or before a newline.
+    /// The ellipsis takes no position of its own.
+    fn caret_stops(&self, line: usize) -> Vec<(f32, u32)> {
+        let mut positions: Vec<_> = self.clusters[self.lines[line].clusters.clone()]
+            .iter()
+            .filter(|c| c.length > 0)
+            .map(|c| c.position)
+            .collect();
+        if line == self.lines.len() - 1 {
+            positions.push(self.text_length);
+        }
+        let mut stops: Vec<_> = positions
+            .into_iter()
+            .map(|position| (self.hit_test_text_position(position, false).0, position))
+            .collect();
+        stops.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        stops
+    }
+
+    fn cluster_metrics(&self, line: &Line, cluster: &Cluster) -> HitTestMetrics {
+        HitTestMetrics {
+            text_position: cluster.position,
+            length: cluster.length,
+            left: line.left + cluster.x,
+            top: line.top,
+            width: cluster.advance,
+            height: line.metrics.height,
+            bidi_level: u32::from(cluster.level),
+            is_text: true,
+            // The ellipsis, standing in for the trimmed text, has no length.
+            is_trimmed: cluster.trimmed || cluster.length == 0,
+        }
+    }
+
+    fn caret_metrics(
+        &self,
+        line: &Line,
+        text_position: u32,
+        left: f32,
+        level: u8,
+    ) -> HitTestMetrics {
+        HitTestMetrics {
+            text_position,
+            length: 0,
+            left,
+            top: line.top,
+            width: 0.0,
+            height: line.metrics.height,
+            bidi_level: u32::from(level),
+            is_text: true,
+            is_trimmed: false,
+        }
+    }
+}
+
+fn paragraph_level(direction: ReadingDirection) -> u8 {
+    match direction {
+        ReadingDirection::LeftToRight => 0,
+        ReadingDirection::RightToLeft => 1,
+    }
+}
+
+/// Resolves the bidi level of each character of `text`, one paragraph at a
+/// time.
+fn bidi_levels(text: &str, direction: ReadingDirection) -> Vec<u8> {
+    let chars: Vec<char> = text.chars().collect();
+    let classes: Vec<_> = chars.iter().map(|&ch| bidi::class(ch)).collect();
+    let mut levels = Vec::with_capacity(chars.len());
+    let mut start = 0;
+    while start < chars.len() {
+        let end = classes[start..]
+            .iter()
+            .position(|&class| class == BidiClass::B)
+            .map_or(chars.len(), |i| start + i + 1);
+        let brackets: Vec<_> = chars[start..end]
+            .iter()
+            .map(|&ch| bidi::bracket(ch))
+            .collect();
+        let level = Some(paragraph_level(direction));
+        levels.extend(bidi::resolve_levels(&classes[start..end], &brackets, level).1);
+        start = end;
+    }
+    levels
+}
+
+fn clusters(text: &str, format: &TextFormat, runs: &[TextRun]) -> Vec<Cluster> {
+    let font = format.font_metrics();
+    let mut levels = bidi_levels(text, format.reading_direction()).into_iter();
+    let mut run = 0;
+    let mut breaks = line_breaks(text)
+        .into_iter()
+        .filter(|&(_, opportunity)| opportunity == BreakOpportunity::Allowed)
+        .map(|(offset, _)| offset)
+        .peekable();
+    let mut clusters: Vec<Cluster> = Vec::new();
+    let mut position = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, ch)) = chars.next() {
+        while run + 1 < runs.len() && position >= runs[run + 1].text_position {
+            run += 1;
+        }
+        let style = &runs[run].style;
+        let class = bidi::class(ch);
+        let level = levels.next().unwrap();
+        let mut length = ch.len_utf16() as u32;
+        let kind = match ch {
+            '\r' => {
+                if chars.peek().map(|&(_, next)| next) == Some('\n') {
+                    chars.next();
+                    levels.next();
+                    length += 1;
+                }
+                ClusterKind::Newline
+            }
+            '\n' | '\u{b}' | '\u{c}' | '\u{85}' | '\u{2028}' | '\u{2029}' => ClusterKind::Newline,
+            // No-break spaces join the words on either side.
+            '\u{a0}' | '\u{2007}' | '\u{202f}' => ClusterKind::Text,
+            c if c.is_whitespace() => ClusterKind::Whitespace,
+            _ => ClusterKind::Text,
+        };
+        let ch = if level % 2 == 1 { bidi::mirror(ch) } else { ch };
+        let advance = match kind {
+            ClusterKind::Newline => 0.0,
+            _ => font.advance(ch) * style.size + style.letter_spacing,
+        };
+        let end = chars.peek().map_or(text.len(), |&(offset, _)| offset);
+        let break_after = breaks.peek() == Some(&end);
+        if break_after {
+            breaks.next();
+        }
+        // Pairs are only kerned within a run of one direction.
+        match clusters.last_mut() {
+            Some(prev)
+                if prev.run == run
+                    && prev.level == level
+                    && prev.kind != ClusterKind::Newline
+                    && kind != ClusterKind::Newline =>
+            {
+                prev.advance += font.kerning(prev.ch, ch) * style.size;
+            }
+            _ => {}
+        }
+        clusters.push(Cluster {
+            ch,
+            position,
+            length,
+            kind,
+            break_after,
+            run,
+            class,
+            level,
+            advance,
+            x: 0.0,
+            trimmed: false,
+        });
+        position += length;
+    }
+    clusters
+}
+
+/// Font size of a line, which is as tall as its largest run.
+fn line_size(line: &[Cluster], runs: &[TextRun]) -> f32 {
+    // Empty lines only come at the end of the text.
+    if line.is_empty() {
+        runs[runs.len() - 1].style.size
+    } else {
+        line.iter()
+            .map(|c| runs[c.run].style.size)
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Drops the lines after the format's line limit and, if the format trims,
+/// those past the bottom of the box. The last line kept is cut where the
+/// granularity allows so the ellipsis, and the end of a path when trimming
+/// at a delimiter, fit beside it. Its range grows to cover the dropped
+/// clusters, which are hidden. Returns whether any text was dropped.
+fn trim(
+    clusters: &mut Vec<Cluster>,
+    lines: &mut Vec<Range<usize>>,
+    format: &TextFormat,
+    runs: &[TextRun],
+    paragraph_level: u8,
+    max_width: f32,
+    max_height: f32,
+) -> bool {
+    let font = format.font_metrics();
+    let trimming = format.trimming();
+    let granularity = trimming.granularity;
+    let max_lines = format
+        .max_lines()
+        .map_or(usize::MAX, |max_lines| max_lines.max(1) as usize);
+
+    // The first line is kept even if it does not fit.
+    let line_height = font.ascent() + font.descent() + font.line_gap();
+    let mut bottom = line_height * line_size(&clusters[lines[0].clone()], runs);
+    let mut kept = 1;
+    while kept < lines.len().min(max_lines) {
+        bottom += line_height * line_size(&clusters[lines[kept].clone()], runs);
+        if granularity != TrimmingGranularity::None && bottom > max_height {
+            break;
+        }
+        kept += 1;
+    }
+    // Dropping the empty line after a final newline loses no text.
+    if kept == lines.len() || (kept == lines.len() - 1 && lines[kept].is_empty()) {
+        return false;
+    }
+    let Range { start, end } = lines[kept - 1].clone();
+    lines.truncate(kept);
+
+    let paragraph_end = clusters[start..]
+        .iter()
+        .position(|c| c.kind == ClusterKind::Newline)
+        .map_or(clusters.len(), |i| start + i);
+    let width = |clusters: &[Cluster]| clusters.iter().map(|c| c.advance).sum::<f32>();
+    let ellipsis_width = |run: usize| {
+        let style = &runs[run].style;
+        if trimming.ellipsis {
+            font.advance(ELLIPSIS) * style.size + style.letter_spacing
+        } else {
+            0.0
+        }
+    };
+    // Whitespace before the ellipsis is hidden too.
+    let text_end = |cut: usize| {
+        clusters[start..cut]
+            .iter()
+            .rposition(|c| c.kind == ClusterKind::Text)
+            .map_or(start, |i| start + i + 1)
+    };
+
+    let (text_end, suffix) = if granularity == TrimmingGranularity::None {
+        (end, 0..0)
+    } else {
+        // Trimming at a delimiter keeps the end of a paragraph that runs
+        // past the line, if it fits.
+        let mut suffix = paragraph_end..paragraph_end;
+        if let Some(delimiter) = trimming.delimiter {
+            let from = (start..paragraph_end)
+                .rev()
+                .filter(|&i| clusters[i].ch == delimiter)
+                .nth(trimming.delimiter_count.max(1) as usize - 1)
+                .filter(|&from| {
+                    paragraph_end > end
+                        && width(&clusters[from..paragraph_end])
+                            + ellipsis_width(clusters[from].run)
+                            <= max_width
+                });
+            if let Some(from) = from {
+                suffix = from..paragraph_end;
+            }
+        }
+        let suffix_width = width(&clusters[suffix.clone()]);
+        let limit = end.min(suffix.start);
+        let cut = (start..=limit)
+            .rev()
+            .filter(|&cut| {
+                cut == start
+                    || cut == paragraph_end
+                    || granularity == TrimmingGranularity::Character
+                    || clusters[cut - 1].break_after
+            })
+            .find(|&cut| {
+                let text_end = text_end(cut);
+                let run = clusters[text_end.max(start + 1) - 1].run;
+                width(&clusters[start..text_end]) + ellipsis_width(run) + suffix_width <= max_width
+            })
+            .unwrap_or(start);
+        (text_end(cut), suffix)
+    };
+
+    for (i, cluster) in clusters.iter_mut().enumerate().skip(text_end) {
+        if !suffix.contains(&i) {
+            cluster.trimmed = true;
+            cluster.advance = 0.0;
+        }
+    }
+    if granularity != TrimmingGranularity::None && trimming.ellipsis {
+        // Between text of one level the ellipsis takes that level, as a
+        // neutral would.
+        let before = clusters[start..text_end].last().map(|c| (c.run, c.level));
+        let after = clusters.get(suffix.start).filter(|_| !suffix.is_empty());
+        let after_level = after.map_or(paragraph_level, |c| c.level);
+        let run = before.map_or(clusters[start].run, |(run, _)| run);
+        let level = match before {
+            Some((_, level)) if level == after_level => level,
+            _ => paragraph_level,
+        };
+        let ellipsis = Cluster {
+            ch: ELLIPSIS,
+            position: clusters[text_end].position,
+            length: 0,
+            kind: ClusterKind::Text,
+            break_after: false,
+            run,
+            class: BidiClass::ON,
+            level,
+            advance: ellipsis_width(run),
+            x: 0.0,
+            trimmed: false,
+        };
+        clusters.insert(text_end, ellipsis);
+    }
+    lines[kept - 1] = start..clusters.len();
+    true
+}
+
+const ELLIPSIS: char = '\u{2026}';
+
+/// Widens the spaces between words so the line fills `max_width`.
+fn justify(line: &mut [Cluster], max_width: f32) {
+    let words = match line.iter().rposition(|c| c.kind == ClusterKind::Text) {
+        Some(last) => &mut line[..=last],
+        None => return,
+    };
+    let width: f32 = words.iter().map(|c| c.advance).sum();
+    let spaces = words
+        .iter()
+        .filter(|c| c.kind == ClusterKind::Whitespace)
+        .count();
+    if spaces == 0 || width >= max_width {
+        return;
+    }
+    let extra = (max_width - width) / spaces as f32;
+    for cluster in words
+        .iter_mut()
+        .filter(|c| c.kind == ClusterKind::Whitespace)
+    {
+        cluster.advance += extra;
+    }
+}
+
+/// Splits clusters into lines no wider than `max_width`, not counting
+/// trailing whitespace, which hangs past the edge.
+fn break_lines(clusters: &[Cluster], max_width: f32) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    while start < clusters.len() {
+        let mut x = 0.0;
+        let mut last_break = None;
+        let mut end = clusters.len();
+        for (i, cluster) in clusters.iter().enumerate().skip(start) {
+            match cluster.kind {
+                ClusterKind::Newline => {
+                    end = i + 1;
+                    break;
+                }
+                ClusterKind::Text if i > start && x + cluster.advance > max_width => {
+                    // Fall back to breaking inside the word if it is alone
+                    // on the line.
+                    end = last_break.unwrap_or(i);
+                    break;
+                }
+                _ => {}
+            }
+            x += cluster.advance;
+            if cluster.break_after {
+                last_break = Some(i + 1);
+            }
+        }
+        lines.push(start..end);
+        start = end;
+    }
+
+    // Empty text, and text ending in a newline, end with an empty line.
+    if clusters
+        .last()
+        .map_or(true, |c| c.kind == ClusterKind::Newline)
+    {
+        lines.push(clusters.len()..clusters.len());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::{FontMetrics, FontStretch, FontStyle, FontWeight, TextAttribute, Trimming};
+    use std::rc::Rc;
+
+    /// Every character is one em wide, so at 10 DIPs each is 10 wide and
+    /// lines are 10 high.
+    struct Mono;
+
+    impl FontMetrics for Mono {
+        fn advance(&self, _: char) -> f32 {
+            1.0
+        }
+
+        fn ascent(&self) -> f32 {
+            0.8
+        }
+
+        fn descent(&self) -> f32 {
+            0.2
+        }
+    }
+
+    fn aligned(
+        text: &str,
+        max_width: f32,
+        text_alignment: TextAlignment,
+        paragraph_alignment: ParagraphAlignment,
+    ) -> TextLayout {
+        let mut format = TextFormat::new(
+            "Mono",
+            FontWeight::Normal,
+            FontStyle::Normal,
+            FontStretch::Normal,
+            10.0,
+            "en-us",
+        );
+        format.set_font_metrics(Rc::new(Mono));
+        format.set_text_alignment(text_alignment);
+        format.set_paragraph_alignment(paragraph_alignment);
+        TextLayout::new(text, &format, max_width, 100.0)
+    }
+
+    fn right_to_left(text: &str, max_width: f32, text_alignment: TextAlignment) -> TextLayout {
+        let mut format = TextFormat::new(
+            "Mono",
+            FontWeight::Normal,
+            FontStyle::Normal,
+            FontStretch::Normal,
+            10.0,
+            "he-il",
+        );
+        format.set_font_metrics(Rc::new(Mono));
+        format.set_text_alignment(text_alignment);
+        format.set_reading_direction(ReadingDirection::for_locale(format.locale_name()));
+        TextLayout::new(text, &format, max_width, 100.0)
+    }
+
+    fn visual(layout: &TextLayout, line: usize) -> Vec<(char, u32)> {
+        layout.runs()[line]
+            .glyphs
+            .iter()
+            .map(|g| (g.ch, g.text_position))
+            .collect()
+    }
+
+    fn layout(text: &str, max_width: f32) -> TextLayout {
+        aligned(
+            text,
+            max_width,
+            TextAlignment::Leading,
+            ParagraphAlignment::Near,
+        )
+    }
+
+    fn lengths(layout: &TextLayout) -> Vec<u32> {
+        layout.line_metrics().iter().map(|m| m.length).collect()
+    }
+
+    #[test]
+    fn wrapping() {
+        let text = layout("hello world foo", 80.0);
+        assert_eq!(lengths(&text), [6, 6, 3]);
+        let lines = text.line_metrics();
+        assert_eq!(lines[0].trailing_whitespace_length, 1);
+        assert_eq!(lines[0].baseline, 8.0);
+
+        let metrics = text.metrics();
+        assert_eq!(metrics.width, 50.0);
+        assert_eq!(metrics.width_including_trailing_whitespace, 60.0);
+        assert_eq!(metrics.height, 30.0);
+        assert_eq!(metrics.line_count, 3);
+
+        assert_eq!(lengths(&layout("abcdefghij", 45.0)), [4, 4, 2]);
+        assert_eq!(lengths(&layout("well-known", 60.0)), [5, 5]);
+        assert_eq!(lengths(&layout("(a) (b)", 35.0)), [4, 3]);
+        assert_eq!(lengths(&layout("日本語", 25.0)), [2, 1]);
+    }
+
+    #[test]
+    fn rich_text() {
+        let mut format = TextFormat::new(
+            "Mono",
+            FontWeight::Normal,
+            FontStyle::Normal,
+            FontStretch::Normal,
+            10.0,
+            "en-us",
+        );
+        format.set_font_metrics(Rc::new(Mono));
+        let text = RichText::new("ab cd\nef")
+            .set(0..2, TextAttribute::Size(20.0))
+            .set(3..5, TextAttribute::LetterSpacing(2.0));
+        let layout = TextLayout::with_rich_text(&text, &format, 100.0, 100.0);
+        assert_eq!(layout.text_runs().len(), 4);
+
+        // The first line is as tall as its larger characters.
+        let lines = layout.line_metrics();
+        assert_eq!((lines[0].height, lines[0].baseline), (20.0, 16.0));
+        assert_eq!((lines[1].height, lines[1].baseline), (10.0, 8.0));
+        assert_eq!(layout.metrics().height, 30.0);
+
+        let runs = layout.runs();
+        let glyphs: Vec<_> = runs[0].glyphs.iter().map(|g| (g.x, g.run)).collect();
+        assert_eq!(
+            glyphs,
+            [(0.0, 0), (20.0, 0), (40.0, 1), (50.0, 2), (62.0, 2)]
+        );
+        assert_eq!(runs[0].width, 74.0);
+        assert_eq!(runs[1].top, 20.0);
+        assert_eq!(runs[1].glyphs[0].run, 3);
+    }
+
+    #[test]
+    fn text_alignment() {
+        let lefts = |alignment| {
+            aligned("hello world foo", 80.0, alignment, ParagraphAlignment::Near)
+                .runs()
+                .iter()
+                .map(|run| run.left)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lefts(TextAlignment::Leading), [0.0, 0.0, 0.0]);
+        assert_eq!(lefts(TextAlignment::Trailing), [30.0, 30.0, 50.0]);
+        assert_eq!(lefts(TextAlignment::Center), [15.0, 15.0, 25.0]);
+        assert_eq!(lefts(TextAlignment::Justified), [0.0, 0.0, 0.0]);
+
+        // Spaces on all but the last line grow to fill the box.
+        let text = aligned(
+            "aa b cc dd",
+            80.0,
+            TextAlignment::Justified,
+            ParagraphAlignment::Near,
+        );
+        let runs = text.runs();
+        assert_eq!(runs[0].width, 80.0);
+        let xs: Vec<f32> = runs[0].glyphs.iter().map(|g| g.x).collect();
+        assert_eq!(xs, [0.0, 10.0, 20.0, 35.0, 45.0, 60.0, 70.0, 80.0]);
+        assert_eq!(runs[1].width, 20.0);
+        assert_eq!(text.hit_test_point(36.0, 5.0).metrics.text_position, 3);
+    }
+
+    #[test]
+    fn paragraph_alignment() {
+        let tops = |alignment| {
+            let text = aligned("hello world", 60.0, TextAlignment::Leading, alignment);
+            let runs = text.runs();
+            assert_eq!(text.metrics().top, runs[0].top);
+            assert_eq!(runs[0].baseline, runs[0].top + 8.0);
+            runs.iter().map(|run| run.top).collect::<Vec<_>>()
+        };
+        assert_eq!(tops(ParagraphAlignment::Near), [0.0, 10.0]);
+        assert_eq!(tops(ParagraphAlignment::Far), [80.0, 90.0]);
+        assert_eq!(tops(ParagraphAlignment::Center), [40.0, 50.0]);
+    }
+
+    #[test]
+    fn newlines() {
+        let text = layout("a\r\nb\n", 100.0);
+        assert_eq!(lengths(&text), [3, 2, 0]);
+        let lines = text.line_metrics();
+        assert_eq!(lines[0].newline_length, 2);
+        assert_eq!(lines[0].trailing_whitespace_length, 2);
+
+        let empty = layout("", 100.0);
+        assert_eq!(empty.metrics().line_count, 1);
+        assert_eq!(empty.metrics().height, 10.0);
+    }
+
+    #[test]
+    fn hit_test_point() {
+        let text = layout("hello world", 1000.0);
+        let hit = text.hit_test_point(12.0, 5.0);
+        assert_eq!(hit.metrics.text_position, 1);
+        assert!(!hit.is_trailing_hit);
+        assert!(hit.is_inside);
+        assert!(text.hit_test_point(17.0, 5.0).is_trailing_hit);
+
+        let hit = text.hit_test_point(500.0, 5.0);
+        assert_eq!(hit.metrics.text_position, 10);
+        assert!(hit.is_trailing_hit);
+        assert!(!hit.is_inside);
+        assert!(!text.hit_test_point(12.0, 50.0).is_inside);
+    }
+
+    #[test]
+    fn hit_test_text_position() {
+        let text = layout("hello world foo", 80.0);
+        assert_eq!(text.hit_test_text_position(3, false).0, 30.0);
+        assert_eq!(text.hit_test_text_position(3, true).0, 40.0);
+        let (x, y, metrics) = text.hit_test_text_position(7, false);
+        assert_eq!((x, y, metrics.text_position), (10.0, 10.0, 7));
+        let (x, y, metrics) = text.hit_test_text_position(100, false);
+        assert_eq!((x, y, metrics.text_position), (30.0, 20.0, 15));
+
+        let text = layout("a\u{1f600}b", 100.0);
+        let (_, _, metrics) = text.hit_test_text_position(2, false);
+        assert_eq!((metrics.text_position, metrics.length), (1, 2));
+    }
+
+    #[test]
+    fn hit_test_text_range() {
+        let text = layout("hello world foo", 80.0);
+        let rects = text.hit_test_text_range(3, 6, 1.0, 2.0);
+        assert_eq!(rects.len(), 2);
+        assert_eq!(
+            (
+                rects[0].text_position,
+                rects[0].length,
+                rects[0].left,
+                rects[0].width
+            ),
+            (3, 3, 31.0, 30.0)
+        );
+        assert_eq!(
+            (
+                rects[1].text_position,
+                rects[1].length,
+                rects[1].left,
+                rects[1].top
+            ),
+            (6, 3, 1.0, 12.0)
+        );
+        assert!(text.hit_test_text_range(3, 0, 0.0, 0.0).is_empty());
+    }
+
+    #[test]
+    fn reading_direction() {
+        let rtl = ReadingDirection::RightToLeft;
+        assert_eq!(ReadingDirection::for_locale("ar-EG"), rtl);
+        assert_eq!(ReadingDirection::for_locale("he"), rtl);
+        assert_eq!(ReadingDirection::for_locale("az_Arab_IR"), rtl);
+        assert_eq!(
+            ReadingDirection::for_locale("ku-Latn"),
+            ReadingDirection::LeftToRight
+        );
+        assert_eq!(
+            ReadingDirection::for_locale("en-us"),
+            ReadingDirection::LeftToRight
+        );
+        assert_eq!(
+            ReadingDirection::for_locale(""),
+            ReadingDirection::LeftToRight
+        );
+    }
+
+    #[test]
+    fn bidi_reordering() {
+        // Hebrew in a left-to-right paragraph reads right to left.
+        let text = layout("abc אבג", 1000.0);
+        assert_eq!(
+            visual(&text, 0),
+            [
+                ('a', 0),
+                ('b', 1),
+                ('c', 2),
+                (' ', 3),
+                ('ג', 6),
+                ('ב', 5),
+                ('א', 4)
+            ]
+        );
+        let glyphs = &text.runs()[0].glyphs;
+        assert_eq!((glyphs[4].x, glyphs[4].bidi_level), (40.0, 1));
+
+        // Numbers keep their order and brackets are mirrored.
+        let text = right_to_left("א (12)", 1000.0, TextAlignment::Leading);
+        assert_eq!(
+            visual(&text, 0),
+            [('(', 5), ('1', 3), ('2', 4), (')', 2), (' ', 1), ('א', 0)]
+        );
+        let levels: Vec<_> = text.runs()[0].glyphs.iter().map(|g| g.bidi_level).collect();
+        assert_eq!(levels, [1, 2, 2, 1, 1, 1]);
+
+        // Lines are broken first and each is reordered on its own.
+        let text = layout("אב גד", 30.0);
+        assert_eq!(lengths(&text), [3, 2]);
+        assert_eq!(visual(&text, 0), [('ב', 1), ('א', 0), (' ', 2)]);
+        assert_eq!(visual(&text, 1), [('ד', 4), ('ג', 3)]);
+    }
+
+    #[test]
+    fn right_to_left_alignment() {
+        let lefts = |alignment| {
+            right_to_left("אב גד הו", 60.0, alignment)
+                .runs()
+                .iter()
+                .map(|run| run.left)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lefts(TextAlignment::Leading), [10.0, 40.0]);
+        assert_eq!(lefts(TextAlignment::Trailing), [0.0, 0.0]);
+        assert_eq!(lefts(TextAlignment::Center), [5.0, 20.0]);
+
+        // Trailing whitespace hangs off the left edge.
+        let text = right_to_left("אב גד הו", 60.0, TextAlignment::Leading);
+        let runs = text.runs();
+        assert_eq!(runs[0].width, 50.0);
+        assert_eq!(runs[0].glyphs[0].ch, ' ');
+        assert_eq!(runs[0].glyphs[0].x, 0.0);
+        assert_eq!(runs[0].glyphs[1].x, 10.0);
+        assert_eq!(text.metrics().width_including_trailing_whitespace, 60.0);
+    }
+
+    #[test]
+    fn bidi_hit_testing() {
+        let text = right_to_left("אבג", 100.0, TextAlignment::Leading);
+        let hit = text.hit_test_point(95.0, 5.0);
+        assert_eq!(hit.metrics.text_position, 0);
+        assert_eq!(hit.metrics.bidi_level, 1);
+        assert!(!hit.is_trailing_hit);
+        assert!(text.hit_test_point(91.0, 5.0).is_trailing_hit);
+        let hit = text.hit_test_point(10.0, 5.0);
+        assert_eq!(hit.metrics.text_position, 2);
+        assert!(hit.is_trailing_hit);
+        assert!(!hit.is_inside);
+
+        assert_eq!(text.hit_test_text_position(0, false).0, 100.0);
+        assert_eq!(text.hit_test_text_position(0, true).0, 90.0);
+        assert_eq!(text.hit_test_text_position(3, false).0, 70.0);
+
+        // A selection splits where the direction changes.
+        let text = layout("abc אבג", 1000.0);
+        let rects: Vec<_> = text
+            .hit_test_text_range(2, 3, 0.0, 0.0)
+            .iter()
+            .map(|r| (r.text_position, r.length, r.left, r.width, r.bidi_level))
+            .collect();
+        assert_eq!(rects, [(2, 2, 20.0, 20.0, 0), (4, 1, 60.0, 10.0, 1)]);
+    }
+
+    #[test]
+    fn caret_movement() {
+        let text = layout("abc אבג", 1000.0);
+        let right = |position| text.move_caret(position, CaretMovement::Right);
+        let left = |position| text.move_caret(position, CaretMovement::Left);
+
+        // Moving right crosses the Hebrew word from its visual left, which
+        // is its logical end.
+        assert_eq!(right(3), 7);
+        assert_eq!(right(7), 6);
+        assert_eq!(right(5), 4);
+        assert_eq!(right(4), 4);
+        assert_eq!(left(4), 5);
+        assert_eq!(left(7), 3);
+        assert_eq!(left(0), 0);
+
+        assert_eq!(text.move_caret(3, CaretMovement::Next), 4);
+        assert_eq!(text.move_caret(4, CaretMovement::Previous), 3);
+        assert_eq!(text.move_caret(7, CaretMovement::Next), 7);
+        assert_eq!(text.move_caret(0, CaretMovement::Previous), 0);
+
+        // The newline's position is at the right end of the line, and the
+        // lines connect at their ends.
+        let text = layout("abc אבג\nd", 1000.0);
+        let right = |position| text.move_caret(position, CaretMovement::Right);
+        let left = |position| text.move_caret(position, CaretMovement::Left);
+        assert_eq!(right(4), 7);
+        assert_eq!(right(7), 8);
+        assert_eq!(left(8), 7);
+        assert_eq!(right(8), 9);
+        assert_eq!(right(9), 9);
+
+        // In a right-to-left paragraph the next line is reached moving left.
+        let text = right_to_left("אב גד", 30.0, TextAlignment::Leading);
+        assert_eq!(text.move_caret(0, CaretMovement::Right), 0);
+        assert_eq!(text.move_caret(0, CaretMovement::Left), 1);
+        assert_eq!(text.move_caret(2, CaretMovement::Left), 3);
+        assert_eq!(text.move_caret(3, CaretMovement::Right), 2);
+    }
+
+    fn trimmed(
+        text: &str,
+        max_width: f32,
+        max_height: f32,
+        trimming: Trimming,
+        max_lines: Option<u32>,
+    ) -> TextLayout {
+        let mut format = TextFormat::new(
+            "Mono",
+            FontWeight::Normal,
+            FontStyle::Normal,
+            FontStretch::Normal,
+            10.0,
+            "en-us",
+        );
+        format.set_font_metrics(Rc::new(Mono));
+        format.set_trimming(trimming);
+        format.set_max_lines(max_lines);
+        TextLayout::new(text, &format, max_width, max_height)
+    }
+
+    fn shown(layout: &TextLayout, line: usize) -> String {
+        visual(layout, line).into_iter().map(|(ch, _)| ch).collect()
+    }
+
+    #[test]
+    fn trimming() {
+        let ellipsis = Trimming::ellipsis(TrimmingGranularity::Character);
+        let text = trimmed("hello world foo", 80.0, 100.0, ellipsis, Some(1));
+        assert!(text.is_trimmed());
+        assert_eq!(lengths(&text), [15]);
+        assert_eq!(shown(&text, 0), "hello…");
+        assert_eq!(text.metrics().width, 60.0);
+
+        // Lines past the bottom of the box only go when trimming.
+        let text = trimmed("hello world foo", 80.0, 25.0, ellipsis, None);
+        assert_eq!(lengths(&text), [6, 9]);
+        assert_eq!(shown(&text, 1), "world…");
+        assert!(text.line_metrics()[1].is_trimmed);
+        let text = trimmed("hello world foo", 80.0, 25.0, Trimming::default(), None);
+        assert_eq!(lengths(&text), [6, 6, 3]);
+        assert!(!text.is_trimmed());
+
+        // Without granularity the line limit drops lines without an
+        // ellipsis.
+        let text = trimmed("hello world foo", 80.0, 100.0, Trimming::default(), Some(2));
+        assert_eq!(lengths(&text), [6, 9]);
+        assert_eq!(shown(&text, 1), "world ");
+        assert!(text.is_trimmed());
+
+        // Dropping the empty line after a final newline trims nothing.
+        let text = trimmed("hello\n", 80.0, 100.0, ellipsis, Some(1));
+        assert_eq!(lengths(&text), [6, 0]);
+        assert!(!text.is_trimmed());
+
+        // Hidden text is trimmed and not hit.
+        let text = trimmed("hello world foo", 80.0, 100.0, ellipsis, Some(1));
+        assert!(text.hit_test_text_position(12, false).2.is_trimmed);
+        assert!(!text.hit_test_text_position(2, false).2.is_trimmed);
+        assert_eq!(text.hit_test_text_range(0, 5, 0.0, 0.0)[0].width, 50.0);
+        assert_eq!(text.hit_test_text_range(5, 1, 0.0, 0.0)[0].width, 10.0);
+        let hit = text.hit_test_point(55.0, 5.0);
+        assert_eq!((hit.metrics.text_position, hit.metrics.length), (5, 0));
+        assert!(hit.metrics.is_trimmed);
+        assert_eq!(text.move_caret(4, CaretMovement::Right), 5);
+    }
+
+    #[test]
+    fn trimming_granularity() {
+        let trim = |granularity| {
+            let text = trimmed(
+                "ab cd ef",
+                50.0,
+                100.0,
+                Trimming::ellipsis(granularity),
+                Some(1),
+            );
+            shown(&text, 0)
+        };
+        assert_eq!(trim(TrimmingGranularity::Character), "ab c…");
+        assert_eq!(trim(TrimmingGranularity::Word), "ab…");
+
+        // A path keeps its file name after a middle ellipsis.
+        let path = Trimming {
+            delimiter: Some('/'),
+            delimiter_count: 1,
+            ..Trimming::ellipsis(TrimmingGranularity::Character)
+        };
+        let text = trimmed("ab/cd/ef/gh", 80.0, 100.0, path, Some(1));
+        assert_eq!(shown(&text, 0), "ab/c…/gh");
+        assert_eq!(visual(&text, 0)[4], ('…', 4));
+        assert_eq!(lengths(&text), [11]);
+
+        // Without room for the file name the end is trimmed instead.
+        let text = trimmed("ab/cd/ef/ghijklmnop", 80.0, 100.0, path, Some(1));
+        assert_eq!(shown(&text, 0), "ab/cd/…");
+    }
+}
@@ -0,0 +1,544 @@
+//! Line break opportunities, following the Unicode line breaking algorithm
+//! (UAX #14).
+//!
+//! The pair rules are implemented in full apart from the regular expression
+//! form of LB25, which is approximated by its pair table. Character classes
+//! come from a compact table covering Latin, Greek, Cyrillic, Hebrew, Arabic,
+//! Thai, CJK, Hangul and emoji, plus the punctuation used with them; other
+//! characters are treated as alphabetic.
+
+/// Whether a line may or must break at a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakOpportunity {
+    /// The line must break here, such as after a newline or at the end of
+    /// the text.
+    Mandatory,
+    /// The line may break here if it does not fit.
+    Allowed,
+}
+
+/// Line breaking classes, named as in UAX #14. `AI`, `SA`, `SG`, `XX` and
+/// `CJ` never appear here; `class` resolves them as rule LB1 allows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
+enum Class {
+    BK,
+    CR,
+    LF,
+    NL,
+    SP,
+    ZW,
+    ZWJ,
+    CM,
+    WJ,
+    GL,
+    BA,
+    BB,
+    B2,
+    HY,
+    CL,
+    CP,
+    EX,
+    IN,
+    NS,
+    OP,
+    QU,
+    IS,
+    NU,
+    PO,
+    PR,
+    SY,
+    AL,
+    HL,
+    ID,
+    EB,
+    EM,
+    H2,
+    H3,
+    JL,
+    JV,
+    JT,
+    RI,
+    CB,
+}
+
+use self::Class::*;
+
+/// Returns the break opportunities in `text` as byte offsets, each the start
+/// of the line that would follow the break. The end of the text is always a
+/// mandatory break, unless the text is empty.
+pub fn line_breaks(text: &str) -> Vec<(usize, BreakOpportunity)> {
+    let mut breaks = Vec::new();
+    let mut chars = text.char_indices();
+    let first = match chars.next() {
+        Some((_, ch)) => class(ch),
+        None => return breaks,
+    };
+
+    // The class before the current position, after LB9 and LB10.
+    let mut prev = resolve_lb10(first);
+    // `prev` before any run of spaces, for LB8 and LB14 to LB17.
+    let mut before_spaces = prev;
+    // The class of the previous character, as written.
+    let mut prev_raw = first;
+    // Regional indicators in a row, for LB30a.
+    let mut regional = (first == RI) as usize;
+    // Whether the previous character was a hyphen or break after following
+    // a Hebrew letter, for LB21a.
+    let mut hebrew_hyphen = false;
+
+    for (i, ch) in chars {
+        let cur = class(ch);
+        let opportunity = pair(prev, before_spaces, prev_raw, cur, regional, hebrew_hyphen);
+        if let Some(opportunity) = opportunity {
+            breaks.push((i, opportunity));
+        }
+
+        // LB9: combining marks and joiners take the class of the character
+        // they follow.
+        let absorbed = matches!(cur, CM | ZWJ) && !matches!(prev, BK | CR | LF | NL | SP | ZW);
+        if !absorbed {
+            hebrew_hyphen = prev == HL && matches!(cur, HY | BA);
+            regional = if cur == RI { regional + 1 } else { 0 };
+            prev = resolve_lb10(cur);
+            if prev != SP {
+                before_spaces = prev;
+            }
+        }
+        prev_raw = cur;
+    }
+
+    breaks.push((text.len(), BreakOpportunity::Mandatory));
+    breaks
+}
+
+/// LB10: marks with nothing to attach to act as alphabetic characters.
+fn resolve_lb10(class: Class) -> Class {
+    match class {
+        CM | ZWJ => AL,
+        class => class,
+    }
+}
+
+/// Decides the boundary between `prev` and `cur`, returning `None` where
+/// breaking is prohibited.
+fn pair(
+    prev: Class,
+    before_spaces: Class,
+    prev_raw: Class,
+    cur: Class,
+    regional: usize,
+    hebrew_hyphen: bool,
+) -> Option<BreakOpportunity> {
+    use self::BreakOpportunity::*;
+
+    // LB4, LB5
+    match (prev, cur) {
+        (CR, LF) => return None,
+        (BK, _) | (CR, _) | (LF, _) | (NL, _) => return Some(Mandatory),
+        _ => {}
+    }
+    // LB6, LB7
+    if matches!(cur, BK | CR | LF | NL | SP | ZW) {
+        return None;
+    }
+    // LB8
+    if before_spaces == ZW {
+        return Some(Allowed);
+    }
+    // LB8a
+    if prev_raw == ZWJ {
+        return None;
+    }
+    // LB9
+    if matches!(cur, CM | ZWJ) && prev != SP {
+        return None;
+    }
+    let cur = resolve_lb10(cur);
+
+    // LB11, LB12, LB12a
+    if cur == WJ || prev == WJ || prev == GL {
+        return None;
+    }
+    if cur == GL && !matches!(prev, SP | BA | HY) {
+        return None;
+    }
+    // LB13
+    if matches!(cur, CL | CP | EX | IS | SY) {
+        return None;
+    }
+    // LB14 to LB17 look through spaces.
+    match (before_spaces, cur) {
+        (OP, _) | (QU, OP) | (CL, NS) | (CP, NS) | (B2, B2) => return None,
+        _ => {}
+    }
+    // LB18
+    if prev == SP {
+        return Some(Allowed);
+    }
+    // LB19, LB20
+    if cur == QU || prev == QU {
+        return None;
+    }
+    if cur == CB || prev == CB {
+        return Some(Allowed);
+    }
+    // LB21, LB21a, LB21b
+    if matches!(cur, BA | HY | NS) || prev == BB || hebrew_hyphen || (prev == SY && cur == HL) {
+        return None;
+    }
+    // LB22
+    if cur == IN {
+        return None;
+    }
+
+    let prohibited = match (prev, cur) {
+        // LB23, LB23a, LB24
+        (AL, NU) | (HL, NU) | (NU, AL) | (NU, HL) => true,
+        (PR, ID) | (PR, EB) | (PR, EM) | (ID, PO) | (EB, PO) | (EM, PO) => true,
+        (PR, AL) | (PR, HL) | (PO, AL) | (PO, HL) => true,
+        (AL, PR) | (AL, PO) | (HL, PR) | (HL, PO) => true,
+        // LB25
+        (CL, PO) | (CP, PO) | (CL, PR) | (CP, PR) | (NU, PO) | (NU, PR) => true,
+        (PO, OP) | (PO, NU) | (PR, OP) | (PR, NU) | (HY, NU) | (IS, NU) => true,
+        (NU, NU) | (SY, NU) => true,
+        // LB26, LB27
+        (JL, JL) | (JL, JV) | (JL, H2) | (JL, H3) => true,
+        (JV, JV) | (JV, JT) | (H2, JV) | (H2, JT) => true,
+        (JT, JT) | (H3, JT) => true,
+        (JL, PO) | (JV, PO) | (JT, PO) | (H2, PO) | (H3, PO) => true,
+        (PR, JL) | (PR, JV) | (PR, JT) | (PR, H2) | (PR, H3) => true,
+        // LB28, LB29, LB30
+        (AL, AL) | (AL, HL) | (HL, AL) | (HL, HL) => true,
+        (IS, AL) | (IS, HL) => true,
+        (AL, OP) | (HL, OP) | (NU, OP) | (CP, AL) | (CP, HL) | (CP, NU) => true,
+        // LB30a
+        (RI, RI) => regional % 2 == 1,
+        // LB30b
+        (EB, EM) => true,
+        _ => false,
+    };
+
+    // LB31
+    if prohibited {
+        None
+    } else {
+        Some(Allowed)
+    }
+}
+
+/// Looks up the line breaking class of `ch`.
+fn class(ch: char) -> Class {
+    match ch {
+        '\t' => BA,
+        '\n' => LF,
+        '\u{b}' | '\u{c}' => BK,
+        '\r' => CR,
+        '\u{0}'..='\u{1f}' | '\u{7f}'..='\u{84}' | '\u{86}'..='\u{9f}' => CM,
+        '\u{85}' => NL,
+        ' ' => SP,
+        '!' | '?' => EX,
+        '"' | '\'' => QU,
+        '$' | '+' | '\\' => PR,
+        '%' => PO,
+        '(' | '[' | '{' => OP,
+        ')' | ']' => CP,
+        '}' => CL,
+        ',' | '.' | ':' | ';' => IS,
+        '-' => HY,
+        '/' => SY,
+        '0'..='9' => NU,
+        '|' => BA,
+        '\u{a0}' => GL,
+        '\u{a1}' | '\u{bf}' => OP,
+        '\u{a2}' | '\u{b0}' => PO,
+        '\u{a3}'..='\u{a5}' | '\u{b1}' => PR,
+        '\u{ab}' | '\u{bb}' => QU,
+        '\u{ad}' => BA,
+        '\u{b4}' => BB,
+
+        '\u{300}'..='\u{36f}'
+        | '\u{483}'..='\u{489}'
+        | '\u{591}'..='\u{5bd}'
+        | '\u{5bf}'
+        | '\u{5c1}'..='\u{5c2}'
+        | '\u{5c4}'..='\u{5c5}'
+        | '\u{5c7}'
+        | '\u{610}'..='\u{61a}'
+        | '\u{64b}'..='\u{65f}'
+        | '\u{670}'
+        | '\u{6d6}'..='\u{6dc}'
+        | '\u{6df}'..='\u{6e4}'
+        | '\u{6e7}'..='\u{6e8}'
+        | '\u{6ea}'..='\u{6ed}'
+        | '\u{900}'..='\u{903}'
+        | '\u{93a}'..='\u{94f}'
+        | '\u{951}'..='\u{957}'
+        | '\u{962}'..='\u{963}'
+        | '\u{1ab0}'..='\u{1aff}'
+        | '\u{1dc0}'..='\u{1dff}'
+        | '\u{200c}'
+        | '\u{20d0}'..='\u{20ff}'
+        | '\u{fe00}'..='\u{fe0f}'
+        | '\u{fe20}'..='\u{fe2f}'
+        | '\u{e0001}'..='\u{e007f}'
+        | '\u{e0100}'..='\u{e01ef}' => CM,
+
+        '\u{5be}' => BA,
+        '\u{5d0}'..='\u{5ea}' | '\u{5ef}'..='\u{5f2}' | '\u{fb1d}'..='\u{fb4f}' => HL,
+        '\u{5f3}'..='\u{5f4}' => AL,
+        '\u{60c}' | '\u{60d}' => IS,
+        '\u{61f}' => EX,
+        '\u{66a}' => PO,
+        '\u{660}'..='\u{669}' | '\u{6f0}'..='\u{6f9}' | '\u{966}'..='\u{96f}' => NU,
+        '\u{964}'..='\u{965}' => BA,
+        // Thai and Lao need a dictionary to find word boundaries, which LB1
+        // allows treating them as alphabetic instead.
+        '\u{e2f}' | '\u{e5a}'..='\u{e5b}' => BA,
+        '\u{e50}'..='\u{e59}' => NU,
+        '\u{e31}' | '\u{e34}'..='\u{e3a}' | '\u{e47}'..='\u{e4e}' => CM,
+
+        '\u{1100}'..='\u{115f}' | '\u{a960}'..='\u{a97c}' => JL,
+        '\u{1160}'..='\u{11a7}' | '\u{d7b0}'..='\u{d7c6}' => JV,
+        '\u{11a8}'..='\u{11ff}' | '\u{d7cb}'..='\u{d7fb}' => JT,
+        '\u{ac00}'..='\u{d7a3}' => {
+            if (ch as u32 - 0xac00) % 28 == 0 {
+                H2
+            } else {
+                H3
+            }
+        }
+
+        '\u{1680}' | '\u{2000}'..='\u{2006}' | '\u{2008}'..='\u{200a}' => BA,
+        '\u{2007}' | '\u{2011}' | '\u{202f}' => GL,
+        '\u{200b}' => ZW,
+        '\u{200d}' => ZWJ,
+        '\u{2010}' | '\u{2012}' | '\u{2013}' | '\u{2027}' => BA,
+        '\u{2014}' => B2,
+        '\u{2018}' | '\u{2019}' | '\u{201b}'..='\u{201d}' | '\u{201f}' => QU,
+        '\u{2039}' | '\u{203a}' => QU,
+        '\u{201a}' | '\u{201e}' | '\u{2045}' | '\u{207d}' | '\u{208d}' => OP,
+        '\u{2046}' | '\u{207e}' | '\u{208e}' => CL,
+        '\u{2024}'..='\u{2026}' => IN,
+        '\u{2028}' | '\u{2029}' => BK,
+        '\u{2030}'..='\u{2037}' | '\u{2103}' | '\u{2109}' => PO,
+        '\u{203c}' | '\u{203d}' | '\u{2047}'..='\u{2049}' => NS,
+        '\u{2044}' => IS,
+        '\u{2060}' | '\u{feff}' => WJ,
+        '\u{20a0}'..='\u{20cf}' | '\u{2116}' => PR,
+        '\u{2212}' | '\u{2213}' => PR,
+
+        '\u{261d}' | '\u{26f9}' | '\u{270a}'..='\u{270d}' => EB,
+        '\u{2600}'..='\u{2603}' | '\u{2614}'..='\u{2615}' | '\u{2618}'..='\u{261c}' => ID,
+        '\u{261e}'..='\u{261f}' | '\u{2639}'..='\u{263b}' | '\u{2668}' | '\u{267f}' => ID,
+        '\u{26bd}'..='\u{26c8}'
+        | '\u{26cd}'
+        | '\u{26cf}'..='\u{26d1}'
+        | '\u{26d3}'..='\u{26d4}' => ID,
+        '\u{26d8}'..='\u{26d9}' | '\u{26dc}' | '\u{26df}'..='\u{26e1}' | '\u{26ea}' => ID,
+        '\u{26f1}'..='\u{26f5}'
+        | '\u{26f7}'..='\u{26f8}'
+        | '\u{26fa}'
+        | '\u{26fd}'..='\u{2704}' => ID,
+        '\u{2708}'..='\u{2709}' | '\u{2764}' => ID,
+
+        // CJK punctuation, kana and ideographs.
+        '\u{3001}' | '\u{3002}' | '\u{fe10}' | '\u{fe11}' | '\u{fe12}' | '\u{fe50}'
+        | '\u{fe52}' => CL,
+        '\u{3008}' | '\u{300a}' | '\u{300c}' | '\u{300e}' | '\u{3010}' | '\u{3014}'
+        | '\u{3016}' | '\u{3018}' | '\u{301a}' | '\u{301d}' => OP,
+        '\u{3009}' | '\u{300b}' | '\u{300d}' | '\u{300f}' | '\u{3011}' | '\u{3015}'
+        | '\u{3017}' | '\u{3019}' | '\u{301b}' | '\u{301e}' | '\u{301f}' => CL,
+        '\u{3000}' => BA,
+        '\u{3005}' | '\u{301c}' | '\u{303b}' | '\u{303c}' | '\u{309b}'..='\u{309e}' => NS,
+        '\u{30a0}' | '\u{30fb}' | '\u{30fd}' | '\u{30fe}' => NS,
+        // Small kana and the prolonged sound mark are class CJ, resolved to
+        // NS for strict line breaking as Japanese text expects.
+        '\u{3041}'
+        | '\u{3043}'
+        | '\u{3045}'
+        | '\u{3047}'
+        | '\u{3049}'
+        | '\u{3063}'
+        | '\u{3083}'
+        | '\u{3085}'
+        | '\u{3087}'
+        | '\u{308e}'
+        | '\u{3095}'
+        | '\u{3096}'
+        | '\u{30a1}'
+        | '\u{30a3}'
+        | '\u{30a5}'
+        | '\u{30a7}'
+        | '\u{30a9}'
+        | '\u{30c3}'
+        | '\u{30e3}'
+        | '\u{30e5}'
+        | '\u{30e7}'
+        | '\u{30ee}'
+        | '\u{30f5}'
+        | '\u{30f6}'
+        | '\u{30fc}'
+        | '\u{31f0}'..='\u{31ff}' => NS,
+        '\u{3099}' | '\u{309a}' => CM,
+        '\u{2e80}'..='\u{2fff}'
+        | '\u{3003}'..='\u{3004}'
+        | '\u{3006}'..='\u{3007}'
+        | '\u{3012}'..='\u{3013}'
+        | '\u{3020}'..='\u{303a}'
+        | '\u{303d}'..='\u{3098}'
+        | '\u{309f}'
+        | '\u{30a2}'..='\u{30fa}'
+        | '\u{30ff}'..='\u{31ef}'
+        | '\u{3200}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{a000}'..='\u{a48f}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{fe30}'..='\u{fe4f}'
+        | '\u{20000}'..='\u{2fffd}'
+        | '\u{30000}'..='\u{3fffd}' => ID,
+
+        '\u{ff01}' | '\u{ff1f}' => EX,
+        '\u{ff04}' | '\u{ffe1}' | '\u{ffe5}' | '\u{ffe6}' => PR,
+        '\u{ff05}' | '\u{ffe0}' => PO,
+        '\u{ff08}' | '\u{ff3b}' | '\u{ff5b}' | '\u{ff5f}' | '\u{ff62}' => OP,
+        '\u{ff09}' | '\u{ff0c}' | '\u{ff0e}' | '\u{ff3d}' | '\u{ff5d}' | '\u{ff60}'
+        | '\u{ff61}' | '\u{ff63}' | '\u{ff64}' => CL,
+        '\u{ff1a}'
+        | '\u{ff1b}'
+        | '\u{ff65}'
+        | '\u{ff67}'..='\u{ff70}'
+        | '\u{ff9e}'
+        | '\u{ff9f}' => NS,
+        '\u{ff00}'..='\u{ff60}' | '\u{ffe2}'..='\u{ffe4}' => ID,
+        '\u{fffc}' => CB,
+
+        '\u{1f1e6}'..='\u{1f1ff}' => RI,
+        '\u{1f3fb}'..='\u{1f3ff}' => EM,
+        '\u{1f385}'
+        | '\u{1f3c2}'..='\u{1f3c4}'
+        | '\u{1f3c7}'
+        | '\u{1f3ca}'..='\u{1f3cc}'
+        | '\u{1f442}'..='\u{1f443}'
+        | '\u{1f446}'..='\u{1f450}'
+        | '\u{1f466}'..='\u{1f478}'
+        | '\u{1f47c}'
+        | '\u{1f481}'..='\u{1f483}'
+        | '\u{1f485}'..='\u{1f487}'
+        | '\u{1f4aa}'
+        | '\u{1f574}'..='\u{1f575}'
+        | '\u{1f57a}'
+        | '\u{1f590}'
+        | '\u{1f595}'..='\u{1f596}'
+        | '\u{1f645}'..='\u{1f647}'
+        | '\u{1f64b}'..='\u{1f64f}'
+        | '\u{1f6a3}'
+        | '\u{1f6b4}'..='\u{1f6b6}'
+        | '\u{1f6c0}'
+        | '\u{1f6cc}'
+        | '\u{1f90c}'
+        | '\u{1f90f}'
+        | '\u{1f918}'..='\u{1f91f}'
+        | '\u{1f926}'
+        | '\u{1f930}'..='\u{1f939}'
+        | '\u{1f93c}'..='\u{1f93e}'
+        | '\u{1f977}'
+        | '\u{1f9b5}'..='\u{1f9b6}'
+        | '\u{1f9b8}'..='\u{1f9b9}'
+        | '\u{1f9bb}'
+        | '\u{1f9cd}'..='\u{1f9cf}'
+        | '\u{1f9d1}'..='\u{1f9dd}' => EB,
+        '\u{1f000}'..='\u{1f0ff}'
+        | '\u{1f10d}'..='\u{1f10f}'
+        | '\u{1f16d}'..='\u{1f16f}'
+        | '\u{1f1ad}'..='\u{1f1e5}'
+        | '\u{1f200}'..='\u{1f3fa}'
+        | '\u{1f400}'..='\u{1f53d}'
+        | '\u{1f546}'..='\u{1f64f}'
+        | '\u{1f680}'..='\u{1f6ff}'
+        | '\u{1f774}'..='\u{1f77f}'
+        | '\u{1f7d5}'..='\u{1f7ff}'
+        | '\u{1f80c}'..='\u{1f80f}'
+        | '\u{1f848}'..='\u{1f84f}'
+        | '\u{1f85a}'..='\u{1f85f}'
+        | '\u{1f888}'..='\u{1f88f}'
+        | '\u{1f8ae}'..='\u{1f8ff}'
+        | '\u{1f90c}'..='\u{1f93a}'
+        | '\u{1f93c}'..='\u{1f945}'
+        | '\u{1f947}'..='\u{1faff}'
+        | '\u{1fc00}'..='\u{1fffd}' => ID,
+
+        _ => AL,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{line_breaks, BreakOpportunity::*};
+
+    /// Splits `text` into the pieces between break opportunities, marking
+    /// mandatory breaks with `!`.
+    fn split(text: &str) -> Vec<String> {
+        let mut start = 0;
+        line_breaks(text)
+            .into_iter()
+            .map(|(end, opportunity)| {
+                let mut piece = text[start..end].to_owned();
+                if opportunity == Mandatory && end < text.len() {
+                    piece.push('!');
+                }
+                start = end;
+                piece
+            })
+            .collect()
+    }
+
+    #[test]
+    fn latin() {
+        assert_eq!(split("hello world"), ["hello ", "world"]);
+        assert_eq!(split("one  two"), ["one  ", "two"]);
+        assert_eq!(split("well-known"), ["well-", "known"]);
+        assert_eq!(split("(hello), world!"), ["(hello), ", "world!"]);
+        assert_eq!(split("don't \"quote\""), ["don't ", "\"quote\""]);
+        assert_eq!(split("10\u{a0}km"), ["10\u{a0}km"]);
+        assert_eq!(split("a/b"), ["a/", "b"]);
+        assert!(line_breaks("").is_empty());
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(split("$3.14 -5 50%"), ["$3.14 ", "-5 ", "50%"]);
+        assert_eq!(split("1,000.00"), ["1,000.00"]);
+    }
+
+    #[test]
+    fn mandatory() {
+        assert_eq!(split("a\r\nb\nc"), ["a\r\n!", "b\n!", "c"]);
+        assert_eq!(split("a\u{2028}b"), ["a\u{2028}!", "b"]);
+        assert_eq!(split("a\u{200b}b"), ["a\u{200b}", "b"]);
+    }
+
+    #[test]
+    fn cjk_and_emoji() {
+        assert_eq!(split("日本語"), ["日", "本", "語"]);
+        assert_eq!(
+            split("これは、テスト。"),
+            ["こ", "れ", "は、", "テ", "ス", "ト。"]
+        );
+        assert_eq!(split("한국어"), ["한", "국", "어"]);
+        // Emoji modifiers, joiners and flags stay with their base.
+        assert_eq!(
+            split("\u{1f44d}\u{1f3fd}\u{1f600}"),
+            ["\u{1f44d}\u{1f3fd}", "\u{1f600}"]
+        );
+        assert_eq!(
+            split("\u{1f468}\u{200d}\u{1f469}"),
+            ["\u{1f468}\u{200d}\u{1f469}"]
+        );
+        assert_eq!(
+            split("\u{1f1ef}\u{1f1f5}\u{1f1fa}\u{1f1f8}"),
+            ["\u{1f1ef}\u{1f1f5}", "\u{1f1fa}\u{1f1f8}"]
+        );
+        assert_eq!(split("e\u{301}\u{301} x"), ["e\u{301}\u{301} ", "x"]);
+    }
+}
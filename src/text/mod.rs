@@ -0,0 +1,43 @@
+//! Text handling that does not depend on DirectWrite.
+//!
+//! The font enums and `TextFormat` here describe text the same way
+//! `dwrite::TextFormat` does, and `TextLayout` measures and hit tests it
+//! without a font system. `dwrite` re-exports the enums, so code written
+//! against either side uses the same types.
+//!
+//! `FontCollection` loads application fonts with `opentype` and matches them
+//! to formats, and `dwrite::Factory::create_font_collection` hands the same
+//! fonts to DirectWrite.
+//!
+//! `RichText` attaches formatting to ranges of a string, for
+//! `TextLayout::with_rich_text` and `dwrite::Factory::create_rich_text_layout`.
+//!
+//! `TextLayout` orders right-to-left and mixed text with the Unicode
+//! Bidirectional Algorithm. Set the format's `ReadingDirection`, for example
+//! from `ReadingDirection::for_locale`, for Arabic and Hebrew paragraphs.
+//!
+//! Text that overflows the layout box, or the format's line limit, can be
+//! trimmed with an ellipsis. Both layouts report trimmed lines, so a UI can
+//! offer the full text in a tooltip.
+
+mod bidi;
+mod collection;
+mod format;
+mod layout;
+mod linebreak;
+pub mod opentype;
+mod rich;
+mod wide;
+
+pub use collection::FontCollection;
+pub use format::{
+    ApproximateMetrics, FontMetrics, FontStretch, FontStyle, FontWeight, ParagraphAlignment,
+    ParseFontError, ReadingDirection, TextAlignment, TextFormat, Trimming, TrimmingGranularity,
+};
+pub use layout::{
+    CaretMovement, HitTestMetrics, HitTestPoint, LineMetrics, LineRun, PositionedGlyph, TextLayout,
+    TextMetrics,
+};
+pub use linebreak::{line_breaks, BreakOpportunity};
+pub use rich::{RichText, RunStyle, TextAttribute, TextRun};
+pub use wide::{InteriorNulError, ToWideStr, WideStr, WideString};
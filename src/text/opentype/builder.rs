@@ -0,0 +1,770 @@
+//! Builds small fonts in memory, so tests do not depend on font files.
+//!
+//! Glyph 0 is `.notdef` and each added character gets the next glyph.
+
+use crate::text::FontStyle;
+
+/// Appends big-endian values.
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u16(&mut self, value: u16) -> &mut Self {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn i16(&mut self, value: i16) -> &mut Self {
+        self.u16(value as u16)
+    }
+
+    fn u32(&mut self, value: u32) -> &mut Self {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn len(&self) -> u16 {
+        self.0.len() as u16
+    }
+}
+
+/// Values for the binary search header fields of `cmap` and `kern`.
+fn search_params(count: u16, size: u16) -> (u16, u16, u16) {
+    let mut power = 1;
+    let mut log = 0;
+    while power * 2 <= count {
+        power *= 2;
+        log += 1;
+    }
+    (power * size, log, count.saturating_sub(power) * size)
+}
+
+/// Points of a TrueType contour, as `(x, y, on_curve)`.
+type Contour = Vec<(i16, i16, bool)>;
+
+/// Components of a composite glyph, as `(ch, dx, dy)`.
+type Components = Vec<(char, i16, i16)>;
+
+#[derive(Clone, Debug)]
+crate struct FontBuilder {
+    family: String,
+    typographic_family: Option<String>,
+    weight_class: u16,
+    width_class: u16,
+    style: FontStyle,
+    units_per_em: u16,
+    ascent: i16,
+    descent: i16,
+    line_gap: i16,
+    notdef_advance: u16,
+    glyphs: Vec<(char, u16)>,
+    kern_pairs: Vec<(char, char, i16)>,
+    gpos_pairs: Vec<(char, char, i16)>,
+    gpos_classes: Option<(Vec<char>, Vec<char>, i16)>,
+    outlines: Vec<(char, Vec<Contour>)>,
+    composites: Vec<(char, Components)>,
+    cff: bool,
+}
+
+impl FontBuilder {
+    /// A regular face with 1000 units per em, an ascent of 800 and a descent
+    /// of 200.
+    crate fn new(family: &str) -> Self {
+        FontBuilder {
+            family: family.to_owned(),
+            typographic_family: None,
+            weight_class: 400,
+            width_class: 5,
+            style: FontStyle::Normal,
+            units_per_em: 1000,
+            ascent: 800,
+            descent: -200,
+            line_gap: 0,
+            notdef_advance: 500,
+            glyphs: Vec::new(),
+            kern_pairs: Vec::new(),
+            gpos_pairs: Vec::new(),
+            gpos_classes: None,
+            outlines: Vec::new(),
+            composites: Vec::new(),
+            cff: false,
+        }
+    }
+
+    /// Sets name ID 16, which readers prefer over the legacy family name.
+    crate fn typographic_family(mut self, family: &str) -> Self {
+        self.typographic_family = Some(family.to_owned());
+        self
+    }
+
+    crate fn weight(mut self, weight_class: u16) -> Self {
+        self.weight_class = weight_class;
+        self
+    }
+
+    crate fn width(mut self, width_class: u16) -> Self {
+        self.width_class = width_class;
+        self
+    }
+
+    crate fn italic(mut self) -> Self {
+        self.style = FontStyle::Italic;
+        self
+    }
+
+    crate fn oblique(mut self) -> Self {
+        self.style = FontStyle::Oblique;
+        self
+    }
+
+    crate fn vertical_metrics(mut self, ascent: i16, descent: i16, line_gap: i16) -> Self {
+        self.ascent = ascent;
+        self.descent = descent;
+        self.line_gap = line_gap;
+        self
+    }
+
+    /// Adds a glyph for each character, all with the same advance.
+    crate fn glyphs(mut self, chars: &str, advance: u16) -> Self {
+        self.glyphs.extend(chars.chars().map(|ch| (ch, advance)));
+        self
+    }
+
+    /// Adds a pair to the `kern` table.
+    crate fn kern(mut self, left: char, right: char, value: i16) -> Self {
+        self.kern_pairs.push((left, right, value));
+        self
+    }
+
+    /// Adds a pair to a GPOS pair adjustment subtable in format 1.
+    crate fn gpos_kern(mut self, left: char, right: char, value: i16) -> Self {
+        self.gpos_pairs.push((left, right, value));
+        self
+    }
+
+    /// Kerns every left character against every right one with a GPOS pair
+    /// adjustment subtable in format 2.
+    crate fn gpos_class_kern(mut self, left: &str, right: &str, value: i16) -> Self {
+        self.gpos_classes = Some((left.chars().collect(), right.chars().collect(), value));
+        self
+    }
+
+    /// Gives `ch`, which needs a glyph from `glyphs`, an outline of
+    /// TrueType contours: lists of `(x, y, on_curve)` points.
+    crate fn outline(mut self, ch: char, contours: &[&[(i16, i16, bool)]]) -> Self {
+        let contours = contours.iter().map(|contour| contour.to_vec()).collect();
+        self.outlines.push((ch, contours));
+        self
+    }
+
+    /// Makes the glyph for `ch` a composite of the glyphs of other
+    /// characters, each offset by `(dx, dy)`. Only written to `glyf`.
+    crate fn composite(mut self, ch: char, components: &[(char, i16, i16)]) -> Self {
+        self.composites.push((ch, components.to_vec()));
+        self
+    }
+
+    /// Writes outlines to a `CFF ` table instead of `glyf`, with quadratic
+    /// curves raised to cubics. Contours must start on the curve and may not
+    /// have two off-curve points in a row.
+    crate fn cff(mut self) -> Self {
+        self.cff = true;
+        self
+    }
+
+    fn glyph(&self, ch: char) -> u16 {
+        let index = self.glyphs.iter().position(|&(c, _)| c == ch);
+        index.expect("character without a glyph") as u16 + 1
+    }
+
+    crate fn build(&self) -> Vec<u8> {
+        self.build_at(0)
+    }
+
+    /// Lays out the font to start `base` bytes into a file, as in a
+    /// collection.
+    fn build_at(&self, base: u32) -> Vec<u8> {
+        let mut tables = vec![
+            (*b"OS/2", self.os2()),
+            (*b"cmap", self.cmap()),
+            (*b"head", self.head()),
+            (*b"hhea", self.hhea()),
+            (*b"hmtx", self.hmtx()),
+            (*b"maxp", self.maxp()),
+            (*b"name", self.name()),
+        ];
+        if self.cff {
+            tables.push((*b"CFF ", self.cff_table()));
+        } else if !self.outlines.is_empty() || !self.composites.is_empty() {
+            let (glyf, loca) = self.glyf();
+            tables.push((*b"glyf", glyf));
+            tables.push((*b"loca", loca));
+        }
+        if !self.kern_pairs.is_empty() {
+            tables.push((*b"kern", self.kern_table()));
+        }
+        if !self.gpos_pairs.is_empty() || self.gpos_classes.is_some() {
+            tables.push((*b"GPOS", self.gpos()));
+        }
+        tables.sort_by_key(|&(tag, _)| tag);
+
+        let count = tables.len() as u16;
+        let (range, selector, shift) = search_params(count, 16);
+        let mut out = Writer::default();
+        out.u32(if self.cff { 0x4f54_544f } else { 0x0001_0000 })
+            .u16(count)
+            .u16(range)
+            .u16(selector)
+            .u16(shift);
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, data) in &tables {
+            out.bytes(tag)
+                .u32(0)
+                .u32(base + offset as u32)
+                .u32(data.len() as u32);
+            offset += (data.len() + 3) & !3;
+        }
+        for (_, data) in &tables {
+            out.bytes(data);
+            while out.0.len() % 4 != 0 {
+                out.0.push(0);
+            }
+        }
+        out.0
+    }
+
+    fn head(&self) -> Vec<u8> {
+        let mut head = vec![0; 54];
+        head[0..4].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        head[12..16].copy_from_slice(&0x5f0f_3cf5u32.to_be_bytes());
+        head[18..20].copy_from_slice(&self.units_per_em.to_be_bytes());
+        let mac_style: u16 = if self.style == FontStyle::Normal {
+            0
+        } else {
+            2
+        };
+        head[44..46].copy_from_slice(&mac_style.to_be_bytes());
+        // Long `loca` offsets.
+        head[50..52].copy_from_slice(&1i16.to_be_bytes());
+        head
+    }
+
+    fn hhea(&self) -> Vec<u8> {
+        let mut hhea = vec![0; 36];
+        hhea[0..4].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        hhea[4..6].copy_from_slice(&self.ascent.to_be_bytes());
+        hhea[6..8].copy_from_slice(&self.descent.to_be_bytes());
+        hhea[8..10].copy_from_slice(&self.line_gap.to_be_bytes());
+        hhea[34..36].copy_from_slice(&(self.glyphs.len() as u16 + 1).to_be_bytes());
+        hhea
+    }
+
+    fn maxp(&self) -> Vec<u8> {
+        let mut maxp = Writer::default();
+        maxp.u32(0x0000_5000).u16(self.glyphs.len() as u16 + 1);
+        maxp.0
+    }
+
+    fn hmtx(&self) -> Vec<u8> {
+        let mut hmtx = Writer::default();
+        hmtx.u16(self.notdef_advance).i16(0);
+        for &(_, advance) in &self.glyphs {
+            hmtx.u16(advance).i16(0);
+        }
+        hmtx.0
+    }
+
+    fn os2(&self) -> Vec<u8> {
+        let mut os2 = vec![0; 96];
+        os2[0..2].copy_from_slice(&4u16.to_be_bytes());
+        os2[4..6].copy_from_slice(&self.weight_class.to_be_bytes());
+        os2[6..8].copy_from_slice(&self.width_class.to_be_bytes());
+        // ITALIC or REGULAR, with USE_TYPO_METRICS.
+        let selection: u16 = match self.style {
+            FontStyle::Normal => 0x00c0,
+            FontStyle::Oblique => 0x0280,
+            FontStyle::Italic => 0x0081,
+        };
+        os2[62..64].copy_from_slice(&selection.to_be_bytes());
+        os2[68..70].copy_from_slice(&self.ascent.to_be_bytes());
+        os2[70..72].copy_from_slice(&self.descent.to_be_bytes());
+        os2[72..74].copy_from_slice(&self.line_gap.to_be_bytes());
+        os2[74..76].copy_from_slice(&(self.ascent as u16).to_be_bytes());
+        os2[76..78].copy_from_slice(&(-self.descent as u16).to_be_bytes());
+        os2
+    }
+
+    fn name(&self) -> Vec<u8> {
+        let subfamily = match self.style {
+            FontStyle::Normal => "Regular",
+            FontStyle::Oblique => "Oblique",
+            FontStyle::Italic => "Italic",
+        };
+        let mut names = vec![(1, self.family.as_str()), (2, subfamily)];
+        if let Some(family) = &self.typographic_family {
+            names.push((16, family));
+        }
+
+        let mut storage = Writer::default();
+        let mut name = Writer::default();
+        name.u16(0)
+            .u16(names.len() as u16)
+            .u16(6 + 12 * names.len() as u16);
+        for (id, value) in names {
+            let offset = storage.len();
+            for unit in value.encode_utf16() {
+                storage.u16(unit);
+            }
+            name.u16(3)
+                .u16(1)
+                .u16(0x409)
+                .u16(id)
+                .u16(storage.len() - offset)
+                .u16(offset);
+        }
+        name.bytes(&storage.0);
+        name.0
+    }
+
+    /// A format 4 subtable for the BMP, plus a format 12 subtable when any
+    /// character is outside it.
+    fn cmap(&self) -> Vec<u8> {
+        let mut mapping: Vec<(u32, u16)> = self
+            .glyphs
+            .iter()
+            .enumerate()
+            .map(|(i, &(ch, _))| (ch as u32, i as u16 + 1))
+            .collect();
+        mapping.sort();
+
+        // One segment per character, and the final segment for U+FFFF.
+        let mut segments: Vec<(u16, u16)> = mapping
+            .iter()
+            .filter(|&&(code, _)| code < 0xffff)
+            .map(|&(code, glyph)| (code as u16, glyph.wrapping_sub(code as u16)))
+            .collect();
+        segments.push((0xffff, 1));
+        let seg_count = segments.len() as u16;
+        let (range, selector, shift) = search_params(seg_count, 2);
+        let mut format4 = Writer::default();
+        format4
+            .u16(4)
+            .u16(16 + 8 * seg_count)
+            .u16(0)
+            .u16(seg_count * 2)
+            .u16(range)
+            .u16(selector)
+            .u16(shift);
+        for &(code, _) in &segments {
+            format4.u16(code);
+        }
+        format4.u16(0);
+        for &(code, _) in &segments {
+            format4.u16(code);
+        }
+        for &(_, delta) in &segments {
+            format4.u16(delta);
+        }
+        for _ in &segments {
+            format4.u16(0);
+        }
+
+        let mut subtables = vec![((3, 1), format4.0)];
+        if mapping.iter().any(|&(code, _)| code > 0xffff) {
+            let mut format12 = Writer::default();
+            format12
+                .u16(12)
+                .u16(0)
+                .u32(16 + 12 * mapping.len() as u32)
+                .u32(0)
+                .u32(mapping.len() as u32);
+            for &(code, glyph) in &mapping {
+                format12.u32(code).u32(code).u32(u32::from(glyph));
+            }
+            subtables.push(((3, 10), format12.0));
+        }
+
+        let mut cmap = Writer::default();
+        cmap.u16(0).u16(subtables.len() as u16);
+        let mut offset = 4 + 8 * subtables.len() as u32;
+        for ((platform, encoding), data) in &subtables {
+            cmap.u16(*platform).u16(*encoding).u32(offset);
+            offset += data.len() as u32;
+        }
+        for (_, data) in &subtables {
+            cmap.bytes(data);
+        }
+        cmap.0
+    }
+
+    fn kern_table(&self) -> Vec<u8> {
+        let mut pairs: Vec<(u16, u16, i16)> = self
+            .kern_pairs
+            .iter()
+            .map(|&(l, r, value)| (self.glyph(l), self.glyph(r), value))
+            .collect();
+        pairs.sort();
+        let count = pairs.len() as u16;
+        let (range, selector, shift) = search_params(count, 6);
+
+        let mut kern = Writer::default();
+        kern.u16(0).u16(1);
+        kern.u16(0).u16(14 + 6 * count).u16(0x0001);
+        kern.u16(count).u16(range).u16(selector).u16(shift);
+        for (left, right, value) in pairs {
+            kern.u16(left).u16(right).i16(value);
+        }
+        kern.0
+    }
+
+    /// A GPOS table with a `kern` feature for the default script, holding
+    /// one lookup per subtable format used.
+    fn gpos(&self) -> Vec<u8> {
+        let mut subtables = Vec::new();
+        if !self.gpos_pairs.is_empty() {
+            subtables.push(self.pair_pos1());
+        }
+        if self.gpos_classes.is_some() {
+            subtables.push(self.pair_pos2());
+        }
+        let lookups = subtables.len() as u16;
+
+        let script_list = 10;
+        let feature_list = script_list + 20;
+        let lookup_list = feature_list + 12 + 2 * lookups;
+
+        let mut gpos = Writer::default();
+        gpos.u16(1)
+            .u16(0)
+            .u16(script_list)
+            .u16(feature_list)
+            .u16(lookup_list);
+        // ScriptList with DFLT, whose default LangSys uses feature 0.
+        gpos.u16(1).bytes(b"DFLT").u16(8);
+        gpos.u16(4).u16(0);
+        gpos.u16(0).u16(0xffff).u16(1).u16(0);
+        // FeatureList with `kern` using every lookup.
+        gpos.u16(1).bytes(b"kern").u16(8);
+        gpos.u16(0).u16(lookups);
+        for i in 0..lookups {
+            gpos.u16(i);
+        }
+        // LookupList, with each lookup followed by its subtable.
+        assert_eq!(gpos.len(), lookup_list);
+        gpos.u16(lookups);
+        let mut offset = 2 + 2 * lookups;
+        for subtable in &subtables {
+            gpos.u16(offset);
+            offset += 8 + subtable.len() as u16;
+        }
+        for subtable in &subtables {
+            gpos.u16(2).u16(0).u16(1).u16(8);
+            gpos.bytes(subtable);
+        }
+        gpos.0
+    }
+
+    fn pair_pos1(&self) -> Vec<u8> {
+        let mut pairs: Vec<(u16, u16, i16)> = self
+            .gpos_pairs
+            .iter()
+            .map(|&(l, r, value)| (self.glyph(l), self.glyph(r), value))
+            .collect();
+        pairs.sort();
+        let mut firsts: Vec<u16> = pairs.iter().map(|&(l, _, _)| l).collect();
+        firsts.dedup();
+        let count = firsts.len() as u16;
+
+        let coverage = 10 + 2 * count;
+        let mut sets = Writer::default();
+        let mut set_offsets = Vec::new();
+        let sets_start = coverage + 4 + 2 * count;
+        for &first in &firsts {
+            set_offsets.push(sets_start + sets.len());
+            let set: Vec<_> = pairs.iter().filter(|&&(l, _, _)| l == first).collect();
+            sets.u16(set.len() as u16);
+            for &&(_, right, value) in &set {
+                sets.u16(right).i16(value);
+            }
+        }
+
+        let mut sub = Writer::default();
+        sub.u16(1).u16(coverage).u16(0x0004).u16(0).u16(count);
+        for offset in set_offsets {
+            sub.u16(offset);
+        }
+        sub.u16(1).u16(count);
+        for &first in &firsts {
+            sub.u16(first);
+        }
+        sub.bytes(&sets.0);
+        sub.0
+    }
+
+    /// Class 1 of the first class definition, in format 2, against class 1
+    /// of the second, in format 1.
+    fn pair_pos2(&self) -> Vec<u8> {
+        let (left, right, value) = self.gpos_classes.as_ref().unwrap();
+        let mut left: Vec<u16> = left.iter().map(|&ch| self.glyph(ch)).collect();
+        left.sort();
+        let right: Vec<u16> = right.iter().map(|&ch| self.glyph(ch)).collect();
+        let first = *right.iter().min().unwrap();
+        let last = *right.iter().max().unwrap();
+
+        let records = 16 + 8;
+        let coverage = records;
+        let class_def1 = coverage + 4 + 2 * left.len() as u16;
+        let class_def2 = class_def1 + 4 + 6 * left.len() as u16;
+
+        let mut sub = Writer::default();
+        sub.u16(2)
+            .u16(coverage)
+            .u16(0x0004)
+            .u16(0)
+            .u16(class_def1)
+            .u16(class_def2)
+            .u16(2)
+            .u16(2);
+        sub.i16(0).i16(0).i16(0).i16(*value);
+        sub.u16(1).u16(left.len() as u16);
+        for &glyph in &left {
+            sub.u16(glyph);
+        }
+        sub.u16(2).u16(left.len() as u16);
+        for &glyph in &left {
+            sub.u16(glyph).u16(glyph).u16(1);
+        }
+        sub.u16(1).u16(first).u16(last - first + 1);
+        for glyph in first..=last {
+            sub.u16(right.contains(&glyph) as u16);
+        }
+        sub.0
+    }
+
+    fn contours(&self, glyph: u16) -> Option<&[Contour]> {
+        self.outlines
+            .iter()
+            .find(|&&(ch, _)| self.glyph(ch) == glyph)
+            .map(|(_, contours)| &contours[..])
+    }
+
+    /// Simple glyphs with every delta in its smallest form, and composites
+    /// of offset components.
+    fn glyf(&self) -> (Vec<u8>, Vec<u8>) {
+        const ON_CURVE: u8 = 0x01;
+        const REPEAT: u8 = 0x08;
+
+        let mut glyf = Writer::default();
+        let mut loca = Writer::default();
+        for glyph in 0..=self.glyphs.len() as u16 {
+            loca.u32(glyf.0.len() as u32);
+            let composite = self
+                .composites
+                .iter()
+                .find(|&&(ch, _)| self.glyph(ch) == glyph);
+            if let Some((_, components)) = composite {
+                glyf.i16(-1).i16(0).i16(0).i16(0).i16(0);
+                for (i, &(ch, dx, dy)) in components.iter().enumerate() {
+                    // ARG_1_AND_2_ARE_WORDS, ARGS_ARE_XY_VALUES and
+                    // MORE_COMPONENTS.
+                    let more = if i + 1 < components.len() { 0x0020 } else { 0 };
+                    glyf.u16(0x0003 | more).u16(self.glyph(ch)).i16(dx).i16(dy);
+                }
+                continue;
+            }
+            let contours = match self.contours(glyph) {
+                Some(contours) => contours,
+                None => continue,
+            };
+            let points: Vec<_> = contours.iter().flatten().collect();
+            let xs: Vec<i16> = points.iter().map(|p| p.0).collect();
+            let ys: Vec<i16> = points.iter().map(|p| p.1).collect();
+            glyf.i16(contours.len() as i16)
+                .i16(*xs.iter().min().unwrap())
+                .i16(*ys.iter().min().unwrap())
+                .i16(*xs.iter().max().unwrap())
+                .i16(*ys.iter().max().unwrap());
+            let mut end = 0;
+            for contour in contours {
+                end += contour.len() as u16;
+                glyf.u16(end - 1);
+            }
+            glyf.u16(0);
+
+            let mut flags = Vec::new();
+            let mut coordinates = [Writer::default(), Writer::default()];
+            let mut previous = (0, 0);
+            for point in &points {
+                let mut flag = if point.2 { ON_CURVE } else { 0 };
+                let deltas = [point.0 - previous.0, point.1 - previous.1];
+                // X_SHORT or Y_SHORT, and X_SAME_OR_POSITIVE or
+                // Y_SAME_OR_POSITIVE.
+                for (axis, &delta) in deltas.iter().enumerate() {
+                    let (short, same_or_positive) = (0x02 << axis, 0x10 << axis);
+                    if delta == 0 {
+                        flag |= same_or_positive;
+                    } else if delta > 0 && delta <= 255 {
+                        flag |= short | same_or_positive;
+                        coordinates[axis].0.push(delta as u8);
+                    } else if delta < 0 && delta >= -255 {
+                        flag |= short;
+                        coordinates[axis].0.push((-delta) as u8);
+                    } else {
+                        coordinates[axis].i16(delta);
+                    }
+                }
+                flags.push(flag);
+                previous = (point.0, point.1);
+            }
+            let mut i = 0;
+            while i < flags.len() {
+                let run = flags[i..].iter().take_while(|&&f| f == flags[i]).count();
+                if run > 1 {
+                    glyf.bytes(&[flags[i] | REPEAT, run as u8 - 1]);
+                } else {
+                    glyf.bytes(&[flags[i]]);
+                }
+                i += run;
+            }
+            glyf.bytes(&coordinates[0].0).bytes(&coordinates[1].0);
+        }
+        loca.u32(glyf.0.len() as u32);
+        (glyf.0, loca.0)
+    }
+
+    /// A CFF table whose charstrings call a local subroutine per outline,
+    /// with the advance width on the stack.
+    fn cff_table(&self) -> Vec<u8> {
+        fn number(out: &mut Vec<u8>, value: i32) {
+            if value.abs() <= 107 {
+                out.push((value + 139) as u8);
+            } else {
+                out.push(28);
+                out.extend_from_slice(&(value as i16).to_be_bytes());
+            }
+        }
+        fn index(objects: &[Vec<u8>]) -> Vec<u8> {
+            let mut out = Writer::default();
+            out.u16(objects.len() as u16);
+            if objects.is_empty() {
+                return out.0;
+            }
+            out.bytes(&[4]).u32(1);
+            let mut offset = 1;
+            for object in objects {
+                offset += object.len() as u32;
+                out.u32(offset);
+            }
+            for object in objects {
+                out.bytes(object);
+            }
+            out.0
+        }
+
+        let mut char_strings = Vec::new();
+        let mut subrs = Vec::new();
+        for glyph in 0..=self.glyphs.len() as u16 {
+            let mut charstring = Vec::new();
+            let advance = match glyph {
+                0 => self.notdef_advance,
+                _ => self.glyphs[usize::from(glyph) - 1].1,
+            };
+            number(&mut charstring, i32::from(advance));
+            if let Some(contours) = self.contours(glyph) {
+                number(&mut charstring, subrs.len() as i32 - 107);
+                charstring.push(10);
+
+                let mut subr = Vec::new();
+                let mut current = (0, 0);
+                let mut to = |subr: &mut Vec<u8>, point: (i32, i32)| {
+                    number(subr, point.0 - current.0);
+                    number(subr, point.1 - current.1);
+                    current = point;
+                };
+                for contour in contours {
+                    let point = |i: usize| {
+                        let (x, y, on) = contour[i % contour.len()];
+                        (i32::from(x), i32::from(y), on)
+                    };
+                    let start = point(0);
+                    to(&mut subr, (start.0, start.1));
+                    subr.push(21);
+                    let mut i = 1;
+                    while i <= contour.len() {
+                        let p = point(i);
+                        if p.2 {
+                            if i < contour.len() {
+                                to(&mut subr, (p.0, p.1));
+                                subr.push(5);
+                            }
+                            i += 1;
+                            continue;
+                        }
+                        // Raise the quadratic curve from the previous point
+                        // through `p` to the next point.
+                        let (from, end) = (point(i - 1), point(i + 1));
+                        let third = |a: i32, c: i32| a + (2 * (c - a)) / 3;
+                        to(&mut subr, (third(from.0, p.0), third(from.1, p.1)));
+                        to(&mut subr, (third(end.0, p.0), third(end.1, p.1)));
+                        to(&mut subr, (end.0, end.1));
+                        subr.push(8);
+                        i += 2;
+                    }
+                }
+                subr.push(11);
+                subrs.push(subr);
+            }
+            charstring.push(14);
+            char_strings.push(charstring);
+        }
+
+        let names = index(&[b"Test".to_vec()]);
+        let char_strings = index(&char_strings);
+        // Header, names, a 17 byte top DICT and empty string and global
+        // subroutine INDEXes.
+        let char_strings_offset = 4 + names.len() + index(&[vec![0; 17]]).len() + 4;
+        let private_offset = char_strings_offset + char_strings.len();
+        let mut top = vec![29];
+        top.extend_from_slice(&(char_strings_offset as u32).to_be_bytes());
+        top.extend_from_slice(&[17, 29, 0, 0, 0, 6, 29]);
+        top.extend_from_slice(&(private_offset as u32).to_be_bytes());
+        top.push(18);
+
+        let mut cff = Writer::default();
+        cff.bytes(&[1, 0, 4, 4])
+            .bytes(&names)
+            .bytes(&index(&[top]))
+            .u16(0)
+            .u16(0);
+        assert_eq!(cff.0.len(), char_strings_offset);
+        cff.bytes(&char_strings)
+            .bytes(&[29, 0, 0, 0, 6, 19])
+            .bytes(&index(&subrs));
+        cff.0
+    }
+}
+
+/// Combines fonts into a TrueType collection.
+crate fn collection(fonts: &[FontBuilder]) -> Vec<u8> {
+    let mut out = Writer::default();
+    out.bytes(b"ttcf").u32(0x0001_0000).u32(fonts.len() as u32);
+    let mut offset = 12 + 4 * fonts.len() as u32;
+    let built: Vec<Vec<u8>> = fonts
+        .iter()
+        .map(|font| {
+            let data = font.build_at(offset);
+            out.u32(offset);
+            offset += data.len() as u32;
+            data
+        })
+        .collect();
+    for data in built {
+        out.bytes(&data);
+    }
+    out.0
+}
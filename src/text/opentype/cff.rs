@@ -0,0 +1,565 @@
+//! PostScript outlines from the `CFF ` table.
+//!
+//! Only what drawing needs is read: the charstrings and their subroutines,
+//! including the per-font subroutines of CID-keyed fonts. Hints are skipped.
+
+use super::{
+    outline::Path,
+    parse::{i16_at, u16_at, u32_at, u8_at},
+};
+use std::ops::Range;
+
+/// Subroutine calls nested deeper than this are treated as malformed.
+const MAX_CALL_DEPTH: usize = 10;
+/// The Type 2 charstring argument stack limit.
+const MAX_STACK: usize = 48;
+
+/// An INDEX structure: a count of objects and their offsets.
+#[derive(Clone, Copy, Debug, Default)]
+struct Index {
+    count: usize,
+    off_size: usize,
+    /// Position of the offset array.
+    offsets: usize,
+    /// Position that offsets are relative to, less one.
+    data: usize,
+    /// Position just past the INDEX.
+    end: usize,
+}
+
+impl Index {
+    fn parse(table: &[u8], pos: usize) -> Option<Index> {
+        let count = usize::from(u16_at(table, pos)?);
+        if count == 0 {
+            return Some(Index {
+                end: pos + 2,
+                ..Index::default()
+            });
+        }
+        let off_size = usize::from(u8_at(table, pos + 2)?);
+        if off_size == 0 || off_size > 4 {
+            return None;
+        }
+        let mut index = Index {
+            count,
+            off_size,
+            offsets: pos + 3,
+            data: pos + 2 + (count + 1) * off_size,
+            end: 0,
+        };
+        index.end = index.data + index.offset(table, count)?;
+        Some(index)
+    }
+
+    fn offset(&self, table: &[u8], i: usize) -> Option<usize> {
+        let pos = self.offsets + i * self.off_size;
+        let mut value = 0;
+        for byte in 0..self.off_size {
+            value = value << 8 | usize::from(u8_at(table, pos + byte)?);
+        }
+        Some(value)
+    }
+
+    fn get(&self, table: &[u8], i: usize) -> Option<Range<usize>> {
+        if i >= self.count {
+            return None;
+        }
+        let start = self.data + self.offset(table, i)?;
+        let end = self.data + self.offset(table, i + 1)?;
+        if start > end || end > table.len() {
+            return None;
+        }
+        Some(start..end)
+    }
+}
+
+/// Font DICT selection for CID-keyed fonts.
+#[derive(Clone, Debug)]
+struct CidFonts {
+    /// Local subroutines of each font DICT.
+    subrs: Vec<Index>,
+    fd_select: usize,
+}
+
+#[derive(Clone, Debug)]
+crate struct Cff {
+    char_strings: Index,
+    global_subrs: Index,
+    local_subrs: Index,
+    cid: Option<CidFonts>,
+}
+
+impl Cff {
+    crate fn parse(table: &[u8]) -> Option<Cff> {
+        if u8_at(table, 0)? != 1 {
+            return None;
+        }
+        let names = Index::parse(table, usize::from(u8_at(table, 2)?))?;
+        let top_dicts = Index::parse(table, names.end)?;
+        let strings = Index::parse(table, top_dicts.end)?;
+        let global_subrs = Index::parse(table, strings.end)?;
+
+        let top = dict(table, top_dicts.get(table, 0)?)?;
+        let char_strings = Index::parse(table, *operands(&top, CHAR_STRINGS)?.first()? as usize)?;
+        let local_subrs = match operands(&top, PRIVATE) {
+            Some(private) => private_subrs(table, private)?,
+            None => Index::default(),
+        };
+
+        let cid = match (operands(&top, FD_ARRAY), operands(&top, FD_SELECT)) {
+            (Some(fd_array), Some(fd_select)) => {
+                let fd_array = Index::parse(table, *fd_array.first()? as usize)?;
+                let subrs = (0..fd_array.count)
+                    .map(|i| {
+                        let font = dict(table, fd_array.get(table, i)?)?;
+                        match operands(&font, PRIVATE) {
+                            Some(private) => private_subrs(table, private),
+                            None => Some(Index::default()),
+                        }
+                    })
+                    .collect::<Option<_>>()?;
+                Some(CidFonts {
+                    subrs,
+                    fd_select: *fd_select.first()? as usize,
+                })
+            }
+            _ => None,
+        };
+
+        Some(Cff {
+            char_strings,
+            global_subrs,
+            local_subrs,
+            cid,
+        })
+    }
+
+    /// Appends the outline of `glyph` to `path`. Returns `None` if the
+    /// charstring is malformed.
+    crate fn outline(&self, table: &[u8], glyph: u16, path: &mut Path) -> Option<()> {
+        let local_subrs = match &self.cid {
+            Some(cid) => {
+                let fd = fd_select(table, cid.fd_select, glyph)?;
+                *cid.subrs.get(usize::from(fd))?
+            }
+            None => self.local_subrs,
+        };
+        let mut charstring = Charstring {
+            table,
+            global_subrs: self.global_subrs,
+            local_subrs,
+            stack: Vec::with_capacity(MAX_STACK),
+            x: 0.0,
+            y: 0.0,
+            stems: 0,
+            width_parsed: false,
+            path,
+        };
+        let range = self.char_strings.get(table, usize::from(glyph))?;
+        charstring.run(range, 0)?;
+        charstring.path.close();
+        Some(())
+    }
+}
+
+const CHAR_STRINGS: u16 = 17;
+const PRIVATE: u16 = 18;
+const SUBRS: u16 = 19;
+const FD_ARRAY: u16 = 0x0c24;
+const FD_SELECT: u16 = 0x0c25;
+
+/// Parses a DICT into its operators and their operands. Real operands are
+/// read as zero, since none of the operators we use take them.
+fn dict(table: &[u8], range: Range<usize>) -> Option<Vec<(u16, Vec<i32>)>> {
+    let mut entries = Vec::new();
+    let mut operands = Vec::new();
+    let mut pos = range.start;
+    while pos < range.end {
+        let b0 = u8_at(table, pos)?;
+        pos += 1;
+        match b0 {
+            0..=11 | 13..=21 => entries.push((u16::from(b0), std::mem::take(&mut operands))),
+            12 => {
+                let b1 = u8_at(table, pos)?;
+                pos += 1;
+                entries.push((0x0c00 | u16::from(b1), std::mem::take(&mut operands)));
+            }
+            28 => {
+                operands.push(i32::from(i16_at(table, pos)?));
+                pos += 2;
+            }
+            29 => {
+                operands.push(u32_at(table, pos)? as i32);
+                pos += 4;
+            }
+            30 => {
+                // Nibbles until one is 0xf.
+                loop {
+                    let byte = u8_at(table, pos)?;
+                    pos += 1;
+                    if byte & 0x0f == 0x0f || byte >> 4 == 0x0f {
+                        break;
+                    }
+                }
+                operands.push(0);
+            }
+            32..=246 => operands.push(i32::from(b0) - 139),
+            247..=250 => {
+                let b1 = i32::from(u8_at(table, pos)?);
+                pos += 1;
+                operands.push((i32::from(b0) - 247) * 256 + b1 + 108);
+            }
+            251..=254 => {
+                let b1 = i32::from(u8_at(table, pos)?);
+                pos += 1;
+                operands.push(-(i32::from(b0) - 251) * 256 - b1 - 108);
+            }
+            _ => return None,
+        }
+    }
+    Some(entries)
+}
+
+fn operands(dict: &[(u16, Vec<i32>)], op: u16) -> Option<&[i32]> {
+    dict.iter()
+        .find(|(entry, _)| *entry == op)
+        .map(|(_, operands)| &operands[..])
+}
+
+/// Reads the local subroutines of the Private DICT given by its `[size,
+/// offset]` operands.
+fn private_subrs(table: &[u8], private: &[i32]) -> Option<Index> {
+    let (size, offset) = match private {
+        [size, offset] => (*size as usize, *offset as usize),
+        _ => return None,
+    };
+    let private = dict(table, offset..offset + size)?;
+    match operands(&private, SUBRS) {
+        Some(subrs) => Index::parse(table, offset + *subrs.first()? as usize),
+        None => Some(Index::default()),
+    }
+}
+
+fn fd_select(table: &[u8], pos: usize, glyph: u16) -> Option<u8> {
+    match u8_at(table, pos)? {
+        0 => u8_at(table, pos + 1 + usize::from(glyph)),
+        3 => {
+            let ranges = usize::from(u16_at(table, pos + 1)?);
+            let mut fd = None;
+            for i in 0..ranges {
+                let range = pos + 3 + i * 3;
+                if u16_at(table, range)? > glyph {
+                    break;
+                }
+                fd = Some(u8_at(table, range + 2)?);
+            }
+            fd
+        }
+        _ => None,
+    }
+}
+
+/// Subroutine numbers are stored minus a bias that depends on how many
+/// subroutines there are.
+fn bias(subrs: &Index) -> i32 {
+    match subrs.count {
+        0..=1239 => 107,
+        1240..=33899 => 1131,
+        _ => 32768,
+    }
+}
+
+/// A Type 2 charstring interpreter.
+struct Charstring<'a> {
+    table: &'a [u8],
+    global_subrs: Index,
+    local_subrs: Index,
+    stack: Vec<f32>,
+    x: f32,
+    y: f32,
+    stems: usize,
+    /// The advance width may precede the first stack-clearing operator; we
+    /// take advances from `hmtx`, so it only needs skipping.
+    width_parsed: bool,
+    path: &'a mut Path,
+}
+
+/// Returned when `endchar` stops the charstring.
+struct End;
+
+impl<'a> Charstring<'a> {
+    fn run(&mut self, range: Range<usize>, depth: usize) -> Option<()> {
+        match self.execute(range, depth)? {
+            Ok(()) | Err(End) => Some(()),
+        }
+    }
+
+    fn execute(&mut self, range: Range<usize>, depth: usize) -> Option<Result<(), End>> {
+        let table = self.table;
+        let mut pos = range.start;
+        while pos < range.end {
+            let b0 = u8_at(table, pos)?;
+            pos += 1;
+            match b0 {
+                // hstem, vstem, hstemhm, vstemhm
+                1 | 3 | 18 | 23 => {
+                    self.skip_width(self.stack.len() % 2 == 1);
+                    self.stems += self.stack.len() / 2;
+                    self.stack.clear();
+                }
+                // hintmask, cntrmask
+                19 | 20 => {
+                    // Arguments here are an implied vstem.
+                    self.skip_width(self.stack.len() % 2 == 1);
+                    self.stems += self.stack.len() / 2;
+                    self.stack.clear();
+                    pos += (self.stems + 7) / 8;
+                }
+                // rmoveto
+                21 => {
+                    self.skip_width(self.stack.len() > 2);
+                    let (dx, dy) = (self.arg(0)?, self.arg(1)?);
+                    self.move_by(dx, dy);
+                }
+                // hmoveto
+                22 => {
+                    self.skip_width(self.stack.len() > 1);
+                    let dx = self.arg(0)?;
+                    self.move_by(dx, 0.0);
+                }
+                // vmoveto
+                4 => {
+                    self.skip_width(self.stack.len() > 1);
+                    let dy = self.arg(0)?;
+                    self.move_by(0.0, dy);
+                }
+                // rlineto
+                5 => {
+                    for pair in self.take().chunks_exact(2) {
+                        self.line_by(pair[0], pair[1]);
+                    }
+                }
+                // hlineto, vlineto
+                6 | 7 => {
+                    let mut horizontal = b0 == 6;
+                    for &d in &self.take() {
+                        if horizontal {
+                            self.line_by(d, 0.0);
+                        } else {
+                            self.line_by(0.0, d);
+                        }
+                        horizontal = !horizontal;
+                    }
+                }
+                // rrcurveto
+                8 => {
+                    for c in self.take().chunks_exact(6) {
+                        self.curve_by(c[0], c[1], c[2], c[3], c[4], c[5]);
+                    }
+                }
+                // callsubr, callgsubr
+                10 | 29 => {
+                    let subrs = if b0 == 10 {
+                        self.local_subrs
+                    } else {
+                        self.global_subrs
+                    };
+                    let index = self.stack.pop()? as i32 + bias(&subrs);
+                    if index < 0 || depth >= MAX_CALL_DEPTH {
+                        return None;
+                    }
+                    let subr = subrs.get(table, index as usize)?;
+                    if let Err(End) = self.execute(subr, depth + 1)? {
+                        return Some(Err(End));
+                    }
+                }
+                // return
+                11 => return Some(Ok(())),
+                // endchar
+                14 => {
+                    self.skip_width(self.stack.len() == 1 || self.stack.len() == 5);
+                    self.stack.clear();
+                    self.path.close();
+                    return Some(Err(End));
+                }
+                // rcurveline
+                24 => {
+                    let args = self.take();
+                    if args.len() < 8 {
+                        return None;
+                    }
+                    let (curves, line) = args.split_at(args.len() - 2);
+                    for c in curves.chunks_exact(6) {
+                        self.curve_by(c[0], c[1], c[2], c[3], c[4], c[5]);
+                    }
+                    self.line_by(line[0], line[1]);
+                }
+                // rlinecurve
+                25 => {
+                    let args = self.take();
+                    if args.len() < 8 {
+                        return None;
+                    }
+                    let (lines, c) = args.split_at(args.len() - 6);
+                    for pair in lines.chunks_exact(2) {
+                        self.line_by(pair[0], pair[1]);
+                    }
+                    self.curve_by(c[0], c[1], c[2], c[3], c[4], c[5]);
+                }
+                // vvcurveto
+                26 => {
+                    let args = self.take();
+                    let (mut dx1, rest) = match args.len() % 4 {
+                        1 => (args[0], &args[1..]),
+                        _ => (0.0, &args[..]),
+                    };
+                    for c in rest.chunks_exact(4) {
+                        self.curve_by(dx1, c[0], c[1], c[2], 0.0, c[3]);
+                        dx1 = 0.0;
+                    }
+                }
+                // hhcurveto
+                27 => {
+                    let args = self.take();
+                    let (mut dy1, rest) = match args.len() % 4 {
+                        1 => (args[0], &args[1..]),
+                        _ => (0.0, &args[..]),
+                    };
+                    for c in rest.chunks_exact(4) {
+                        self.curve_by(c[0], dy1, c[1], c[2], c[3], 0.0);
+                        dy1 = 0.0;
+                    }
+                }
+                // vhcurveto, hvcurveto
+                30 | 31 => {
+                    let args = self.take();
+                    let mut horizontal = b0 == 31;
+                    let mut i = 0;
+                    while i + 4 <= args.len() {
+                        let last = if args.len() - i == 5 {
+                            args[i + 4]
+                        } else {
+                            0.0
+                        };
+                        let c = &args[i..i + 4];
+                        if horizontal {
+                            self.curve_by(c[0], 0.0, c[1], c[2], last, c[3]);
+                        } else {
+                            self.curve_by(0.0, c[0], c[1], c[2], c[3], last);
+                        }
+                        horizontal = !horizontal;
+                        i += 4;
+                    }
+                }
+                12 => {
+                    let b1 = u8_at(table, pos)?;
+                    pos += 1;
+                    self.flex(b1)?;
+                }
+                28 => {
+                    self.push(f32::from(i16_at(table, pos)?))?;
+                    pos += 2;
+                }
+                32..=246 => self.push(f32::from(b0) - 139.0)?,
+                247..=250 => {
+                    let b1 = f32::from(u8_at(table, pos)?);
+                    pos += 1;
+                    self.push((f32::from(b0) - 247.0) * 256.0 + b1 + 108.0)?;
+                }
+                251..=254 => {
+                    let b1 = f32::from(u8_at(table, pos)?);
+                    pos += 1;
+                    self.push(-(f32::from(b0) - 251.0) * 256.0 - b1 - 108.0)?;
+                }
+                255 => {
+                    let fixed = u32_at(table, pos)? as i32;
+                    pos += 4;
+                    self.push(fixed as f32 / 65536.0)?;
+                }
+                _ => return None,
+            }
+        }
+        Some(Ok(()))
+    }
+
+    fn flex(&mut self, op: u8) -> Option<()> {
+        let a = self.take();
+        match (op, a.len()) {
+            // flex
+            (35, 13) => {
+                self.curve_by(a[0], a[1], a[2], a[3], a[4], a[5]);
+                self.curve_by(a[6], a[7], a[8], a[9], a[10], a[11]);
+            }
+            // hflex
+            (34, 7) => {
+                self.curve_by(a[0], 0.0, a[1], a[2], a[3], 0.0);
+                self.curve_by(a[4], 0.0, a[5], -a[2], a[6], 0.0);
+            }
+            // hflex1
+            (36, 9) => {
+                self.curve_by(a[0], a[1], a[2], a[3], a[4], 0.0);
+                self.curve_by(a[5], 0.0, a[6], a[7], a[8], -(a[1] + a[3] + a[7]));
+            }
+            // flex1
+            (37, 11) => {
+                let dx: f32 = a[..10].iter().step_by(2).sum();
+                let dy: f32 = a[1..10].iter().step_by(2).sum();
+                let (dx6, dy6) = if dx.abs() > dy.abs() {
+                    (a[10], -dy)
+                } else {
+                    (-dx, a[10])
+                };
+                self.curve_by(a[0], a[1], a[2], a[3], a[4], a[5]);
+                self.curve_by(a[6], a[7], a[8], a[9], dx6, dy6);
+            }
+            // Arithmetic and storage operators are deprecated and unused
+            // in practice.
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn push(&mut self, value: f32) -> Option<()> {
+        if self.stack.len() == MAX_STACK {
+            return None;
+        }
+        self.stack.push(value);
+        Some(())
+    }
+
+    fn skip_width(&mut self, has_width: bool) {
+        if !self.width_parsed && has_width {
+            self.stack.remove(0);
+        }
+        self.width_parsed = true;
+    }
+
+    fn arg(&self, i: usize) -> Option<f32> {
+        self.stack.get(i).copied()
+    }
+
+    fn take(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.stack)
+    }
+
+    fn move_by(&mut self, dx: f32, dy: f32) {
+        self.stack.clear();
+        self.x += dx;
+        self.y += dy;
+        self.path.move_to(self.x, self.y);
+    }
+
+    fn line_by(&mut self, dx: f32, dy: f32) {
+        self.x += dx;
+        self.y += dy;
+        self.path.line_to(self.x, self.y);
+    }
+
+    fn curve_by(&mut self, dx1: f32, dy1: f32, dx2: f32, dy2: f32, dx3: f32, dy3: f32) {
+        let (x1, y1) = (self.x + dx1, self.y + dy1);
+        let (x2, y2) = (x1 + dx2, y1 + dy2);
+        self.x = x2 + dx3;
+        self.y = y2 + dy3;
+        self.path.cubic_to(x1, y1, x2, y2, self.x, self.y);
+    }
+}
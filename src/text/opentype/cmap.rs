@@ -0,0 +1,138 @@
+//! The `cmap` table, which maps characters to glyphs.
+
+use super::parse::{u16_at, u32_at, u8_at};
+
+/// The subtable chosen for lookups, as an offset into the `cmap` table.
+#[derive(Clone, Copy, Debug)]
+crate struct Cmap {
+    subtable: usize,
+    format: u16,
+    /// Symbol fonts map their characters into the private use area at
+    /// U+F000.
+    symbol: bool,
+}
+
+impl Cmap {
+    /// Picks the subtable with the widest Unicode coverage that we can read.
+    crate fn parse(table: &[u8]) -> Option<Self> {
+        let count = usize::from(u16_at(table, 2)?);
+        let mut best: Option<(u8, Cmap)> = None;
+        for i in 0..count {
+            let record = 4 + i * 8;
+            let platform = u16_at(table, record)?;
+            let encoding = u16_at(table, record + 2)?;
+            let subtable = u32_at(table, record + 4)? as usize;
+            let format = u16_at(table, subtable)?;
+            if ![0, 4, 6, 12].contains(&format) {
+                continue;
+            }
+            let rank = match (platform, encoding, format) {
+                (3, 10, 12) | (0, 4, 12) | (0, 6, 12) => 5,
+                (0, 0..=3, 12) => 4,
+                (3, 1, _) => 3,
+                (0, 0..=3, _) => 2,
+                (3, 0, _) => 1,
+                _ => continue,
+            };
+            if best.map_or(true, |(best, _)| rank > best) {
+                let symbol = platform == 3 && encoding == 0;
+                best = Some((
+                    rank,
+                    Cmap {
+                        subtable,
+                        format,
+                        symbol,
+                    },
+                ));
+            }
+        }
+        best.map(|(_, cmap)| cmap)
+    }
+
+    crate fn glyph_index(&self, table: &[u8], ch: char) -> Option<u16> {
+        let code = ch as u32;
+        let lookup = |code| self.lookup(table, code).filter(|&glyph| glyph != 0);
+        lookup(code).or_else(|| {
+            if self.symbol && code < 0x100 {
+                lookup(0xf000 | code)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn lookup(&self, table: &[u8], code: u32) -> Option<u16> {
+        let sub = self.subtable;
+        match self.format {
+            0 if code < 256 => u8_at(table, sub + 6 + code as usize).map(u16::from),
+            4 if code <= 0xffff => format4(table, sub, code as u16),
+            6 => {
+                let first = u32::from(u16_at(table, sub + 6)?);
+                let count = u32::from(u16_at(table, sub + 8)?);
+                if code < first || code - first >= count {
+                    return None;
+                }
+                u16_at(table, sub + 10 + 2 * (code - first) as usize)
+            }
+            12 => {
+                let groups = u32_at(table, sub + 12)? as usize;
+                let (mut lo, mut hi) = (0, groups);
+                while lo < hi {
+                    let mid = (lo + hi) / 2;
+                    let group = sub + 16 + mid * 12;
+                    let start = u32_at(table, group)?;
+                    let end = u32_at(table, group + 4)?;
+                    if code < start {
+                        hi = mid;
+                    } else if code > end {
+                        lo = mid + 1;
+                    } else {
+                        let glyph = u32_at(table, group + 8)? + (code - start);
+                        return Some(glyph as u16);
+                    }
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+fn format4(table: &[u8], sub: usize, code: u16) -> Option<u16> {
+    let seg_count = usize::from(u16_at(table, sub + 6)? / 2);
+    let ends = sub + 14;
+    let starts = ends + 2 * seg_count + 2;
+    let deltas = starts + 2 * seg_count;
+    let range_offsets = deltas + 2 * seg_count;
+
+    // Segments are sorted by end code; find the first that ends at or after
+    // `code`.
+    let (mut lo, mut hi) = (0, seg_count);
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if u16_at(table, ends + 2 * mid)? < code {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    let seg = lo;
+    if seg == seg_count {
+        return None;
+    }
+    let start = u16_at(table, starts + 2 * seg)?;
+    if code < start {
+        return None;
+    }
+    let delta = u16_at(table, deltas + 2 * seg)?;
+    let range_offset_pos = range_offsets + 2 * seg;
+    let range_offset = usize::from(u16_at(table, range_offset_pos)?);
+    if range_offset == 0 {
+        return Some(code.wrapping_add(delta));
+    }
+    let pos = range_offset_pos + range_offset + 2 * usize::from(code - start);
+    match u16_at(table, pos)? {
+        0 => None,
+        glyph => Some(glyph.wrapping_add(delta)),
+    }
+}
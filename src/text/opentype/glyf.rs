@@ -0,0 +1,264 @@
+//! TrueType outlines from the `glyf` and `loca` tables.
+
+use super::{
+    outline::Path,
+    parse::{i16_at, u16_at, u32_at, u8_at},
+};
+
+/// Composite glyphs nested deeper than this are treated as malformed.
+const MAX_DEPTH: u32 = 8;
+
+/// An affine transform `[a, b, c, d, e, f]` mapping `(x, y)` to
+/// `(a x + c y + e, b x + d y + f)`.
+type Transform = [f32; 6];
+
+const IDENTITY: Transform = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Appends the outline of `glyph` to `path`. Returns `None` if the tables are
+/// malformed.
+crate fn outline(
+    glyf: &[u8],
+    loca: &[u8],
+    long_offsets: bool,
+    glyph: u16,
+    path: &mut Path,
+) -> Option<()> {
+    let tables = Tables {
+        glyf,
+        loca,
+        long_offsets,
+    };
+    tables.glyph(glyph, IDENTITY, 0, path)
+}
+
+struct Tables<'a> {
+    glyf: &'a [u8],
+    loca: &'a [u8],
+    long_offsets: bool,
+}
+
+impl<'a> Tables<'a> {
+    fn data(&self, glyph: u16) -> Option<&'a [u8]> {
+        let index = usize::from(glyph);
+        let (start, end) = if self.long_offsets {
+            (
+                u32_at(self.loca, 4 * index)? as usize,
+                u32_at(self.loca, 4 * index + 4)? as usize,
+            )
+        } else {
+            (
+                2 * usize::from(u16_at(self.loca, 2 * index)?),
+                2 * usize::from(u16_at(self.loca, 2 * index + 2)?),
+            )
+        };
+        if start > end {
+            return None;
+        }
+        self.glyf.get(start..end)
+    }
+
+    fn glyph(&self, glyph: u16, transform: Transform, depth: u32, path: &mut Path) -> Option<()> {
+        let data = self.data(glyph)?;
+        // Glyphs without contours, like the space, have no data at all.
+        if data.is_empty() {
+            return Some(());
+        }
+        let contours = i16_at(data, 0)?;
+        if contours >= 0 {
+            simple(data, contours as usize, transform, path)
+        } else if depth < MAX_DEPTH {
+            self.composite(data, transform, depth, path)
+        } else {
+            None
+        }
+    }
+
+    fn composite(
+        &self,
+        data: &[u8],
+        transform: Transform,
+        depth: u32,
+        path: &mut Path,
+    ) -> Option<()> {
+        const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+        const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+        const WE_HAVE_A_SCALE: u16 = 0x0008;
+        const MORE_COMPONENTS: u16 = 0x0020;
+        const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+        const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+        let f2dot14 = |pos| i16_at(data, pos).map(|v| f32::from(v) / 16384.0);
+        let mut pos = 10;
+        loop {
+            let flags = u16_at(data, pos)?;
+            let glyph = u16_at(data, pos + 2)?;
+            pos += 4;
+            let (dx, dy) = if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                pos += 4;
+                (i16_at(data, pos - 4)?, i16_at(data, pos - 2)?)
+            } else {
+                pos += 2;
+                (
+                    i16::from(u8_at(data, pos - 2)? as i8),
+                    i16::from(u8_at(data, pos - 1)? as i8),
+                )
+            };
+            // Components positioned by matching points are placed at the
+            // origin.
+            let (dx, dy) = if flags & ARGS_ARE_XY_VALUES != 0 {
+                (f32::from(dx), f32::from(dy))
+            } else {
+                (0.0, 0.0)
+            };
+            let [mut a, mut b, mut c, mut d] = [1.0, 0.0, 0.0, 1.0];
+            if flags & WE_HAVE_A_SCALE != 0 {
+                a = f2dot14(pos)?;
+                d = a;
+                pos += 2;
+            } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                a = f2dot14(pos)?;
+                d = f2dot14(pos + 2)?;
+                pos += 4;
+            } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                a = f2dot14(pos)?;
+                b = f2dot14(pos + 2)?;
+                c = f2dot14(pos + 4)?;
+                d = f2dot14(pos + 6)?;
+                pos += 8;
+            }
+
+            let [ta, tb, tc, td, te, tf] = transform;
+            let component = [
+                ta * a + tc * b,
+                tb * a + td * b,
+                ta * c + tc * d,
+                tb * c + td * d,
+                ta * dx + tc * dy + te,
+                tb * dx + td * dy + tf,
+            ];
+            self.glyph(glyph, component, depth + 1, path)?;
+
+            if flags & MORE_COMPONENTS == 0 {
+                return Some(());
+            }
+        }
+    }
+}
+
+fn simple(data: &[u8], contours: usize, transform: Transform, path: &mut Path) -> Option<()> {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const REPEAT: u8 = 0x08;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+    if contours == 0 {
+        return Some(());
+    }
+    let ends = 10;
+    let point_count = usize::from(u16_at(data, ends + 2 * (contours - 1))?) + 1;
+    let instructions = usize::from(u16_at(data, ends + 2 * contours)?);
+    let mut pos = ends + 2 * contours + 2 + instructions;
+
+    let mut flags = Vec::with_capacity(point_count);
+    while flags.len() < point_count {
+        let flag = u8_at(data, pos)?;
+        pos += 1;
+        let repeat = if flag & REPEAT != 0 {
+            pos += 1;
+            usize::from(u8_at(data, pos - 1)?)
+        } else {
+            0
+        };
+        for _ in 0..=repeat {
+            flags.push(flag);
+        }
+    }
+    flags.truncate(point_count);
+
+    // Coordinates are deltas, each a byte or a word depending on its flags.
+    let mut read = |short: u8, same_or_positive: u8| -> Option<Vec<i32>> {
+        let mut value = 0i32;
+        let mut values = Vec::with_capacity(point_count);
+        for &flag in &flags {
+            if flag & short != 0 {
+                let delta = i32::from(u8_at(data, pos)?);
+                pos += 1;
+                value += if flag & same_or_positive != 0 {
+                    delta
+                } else {
+                    -delta
+                };
+            } else if flag & same_or_positive == 0 {
+                value += i32::from(i16_at(data, pos)?);
+                pos += 2;
+            }
+            values.push(value);
+        }
+        Some(values)
+    };
+    let xs = read(X_SHORT, X_SAME_OR_POSITIVE)?;
+    let ys = read(Y_SHORT, Y_SAME_OR_POSITIVE)?;
+
+    let [a, b, c, d, e, f] = transform;
+    let points: Vec<(f32, f32, bool)> = (0..point_count)
+        .map(|i| {
+            let (x, y) = (xs[i] as f32, ys[i] as f32);
+            (
+                a * x + c * y + e,
+                b * x + d * y + f,
+                flags[i] & ON_CURVE != 0,
+            )
+        })
+        .collect();
+
+    let mut start = 0;
+    for i in 0..contours {
+        let end = usize::from(u16_at(data, ends + 2 * i)?) + 1;
+        if end <= start || end > point_count {
+            return None;
+        }
+        contour(&points[start..end], path);
+        start = end;
+    }
+    path.close();
+    Some(())
+}
+
+/// Converts a contour of on- and off-curve points into path commands. Two
+/// off-curve points in a row imply an on-curve point halfway between them.
+fn contour(points: &[(f32, f32, bool)], path: &mut Path) {
+    let mid = |p: (f32, f32, bool), q: (f32, f32, bool)| ((p.0 + q.0) / 2.0, (p.1 + q.1) / 2.0);
+
+    // Start on the first on-curve point, or halfway between the last and
+    // first points if every point is off the curve.
+    let (start, rest) = match points.iter().position(|p| p.2) {
+        Some(i) => ((points[i].0, points[i].1), (i + 1..i + points.len())),
+        None => (mid(points[0], points[points.len() - 1]), (0..points.len())),
+    };
+    path.move_to(start.0, start.1);
+
+    let mut control: Option<(f32, f32)> = None;
+    for k in rest {
+        let p = points[k % points.len()];
+        match (p.2, control) {
+            (true, None) => path.line_to(p.0, p.1),
+            (true, Some(c)) => {
+                path.quad_to(c.0, c.1, p.0, p.1);
+                control = None;
+            }
+            (false, None) => control = Some((p.0, p.1)),
+            (false, Some(c)) => {
+                let m = ((c.0 + p.0) / 2.0, (c.1 + p.1) / 2.0);
+                path.quad_to(c.0, c.1, m.0, m.1);
+                control = Some((p.0, p.1));
+            }
+        }
+    }
+    // The closing segment ends where the contour started.
+    if let Some(c) = control {
+        path.quad_to(c.0, c.1, start.0, start.1);
+    }
+    path.close();
+}
@@ -0,0 +1,223 @@
+//! Pair kerning from the GPOS `kern` feature, or failing that the `kern`
+//! table.
+//!
+//! Only horizontal advance adjustments are read. GPOS lookups apply to every
+//! script and language, and lookup flags are ignored, which is enough for
+//! measuring text but not for shaping it.
+
+use super::parse::{i16_at, search, tag_at, u16_at, u32_at};
+
+/// Offsets of the subtables that can kern a pair, found when the face is
+/// parsed.
+#[derive(Clone, Debug, Default)]
+crate struct Kerning {
+    /// PairPos subtables relative to the GPOS table, grouped by lookup.
+    gpos: Vec<Vec<usize>>,
+    /// Format 0 subtables relative to the `kern` table.
+    kern: Vec<usize>,
+}
+
+impl Kerning {
+    crate fn parse(gpos: Option<&[u8]>, kern: Option<&[u8]>) -> Self {
+        let gpos = gpos.and_then(gpos_lookups).unwrap_or_default();
+        let kern = if gpos.is_empty() {
+            kern.and_then(kern_subtables).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        Kerning { gpos, kern }
+    }
+
+    /// Adjustment to the advance of `left` when followed by `right`, in font
+    /// units.
+    crate fn pair(&self, gpos: &[u8], kern: &[u8], left: u16, right: u16) -> i16 {
+        if !self.gpos.is_empty() {
+            // The first subtable of a lookup that covers the pair applies.
+            return self
+                .gpos
+                .iter()
+                .filter_map(|lookup| {
+                    lookup
+                        .iter()
+                        .find_map(|&sub| pair_pos(gpos, sub, left, right))
+                })
+                .fold(0, i16::wrapping_add);
+        }
+        let key = u32::from(left) << 16 | u32::from(right);
+        self.kern
+            .iter()
+            .filter_map(|&sub| {
+                let count = usize::from(u16_at(kern, sub + 6)?);
+                let record = search(kern, sub + 14, count, 6, key, u32_at)?;
+                i16_at(kern, record + 4)
+            })
+            .fold(0, i16::wrapping_add)
+    }
+}
+
+fn kern_subtables(table: &[u8]) -> Option<Vec<usize>> {
+    // Version 1 tables come from Apple and use a different layout.
+    if u16_at(table, 0)? != 0 {
+        return None;
+    }
+    let count = u16_at(table, 2)?;
+    let mut subtables = Vec::new();
+    let mut sub = 4;
+    for _ in 0..count {
+        let length = usize::from(u16_at(table, sub + 2)?);
+        let coverage = u16_at(table, sub + 4)?;
+        // Horizontal, not minimum values, not cross-stream, format 0.
+        if coverage & 0xff07 == 0x0001 {
+            subtables.push(sub);
+        }
+        sub += length.max(6);
+    }
+    Some(subtables)
+}
+
+fn gpos_lookups(table: &[u8]) -> Option<Vec<Vec<usize>>> {
+    let features = usize::from(u16_at(table, 6)?);
+    let lookups = usize::from(u16_at(table, 8)?);
+
+    let mut indices = Vec::new();
+    for i in 0..usize::from(u16_at(table, features)?) {
+        let record = features + 2 + i * 6;
+        if &tag_at(table, record)? != b"kern" {
+            continue;
+        }
+        let feature = features + usize::from(u16_at(table, record + 4)?);
+        for j in 0..usize::from(u16_at(table, feature + 2)?) {
+            indices.push(u16_at(table, feature + 4 + j * 2)?);
+        }
+    }
+    indices.sort();
+    indices.dedup();
+
+    let lookup_count = u16_at(table, lookups)?;
+    let mut result = Vec::new();
+    for index in indices.into_iter().filter(|&i| i < lookup_count) {
+        let lookup = lookups + usize::from(u16_at(table, lookups + 2 + usize::from(index) * 2)?);
+        let kind = u16_at(table, lookup)?;
+        let mut subtables = Vec::new();
+        for i in 0..usize::from(u16_at(table, lookup + 4)?) {
+            let sub = lookup + usize::from(u16_at(table, lookup + 6 + i * 2)?);
+            match kind {
+                2 => subtables.push(sub),
+                // Extension subtables point at the real one with a 32-bit
+                // offset.
+                9 if u16_at(table, sub + 2)? == 2 => {
+                    subtables.push(sub + u32_at(table, sub + 4)? as usize)
+                }
+                _ => {}
+            }
+        }
+        if !subtables.is_empty() {
+            result.push(subtables);
+        }
+    }
+    Some(result)
+}
+
+/// Looks up a pair in a PairPos subtable, returning `None` if the subtable
+/// does not cover it.
+fn pair_pos(table: &[u8], sub: usize, left: u16, right: u16) -> Option<i16> {
+    let format = u16_at(table, sub)?;
+    let coverage = sub + usize::from(u16_at(table, sub + 2)?);
+    let value_format1 = u16_at(table, sub + 4)?;
+    let value_format2 = u16_at(table, sub + 6)?;
+    let index = coverage_index(table, coverage, left)?;
+    let value_size = 2 * (value_format1.count_ones() + value_format2.count_ones()) as usize;
+
+    match format {
+        1 => {
+            if index >= usize::from(u16_at(table, sub + 8)?) {
+                return None;
+            }
+            let set = sub + usize::from(u16_at(table, sub + 10 + index * 2)?);
+            let count = usize::from(u16_at(table, set)?);
+            let record = search(table, set + 2, count, 2 + value_size, right, u16_at)?;
+            x_advance(table, record + 2, value_format1)
+        }
+        2 => {
+            let class_def1 = sub + usize::from(u16_at(table, sub + 8)?);
+            let class_def2 = sub + usize::from(u16_at(table, sub + 10)?);
+            let class1_count = usize::from(u16_at(table, sub + 12)?);
+            let class2_count = usize::from(u16_at(table, sub + 14)?);
+            let class1 = usize::from(class_of(table, class_def1, left)?);
+            let class2 = usize::from(class_of(table, class_def2, right)?);
+            if class1 >= class1_count || class2 >= class2_count {
+                return None;
+            }
+            let record = sub + 16 + (class1 * class2_count + class2) * value_size;
+            x_advance(table, record, value_format1)
+        }
+        _ => None,
+    }
+}
+
+/// Reads the XAdvance field of a value record, which follows XPlacement and
+/// YPlacement when they are present.
+fn x_advance(table: &[u8], record: usize, value_format: u16) -> Option<i16> {
+    if value_format & 0x0004 == 0 {
+        return Some(0);
+    }
+    let skip = 2 * (value_format & 0x0003).count_ones() as usize;
+    i16_at(table, record + skip)
+}
+
+fn coverage_index(table: &[u8], coverage: usize, glyph: u16) -> Option<usize> {
+    let count = usize::from(u16_at(table, coverage + 2)?);
+    match u16_at(table, coverage)? {
+        1 => {
+            let record = search(table, coverage + 4, count, 2, glyph, u16_at)?;
+            Some((record - coverage - 4) / 2)
+        }
+        2 => {
+            let record = range_record(table, coverage + 4, count, glyph)?;
+            let start = u16_at(table, record)?;
+            let start_index = u16_at(table, record + 4)?;
+            Some(usize::from(start_index) + usize::from(glyph - start))
+        }
+        _ => None,
+    }
+}
+
+/// Glyphs missing from a class definition are in class 0.
+fn class_of(table: &[u8], class_def: usize, glyph: u16) -> Option<u16> {
+    match u16_at(table, class_def)? {
+        1 => {
+            let start = u16_at(table, class_def + 2)?;
+            let count = u16_at(table, class_def + 4)?;
+            if glyph < start || glyph - start >= count {
+                return Some(0);
+            }
+            u16_at(table, class_def + 6 + 2 * usize::from(glyph - start))
+        }
+        2 => {
+            let count = usize::from(u16_at(table, class_def + 2)?);
+            match range_record(table, class_def + 4, count, glyph) {
+                Some(record) => u16_at(table, record + 4),
+                None => Some(0),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Finds the record containing `glyph` among sorted, six byte
+/// `(start, end, value)` range records.
+fn range_record(table: &[u8], pos: usize, count: usize, glyph: u16) -> Option<usize> {
+    let (mut lo, mut hi) = (0, count);
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        let record = pos + mid * 6;
+        if glyph < u16_at(table, record)? {
+            hi = mid;
+        } else if glyph > u16_at(table, record + 2)? {
+            lo = mid + 1;
+        } else {
+            return Some(record);
+        }
+    }
+    None
+}
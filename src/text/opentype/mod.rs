@@ -0,0 +1,725 @@
+//! Reads OpenType and TrueType font files, and collections of them, without
+//! DirectWrite.
+//!
+//! A `Face` provides the naming and classification that font matching needs,
+//! and the character mapping, advances and kerning that text layout needs.
+//! It implements `FontMetrics`, so a face can measure text directly, and
+//! reads glyph outlines from TrueType `glyf` or CFF tables for rasterizing:
+//!
+//! ```ignore
+//! let face = Face::from_file("fonts/Body-Regular.ttf", 0)?;
+//! format.set_font_metrics(Rc::new(face));
+//! ```
+
+#[cfg(test)]
+crate mod builder;
+mod cff;
+mod cmap;
+mod glyf;
+mod kern;
+mod outline;
+mod parse;
+
+pub use self::outline::PathCommand;
+
+use self::{
+    cff::Cff,
+    cmap::Cmap,
+    kern::Kerning,
+    parse::{i16_at, tag_at, u16_at, u32_at},
+};
+use super::{FontMetrics, FontStretch, FontStyle, FontWeight};
+use std::{
+    fmt, fs, io,
+    ops::Range,
+    path::Path,
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// An error reading a font file.
+#[derive(Debug)]
+pub enum FontError {
+    Io(io::Error),
+    /// The data is not an OpenType font or font collection.
+    UnknownFormat,
+    /// A table every font needs is missing.
+    MissingTable(&'static str),
+    /// A table is too short for its contents, or lies outside the file.
+    Malformed(&'static str),
+    /// The collection has fewer faces than the index asked for.
+    FaceIndexOutOfRange(u32),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::Io(e) => e.fmt(f),
+            FontError::UnknownFormat => f.write_str("not an OpenType font"),
+            FontError::MissingTable(tag) => write!(f, "missing '{}' table", tag),
+            FontError::Malformed(tag) => write!(f, "malformed '{}' table", tag),
+            FontError::FaceIndexOutOfRange(index) => {
+                write!(f, "no face {} in font collection", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FontError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FontError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FontError {
+    fn from(e: io::Error) -> Self {
+        FontError::Io(e)
+    }
+}
+
+/// Vertical metrics of a face in design units, as DirectWrite reports them in
+/// `DWRITE_FONT_METRICS`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FaceMetrics {
+    pub design_units_per_em: u16,
+    /// Distance from the baseline to the top of a line.
+    pub ascent: u16,
+    /// Distance from the baseline to the bottom of a line.
+    pub descent: u16,
+    /// Extra space between lines.
+    pub line_gap: i16,
+}
+
+/// A table tag and the byte range it occupies in the file.
+type TableRecord = ([u8; 4], Range<usize>);
+
+/// Where a face keeps its glyph outlines.
+#[derive(Clone, Debug)]
+enum Outlines {
+    None,
+    Glyf { long_offsets: bool },
+    Cff(Cff),
+}
+
+static NEXT_FACE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// One face of a font file.
+#[derive(Clone)]
+pub struct Face {
+    id: usize,
+    data: Rc<[u8]>,
+    index: u32,
+    tables: Vec<TableRecord>,
+    family_name: String,
+    weight: FontWeight,
+    style: FontStyle,
+    stretch: FontStretch,
+    metrics: FaceMetrics,
+    glyph_count: u16,
+    h_metric_count: u16,
+    cmap: Option<Cmap>,
+    kerning: Kerning,
+    outlines: Outlines,
+}
+
+/// Returns the number of faces in a font file: the size of a collection, or
+/// one for a plain font.
+pub fn face_count(data: &[u8]) -> Result<u32, FontError> {
+    match &tag_at(data, 0).ok_or(FontError::UnknownFormat)? {
+        [0, 1, 0, 0] | b"OTTO" | b"true" => Ok(1),
+        b"ttcf" => u32_at(data, 8).ok_or(FontError::Malformed("ttcf")),
+        _ => Err(FontError::UnknownFormat),
+    }
+}
+
+impl Face {
+    /// Parses face `index` of a font file or collection.
+    pub fn parse(data: impl Into<Rc<[u8]>>, index: u32) -> Result<Self, FontError> {
+        let data = data.into();
+        let tables = table_directory(&data, face_offset(&data, index)?)?;
+        let table = |tag| find_table(&data, &tables, tag);
+
+        let head = table(b"head").ok_or(FontError::MissingTable("head"))?;
+        let units_per_em = u16_at(head, 18).ok_or(FontError::Malformed("head"))?;
+        let mac_style = u16_at(head, 44).ok_or(FontError::Malformed("head"))?;
+
+        let hhea = table(b"hhea").ok_or(FontError::MissingTable("hhea"))?;
+        let malformed = || FontError::Malformed("hhea");
+        let hhea_ascender = i16_at(hhea, 4).ok_or_else(malformed)?;
+        let hhea_descender = i16_at(hhea, 6).ok_or_else(malformed)?;
+        let hhea_line_gap = i16_at(hhea, 8).ok_or_else(malformed)?;
+        let h_metric_count = u16_at(hhea, 34).ok_or_else(malformed)?;
+
+        let maxp = table(b"maxp").ok_or(FontError::MissingTable("maxp"))?;
+        let glyph_count = u16_at(maxp, 4).ok_or(FontError::Malformed("maxp"))?;
+
+        let hmtx = table(b"hmtx").ok_or(FontError::MissingTable("hmtx"))?;
+        if hmtx.len() < 4 * usize::from(h_metric_count) {
+            return Err(FontError::Malformed("hmtx"));
+        }
+
+        let mut metrics = FaceMetrics {
+            design_units_per_em: units_per_em,
+            ascent: hhea_ascender.max(0) as u16,
+            descent: hhea_descender.min(0).wrapping_neg() as u16,
+            line_gap: hhea_line_gap,
+        };
+        let mut weight = FontWeight::Normal;
+        let mut stretch = FontStretch::Normal;
+        let mut style = if mac_style & 2 != 0 {
+            FontStyle::Italic
+        } else {
+            FontStyle::Normal
+        };
+
+        if let Some(os2) = table(b"OS/2") {
+            let malformed = || FontError::Malformed("OS/2");
+            let selection = u16_at(os2, 62).ok_or_else(malformed)?;
+            weight = FontWeight::from_numeric(u16_at(os2, 4).ok_or_else(malformed)?);
+            stretch = match FontStretch::from_numeric(u16_at(os2, 6).ok_or_else(malformed)?) {
+                FontStretch::Undefined => FontStretch::Normal,
+                stretch => stretch,
+            };
+            if selection & 0x0001 != 0 {
+                style = FontStyle::Italic;
+            } else if selection & 0x0200 != 0 {
+                style = FontStyle::Oblique;
+            }
+
+            // Like DirectWrite, prefer the typographic metrics only when the
+            // font asks for them, and otherwise use the Windows clipping
+            // metrics with whatever gap keeps the hhea line spacing.
+            if selection & 0x0080 != 0 {
+                let ascent = i16_at(os2, 68).ok_or_else(malformed)?;
+                let descent = i16_at(os2, 70).ok_or_else(malformed)?;
+                metrics.ascent = ascent.max(0) as u16;
+                metrics.descent = descent.min(0).wrapping_neg() as u16;
+                metrics.line_gap = i16_at(os2, 72).ok_or_else(malformed)?;
+            } else if let (Some(ascent), Some(descent)) = (u16_at(os2, 74), u16_at(os2, 76)) {
+                let hhea_height =
+                    i32::from(hhea_ascender) - i32::from(hhea_descender) + i32::from(hhea_line_gap);
+                let gap = hhea_height - i32::from(ascent) - i32::from(descent);
+                metrics.ascent = ascent;
+                metrics.descent = descent;
+                metrics.line_gap = gap.max(0).min(i32::from(i16::max_value())) as i16;
+            }
+        }
+
+        let family_name = table(b"name")
+            .and_then(|name| find_name(name, 16).or_else(|| find_name(name, 1)))
+            .unwrap_or_default();
+        let cmap = table(b"cmap").and_then(Cmap::parse);
+        let kerning = Kerning::parse(table(b"GPOS"), table(b"kern"));
+        let outlines = if let Some(cff) = table(b"CFF ") {
+            Outlines::Cff(Cff::parse(cff).ok_or(FontError::Malformed("CFF "))?)
+        } else if table(b"glyf").is_some() {
+            let format = i16_at(head, 50).ok_or(FontError::Malformed("head"))?;
+            Outlines::Glyf {
+                long_offsets: format == 1,
+            }
+        } else {
+            Outlines::None
+        };
+
+        Ok(Face {
+            id: NEXT_FACE_ID.fetch_add(1, Ordering::Relaxed),
+            data,
+            index,
+            tables,
+            family_name,
+            weight,
+            style,
+            stretch,
+            metrics,
+            glyph_count,
+            h_metric_count,
+            cmap,
+            kerning,
+            outlines,
+        })
+    }
+
+    /// Parses every face of a font file or collection.
+    pub fn parse_collection(data: impl Into<Rc<[u8]>>) -> Result<Vec<Self>, FontError> {
+        let data = data.into();
+        (0..face_count(&data)?)
+            .map(|index| Face::parse(data.clone(), index))
+            .collect()
+    }
+
+    pub fn from_file(path: impl AsRef<Path>, index: u32) -> Result<Self, FontError> {
+        Face::parse(fs::read(path)?, index)
+    }
+
+    /// A number unique to this face among those parsed by the process, and
+    /// shared by its clones.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The whole font file this face came from.
+    pub fn data(&self) -> &Rc<[u8]> {
+        &self.data
+    }
+
+    /// Index of the face within its file.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// The typographic family name if the font has one, otherwise the legacy
+    /// family name, which may include the style for fonts with many weights.
+    pub fn family_name(&self) -> &str {
+        &self.family_name
+    }
+
+    pub fn weight(&self) -> FontWeight {
+        self.weight
+    }
+
+    pub fn style(&self) -> FontStyle {
+        self.style
+    }
+
+    pub fn stretch(&self) -> FontStretch {
+        self.stretch
+    }
+
+    pub fn metrics(&self) -> FaceMetrics {
+        self.metrics
+    }
+
+    pub fn glyph_count(&self) -> u16 {
+        self.glyph_count
+    }
+
+    /// Returns the glyph for `ch`, or `None` if the font lacks one.
+    pub fn glyph_index(&self, ch: char) -> Option<u16> {
+        let cmap = self.cmap.as_ref()?;
+        cmap.glyph_index(self.table(b"cmap")?, ch)
+    }
+
+    /// Horizontal advance of `glyph` in design units.
+    pub fn glyph_advance(&self, glyph: u16) -> u16 {
+        let hmtx = self.table(b"hmtx").unwrap_or(&[]);
+        let index = glyph.min(self.h_metric_count.saturating_sub(1));
+        u16_at(hmtx, 4 * usize::from(index)).unwrap_or(0)
+    }
+
+    /// Adjustment to the advance of `left` when followed by `right`, in
+    /// design units.
+    pub fn glyph_kerning(&self, left: u16, right: u16) -> i16 {
+        let gpos = self.table(b"GPOS").unwrap_or(&[]);
+        let kern = self.table(b"kern").unwrap_or(&[]);
+        self.kerning.pair(gpos, kern, left, right)
+    }
+
+    /// Returns the outline of `glyph` in design units, or `None` if the face
+    /// has no outlines or the glyph's are malformed. Glyphs without contours,
+    /// like the space, have an empty outline.
+    pub fn glyph_outline(&self, glyph: u16) -> Option<Vec<PathCommand>> {
+        let mut path = outline::Path::default();
+        match &self.outlines {
+            Outlines::Glyf { long_offsets } => glyf::outline(
+                self.table(b"glyf")?,
+                self.table(b"loca")?,
+                *long_offsets,
+                glyph,
+                &mut path,
+            )?,
+            Outlines::Cff(cff) => cff.outline(self.table(b"CFF ")?, glyph, &mut path)?,
+            Outlines::None => return None,
+        }
+        Some(path.finish())
+    }
+
+    /// Returns the raw data of a table.
+    pub fn table(&self, tag: &[u8; 4]) -> Option<&[u8]> {
+        find_table(&self.data, &self.tables, tag)
+    }
+
+    fn units_per_em(&self) -> f32 {
+        f32::from(self.metrics.design_units_per_em.max(1))
+    }
+}
+
+impl fmt::Debug for Face {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Face")
+            .field("family_name", &self.family_name)
+            .field("weight", &self.weight)
+            .field("style", &self.style)
+            .field("stretch", &self.stretch)
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+/// Characters the face lacks are measured with its `.notdef` glyph.
+impl FontMetrics for Face {
+    fn advance(&self, ch: char) -> f32 {
+        let glyph = self.glyph_index(ch).unwrap_or(0);
+        f32::from(self.glyph_advance(glyph)) / self.units_per_em()
+    }
+
+    fn ascent(&self) -> f32 {
+        f32::from(self.metrics.ascent) / self.units_per_em()
+    }
+
+    fn descent(&self) -> f32 {
+        f32::from(self.metrics.descent) / self.units_per_em()
+    }
+
+    fn line_gap(&self) -> f32 {
+        f32::from(self.metrics.line_gap) / self.units_per_em()
+    }
+
+    fn kerning(&self, left: char, right: char) -> f32 {
+        match (self.glyph_index(left), self.glyph_index(right)) {
+            (Some(left), Some(right)) => {
+                f32::from(self.glyph_kerning(left, right)) / self.units_per_em()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+fn face_offset(data: &[u8], index: u32) -> Result<usize, FontError> {
+    let count = face_count(data)?;
+    if index >= count {
+        return Err(FontError::FaceIndexOutOfRange(index));
+    }
+    if tag_at(data, 0) == Some(*b"ttcf") {
+        let offset = u32_at(data, 12 + 4 * index as usize).ok_or(FontError::Malformed("ttcf"))?;
+        Ok(offset as usize)
+    } else {
+        Ok(0)
+    }
+}
+
+fn table_directory(data: &[u8], offset: usize) -> Result<Vec<TableRecord>, FontError> {
+    match &tag_at(data, offset).ok_or(FontError::UnknownFormat)? {
+        [0, 1, 0, 0] | b"OTTO" | b"true" => {}
+        _ => return Err(FontError::UnknownFormat),
+    }
+    let malformed = || FontError::Malformed("sfnt");
+    let count = u16_at(data, offset + 4).ok_or_else(malformed)?;
+    (0..usize::from(count))
+        .map(|i| {
+            let record = offset + 12 + i * 16;
+            let tag = tag_at(data, record).ok_or_else(malformed)?;
+            let start = u32_at(data, record + 8).ok_or_else(malformed)? as usize;
+            let length = u32_at(data, record + 12).ok_or_else(malformed)? as usize;
+            let end = start.checked_add(length).filter(|&end| end <= data.len());
+            let end = end.ok_or_else(malformed)?;
+            Ok((tag, start..end))
+        })
+        .collect()
+}
+
+fn find_table<'a>(data: &'a [u8], tables: &[TableRecord], tag: &[u8; 4]) -> Option<&'a [u8]> {
+    tables
+        .iter()
+        .find(|(t, _)| t == tag)
+        .map(|(_, range)| &data[range.clone()])
+}
+
+/// Finds a name by ID, preferring US English Windows names, then any Windows
+/// or Unicode name, then Macintosh Roman names.
+fn find_name(table: &[u8], name_id: u16) -> Option<String> {
+    let count = usize::from(u16_at(table, 2)?);
+    let storage = usize::from(u16_at(table, 4)?);
+    let mut best: Option<(u8, String)> = None;
+    for i in 0..count {
+        let record = 6 + i * 12;
+        if u16_at(table, record + 6)? != name_id {
+            continue;
+        }
+        let platform = u16_at(table, record)?;
+        let encoding = u16_at(table, record + 2)?;
+        let language = u16_at(table, record + 4)?;
+        let length = usize::from(u16_at(table, record + 8)?);
+        let start = storage + usize::from(u16_at(table, record + 10)?);
+        let bytes = match table.get(start..start + length) {
+            Some(bytes) => bytes,
+            None => continue,
+        };
+        let rank = match (platform, encoding, language) {
+            (3, 1, 0x409) | (3, 10, 0x409) => 4,
+            (3, 1, _) | (3, 10, _) => 3,
+            (0, _, _) => 2,
+            (1, 0, _) => 1,
+            _ => continue,
+        };
+        if best.as_ref().map_or(false, |(best, _)| *best >= rank) {
+            continue;
+        }
+        let name = if platform == 1 {
+            // Close enough to Mac Roman for the ASCII that family names use.
+            bytes.iter().map(|&b| char::from(b)).collect()
+        } else {
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|pair| u16::from(pair[0]) << 8 | u16::from(pair[1]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        };
+        best = Some((rank, name));
+    }
+    best.map(|(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{builder::*, *};
+    use crate::text::{TextFormat, TextLayout};
+
+    fn face(builder: &FontBuilder) -> Face {
+        Face::parse(builder.build(), 0).unwrap()
+    }
+
+    #[test]
+    fn naming_and_classification() {
+        let regular = face(&FontBuilder::new("Body"));
+        assert_eq!(regular.family_name(), "Body");
+        assert_eq!(regular.weight(), FontWeight::Normal);
+        assert_eq!(regular.style(), FontStyle::Normal);
+        assert_eq!(regular.stretch(), FontStretch::Normal);
+
+        let bold = face(
+            &FontBuilder::new("Body Condensed Bold")
+                .typographic_family("Body")
+                .weight(700)
+                .width(3)
+                .italic(),
+        );
+        assert_eq!(bold.family_name(), "Body");
+        assert_eq!(bold.weight(), FontWeight::Bold);
+        assert_eq!(bold.style(), FontStyle::Italic);
+        assert_eq!(bold.stretch(), FontStretch::Condensed);
+    }
+
+    #[test]
+    fn glyphs_and_metrics() {
+        let face = face(
+            &FontBuilder::new("Body")
+                .vertical_metrics(900, -300, 100)
+                .glyphs("ab", 600)
+                .glyphs("\u{1f600}", 1200),
+        );
+        assert_eq!(face.glyph_count(), 4);
+        assert_eq!(face.glyph_index('a'), Some(1));
+        assert_eq!(face.glyph_index('b'), Some(2));
+        assert_eq!(face.glyph_index('\u{1f600}'), Some(3));
+        assert_eq!(face.glyph_index('c'), None);
+        assert_eq!(face.glyph_advance(3), 1200);
+        assert_eq!(
+            face.metrics(),
+            FaceMetrics {
+                design_units_per_em: 1000,
+                ascent: 900,
+                descent: 300,
+                line_gap: 100,
+            }
+        );
+
+        // Em-relative metrics, with `.notdef` for missing characters.
+        assert_eq!(face.advance('a'), 0.6);
+        assert_eq!(face.advance('c'), 0.5);
+        assert_eq!(face.ascent(), 0.9);
+        assert_eq!(face.descent(), 0.3);
+        assert_eq!(face.line_gap(), 0.1);
+    }
+
+    #[test]
+    fn kerning() {
+        let kern = face(
+            &FontBuilder::new("Kern")
+                .glyphs("AVT", 600)
+                .kern('A', 'V', -80),
+        );
+        assert_eq!(kern.glyph_kerning(1, 2), -80);
+        assert_eq!(kern.glyph_kerning(2, 1), 0);
+
+        // GPOS takes precedence over the `kern` table.
+        let gpos = face(
+            &FontBuilder::new("Gpos")
+                .glyphs("AVTo", 600)
+                .kern('A', 'V', -80)
+                .gpos_kern('A', 'V', -60)
+                .gpos_kern('A', 'T', -40)
+                .gpos_class_kern("TV", "oA", -30),
+        );
+        assert_eq!(gpos.glyph_kerning(1, 2), -60);
+        assert_eq!(gpos.glyph_kerning(1, 3), -40);
+        assert_eq!(gpos.glyph_kerning(3, 4), -30);
+        assert_eq!(gpos.glyph_kerning(2, 1), -30);
+        assert_eq!(gpos.glyph_kerning(4, 1), 0);
+        assert_eq!(gpos.kerning('T', 'o'), -0.03);
+    }
+
+    #[test]
+    fn outlines() {
+        use PathCommand::*;
+
+        let square: &[_] = &[
+            (100, 0, true),
+            (500, 0, true),
+            (500, 700, true),
+            (100, 700, true),
+        ];
+        let round: &[_] = &[
+            (0, 0, true),
+            (300, 0, false),
+            (300, 300, true),
+            (0, 300, false),
+        ];
+        let font = FontBuilder::new("Outlines")
+            .glyphs("aocb", 600)
+            .outline(
+                'a',
+                &[
+                    square,
+                    &[(200, 200, true), (200, 300, true), (300, 300, true)],
+                ],
+            )
+            .outline('o', &[round]);
+
+        let glyf = face(
+            &font
+                .clone()
+                .composite('b', &[('a', 0, 0), ('o', 600, -100)]),
+        );
+        let a = vec![
+            MoveTo(100.0, 0.0),
+            LineTo(500.0, 0.0),
+            LineTo(500.0, 700.0),
+            LineTo(100.0, 700.0),
+            Close,
+            MoveTo(200.0, 200.0),
+            LineTo(200.0, 300.0),
+            LineTo(300.0, 300.0),
+            Close,
+        ];
+        assert_eq!(glyf.glyph_outline(1).unwrap(), a);
+        assert_eq!(
+            glyf.glyph_outline(2).unwrap(),
+            [
+                MoveTo(0.0, 0.0),
+                QuadTo(300.0, 0.0, 300.0, 300.0),
+                QuadTo(0.0, 300.0, 0.0, 0.0),
+                Close,
+            ]
+        );
+        assert_eq!(glyf.glyph_outline(3).unwrap(), []);
+        let b = glyf.glyph_outline(4).unwrap();
+        assert_eq!(b[..9], a[..]);
+        assert_eq!(
+            b[9..],
+            [
+                MoveTo(600.0, -100.0),
+                QuadTo(900.0, -100.0, 900.0, 200.0),
+                QuadTo(600.0, 200.0, 600.0, -100.0),
+                Close,
+            ]
+        );
+
+        let cff = face(&font.cff());
+        assert_eq!(cff.glyph_outline(1).unwrap(), a);
+        assert_eq!(
+            cff.glyph_outline(2).unwrap(),
+            [
+                MoveTo(0.0, 0.0),
+                CubicTo(200.0, 0.0, 300.0, 100.0, 300.0, 300.0),
+                CubicTo(100.0, 300.0, 0.0, 200.0, 0.0, 0.0),
+                Close,
+            ]
+        );
+        assert_eq!(cff.glyph_outline(3).unwrap(), []);
+        assert_eq!(cff.glyph_outline(0).unwrap(), []);
+        assert_eq!(cff.glyph_outline(9), None);
+
+        // Off-curve points in a row imply on-curve points between them.
+        let off: &[_] = &[
+            (0, 0, false),
+            (200, 0, false),
+            (200, 200, false),
+            (0, 200, false),
+        ];
+        let circle = face(
+            &FontBuilder::new("Circle")
+                .glyphs("o", 200)
+                .outline('o', &[off]),
+        );
+        assert_eq!(
+            circle.glyph_outline(1).unwrap(),
+            [
+                MoveTo(0.0, 100.0),
+                QuadTo(0.0, 0.0, 100.0, 0.0),
+                QuadTo(200.0, 0.0, 200.0, 100.0),
+                QuadTo(200.0, 200.0, 100.0, 200.0),
+                QuadTo(0.0, 200.0, 0.0, 100.0),
+                Close,
+            ]
+        );
+
+        assert_eq!(
+            face(&FontBuilder::new("None").glyphs("a", 500)).glyph_outline(1),
+            None
+        );
+    }
+
+    #[test]
+    fn layout_with_face() {
+        let face = face(
+            &FontBuilder::new("Kern")
+                .glyphs("AV", 600)
+                .kern('A', 'V', -100),
+        );
+        let mut format = TextFormat::new(
+            "Kern",
+            FontWeight::Normal,
+            FontStyle::Normal,
+            FontStretch::Normal,
+            10.0,
+            "en-us",
+        );
+        format.set_font_metrics(Rc::new(face));
+        let layout = TextLayout::new("AVA", &format, 100.0, 100.0);
+        assert_eq!(layout.metrics().width, 17.0);
+        assert_eq!(layout.line_metrics()[0].height, 10.0);
+    }
+
+    #[test]
+    fn collections() {
+        let data = collection(&[
+            FontBuilder::new("Body"),
+            FontBuilder::new("Body").weight(700),
+        ]);
+        assert_eq!(face_count(&data).unwrap(), 2);
+        let faces = Face::parse_collection(data.clone()).unwrap();
+        assert_eq!(faces[1].weight(), FontWeight::Bold);
+        assert_eq!(faces[1].index(), 1);
+        match Face::parse(data, 2) {
+            Err(FontError::FaceIndexOutOfRange(2)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        match Face::parse(b"not a font".to_vec(), 0) {
+            Err(FontError::UnknownFormat) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        let mut data = FontBuilder::new("Body").build();
+        data.truncate(200);
+        match Face::parse(data, 0) {
+            Err(FontError::Malformed("sfnt")) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
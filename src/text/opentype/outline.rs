@@ -0,0 +1,56 @@
+//! Glyph outlines as paths, shared by the `glyf` and CFF readers.
+
+/// One step of a glyph outline, in design units with y pointing up.
+///
+/// Every contour starts with `MoveTo` and ends with `Close`, which implies a
+/// line back to the contour's first point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathCommand {
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    /// A quadratic Bézier curve through a control point.
+    QuadTo(f32, f32, f32, f32),
+    /// A cubic Bézier curve through two control points.
+    CubicTo(f32, f32, f32, f32, f32, f32),
+    Close,
+}
+
+/// Collects path commands, closing each contour before the next starts.
+#[derive(Debug, Default)]
+crate struct Path {
+    commands: Vec<PathCommand>,
+    open: bool,
+}
+
+impl Path {
+    crate fn move_to(&mut self, x: f32, y: f32) {
+        self.close();
+        self.commands.push(PathCommand::MoveTo(x, y));
+        self.open = true;
+    }
+
+    crate fn line_to(&mut self, x: f32, y: f32) {
+        self.commands.push(PathCommand::LineTo(x, y));
+    }
+
+    crate fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.commands.push(PathCommand::QuadTo(x1, y1, x, y));
+    }
+
+    crate fn cubic_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.commands
+            .push(PathCommand::CubicTo(x1, y1, x2, y2, x, y));
+    }
+
+    crate fn close(&mut self) {
+        if self.open {
+            self.commands.push(PathCommand::Close);
+            self.open = false;
+        }
+    }
+
+    crate fn finish(mut self) -> Vec<PathCommand> {
+        self.close();
+        self.commands
+    }
+}
@@ -0,0 +1,49 @@
+//! Bounds-checked big-endian reads. Every read returns `None` rather than
+//! panicking when the data is too short.
+
+crate fn u8_at(data: &[u8], pos: usize) -> Option<u8> {
+    data.get(pos).copied()
+}
+
+crate fn u16_at(data: &[u8], pos: usize) -> Option<u16> {
+    let bytes = data.get(pos..pos.checked_add(2)?)?;
+    Some(u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+}
+
+crate fn i16_at(data: &[u8], pos: usize) -> Option<i16> {
+    u16_at(data, pos).map(|v| v as i16)
+}
+
+crate fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    let hi = u16_at(data, pos)?;
+    let lo = u16_at(data, pos + 2)?;
+    Some(u32::from(hi) << 16 | u32::from(lo))
+}
+
+crate fn tag_at(data: &[u8], pos: usize) -> Option<[u8; 4]> {
+    let bytes = data.get(pos..pos.checked_add(4)?)?;
+    Some([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Binary searches `count` records of `size` bytes starting at `pos`, which
+/// must be sorted by the key `key` reads from each record.
+crate fn search<K: Ord>(
+    data: &[u8],
+    pos: usize,
+    count: usize,
+    size: usize,
+    target: K,
+    key: impl Fn(&[u8], usize) -> Option<K>,
+) -> Option<usize> {
+    let (mut lo, mut hi) = (0, count);
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        let record = pos + mid * size;
+        match key(data, record)?.cmp(&target) {
+            std::cmp::Ordering::Less => lo = mid + 1,
+            std::cmp::Ordering::Greater => hi = mid,
+            std::cmp::Ordering::Equal => return Some(record),
+        }
+    }
+    None
+}
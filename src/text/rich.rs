@@ -0,0 +1,270 @@
+//! Text with formatting attached to ranges of it.
+//!
+//! Ranges count UTF-16 code units, like DirectWrite's `DWRITE_TEXT_RANGE`,
+//! and `set_utf8` converts byte ranges of the string. Attributes are applied
+//! in the order they were set, so where ranges overlap the later value of an
+//! attribute wins and different attributes combine.
+
+use super::{FontStretch, FontStyle, FontWeight, TextFormat};
+use std::ops::Range;
+
+/// Formatting that can be applied to a range of `RichText`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TextAttribute {
+    FamilyName(String),
+    Weight(FontWeight),
+    Style(FontStyle),
+    Stretch(FontStretch),
+    /// Font size in DIPs.
+    Size(f32),
+    /// A straight `[r, g, b, a]` color to draw the range with, instead of the
+    /// brush passed when drawing.
+    Color([f32; 4]),
+    Underline(bool),
+    Strikethrough(bool),
+    /// Space added after each character, in DIPs.
+    LetterSpacing(f32),
+}
+
+/// The formatting of a run of text, after every attribute covering it has
+/// been applied on top of a `TextFormat`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunStyle {
+    pub family_name: String,
+    pub weight: FontWeight,
+    pub style: FontStyle,
+    pub stretch: FontStretch,
+    pub size: f32,
+    /// Color of the run, or `None` to use the brush passed when drawing.
+    pub color: Option<[f32; 4]>,
+    pub underline: bool,
+    pub strikethrough: bool,
+    pub letter_spacing: f32,
+}
+
+impl RunStyle {
+    /// The style of text that no attribute covers.
+    pub fn from_format(format: &TextFormat) -> Self {
+        Self {
+            family_name: format.family_name().to_owned(),
+            weight: format.weight(),
+            style: format.style(),
+            stretch: format.stretch(),
+            size: format.size(),
+            color: None,
+            underline: false,
+            strikethrough: false,
+            letter_spacing: 0.0,
+        }
+    }
+
+    fn apply(&mut self, attribute: &TextAttribute) {
+        match attribute {
+            TextAttribute::FamilyName(name) => self.family_name = name.clone(),
+            TextAttribute::Weight(weight) => self.weight = *weight,
+            TextAttribute::Style(style) => self.style = *style,
+            TextAttribute::Stretch(stretch) => self.stretch = *stretch,
+            TextAttribute::Size(size) => self.size = *size,
+            TextAttribute::Color(color) => self.color = Some(*color),
+            TextAttribute::Underline(underline) => self.underline = *underline,
+            TextAttribute::Strikethrough(strikethrough) => self.strikethrough = *strikethrough,
+            TextAttribute::LetterSpacing(spacing) => self.letter_spacing = *spacing,
+        }
+    }
+}
+
+/// A maximal range of text sharing one style.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextRun {
+    pub text_position: u32,
+    pub length: u32,
+    pub style: RunStyle,
+}
+
+/// Text with formatting attached to UTF-16 ranges of it.
+///
+/// ```ignore
+/// let text = RichText::new("Hello, world")
+///     .set(0..5, TextAttribute::Weight(FontWeight::Bold))
+///     .set_utf8(7..12, TextAttribute::Color([1.0, 0.0, 0.0, 1.0]));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RichText {
+    text: String,
+    length: u32,
+    attributes: Vec<(Range<u32>, TextAttribute)>,
+}
+
+impl RichText {
+    pub fn new(text: impl Into<String>) -> Self {
+        let text = text.into();
+        let length = text.encode_utf16().count() as u32;
+        Self {
+            text,
+            length,
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Length of the text in UTF-16 code units.
+    pub fn len(&self) -> u32 {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Applies `attribute` to a range of UTF-16 code units. The part of the
+    /// range past the end of the text is ignored.
+    pub fn set(mut self, range: Range<u32>, attribute: TextAttribute) -> Self {
+        let range = range.start.min(self.length)..range.end.min(self.length);
+        if range.start < range.end {
+            self.attributes.push((range, attribute));
+        }
+        self
+    }
+
+    /// Applies `attribute` to a range of bytes of the text. Panics if the
+    /// range does not fall on character boundaries.
+    pub fn set_utf8(self, range: Range<usize>, attribute: TextAttribute) -> Self {
+        let utf16 = |offset: usize| self.text[..offset].encode_utf16().count() as u32;
+        let range = utf16(range.start)..utf16(range.end);
+        self.set(range, attribute)
+    }
+
+    /// The attributes with their ranges, in the order they were set.
+    pub fn attributes(&self) -> &[(Range<u32>, TextAttribute)] {
+        &self.attributes
+    }
+
+    /// Splits the text into runs of uniform style, starting from `format`.
+    /// The runs cover the text in order; empty text has a single empty run.
+    pub fn resolve(&self, format: &TextFormat) -> Vec<TextRun> {
+        let mut bounds: Vec<u32> = self
+            .attributes
+            .iter()
+            .flat_map(|(range, _)| vec![range.start, range.end])
+            .chain(vec![0, self.length])
+            .collect();
+        bounds.sort();
+        bounds.dedup();
+
+        let base = RunStyle::from_format(format);
+        let mut runs: Vec<TextRun> = Vec::new();
+        for window in bounds.windows(2) {
+            let (start, end) = (window[0], window[1]);
+            let mut style = base.clone();
+            for (range, attribute) in &self.attributes {
+                if range.start <= start && end <= range.end {
+                    style.apply(attribute);
+                }
+            }
+            match runs.last_mut() {
+                Some(run) if run.style == style => run.length += end - start,
+                _ => runs.push(TextRun {
+                    text_position: start,
+                    length: end - start,
+                    style,
+                }),
+            }
+        }
+        if runs.is_empty() {
+            runs.push(TextRun {
+                text_position: 0,
+                length: 0,
+                style: base,
+            });
+        }
+        runs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format() -> TextFormat {
+        TextFormat::new(
+            "Body",
+            FontWeight::Normal,
+            FontStyle::Normal,
+            FontStretch::Normal,
+            10.0,
+            "en-us",
+        )
+    }
+
+    fn spans(runs: &[TextRun]) -> Vec<(u32, u32)> {
+        runs.iter()
+            .map(|run| (run.text_position, run.length))
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_ranges() {
+        let runs = RichText::new("0123456789")
+            .set(2..6, TextAttribute::Weight(FontWeight::Bold))
+            .set(4..8, TextAttribute::Style(FontStyle::Italic))
+            .set(5..20, TextAttribute::Weight(FontWeight::Light))
+            .resolve(&format());
+        assert_eq!(spans(&runs), [(0, 2), (2, 2), (4, 1), (5, 3), (8, 2)]);
+        let styles: Vec<_> = runs
+            .iter()
+            .map(|run| (run.style.weight, run.style.style))
+            .collect();
+        assert_eq!(
+            styles,
+            [
+                (FontWeight::Normal, FontStyle::Normal),
+                (FontWeight::Bold, FontStyle::Normal),
+                (FontWeight::Bold, FontStyle::Italic),
+                (FontWeight::Light, FontStyle::Italic),
+                (FontWeight::Light, FontStyle::Normal),
+            ]
+        );
+        assert_eq!(runs[0].style, RunStyle::from_format(&format()));
+    }
+
+    #[test]
+    fn merges_equal_runs() {
+        let runs = RichText::new("0123456789")
+            .set(0..3, TextAttribute::Underline(true))
+            .set(3..6, TextAttribute::Underline(true))
+            .set(6..10, TextAttribute::Size(10.0))
+            .set(8..10, TextAttribute::Color([1.0, 0.0, 0.0, 1.0]))
+            .resolve(&format());
+        assert_eq!(spans(&runs), [(0, 6), (6, 2), (8, 2)]);
+        assert!(runs[0].style.underline);
+        assert_eq!(runs[2].style.color, Some([1.0, 0.0, 0.0, 1.0]));
+
+        assert_eq!(spans(&RichText::new("").resolve(&format())), [(0, 0)]);
+        assert_eq!(spans(&RichText::new("abc").resolve(&format())), [(0, 3)]);
+    }
+
+    #[test]
+    fn utf8_ranges() {
+        // "é" is two bytes and one code unit, and "😀" four bytes and two
+        // code units.
+        let text = RichText::new("é😀 x")
+            .set_utf8(2..6, TextAttribute::FamilyName("Emoji".to_owned()))
+            .set_utf8(7..8, TextAttribute::LetterSpacing(2.0));
+        assert_eq!(text.len(), 5);
+        assert_eq!(text.attributes()[0].0, 1..3);
+        assert_eq!(text.attributes()[1].0, 4..5);
+        let runs = text.resolve(&format());
+        assert_eq!(spans(&runs), [(0, 1), (1, 2), (3, 1), (4, 1)]);
+        assert_eq!(runs[1].style.family_name, "Emoji");
+        assert_eq!(runs[3].style.letter_spacing, 2.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn utf8_range_inside_character() {
+        let _ = RichText::new("é").set_utf8(0..1, TextAttribute::Underline(true));
+    }
+}
@@ -0,0 +1,420 @@
+//! NUL-terminated UTF-16 strings for passing text to the Windows API.
+//!
+//! `WideString` owns its data and `WideStr` borrows it, like `CString` and
+//! `CStr`. Both are portable; only the `OsStr` conversion differs between
+//! platforms.
+
+use std::{
+    borrow::{Borrow, Cow},
+    ffi::{OsStr, OsString},
+    fmt,
+    ops::Deref,
+    path::{Path, PathBuf},
+    string::FromUtf16Error,
+};
+
+/// Code units, including the terminator, that a `WideString` stores without
+/// allocating.
+const INLINE_CAPACITY: usize = 24;
+
+/// The input of a `WideString` contained a NUL before its end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InteriorNulError {
+    position: usize,
+}
+
+impl InteriorNulError {
+    /// Index of the NUL, in UTF-16 code units.
+    pub fn nul_position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for InteriorNulError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "interior NUL at UTF-16 offset {}", self.position)
+    }
+}
+
+impl std::error::Error for InteriorNulError {}
+
+/// Borrowed UTF-16 string with a NUL terminator and no interior NULs.
+#[derive(PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct WideStr([u16]);
+
+impl WideStr {
+    /// `units` must end with the only NUL it contains.
+    pub fn from_slice_with_nul(units: &[u16]) -> Result<&WideStr, InteriorNulError> {
+        match units.iter().position(|u| *u == 0) {
+            Some(position) if position + 1 == units.len() => {
+                Ok(unsafe { Self::from_slice_with_nul_unchecked(units) })
+            }
+            Some(position) => Err(InteriorNulError { position }),
+            // A missing terminator is reported where it should have been.
+            None => Err(InteriorNulError {
+                position: units.len(),
+            }),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `units` must end with a NUL and contain no other.
+    pub unsafe fn from_slice_with_nul_unchecked(units: &[u16]) -> &WideStr {
+        &*(units as *const [u16] as *const WideStr)
+    }
+
+    /// Wrap a string returned by the Windows API.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a NUL-terminated string that outlives `'a`.
+    pub unsafe fn from_ptr<'a>(ptr: *const u16) -> &'a WideStr {
+        let mut len = 0;
+        while *ptr.add(len) != 0 {
+            len += 1;
+        }
+        Self::from_slice_with_nul_unchecked(std::slice::from_raw_parts(ptr, len + 1))
+    }
+
+    pub fn as_ptr(&self) -> *const u16 {
+        self.0.as_ptr()
+    }
+
+    /// The code units without the terminator.
+    pub fn as_slice(&self) -> &[u16] {
+        &self.0[..self.0.len() - 1]
+    }
+
+    pub fn as_slice_with_nul(&self) -> &[u16] {
+        &self.0
+    }
+
+    /// Length in code units, without the terminator.
+    pub fn len(&self) -> usize {
+        self.0.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fails on unpaired surrogates.
+    pub fn to_string(&self) -> Result<String, FromUtf16Error> {
+        String::from_utf16(self.as_slice())
+    }
+
+    /// Replaces unpaired surrogates with U+FFFD.
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(self.as_slice())
+    }
+
+    pub fn to_wide_string(&self) -> WideString {
+        WideString::from_units_unchecked(self.as_slice().iter().copied())
+    }
+}
+
+impl fmt::Debug for WideStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string_lossy(), f)
+    }
+}
+
+impl AsRef<WideStr> for WideStr {
+    fn as_ref(&self) -> &WideStr {
+        self
+    }
+}
+
+impl ToOwned for WideStr {
+    type Owned = WideString;
+    fn to_owned(&self) -> WideString {
+        self.to_wide_string()
+    }
+}
+
+#[derive(Clone)]
+enum Storage {
+    /// `len` includes the terminator.
+    Inline {
+        len: u8,
+        units: [u16; INLINE_CAPACITY],
+    },
+    Heap(Vec<u16>),
+}
+
+/// Owned UTF-16 string with a NUL terminator and no interior NULs. Short
+/// strings, such as font family or locale names, are stored inline.
+#[derive(Clone)]
+pub struct WideString(Storage);
+
+impl WideString {
+    pub fn new(s: &str) -> Result<Self, InteriorNulError> {
+        Self::from_units(s.encode_utf16())
+    }
+
+    /// Copy `units`, which must not contain a NUL, and append a terminator.
+    pub fn from_wide(units: &[u16]) -> Result<Self, InteriorNulError> {
+        Self::from_units(units.iter().copied())
+    }
+
+    /// `OsStr` is converted losslessly on Windows. Elsewhere it's converted
+    /// through UTF-8, replacing invalid sequences with U+FFFD.
+    pub fn from_os_str(s: &OsStr) -> Result<Self, InteriorNulError> {
+        #[cfg(windows)]
+        {
+            use std::os::windows::ffi::OsStrExt;
+            Self::from_units(s.encode_wide())
+        }
+        #[cfg(not(windows))]
+        {
+            Self::new(&s.to_string_lossy())
+        }
+    }
+
+    fn from_units(units: impl Iterator<Item = u16>) -> Result<Self, InteriorNulError> {
+        let mut error = None;
+        let string = Self::from_units_unchecked(units.enumerate().filter_map(|(i, u)| {
+            if u == 0 && error.is_none() {
+                error = Some(InteriorNulError { position: i });
+            }
+            if error.is_none() {
+                Some(u)
+            } else {
+                None
+            }
+        }));
+        match error {
+            Some(error) => Err(error),
+            None => Ok(string),
+        }
+    }
+
+    /// `units` must not contain a NUL.
+    fn from_units_unchecked(mut units: impl Iterator<Item = u16>) -> Self {
+        let mut inline = [0; INLINE_CAPACITY];
+        let mut len = 0;
+        loop {
+            match units.next() {
+                None => {
+                    return WideString(Storage::Inline {
+                        len: len as u8 + 1,
+                        units: inline,
+                    })
+                }
+                Some(u) if len < INLINE_CAPACITY - 1 => {
+                    inline[len] = u;
+                    len += 1;
+                }
+                Some(u) => {
+                    let mut heap = inline[..len].to_vec();
+                    heap.push(u);
+                    heap.extend(units);
+                    heap.push(0);
+                    return WideString(Storage::Heap(heap));
+                }
+            }
+        }
+    }
+
+    pub fn as_wide_str(&self) -> &WideStr {
+        let units = match &self.0 {
+            Storage::Inline { len, units } => &units[..*len as usize],
+            Storage::Heap(units) => &units[..],
+        };
+        unsafe { WideStr::from_slice_with_nul_unchecked(units) }
+    }
+
+    pub fn into_vec_with_nul(self) -> Vec<u16> {
+        match self.0 {
+            Storage::Inline { len, units } => units[..len as usize].to_vec(),
+            Storage::Heap(units) => units,
+        }
+    }
+}
+
+impl Deref for WideString {
+    type Target = WideStr;
+    fn deref(&self) -> &WideStr {
+        self.as_wide_str()
+    }
+}
+
+impl AsRef<WideStr> for WideString {
+    fn as_ref(&self) -> &WideStr {
+        self
+    }
+}
+
+impl Borrow<WideStr> for WideString {
+    fn borrow(&self) -> &WideStr {
+        self
+    }
+}
+
+impl PartialEq for WideString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_wide_str() == other.as_wide_str()
+    }
+}
+
+impl Eq for WideString {}
+
+impl std::hash::Hash for WideString {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.as_wide_str().hash(state)
+    }
+}
+
+impl fmt::Debug for WideString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_wide_str(), f)
+    }
+}
+
+impl From<&WideStr> for WideString {
+    fn from(s: &WideStr) -> Self {
+        s.to_wide_string()
+    }
+}
+
+/// Anything that can be passed to an API taking a NUL-terminated UTF-16
+/// string. Borrows when the value already is one.
+pub trait ToWideStr {
+    fn to_wide_str(&self) -> Result<Cow<'_, WideStr>, InteriorNulError>;
+}
+
+impl ToWideStr for WideStr {
+    fn to_wide_str(&self) -> Result<Cow<'_, WideStr>, InteriorNulError> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl ToWideStr for WideString {
+    fn to_wide_str(&self) -> Result<Cow<'_, WideStr>, InteriorNulError> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl ToWideStr for str {
+    fn to_wide_str(&self) -> Result<Cow<'_, WideStr>, InteriorNulError> {
+        WideString::new(self).map(Cow::Owned)
+    }
+}
+
+impl ToWideStr for String {
+    fn to_wide_str(&self) -> Result<Cow<'_, WideStr>, InteriorNulError> {
+        self.as_str().to_wide_str()
+    }
+}
+
+impl ToWideStr for OsStr {
+    fn to_wide_str(&self) -> Result<Cow<'_, WideStr>, InteriorNulError> {
+        WideString::from_os_str(self).map(Cow::Owned)
+    }
+}
+
+impl ToWideStr for OsString {
+    fn to_wide_str(&self) -> Result<Cow<'_, WideStr>, InteriorNulError> {
+        self.as_os_str().to_wide_str()
+    }
+}
+
+impl ToWideStr for Path {
+    fn to_wide_str(&self) -> Result<Cow<'_, WideStr>, InteriorNulError> {
+        self.as_os_str().to_wide_str()
+    }
+}
+
+impl ToWideStr for PathBuf {
+    fn to_wide_str(&self) -> Result<Cow<'_, WideStr>, InteriorNulError> {
+        self.as_os_str().to_wide_str()
+    }
+}
+
+impl<'a, T> ToWideStr for &'a T
+where
+    T: ToWideStr + ?Sized,
+{
+    fn to_wide_str(&self) -> Result<Cow<'_, WideStr>, InteriorNulError> {
+        (*self).to_wide_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_inline(s: &WideString) -> bool {
+        match s.0 {
+            Storage::Inline { .. } => true,
+            Storage::Heap(_) => false,
+        }
+    }
+
+    #[test]
+    fn construction() {
+        let s = WideString::new("Segoe UI").unwrap();
+        assert!(is_inline(&s));
+        assert_eq!(s.len(), 8);
+        assert_eq!(s.as_slice_with_nul().last(), Some(&0));
+        assert_eq!(s.to_string().unwrap(), "Segoe UI");
+
+        let long = "x".repeat(INLINE_CAPACITY);
+        let s = WideString::new(&long).unwrap();
+        assert!(!is_inline(&s));
+        assert_eq!(s.len(), INLINE_CAPACITY);
+        assert_eq!(s.to_string_lossy(), long);
+        assert!(is_inline(&WideString::new(&long[1..]).unwrap()));
+
+        let empty = WideString::new("").unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.as_slice_with_nul(), &[0]);
+    }
+
+    #[test]
+    fn interior_nul() {
+        let error = WideString::new("ab\0cd").unwrap_err();
+        assert_eq!(error.nul_position(), 2);
+        assert_eq!(error.to_string(), "interior NUL at UTF-16 offset 2");
+        // Offsets count UTF-16 units, not bytes or chars.
+        let error = WideString::new("\u{1F600}é\0").unwrap_err();
+        assert_eq!(error.nul_position(), 3);
+
+        assert!(WideStr::from_slice_with_nul(&[97, 0, 98, 0]).is_err());
+        assert_eq!(
+            WideStr::from_slice_with_nul(&[97, 98])
+                .unwrap_err()
+                .nul_position(),
+            2
+        );
+        let s = WideStr::from_slice_with_nul(&[97, 98, 0]).unwrap();
+        assert_eq!(s.to_string().unwrap(), "ab");
+    }
+
+    #[test]
+    fn strict_and_lossy() {
+        let units = [0x61, 0xD800, 0x62];
+        let s = WideString::from_wide(&units).unwrap();
+        assert!(s.to_string().is_err());
+        assert_eq!(s.to_string_lossy(), "a\u{FFFD}b");
+    }
+
+    #[test]
+    fn borrow_and_convert() {
+        let owned = WideString::new("en-US").unwrap();
+        let from_ptr = unsafe { WideStr::from_ptr(owned.as_ptr()) };
+        assert_eq!(from_ptr, &*owned);
+        assert_eq!(from_ptr.to_wide_string(), owned);
+
+        match owned.to_wide_str().unwrap() {
+            Cow::Borrowed(s) => assert_eq!(s.as_ptr(), owned.as_ptr()),
+            Cow::Owned(_) => panic!("expected a borrow"),
+        }
+        assert_eq!(*"en-US".to_wide_str().unwrap(), *owned);
+        assert_eq!(*OsStr::new("en-US").to_wide_str().unwrap(), *owned);
+        assert!("a\0".to_wide_str().is_err());
+        assert_eq!(format!("{:?}", owned), "\"en-US\"");
+    }
+}
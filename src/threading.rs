@@ -0,0 +1,286 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Which threads may use a COM object.
+//!
+//! Nothing in an interface says whether its object is thread-safe, so each
+//! wrapper declares a threading model in `impl_comptr!` and carries it in an
+//! `Affinity`:
+//!
+//! * `Agile` objects are free-threaded, like Direct3D devices and resources,
+//!   DXGI factories, adapters and surfaces, and Media Foundation attribute
+//!   stores, samples and source readers. Their wrappers are `Send` and
+//!   `Sync`.
+//! * `Apartment` objects live in the COM apartment of the thread that
+//!   created them, and may only be called from it.
+//! * `SingleThreaded` objects aren't thread-safe or share state with other
+//!   objects, such as the resources of a single-threaded Direct2D factory, a
+//!   Direct3D device context, a DXGI swap chain, a DirectComposition device
+//!   and everything it creates, or a locked Media Foundation buffer. This is
+//!   the default.
+//!
+//! Wrappers of the last two are neither `Send` nor `Sync`, and neither is
+//! `ComPtr` itself. Unsafe code can still get them to another thread, so in
+//! debug builds their `Affinity` remembers the thread that created them, and
+//! using, cloning or dropping them on another thread panics.
+
+use std::{fmt, marker::PhantomData, thread};
+
+/// A threading model, for `Affinity`.
+pub trait ThreadingModel: 'static {
+    const NAME: &'static str;
+    /// Whether objects are bound to the thread that created them.
+    const BOUND: bool;
+}
+
+/// Free-threaded objects.
+pub enum Agile {}
+
+/// Objects that belong to the apartment of the thread that created them.
+pub struct Apartment(Void, PhantomData<*const ()>);
+
+/// Objects that aren't thread-safe.
+pub struct SingleThreaded(Void, PhantomData<*const ()>);
+
+/// The models that aren't `Agile` are never constructed, and the pointer
+/// makes them neither `Send` nor `Sync`.
+enum Void {}
+
+impl ThreadingModel for Agile {
+    const NAME: &'static str = "agile";
+    const BOUND: bool = false;
+}
+
+impl ThreadingModel for Apartment {
+    const NAME: &'static str = "apartment-bound";
+    const BOUND: bool = true;
+}
+
+impl ThreadingModel for SingleThreaded {
+    const NAME: &'static str = "single-threaded";
+    const BOUND: bool = true;
+}
+
+/// Implemented by `impl_comptr!` for each wrapper.
+pub trait Threaded {
+    type Model: ThreadingModel;
+
+    fn affinity(&self) -> &Affinity<Self::Model>;
+}
+
+/// A wrapper's threading model, and in debug builds, the thread that owns a
+/// bound object.
+pub struct Affinity<M>
+where
+    M: ThreadingModel,
+{
+    #[cfg(debug_assertions)]
+    owner: Option<thread::ThreadId>,
+    model: PhantomData<M>,
+}
+
+impl<M> Affinity<M>
+where
+    M: ThreadingModel,
+{
+    /// Owned by the current thread, if `M` is bound.
+    pub fn new() -> Self {
+        Affinity {
+            #[cfg(debug_assertions)]
+            owner: if M::BOUND {
+                Some(thread::current().id())
+            } else {
+                None
+            },
+            model: PhantomData,
+        }
+    }
+
+    /// Whether the current thread may use the object. Always true in release
+    /// builds, which don't record the owner.
+    pub fn on_owner_thread(&self) -> bool {
+        #[cfg(debug_assertions)]
+        {
+            self.owner
+                .map_or(true, |owner| owner == thread::current().id())
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            true
+        }
+    }
+
+    /// Panics if the current thread may not use the object.
+    pub fn check(&self) {
+        #[cfg(debug_assertions)]
+        {
+            if let Some(owner) = self.owner {
+                let current = thread::current().id();
+                assert!(
+                    owner == current,
+                    "{} COM object used on {:?}, but it belongs to {:?}",
+                    M::NAME,
+                    current,
+                    owner
+                );
+            }
+        }
+    }
+}
+
+impl<M> Clone for Affinity<M>
+where
+    M: ThreadingModel,
+{
+    fn clone(&self) -> Self {
+        self.check();
+        Affinity {
+            #[cfg(debug_assertions)]
+            owner: self.owner,
+            model: PhantomData,
+        }
+    }
+}
+
+impl<M> Default for Affinity<M>
+where
+    M: ThreadingModel,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> Drop for Affinity<M>
+where
+    M: ThreadingModel,
+{
+    fn drop(&mut self) {
+        // A second panic while unwinding would abort.
+        if !thread::panicking() {
+            self.check();
+        }
+    }
+}
+
+impl<M> fmt::Debug for Affinity<M>
+where
+    M: ThreadingModel,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("Affinity");
+        debug.field("model", &M::NAME);
+        #[cfg(debug_assertions)]
+        debug.field("owner", &self.owner);
+        debug.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        impl_comptr,
+        mock::{IBar, IFoo, Mock},
+        sys::IUnknown,
+        AsPtr,
+    };
+
+    impl_comptr! { Free: [IFoo, IUnknown] in Agile }
+    impl_comptr! { Bound: [IFoo, IUnknown] in Apartment }
+    impl_comptr! { Local: [IBar, IFoo, IUnknown] }
+
+    /// Moves a value to another thread regardless, as unsafe code might.
+    struct Smuggle<T>(T);
+    unsafe impl<T> Send for Smuggle<T> {}
+
+    /// Runs `f` on another thread, returning its panic message if it panics.
+    fn elsewhere<T: 'static>(value: T, f: impl FnOnce(T) + Send + 'static) -> Option<String> {
+        let value = Smuggle(value);
+        let result = thread::spawn(move || {
+            let Smuggle(value) = value;
+            f(value)
+        })
+        .join();
+        result.err().map(|panic| match panic.downcast::<String>() {
+            Ok(message) => *message,
+            Err(_) => String::new(),
+        })
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn models() {
+        assert_send_sync::<Free>();
+        assert_send_sync::<Affinity<Agile>>();
+        assert_eq!(<Local as Threaded>::Model::NAME, "single-threaded");
+        assert_eq!(<Bound as Threaded>::Model::NAME, "apartment-bound");
+        assert_eq!(<Free as Threaded>::Model::NAME, "agile");
+    }
+
+    #[test]
+    fn owner() {
+        let agile = Affinity::<Agile>::new();
+        let bound = Affinity::<Apartment>::new();
+        assert!(agile.on_owner_thread());
+        assert!(bound.on_owner_thread());
+        assert!(elsewhere(agile, |agile| assert!(agile.on_owner_thread())).is_none());
+        let checked = elsewhere(bound, |bound| {
+            assert_eq!(bound.on_owner_thread(), !cfg!(debug_assertions));
+            std::mem::forget(bound);
+        });
+        assert!(checked.is_none());
+    }
+
+    #[test]
+    fn agile_objects() {
+        let (foo, mock) = Mock::builder().implements::<IFoo>().build::<IFoo>();
+        let free = Free::from(foo);
+        let moved = free.clone();
+        let used = thread::spawn(move || {
+            let ptr = AsPtr::<IFoo>::as_ptr(&moved) as usize;
+            drop(moved);
+            ptr
+        })
+        .join()
+        .unwrap();
+        assert_eq!(used, free.0.as_ptr() as usize);
+        assert_eq!(mock.ref_count(), 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn bound_objects() {
+        use crate::mock::Call;
+
+        let (bar, mock) = Mock::builder().implements::<IBar>().build::<IBar>();
+        let local = Local::from(bar);
+        let message = elsewhere(local.clone(), |local| {
+            AsPtr::<IFoo>::as_ptr(&local);
+        })
+        .unwrap();
+        assert!(message.starts_with("single-threaded COM object used on"));
+
+        // Cloning, casting and dropping are checked too; the drop in the
+        // first case is skipped while unwinding.
+        assert!(elsewhere(local.clone(), |local| drop(local.clone())).is_some());
+        assert!(elsewhere(local.clone(), |local| drop(local.as_::<Local, _>())).is_some());
+        assert!(elsewhere(local.clone(), drop).is_some());
+
+        let (foo, _) = Mock::builder().implements::<IFoo>().build::<IFoo>();
+        let message = elsewhere(Bound::from(foo), drop).unwrap();
+        assert!(message.starts_with("apartment-bound COM object"));
+
+        // The checks come before any call, and the smuggled references were
+        // released on the other threads.
+        assert!(!mock
+            .calls()
+            .iter()
+            .any(|call| matches!(call, Call::QueryInterface(..))));
+        assert_eq!(mock.ref_count(), 1);
+        drop(local);
+        assert!(mock.is_destroyed());
+    }
+}
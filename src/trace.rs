@@ -0,0 +1,308 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Structured tracing of COM calls, with the `tracing` feature.
+//!
+//! Every call the wrappers make is recorded as an event with the target
+//! `noodle::com`, with the fields:
+//!
+//! * `interface`: the interface called, such as `IDCompositionSurface`, or
+//!   empty for free functions such as `D3D11CreateDevice`.
+//! * `method`: the method or function, such as `BeginDraw`.
+//! * `hresult`: the name and value of the result, such as
+//!   `E_INVALIDARG (0x80070057)`.
+//! * `ok`: whether the call succeeded.
+//! * `duration_us`: how long the call took, in microseconds.
+//!
+//! Calls that return an `HRESULT` to check go through `hr!`, and are
+//! recorded at `TRACE` level if they succeed and `WARN` if they fail. Other
+//! calls go through `call!`, such as `void` methods like `FillRectangle`,
+//! getters, and enumerations that end with an error code, and are recorded
+//! at `TRACE` level without `hresult` and `ok`.
+//!
+//! Calls with interesting arguments add them as further fields, such as the
+//! `update_rect` of a `BeginDraw`, the `format` of a new surface or the
+//! `stream` read from. Work that spans many calls is wrapped in `DEBUG`
+//! spans: `frame`, with the frame's `index`, for each `FrameScheduler`
+//! frame, and `draw` for each DirectComposition or Direct2D draw.
+//!
+//! Any `tracing` subscriber can collect these, for example to find slow or
+//! failing calls within a frame.
+
+use crate::{sys::HRESULT, Hresult};
+use std::fmt;
+
+#[doc(hidden)]
+pub use tracing;
+
+/// The interface called through `receiver`, from its type name, so that
+/// `ComPtr<winapi::um::dcomp::IDCompositionSurface>` and
+/// `&IDCompositionSurface` both give `IDCompositionSurface`.
+pub fn interface_of<T: ?Sized>(_receiver: &T) -> &'static str {
+    interface_name(std::any::type_name::<T>())
+}
+
+fn interface_name(type_name: &'static str) -> &'static str {
+    let inner = match (type_name.find('<'), type_name.rfind('>')) {
+        (Some(open), Some(close)) if open < close => &type_name[open + 1..close],
+        _ => type_name,
+    };
+    inner
+        .rsplit(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or(inner)
+}
+
+/// The method called by the text of a call, for `hr!`.
+#[doc(hidden)]
+pub fn method_name(call: &'static str) -> &'static str {
+    crate::error::method_name(call)
+}
+
+/// The `hresult` field of an event, for `hr!`.
+#[doc(hidden)]
+pub struct HresultField(pub HRESULT);
+
+impl fmt::Display for HresultField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Hresult::from(self.0).name() {
+            Some(name) => write!(f, "{} (0x{:08x})", name, self.0),
+            None => write!(f, "0x{:08x}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        call, hr,
+        mock::{IBar, Mock},
+        scheduler::{Commit, FrameScheduler, ManualClock, Mode},
+        ComPtr, Result,
+    };
+    use std::{
+        collections::BTreeMap,
+        sync::{Arc, Mutex, MutexGuard},
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Level, Metadata, Subscriber,
+    };
+
+    type Fields = BTreeMap<&'static str, String>;
+
+    #[derive(Debug)]
+    struct Recorded {
+        target: &'static str,
+        level: Level,
+        fields: Fields,
+        /// Names of the spans the event happened in, outermost first.
+        spans: Vec<&'static str>,
+    }
+
+    #[derive(Default)]
+    struct Log {
+        events: Vec<Recorded>,
+        spans: Vec<(&'static str, Fields)>,
+        entered: Vec<u64>,
+    }
+
+    /// Records events, and the fields of spans, as strings.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Log>>);
+
+    impl Recorder {
+        fn log(&self) -> MutexGuard<'_, Log> {
+            self.0.lock().unwrap()
+        }
+    }
+
+    struct Visitor<'a>(&'a mut Fields);
+
+    impl Visit for Visitor<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.insert(field.name(), format!("{:?}", value));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name(), value.to_owned());
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &span::Attributes) -> span::Id {
+            let mut fields = Fields::new();
+            span.record(&mut Visitor(&mut fields));
+            let mut log = self.log();
+            log.spans.push((span.metadata().name(), fields));
+            // Ids can't be 0, so a span's id is its index plus one.
+            span::Id::from_u64(log.spans.len() as u64)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event) {
+            let mut fields = Fields::new();
+            event.record(&mut Visitor(&mut fields));
+            let mut log = self.log();
+            let spans = log
+                .entered
+                .iter()
+                .map(|id| log.spans[*id as usize - 1].0)
+                .collect();
+            log.events.push(Recorded {
+                target: event.metadata().target(),
+                level: *event.metadata().level(),
+                fields,
+                spans,
+            });
+        }
+
+        fn enter(&self, span: &span::Id) {
+            self.log().entered.push(span.into_u64());
+        }
+
+        fn exit(&self, _: &span::Id) {
+            self.log().entered.pop();
+        }
+    }
+
+    fn recording(f: impl FnOnce()) -> Log {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), f);
+        let log = std::mem::take(&mut *recorder.log());
+        log
+    }
+
+    const E_FAIL: HRESULT = 0x8000_4005_u32 as HRESULT;
+
+    unsafe fn create_device(_flags: u32) -> HRESULT {
+        E_FAIL
+    }
+
+    #[test]
+    fn names() {
+        let (bar, _mock) = Mock::builder().implements::<IBar>().build::<IBar>();
+        assert_eq!(interface_of(&bar), "IBar");
+        assert_eq!(interface_of(&&*bar), "IBar");
+        assert_eq!(
+            interface_name("noodle::comptr::ComPtr<winapi::um::dcomp::IDCompositionSurface>"),
+            "IDCompositionSurface"
+        );
+        assert_eq!(interface_name("*mut IFoo"), "IFoo");
+        assert_eq!(HresultField(0).to_string(), "S_OK (0x00000000)");
+        assert_eq!(HresultField(E_FAIL).to_string(), "E_FAIL (0x80004005)");
+    }
+
+    #[test]
+    fn calls() {
+        let (bar, mock) = Mock::builder().implements::<IBar>().build::<IBar>();
+        let log = recording(|| {
+            let value = 4;
+            hr!(bar.SetValue(value), value).unwrap();
+            let error = hr!(create_device(1), flags = 1).unwrap_err();
+            assert_eq!(error.call(), "create_device");
+        });
+        assert_eq!(mock.value(), 4);
+
+        let set = &log.events[0];
+        assert_eq!(set.target, "noodle::com");
+        assert_eq!(set.level, Level::TRACE);
+        let names: Vec<_> = set.fields.keys().copied().collect();
+        assert_eq!(
+            names,
+            [
+                "duration_us",
+                "hresult",
+                "interface",
+                "method",
+                "ok",
+                "value"
+            ]
+        );
+        assert_eq!(set.fields["interface"], "IBar");
+        assert_eq!(set.fields["method"], "SetValue");
+        assert_eq!(set.fields["hresult"], "S_OK (0x00000000)");
+        assert_eq!(set.fields["ok"], "true");
+        assert_eq!(set.fields["value"], "4");
+        assert!(set.fields["duration_us"].parse::<u64>().is_ok());
+
+        let failed = &log.events[1];
+        assert_eq!(failed.level, Level::WARN);
+        assert_eq!(failed.fields["interface"], "");
+        assert_eq!(failed.fields["method"], "create_device");
+        assert_eq!(failed.fields["hresult"], "E_FAIL (0x80004005)");
+        assert_eq!(failed.fields["ok"], "false");
+        assert_eq!(failed.fields["flags"], "1");
+        assert_eq!(log.events.len(), 2);
+    }
+
+    #[test]
+    fn unchecked_calls() {
+        let (bar, _mock) = Mock::builder()
+            .implements::<IBar>()
+            .value(7)
+            .build::<IBar>();
+        let log = recording(|| {
+            let index = 2;
+            assert_eq!(call!(bar.Value(), index), 7);
+        });
+
+        let value = &log.events[0];
+        assert_eq!(value.level, Level::TRACE);
+        let names: Vec<_> = value.fields.keys().copied().collect();
+        assert_eq!(names, ["duration_us", "index", "interface", "method"]);
+        assert_eq!(value.fields["interface"], "IBar");
+        assert_eq!(value.fields["method"], "Value");
+        assert_eq!(log.events.len(), 1);
+    }
+
+    /// Commits through a mock, so each commit makes a traced call.
+    struct MockDevice(ComPtr<IBar>);
+
+    impl Commit for MockDevice {
+        fn commit(&self) -> Result<()> {
+            hr!(self.0.SetValue(1))
+        }
+
+        fn wait_for_commit_completion(&self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn frames() {
+        let (bar, _mock) = Mock::builder().implements::<IBar>().build::<IBar>();
+        let device = MockDevice(bar);
+        let mut scheduler = FrameScheduler::with_clock(Mode::OnDemand, ManualClock::new());
+        let log = recording(|| {
+            for _ in 0..2 {
+                scheduler.request_frame();
+                scheduler.frame(&device, |_| Ok(())).unwrap();
+            }
+            // Not in a frame.
+            device.commit().unwrap();
+        });
+
+        let frames: Vec<_> = log
+            .spans
+            .iter()
+            .filter(|(name, _)| *name == "frame")
+            .collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].1["index"], "0");
+        assert_eq!(frames[1].1["index"], "1");
+
+        let spans: Vec<_> = log.events.iter().map(|event| &event.spans[..]).collect();
+        assert_eq!(spans, [&["frame"][..], &["frame"], &[]]);
+        assert!(log.entered.is_empty());
+    }
+}
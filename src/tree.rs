@@ -0,0 +1,765 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A retained visual tree that owns node properties on the Rust side and
+//! mirrors them to a compositor (DirectComposition on Windows) on commit.
+
+use crate::{Rect, Result};
+use bitflags::bitflags;
+use std::{error, fmt, ops::Mul};
+
+/// 2D affine transform, laid out like `D2D_MATRIX_3X2_F`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix3x2 {
+    pub m11: f32,
+    pub m12: f32,
+    pub m21: f32,
+    pub m22: f32,
+    pub dx: f32,
+    pub dy: f32,
+}
+
+impl Matrix3x2 {
+    pub const IDENTITY: Matrix3x2 = Matrix3x2 {
+        m11: 1.0,
+        m12: 0.0,
+        m21: 0.0,
+        m22: 1.0,
+        dx: 0.0,
+        dy: 0.0,
+    };
+
+    pub fn translation(dx: f32, dy: f32) -> Self {
+        Self {
+            dx,
+            dy,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn scale(sx: f32, sy: f32) -> Self {
+        Self {
+            m11: sx,
+            m22: sy,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn transform_point(&self, p: [f32; 2]) -> [f32; 2] {
+        [
+            p[0] * self.m11 + p[1] * self.m21 + self.dx,
+            p[0] * self.m12 + p[1] * self.m22 + self.dy,
+        ]
+    }
+}
+
+impl Default for Matrix3x2 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Matrix3x2 {
+    type Output = Matrix3x2;
+    /// `a * b` applies `a` first, then `b`.
+    fn mul(self, rhs: Self) -> Self::Output {
+        Matrix3x2 {
+            m11: self.m11 * rhs.m11 + self.m12 * rhs.m21,
+            m12: self.m11 * rhs.m12 + self.m12 * rhs.m22,
+            m21: self.m21 * rhs.m11 + self.m22 * rhs.m21,
+            m22: self.m21 * rhs.m12 + self.m22 * rhs.m22,
+            dx: self.dx * rhs.m11 + self.dy * rhs.m21 + rhs.dx,
+            dy: self.dx * rhs.m12 + self.dy * rhs.m22 + rhs.dy,
+        }
+    }
+}
+
+/// The operations a visual tree needs from the underlying compositor.
+///
+/// `VisualTree` only talks to the compositor from `commit`, so every change
+/// made between two commits is applied as one batch. Errors are passed back
+/// to the caller of `commit`.
+pub trait Compositor {
+    type Visual;
+    type Content: Clone;
+
+    fn create_visual(&self) -> Result<Self::Visual>;
+    /// Adds `visual` to `parent` directly above the sibling `below`, or at the
+    /// bottom when `below` is `None`.
+    fn add_visual(
+        &self,
+        parent: &Self::Visual,
+        visual: &Self::Visual,
+        below: Option<&Self::Visual>,
+    ) -> Result<()>;
+    fn remove_all_visuals(&self, parent: &Self::Visual) -> Result<()>;
+    fn set_offset(&self, visual: &Self::Visual, offset: [f32; 2]) -> Result<()>;
+    fn set_content(&self, visual: &Self::Visual, content: Option<&Self::Content>) -> Result<()>;
+    fn set_clip(&self, visual: &Self::Visual, clip: Option<&Rect>) -> Result<()>;
+    fn set_opacity(&self, visual: &Self::Visual, opacity: f32) -> Result<()>;
+    fn set_transform(&self, visual: &Self::Visual, transform: &Matrix3x2) -> Result<()>;
+    fn commit(&self) -> Result<()>;
+}
+
+/// A node of a `VisualTree`. Once the node is destroyed its id goes stale,
+/// and stays stale even if the node's slot is reused by a new node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+/// The error from changing a node through a stale `NodeId`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StaleNode(pub NodeId);
+
+impl fmt::Display for StaleNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} refers to a destroyed node", self.0)
+    }
+}
+
+impl error::Error for StaleNode {}
+
+bitflags! {
+    struct Dirty: u32 {
+        const CHILDREN = 0b0000_0001;
+        const OFFSET = 0b0000_0010;
+        const CONTENT = 0b0000_0100;
+        const CLIP = 0b0000_1000;
+        const OPACITY = 0b0001_0000;
+        const TRANSFORM = 0b0010_0000;
+    }
+}
+
+struct Node<C: Compositor> {
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    offset: [f32; 2],
+    content: Option<C::Content>,
+    clip: Option<Rect>,
+    opacity: f32,
+    transform: Matrix3x2,
+    dirty: Dirty,
+    visual: Option<C::Visual>,
+}
+
+impl<C: Compositor> Node<C> {
+    fn new() -> Self {
+        Self {
+            parent: None,
+            children: Vec::new(),
+            offset: [0.0, 0.0],
+            content: None,
+            clip: None,
+            opacity: 1.0,
+            transform: Matrix3x2::IDENTITY,
+            dirty: Dirty::empty(),
+            visual: None,
+        }
+    }
+}
+
+/// A node, or the place of a destroyed one. `generation` counts the nodes
+/// that have been destroyed in it.
+struct Slot<C: Compositor> {
+    generation: u32,
+    node: Option<Node<C>>,
+}
+
+/// Retained tree of visuals. Children are ordered back to front, so the last
+/// child is drawn on top of its siblings.
+///
+/// Queries about a destroyed node return `None`, and changes to it fail with
+/// `StaleNode`.
+pub struct VisualTree<C: Compositor> {
+    slots: Vec<Slot<C>>,
+    free: Vec<u32>,
+    root: NodeId,
+}
+
+impl<C: Compositor> Default for VisualTree<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Compositor> VisualTree<C> {
+    pub fn new() -> Self {
+        Self {
+            slots: vec![Slot {
+                generation: 0,
+                node: Some(Node::new()),
+            }],
+            free: Vec::new(),
+            root: NodeId {
+                index: 0,
+                generation: 0,
+            },
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    /// Creates a detached node. It becomes visible once it's added to a
+    /// parent that is connected to the root.
+    pub fn create_node(&mut self) -> NodeId {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: None,
+                });
+                (self.slots.len() - 1) as u32
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.node = Some(Node::new());
+        NodeId {
+            index,
+            generation: slot.generation,
+        }
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.node(id).is_some()
+    }
+
+    fn node(&self, id: NodeId) -> Option<&Node<C>> {
+        let slot = self.slots.get(id.index as usize)?;
+        if slot.generation == id.generation {
+            slot.node.as_ref()
+        } else {
+            None
+        }
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Result<&mut Node<C>, StaleNode> {
+        let slot = self.slots.get_mut(id.index as usize).ok_or(StaleNode(id))?;
+        if slot.generation == id.generation {
+            slot.node.as_mut().ok_or(StaleNode(id))
+        } else {
+            Err(StaleNode(id))
+        }
+    }
+
+    /// A node that the tree itself refers to, such as a parent or child,
+    /// which is always live.
+    fn live(&self, id: NodeId) -> &Node<C> {
+        self.node(id).expect("Expected live node")
+    }
+
+    /// Adds `child` on top of `parent`'s existing children.
+    pub fn append_child(&mut self, parent: NodeId, child: NodeId) -> Result<(), StaleNode> {
+        let index = self.node(parent).ok_or(StaleNode(parent))?.children.len();
+        self.insert_child(parent, index, child)
+    }
+
+    /// Inserts `child` at `index` in `parent`'s back to front child list. If
+    /// `child` already has a parent it is moved.
+    pub fn insert_child(
+        &mut self,
+        parent: NodeId,
+        index: usize,
+        child: NodeId,
+    ) -> Result<(), StaleNode> {
+        self.node_mut(parent)?;
+        self.node_mut(child)?;
+        assert!(child != self.root, "The root can't be reparented");
+        assert!(
+            !self.ancestors(parent).any(|a| a == child) && parent != child,
+            "Inserting a node below itself would create a cycle"
+        );
+        self.detach(child)?;
+        let node = self.node_mut(parent)?;
+        let index = std::cmp::min(index, node.children.len());
+        node.children.insert(index, child);
+        node.dirty |= Dirty::CHILDREN;
+        self.node_mut(child)?.parent = Some(parent);
+        Ok(())
+    }
+
+    /// Detaches `id` from its parent. The node and its subtree stay alive and
+    /// can be re-inserted elsewhere.
+    pub fn detach(&mut self, id: NodeId) -> Result<(), StaleNode> {
+        if let Some(parent) = self.node_mut(id)?.parent.take() {
+            let node = self.node_mut(parent)?;
+            node.children.retain(|&c| c != id);
+            node.dirty |= Dirty::CHILDREN;
+        }
+        Ok(())
+    }
+
+    /// Detaches `id` and releases it along with all of its descendants, whose
+    /// ids all go stale.
+    pub fn destroy(&mut self, id: NodeId) -> Result<(), StaleNode> {
+        assert!(id != self.root, "The root can't be destroyed");
+        self.detach(id)?;
+        let subtree: Vec<NodeId> = self.descendants(id).collect();
+        for id in subtree {
+            let slot = &mut self.slots[id.index as usize];
+            slot.node = None;
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free.push(id.index);
+        }
+        Ok(())
+    }
+
+    /// The parent of `id`, or `None` if it has none or is stale.
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id)?.parent
+    }
+
+    pub fn children(&self, id: NodeId) -> Option<&[NodeId]> {
+        self.node(id).map(|node| &node.children[..])
+    }
+
+    pub fn offset(&self, id: NodeId) -> Option<[f32; 2]> {
+        self.node(id).map(|node| node.offset)
+    }
+
+    /// The content of `id`, or `None` if it has none or is stale.
+    pub fn content(&self, id: NodeId) -> Option<&C::Content> {
+        self.node(id)?.content.as_ref()
+    }
+
+    /// The clip of `id`, or `None` if it has none or is stale.
+    pub fn clip(&self, id: NodeId) -> Option<Rect> {
+        self.node(id)?.clip
+    }
+
+    pub fn opacity(&self, id: NodeId) -> Option<f32> {
+        self.node(id).map(|node| node.opacity)
+    }
+
+    pub fn transform(&self, id: NodeId) -> Option<Matrix3x2> {
+        self.node(id).map(|node| node.transform)
+    }
+
+    /// The compositor visual backing `id`. Visuals are created lazily, so this
+    /// is `None` until the node has been committed once, and for stale ids.
+    pub fn visual(&self, id: NodeId) -> Option<&C::Visual> {
+        self.node(id)?.visual.as_ref()
+    }
+
+    pub fn set_offset(&mut self, id: NodeId, offset: [f32; 2]) -> Result<(), StaleNode> {
+        let node = self.node_mut(id)?;
+        if node.offset != offset {
+            node.offset = offset;
+            node.dirty |= Dirty::OFFSET;
+        }
+        Ok(())
+    }
+
+    pub fn set_content(
+        &mut self,
+        id: NodeId,
+        content: impl Into<Option<C::Content>>,
+    ) -> Result<(), StaleNode> {
+        let node = self.node_mut(id)?;
+        node.content = content.into();
+        node.dirty |= Dirty::CONTENT;
+        Ok(())
+    }
+
+    pub fn set_clip(&mut self, id: NodeId, clip: impl Into<Option<Rect>>) -> Result<(), StaleNode> {
+        let clip = clip.into();
+        let node = self.node_mut(id)?;
+        if node.clip != clip {
+            node.clip = clip;
+            node.dirty |= Dirty::CLIP;
+        }
+        Ok(())
+    }
+
+    pub fn set_opacity(&mut self, id: NodeId, opacity: f32) -> Result<(), StaleNode> {
+        let opacity = opacity.max(0.0).min(1.0);
+        let node = self.node_mut(id)?;
+        if node.opacity != opacity {
+            node.opacity = opacity;
+            node.dirty |= Dirty::OPACITY;
+        }
+        Ok(())
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: Matrix3x2) -> Result<(), StaleNode> {
+        let node = self.node_mut(id)?;
+        if node.transform != transform {
+            node.transform = transform;
+            node.dirty |= Dirty::TRANSFORM;
+        }
+        Ok(())
+    }
+
+    /// True if any node has changes that haven't been committed yet.
+    pub fn is_dirty(&self) -> bool {
+        self.nodes()
+            .any(|n| !n.dirty.is_empty() || n.visual.is_none())
+    }
+
+    fn nodes(&self) -> impl Iterator<Item = &Node<C>> {
+        self.slots.iter().filter_map(|slot| slot.node.as_ref())
+    }
+
+    /// Pre-order, back to front iteration over `id` and its descendants.
+    /// Empty if `id` is stale.
+    pub fn descendants(&self, id: NodeId) -> Descendants<'_, C> {
+        Descendants {
+            tree: self,
+            stack: if self.contains(id) { vec![id] } else { vec![] },
+        }
+    }
+
+    /// Iterates from the parent of `id` up to the root. Empty if `id` is
+    /// stale.
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.parent(id), move |&id| self.live(id).parent)
+    }
+
+    /// Transform from `id`'s local coordinate space to the root's, combining
+    /// each node's transform and offset the same way DirectComposition does.
+    pub fn world_transform(&self, id: NodeId) -> Option<Matrix3x2> {
+        self.node(id)?;
+        let transform =
+            std::iter::once(id)
+                .chain(self.ancestors(id))
+                .fold(Matrix3x2::IDENTITY, |m, id| {
+                    let node = self.live(id);
+                    m * node.transform * Matrix3x2::translation(node.offset[0], node.offset[1])
+                });
+        Some(transform)
+    }
+
+    /// Pushes every pending change to the compositor and commits it. On error
+    /// the changes that weren't sent yet stay pending for the next commit.
+    pub fn commit(&mut self, compositor: &C) -> Result<()> {
+        for node in self.slots.iter_mut().filter_map(|slot| slot.node.as_mut()) {
+            if node.visual.is_none() {
+                node.visual = Some(compositor.create_visual()?);
+                // A fresh visual has default properties, so only the
+                // non-default ones need to be sent.
+                let mut dirty = node.dirty;
+                dirty.set(Dirty::OFFSET, node.offset != [0.0, 0.0]);
+                dirty.set(Dirty::CONTENT, node.content.is_some());
+                dirty.set(Dirty::CLIP, node.clip.is_some());
+                dirty.set(Dirty::OPACITY, node.opacity != 1.0);
+                dirty.set(Dirty::TRANSFORM, node.transform != Matrix3x2::IDENTITY);
+                dirty.set(Dirty::CHILDREN, !node.children.is_empty());
+                node.dirty = dirty;
+            }
+        }
+
+        // A visual can only be added to one parent at a time, so every moved
+        // child has to be detached from its old parent before any parent
+        // adds it, whatever order their slots are in.
+        for node in self.slots.iter().filter_map(|slot| slot.node.as_ref()) {
+            if node.dirty.contains(Dirty::CHILDREN) {
+                compositor.remove_all_visuals(node.visual.as_ref().unwrap())?;
+            }
+        }
+
+        for index in 0..self.slots.len() {
+            let dirty = match &self.slots[index].node {
+                Some(node) => node.dirty,
+                None => continue,
+            };
+            if dirty.is_empty() {
+                continue;
+            }
+
+            let node = self.slots[index].node.as_ref().unwrap();
+            let visual = node.visual.as_ref().unwrap();
+            if dirty.contains(Dirty::OFFSET) {
+                compositor.set_offset(visual, node.offset)?;
+            }
+            if dirty.contains(Dirty::TRANSFORM) {
+                compositor.set_transform(visual, &node.transform)?;
+            }
+            if dirty.contains(Dirty::CLIP) {
+                compositor.set_clip(visual, node.clip.as_ref())?;
+            }
+            if dirty.contains(Dirty::OPACITY) {
+                compositor.set_opacity(visual, node.opacity)?;
+            }
+            if dirty.contains(Dirty::CONTENT) {
+                compositor.set_content(visual, node.content.as_ref())?;
+            }
+            if dirty.contains(Dirty::CHILDREN) {
+                let mut below = None;
+                for &child in &node.children {
+                    let child = self.live(child).visual.as_ref().unwrap();
+                    compositor.add_visual(visual, child, below)?;
+                    below = Some(child);
+                }
+            }
+
+            self.slots[index].node.as_mut().unwrap().dirty = Dirty::empty();
+        }
+
+        compositor.commit()
+    }
+}
+
+pub struct Descendants<'a, C: Compositor> {
+    tree: &'a VisualTree<C>,
+    stack: Vec<NodeId>,
+}
+
+impl<C: Compositor> Iterator for Descendants<'_, C> {
+    type Item = NodeId;
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.stack.pop()?;
+        self.stack
+            .extend(self.tree.live(id).children.iter().rev().copied());
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compositor, Matrix3x2, StaleNode, VisualTree};
+    use crate::{Rect, Result};
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Debug, PartialEq)]
+    enum Op {
+        Create(u32),
+        Add(u32, u32, Option<u32>),
+        RemoveAll(u32),
+        Offset(u32, [f32; 2]),
+        Content(u32, Option<&'static str>),
+        Clip(u32, Option<Rect>),
+        Opacity(u32, f32),
+        Transform(u32, Matrix3x2),
+        Commit,
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        next: Cell<u32>,
+        ops: RefCell<Vec<Op>>,
+    }
+
+    impl Recorder {
+        fn take(&self) -> Vec<Op> {
+            self.ops.replace(Vec::new())
+        }
+    }
+
+    impl Compositor for Recorder {
+        type Visual = u32;
+        type Content = &'static str;
+
+        fn create_visual(&self) -> Result<u32> {
+            let v = self.next.get();
+            self.next.set(v + 1);
+            self.ops.borrow_mut().push(Op::Create(v));
+            Ok(v)
+        }
+        fn add_visual(&self, parent: &u32, visual: &u32, below: Option<&u32>) -> Result<()> {
+            self.ops
+                .borrow_mut()
+                .push(Op::Add(*parent, *visual, below.copied()));
+            Ok(())
+        }
+        fn remove_all_visuals(&self, parent: &u32) -> Result<()> {
+            self.ops.borrow_mut().push(Op::RemoveAll(*parent));
+            Ok(())
+        }
+        fn set_offset(&self, visual: &u32, offset: [f32; 2]) -> Result<()> {
+            self.ops.borrow_mut().push(Op::Offset(*visual, offset));
+            Ok(())
+        }
+        fn set_content(&self, visual: &u32, content: Option<&&'static str>) -> Result<()> {
+            self.ops
+                .borrow_mut()
+                .push(Op::Content(*visual, content.copied()));
+            Ok(())
+        }
+        fn set_clip(&self, visual: &u32, clip: Option<&Rect>) -> Result<()> {
+            self.ops.borrow_mut().push(Op::Clip(*visual, clip.copied()));
+            Ok(())
+        }
+        fn set_opacity(&self, visual: &u32, opacity: f32) -> Result<()> {
+            self.ops.borrow_mut().push(Op::Opacity(*visual, opacity));
+            Ok(())
+        }
+        fn set_transform(&self, visual: &u32, transform: &Matrix3x2) -> Result<()> {
+            self.ops
+                .borrow_mut()
+                .push(Op::Transform(*visual, *transform));
+            Ok(())
+        }
+        fn commit(&self) -> Result<()> {
+            self.ops.borrow_mut().push(Op::Commit);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn structure_queries() {
+        let mut tree = VisualTree::<Recorder>::new();
+        let root = tree.root();
+        let a = tree.create_node();
+        let b = tree.create_node();
+        let c = tree.create_node();
+        tree.append_child(root, a).unwrap();
+        tree.append_child(root, b).unwrap();
+        tree.insert_child(root, 0, c).unwrap();
+        assert_eq!(tree.children(root), Some(&[c, a, b][..]));
+        assert_eq!(tree.parent(a), Some(root));
+
+        // Moving a node removes it from its old parent.
+        tree.append_child(a, c).unwrap();
+        assert_eq!(tree.children(root), Some(&[a, b][..]));
+        assert_eq!(tree.children(a), Some(&[c][..]));
+        assert_eq!(tree.ancestors(c).collect::<Vec<_>>(), vec![a, root]);
+        assert_eq!(
+            tree.descendants(root).collect::<Vec<_>>(),
+            vec![root, a, c, b]
+        );
+
+        tree.destroy(a).unwrap();
+        assert!(!tree.contains(a) && !tree.contains(c));
+        assert_eq!(tree.children(root), Some(&[b][..]));
+    }
+
+    #[test]
+    fn stale_ids() {
+        let mut tree = VisualTree::<Recorder>::new();
+        let root = tree.root();
+        let a = tree.create_node();
+        let child = tree.create_node();
+        tree.append_child(root, a).unwrap();
+        tree.append_child(a, child).unwrap();
+        tree.destroy(a).unwrap();
+
+        // The new nodes reuse the destroyed nodes' slots, but not their ids.
+        let b = tree.create_node();
+        let c = tree.create_node();
+        assert!(a != b && a != c && child != b && child != c);
+        tree.append_child(root, b).unwrap();
+        tree.set_offset(b, [1.0, 2.0]).unwrap();
+
+        assert_eq!(tree.set_offset(a, [5.0, 5.0]), Err(StaleNode(a)));
+        assert_eq!(tree.set_opacity(child, 0.5), Err(StaleNode(child)));
+        assert_eq!(tree.append_child(a, c), Err(StaleNode(a)));
+        assert_eq!(tree.append_child(root, child), Err(StaleNode(child)));
+        assert_eq!(tree.destroy(a), Err(StaleNode(a)));
+        assert_eq!(tree.offset(a), None);
+        assert_eq!(tree.parent(a), None);
+        assert_eq!(tree.children(a), None);
+        assert_eq!(tree.world_transform(child), None);
+        assert_eq!(tree.descendants(a).count(), 0);
+        assert_eq!(tree.ancestors(child).count(), 0);
+
+        // The live nodes were left alone.
+        assert_eq!(tree.offset(b), Some([1.0, 2.0]));
+        assert_eq!(tree.opacity(c), Some(1.0));
+        assert_eq!(tree.children(root), Some(&[b][..]));
+        assert_eq!(tree.children(c), Some(&[][..]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn cycles_are_rejected() {
+        let mut tree = VisualTree::<Recorder>::new();
+        let a = tree.create_node();
+        let b = tree.create_node();
+        tree.append_child(a, b).unwrap();
+        tree.append_child(b, a).unwrap();
+    }
+
+    #[test]
+    fn commit_batches_changes() {
+        let recorder = Recorder::default();
+        let mut tree = VisualTree::<Recorder>::new();
+        let root = tree.root();
+        let a = tree.create_node();
+        let b = tree.create_node();
+        tree.append_child(root, a).unwrap();
+        tree.append_child(root, b).unwrap();
+        tree.set_offset(a, [10.0, 20.0]).unwrap();
+        tree.set_offset(a, [5.0, 5.0]).unwrap();
+        tree.set_content(b, "surface").unwrap();
+        assert!(tree.is_dirty());
+
+        tree.commit(&recorder).unwrap();
+        assert!(!tree.is_dirty());
+        assert_eq!(
+            recorder.take(),
+            vec![
+                Op::Create(0),
+                Op::Create(1),
+                Op::Create(2),
+                Op::RemoveAll(0),
+                Op::Add(0, 1, None),
+                Op::Add(0, 2, Some(1)),
+                Op::Offset(1, [5.0, 5.0]),
+                Op::Content(2, Some("surface")),
+                Op::Commit,
+            ]
+        );
+
+        // Unchanged values don't generate any compositor traffic.
+        tree.set_offset(a, [5.0, 5.0]).unwrap();
+        tree.set_opacity(b, 0.5).unwrap();
+        tree.set_clip(a, Rect::new(0, 0, 10, 10)).unwrap();
+        tree.commit(&recorder).unwrap();
+        assert_eq!(
+            recorder.take(),
+            vec![
+                Op::Clip(1, Some(Rect::new(0, 0, 10, 10))),
+                Op::Opacity(2, 0.5),
+                Op::Commit,
+            ]
+        );
+        assert_eq!(tree.visual(b), Some(&2));
+    }
+
+    #[test]
+    fn commit_detaches_before_adding() {
+        let recorder = Recorder::default();
+        let mut tree = VisualTree::<Recorder>::new();
+        let root = tree.root();
+        let a = tree.create_node();
+        let b = tree.create_node();
+        let c = tree.create_node();
+        tree.append_child(root, a).unwrap();
+        tree.append_child(root, b).unwrap();
+        tree.append_child(b, c).unwrap();
+        tree.commit(&recorder).unwrap();
+        recorder.take();
+
+        // `a` was created before `b`, but `c` has to leave `b` before `a`
+        // can add it.
+        tree.append_child(a, c).unwrap();
+        tree.commit(&recorder).unwrap();
+        assert_eq!(
+            recorder.take(),
+            vec![
+                Op::RemoveAll(1),
+                Op::RemoveAll(2),
+                Op::Add(1, 3, None),
+                Op::Commit,
+            ]
+        );
+    }
+
+    #[test]
+    fn world_transform() {
+        let mut tree = VisualTree::<Recorder>::new();
+        let root = tree.root();
+        let a = tree.create_node();
+        tree.append_child(root, a).unwrap();
+        tree.set_offset(root, [100.0, 0.0]).unwrap();
+        tree.set_transform(a, Matrix3x2::scale(2.0, 2.0)).unwrap();
+        tree.set_offset(a, [10.0, 10.0]).unwrap();
+        let m = tree.world_transform(a).unwrap();
+        assert_eq!(m.transform_point([1.0, 1.0]), [112.0, 12.0]);
+    }
+}
@@ -1,6 +1,9 @@
-use crate::{text::ToUtf16, Rect};
-use std::{cell::Cell, ffi::OsStr, ptr};
-use winapi::{shared::minwindef::*, shared::windef::*, um::winuser::*};
+use crate::{Error, Rect, Result, ToWideStr};
+use std::{cell::Cell, ptr};
+use winapi::{
+    shared::{minwindef::*, windef::*, winerror::HRESULT_FROM_WIN32},
+    um::{errhandlingapi::GetLastError, winuser::*},
+};
 
 extern "C" {
     pub static __ImageBase: u8;
@@ -117,15 +120,16 @@ impl std::fmt::Debug for Window<'_> {
 }
 
 impl<'a> Window<'a> {
+    /// Fails with `E_INVALIDARG` if either name contains a NUL.
     pub fn create(
-        class_name: &OsStr,
-        window_name: &OsStr,
+        class_name: impl ToWideStr,
+        window_name: impl ToWideStr,
         style: u32,
         width: i32,
         height: i32,
-    ) -> Option<Window<'a>> {
-        let class_name = class_name.to_utf16();
-        let window_name = window_name.to_utf16();
+    ) -> Result<Window<'a>> {
+        let class_name = class_name.to_wide_str()?;
+        let window_name = window_name.to_wide_str()?;
 
         let mut wc = WNDCLASSW::default();
         wc.lpfnWndProc = Some(wnd_proc);
@@ -160,7 +164,9 @@ impl<'a> Window<'a> {
             )
         };
 
-        Window::from_hwnd(hwnd)
+        Window::from_hwnd(hwnd).ok_or_else(|| {
+            Error::new(HRESULT_FROM_WIN32(unsafe { GetLastError() }), "CreateWindowExW")
+        })
     }
 
     fn from_hwnd(hwnd: HWND) -> Option<Window<'a>> {